@@ -6,10 +6,12 @@ use syn::{
     parse::{Parse, ParseStream, Result},
     parse_macro_input,
     punctuated::{Pair, Punctuated},
-    Data, DataStruct, DeriveInput, Expr, Field, Fields, FieldsNamed, Ident, Path,
-    Token,
+    Data, DataStruct, DeriveInput, Error, Expr, Field, Fields, FieldsNamed, Ident,
+    LitInt, LitStr, Path, Token,
 };
 
+const DEFAULT_HEARTBEAT_MISSES: u32 = 3;
+
 #[derive(PartialEq, Eq, Hash)]
 enum AgentAttr {
     Task,
@@ -18,6 +20,48 @@ enum AgentAttr {
     Init,
     InitAsync,
     Logger(Expr),
+    Heartbeat(u64),
+    HeartbeatMisses(u32),
+    Restart(Option<RestartPolicy>),
+}
+
+#[derive(PartialEq, Eq, Hash)]
+struct RestartPolicy {
+    max: u32,
+    backoff_millis: u64,
+}
+
+impl Parse for RestartPolicy {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let ident = input.parse::<Ident>()?;
+        if ident != "on_failure" {
+            return Err(Error::new(
+                ident.span(),
+                "expected `never` or `on_failure(..)`",
+            ));
+        }
+        let content;
+        syn::parenthesized!(content in input);
+        let mut max = None;
+        let mut backoff_millis = 0;
+        while !content.is_empty() {
+            let key = content.parse::<Ident>()?;
+            content.parse::<Token![=]>()?;
+            match key.to_string().as_str() {
+                "max" => max = Some(content.parse::<LitInt>()?.base10_parse()?),
+                "backoff" => backoff_millis = parse_duration_lit(&content)?,
+                key => panic!("Unknown restart option: {key}"),
+            }
+            if !content.is_empty() {
+                content.parse::<Token![,]>()?;
+            }
+        }
+        let max = max.ok_or_else(|| Error::new(ident.span(), "missing `max`"))?;
+        Ok(Self {
+            max,
+            backoff_millis,
+        })
+    }
 }
 
 impl Parse for AgentAttr {
@@ -33,16 +77,65 @@ impl Parse for AgentAttr {
                 input.parse::<Token![=]>()?;
                 Ok(Self::Logger(input.parse()?))
             }
+            "heartbeat" => {
+                input.parse::<Token![=]>()?;
+                let span = input.span();
+                let millis = parse_duration_lit(input)?;
+                if millis == 0 {
+                    return Err(Error::new(span, "heartbeat must not be zero"));
+                }
+                Ok(Self::Heartbeat(millis))
+            }
+            "heartbeat_misses" => {
+                input.parse::<Token![=]>()?;
+                let lit = input.parse::<LitInt>()?;
+                let misses = lit.base10_parse()?;
+                if misses == 0 {
+                    return Err(Error::new(
+                        lit.span(),
+                        "heartbeat_misses must not be zero",
+                    ));
+                }
+                Ok(Self::HeartbeatMisses(misses))
+            }
+            "restart" => {
+                input.parse::<Token![=]>()?;
+                if input.peek(Ident) && input.fork().parse::<Ident>()? == "never" {
+                    input.parse::<Ident>()?;
+                    Ok(Self::Restart(None))
+                } else {
+                    Ok(Self::Restart(Some(input.parse()?)))
+                }
+            }
             ident => panic!("Unknown #[agent] option: {ident}"),
         }
     }
 }
 
+fn parse_duration_lit(input: ParseStream) -> Result<u64> {
+    let lit = input.parse::<LitStr>()?;
+    parse_millis(&lit.value()).ok_or_else(|| {
+        Error::new(lit.span(), "expected a duration like `500ms` or `2s`")
+    })
+}
+
+fn parse_millis(value: &str) -> Option<u64> {
+    if let Some(millis) = value.strip_suffix("ms") {
+        millis.parse().ok()
+    } else if let Some(secs) = value.strip_suffix('s') {
+        secs.parse::<u64>().ok()?.checked_mul(1000)
+    } else {
+        None
+    }
+}
+
 #[derive(PartialEq, Eq, Hash)]
 enum BrokerAttr {
     Plan(Path),
     Error(Path),
     PollExtra,
+    Metrics,
+    MetricsInterval(u64),
 }
 
 impl Parse for BrokerAttr {
@@ -58,6 +151,11 @@ impl Parse for BrokerAttr {
                 Ok(Self::Error(input.parse()?))
             }
             "poll_extra" => Ok(Self::PollExtra),
+            "metrics" => Ok(Self::Metrics),
+            "metrics_interval" => {
+                input.parse::<Token![=]>()?;
+                parse_duration_lit(input).map(Self::MetricsInterval)
+            }
             ident => panic!("Unknown #[broker] option: {ident}"),
         }
     }
@@ -104,6 +202,28 @@ pub fn proc_macro_derive(input: TokenStream) -> TokenStream {
             }
         })
         .expect("#[broker] attribute must set an `error`");
+    let metrics_interval = broker_attrs.iter().find_map(|attr| {
+        if let BrokerAttr::MetricsInterval(millis) = attr {
+            Some(*millis)
+        } else {
+            None
+        }
+    });
+    let measure_latency =
+        broker_attrs.contains(&BrokerAttr::Metrics) || metrics_interval.is_some();
+
+    // Reports invalid `#[agent]` options, e.g. a zero heartbeat, at their span.
+    let agent_attrs = fields
+        .iter()
+        .flat_map(|field| &field.attrs)
+        .filter(|attr| attr.path().is_ident("agent"));
+    for attr in agent_attrs {
+        if let Err(err) =
+            attr.parse_args_with(Punctuated::<AgentAttr, Token![,]>::parse_terminated)
+        {
+            return err.to_compile_error().into();
+        }
+    }
 
     let agent_fields = fields.iter().filter_map(|field| {
         field
@@ -145,12 +265,32 @@ pub fn proc_macro_derive(input: TokenStream) -> TokenStream {
     let run_handlers = agent_fields.clone().map(|(field, _)| {
         let ident = field.ident.as_ref().unwrap();
         let handler = format_ident!("handle_{}", ident);
+        let call_handler = if measure_latency {
+            quote! {{
+                let metrics = ::std::clone::Clone::clone(port.metrics());
+                let start = ::std::time::Instant::now();
+                let result = fut.broker.#handler(fut.plan, output);
+                metrics.record_handler_latency(start.elapsed());
+                result
+            }}
+        } else {
+            quote!(fut.broker.#handler(fut.plan, output))
+        };
         quote! {
             if let Some(port) = fut.broker.#ident.enabled() {
+                if port.poll_unresponsive(cx).is_ready() {
+                    return ::std::task::Poll::Ready(
+                        ::std::result::Result::Err(
+                            ::agentwire::BrokerError::AgentUnresponsive(
+                                ::std::stringify!(#ident),
+                            ),
+                        ),
+                    );
+                }
                 loop {
                     match ::futures::StreamExt::poll_next_unpin(port, cx) {
                         ::std::task::Poll::Ready(Some(output)) if output.source_ts > fence => {
-                            match fut.broker.#handler(fut.plan, output) {
+                            match #call_handler {
                                 ::std::result::Result::Ok(::agentwire::BrokerFlow::Break) => {
                                     return ::std::task::Poll::Ready(::std::result::Result::Ok(()));
                                 }
@@ -173,13 +313,26 @@ pub fn proc_macro_derive(input: TokenStream) -> TokenStream {
                             continue;
                         }
                         ::std::task::Poll::Ready(::std::option::Option::None) => {
-                            return ::std::task::Poll::Ready(
-                                ::std::result::Result::Err(
-                                    ::agentwire::BrokerError::AgentTerminated(
-                                        ::std::stringify!(#ident),
-                                    ),
-                                ),
-                            );
+                            let name = ::std::stringify!(#ident);
+                            let err = if let ::std::option::Option::Some(error) =
+                                port.take_handshake_error()
+                            {
+                                ::agentwire::BrokerError::AgentIncompatible { name, error }
+                            } else {
+                                match port.take_crash() {
+                                    ::std::option::Option::Some(crash) => {
+                                        ::agentwire::BrokerError::AgentCrashed {
+                                            name,
+                                            exit: crash.exit,
+                                            stderr_tail: crash.stderr_tail,
+                                        }
+                                    }
+                                    ::std::option::Option::None => {
+                                        ::agentwire::BrokerError::AgentTerminated(name)
+                                    }
+                                }
+                            };
+                            return ::std::task::Poll::Ready(::std::result::Result::Err(err));
                         }
                         ::std::task::Poll::Pending => {
                             break;
@@ -206,12 +359,146 @@ pub fn proc_macro_derive(input: TokenStream) -> TokenStream {
             }
         }
     });
+    let (metrics_timer_field, metrics_timer_init, metrics_timer_poll) =
+        if let Some(millis) = metrics_interval {
+            (
+                quote!(metrics_timer: ::agentwire::metrics::MetricsTimer,),
+                quote! {
+                    metrics_timer: ::agentwire::metrics::MetricsTimer::new(
+                        ::std::time::Duration::from_millis(#millis),
+                    ),
+                },
+                quote! {
+                    while fut.metrics_timer.poll_tick(cx).is_ready() {
+                        fut.broker.metrics().trace();
+                    }
+                },
+            )
+        } else {
+            (quote!(), quote!(), quote!())
+        };
+    let restart_policies = agent_fields
+        .clone()
+        .filter_map(|(field, attrs)| {
+            attrs.iter().find_map(|attr| match attr {
+                AgentAttr::Restart(Some(RestartPolicy {
+                    max,
+                    backoff_millis,
+                })) => Some((field, *max, *backoff_millis)),
+                _ => None,
+            })
+        })
+        .collect::<Vec<_>>();
+    let run_methods = if restart_policies.is_empty() {
+        quote! {
+            impl #ident {
+                #[allow(missing_docs)]
+                pub fn run<'a>(&'a mut self, plan: &'a mut dyn #broker_plan) -> #run_fut_name<'a> {
+                    Self::run_with_fence(self, plan, ::std::time::Instant::now())
+                }
+
+                #[allow(missing_docs)]
+                pub fn run_with_fence<'a>(
+                    &'a mut self,
+                    plan: &'a mut dyn #broker_plan,
+                    fence: ::std::time::Instant,
+                ) -> #run_fut_name<'a> {
+                    #run_fut_name {
+                        broker: self,
+                        plan,
+                        fence,
+                        #metrics_timer_init
+                    }
+                }
+            }
+        }
+    } else {
+        let attempts = restart_policies
+            .iter()
+            .map(|(field, _, _)| {
+                format_ident!("{}_attempts", field.ident.as_ref().unwrap())
+            })
+            .collect::<Vec<_>>();
+        let restarts = restart_policies.iter().zip(&attempts).map(
+            |((field, max, backoff_millis), attempts)| {
+                let ident = field.ident.as_ref().unwrap();
+                let enable = format_ident!("enable_{}", ident);
+                let init_async = agent_fields.clone().any(|(other, attrs)| {
+                    other.ident == field.ident && attrs.contains(&AgentAttr::InitAsync)
+                });
+                let enable_await = init_async.then(|| quote!(.await));
+                quote! {
+                    ::std::result::Result::Err(
+                        ::agentwire::BrokerError::AgentTerminated(name)
+                        | ::agentwire::BrokerError::AgentCrashed { name, .. },
+                    ) if name == ::std::stringify!(#ident) && #attempts < #max =>
+                    {
+                        #attempts += 1;
+                        ::agentwire::agent::restart_backoff(
+                            name,
+                            #attempts,
+                            ::std::time::Duration::from_millis(#backoff_millis),
+                        )
+                        .await;
+                        self.#ident = ::agentwire::agent::Cell::Vacant;
+                        self.#enable()#enable_await?;
+                        match self.handle_restarted(&mut *plan, name, #attempts) {
+                            ::std::result::Result::Ok(::agentwire::BrokerFlow::Break) => {
+                                return ::std::result::Result::Ok(());
+                            }
+                            ::std::result::Result::Ok(::agentwire::BrokerFlow::Continue) => {}
+                            ::std::result::Result::Err(err) => {
+                                return ::std::result::Result::Err(
+                                    ::agentwire::BrokerError::Handler(name, err),
+                                );
+                            }
+                        }
+                    }
+                }
+            },
+        );
+        quote! {
+            impl #ident {
+                #[allow(missing_docs)]
+                pub async fn run(
+                    &mut self,
+                    plan: &mut dyn #broker_plan,
+                ) -> ::std::result::Result<(), ::agentwire::BrokerError<#broker_error>> {
+                    self.run_with_fence(plan, ::std::time::Instant::now()).await
+                }
+
+                /// Runs the broker, restarting terminated agents according to
+                /// their restart policies.
+                pub async fn run_with_fence(
+                    &mut self,
+                    plan: &mut dyn #broker_plan,
+                    fence: ::std::time::Instant,
+                ) -> ::std::result::Result<(), ::agentwire::BrokerError<#broker_error>> {
+                    #(let mut #attempts = 0_u32;)*
+                    loop {
+                        let result = #run_fut_name {
+                            broker: &mut *self,
+                            plan: &mut *plan,
+                            fence,
+                            #metrics_timer_init
+                        }
+                        .await;
+                        match result {
+                            #(#restarts)*
+                            result => return result,
+                        }
+                    }
+                }
+            }
+        }
+    };
     let run = quote! {
         #[allow(missing_docs)]
         pub struct #run_fut_name<'a> {
             broker: &'a mut #ident,
             plan: &'a mut dyn #broker_plan,
             fence: ::std::time::Instant,
+            #metrics_timer_field
         }
 
         impl ::futures::future::Future for #run_fut_name<'_> {
@@ -223,6 +510,7 @@ pub fn proc_macro_derive(input: TokenStream) -> TokenStream {
             ) -> ::std::task::Poll<Self::Output> {
                 let fence = self.fence;
                 let fut = self.as_mut().get_mut();
+                #metrics_timer_poll
                 'outer: loop {
                     #(#run_handlers)*
                     #poll_extra
@@ -230,25 +518,7 @@ pub fn proc_macro_derive(input: TokenStream) -> TokenStream {
             }
         }
 
-        impl #ident {
-            #[allow(missing_docs)]
-            pub fn run<'a>(&'a mut self, plan: &'a mut dyn #broker_plan) -> #run_fut_name<'a> {
-                Self::run_with_fence(self, plan, ::std::time::Instant::now())
-            }
-
-            #[allow(missing_docs)]
-            pub fn run_with_fence<'a>(
-                &'a mut self,
-                plan: &'a mut dyn #broker_plan,
-                fence: ::std::time::Instant,
-            ) -> #run_fut_name<'a> {
-                #run_fut_name {
-                    broker: self,
-                    plan,
-                    fence,
-                }
-            }
-        }
+        #run_methods
     };
 
     let methods = agent_fields.clone().map(|(field, attrs)| {
@@ -257,6 +527,13 @@ pub fn proc_macro_derive(input: TokenStream) -> TokenStream {
         let try_enable = format_ident!("try_enable_{}", ident);
         let disable = format_ident!("disable_{}", ident);
         let init = format_ident!("init_{}", ident);
+        assert!(
+            attrs.contains(&AgentAttr::Process)
+                || !attrs.iter().any(|attr| {
+                    matches!(attr, AgentAttr::Heartbeat(_) | AgentAttr::HeartbeatMisses(_))
+                }),
+            "heartbeat is supported only for `process` agents"
+        );
         let (init, init_async) = if attrs.contains(&AgentAttr::InitAsync) {
             let init = quote! {
                 match self.#init().await {
@@ -283,7 +560,29 @@ pub fn proc_macro_derive(input: TokenStream) -> TokenStream {
             } else {
                 quote!(::agentwire::agent::process::default_logger)
             };
-            quote!(::agentwire::agent::Process::spawn_process(#init, #logger))
+            let heartbeat = attrs.iter().find_map(|attr| {
+                if let AgentAttr::Heartbeat(millis) = attr { Some(*millis) } else { None }
+            });
+            if let Some(millis) = heartbeat {
+                let max_missed = attrs
+                    .iter()
+                    .find_map(|attr| {
+                        if let AgentAttr::HeartbeatMisses(n) = attr { Some(*n) } else { None }
+                    })
+                    .unwrap_or(DEFAULT_HEARTBEAT_MISSES);
+                quote! {
+                    ::agentwire::agent::Process::spawn_process_with_heartbeat(
+                        #init,
+                        #logger,
+                        ::agentwire::port::Heartbeat {
+                            interval: ::std::time::Duration::from_millis(#millis),
+                            max_missed: #max_missed,
+                        },
+                    )
+                }
+            } else {
+                quote!(::agentwire::agent::Process::spawn_process(#init, #logger))
+            }
         } else if attrs.contains(&AgentAttr::Thread) {
             quote! {
                 match ::agentwire::agent::Thread::spawn_thread(#init) {
@@ -342,6 +641,38 @@ pub fn proc_macro_derive(input: TokenStream) -> TokenStream {
         }
     });
 
+    let shutdown_agents = agent_fields
+        .clone()
+        .map(|(field, attrs)| {
+            let ident = field.ident.as_ref().unwrap();
+            let kill = attrs.contains(&AgentAttr::Process);
+            (ident, quote!(self.#ident.shutdown(timeout, #kill)))
+        })
+        .collect::<Vec<_>>();
+    let shutdown = if shutdown_agents.is_empty() {
+        quote!(::std::vec::Vec::new())
+    } else {
+        let idents = shutdown_agents.iter().map(|(ident, _)| ident);
+        let names = idents.clone();
+        let futs = shutdown_agents.iter().map(|(_, fut)| fut);
+        quote! {
+            let (#(#idents,)*) = ::futures::join!(#(#futs,)*);
+            [#((::std::stringify!(#names), #names),)*]
+                .into_iter()
+                .filter_map(|(name, report)| report.map(|report| (name, report)))
+                .collect()
+        }
+    };
+
+    let agent_metrics = agent_fields.clone().map(|(field, _)| {
+        let ident = field.ident.as_ref().unwrap();
+        quote! {
+            if let ::std::option::Option::Some(agent) = self.#ident.metrics() {
+                metrics.agents.insert(::std::stringify!(#ident), agent);
+            }
+        }
+    });
+
     let disable_agents = agent_fields.map(|(field, _)| {
         let disable = format_ident!("disable_{}", field.ident.as_ref().unwrap());
         quote!(#disable)
@@ -354,10 +685,27 @@ pub fn proc_macro_derive(input: TokenStream) -> TokenStream {
         impl #ident {
             #(#methods)*
 
+            /// Returns the port metrics of all initialized agents.
+            pub fn metrics(&self) -> ::agentwire::metrics::BrokerMetrics {
+                #[allow(unused_mut)]
+                let mut metrics = ::agentwire::metrics::BrokerMetrics::default();
+                #(#agent_metrics)*
+                metrics
+            }
+
             #[allow(missing_docs)]
             pub fn disable_agents(&mut self) {
                 #(self.#disable_agents();)*
             }
+
+            /// Shuts down all initialized agents concurrently, leaving their
+            /// cells vacant. Process-based agents are killed on timeout.
+            pub async fn shutdown(
+                &mut self,
+                timeout: ::std::time::Duration,
+            ) -> ::std::vec::Vec<(&'static str, ::agentwire::agent::ShutdownReport)> {
+                #shutdown
+            }
         }
     };
     expanded.into()
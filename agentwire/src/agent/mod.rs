@@ -57,9 +57,13 @@ mod thread;
 
 pub use self::{process::Process, task::Task, thread::Thread};
 
-use crate::port::{self, Port};
+use crate::{
+    metrics::AgentMetrics,
+    port::{self, Port},
+};
 use futures::prelude::*;
-use std::{mem::replace, pin::Pin};
+use std::{mem::replace, pin::Pin, time::Duration};
+use tokio::time;
 
 /// Abstract agent.
 pub trait Agent: Port + Sized + 'static {
@@ -70,6 +74,28 @@ pub trait Agent: Port + Sized + 'static {
 /// Future to kill an agent.
 pub type Kill = Pin<Box<dyn Future<Output = ()> + Send>>;
 
+/// How an agent terminated during [`Cell::shutdown`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ShutdownReport {
+    /// The agent closed its port within the timeout.
+    Clean,
+    /// The agent didn't close its port within the timeout and was killed.
+    Killed,
+    /// The agent didn't close its port within the timeout and can't be killed.
+    /// It's left running detached from the broker.
+    TimedOut,
+}
+
+/// Waits before restarting a terminated agent. Used by the code generated for
+/// the `restart` option of the [`Broker`](crate::Broker) macro.
+#[doc(hidden)]
+pub async fn restart_backoff(name: &'static str, attempt: u32, backoff: Duration) {
+    tracing::warn!(
+        "Agent {name} terminated, restarting in {backoff:?} (attempt {attempt})"
+    );
+    time::sleep(backoff).await;
+}
+
 /// Agent cell inside a broker.
 pub enum Cell<T: Agent> {
     /// Agent is not initialized.
@@ -89,6 +115,17 @@ impl<T: Agent> Cell<T> {
         }
     }
 
+    /// Returns the port metrics if the agent is initialized.
+    #[must_use]
+    pub fn metrics(&self) -> Option<AgentMetrics> {
+        match self {
+            Self::Enabled((port, _kill)) | Self::Disabled((port, _kill)) => {
+                Some(port.metrics().snapshot())
+            }
+            Self::Vacant => None,
+        }
+    }
+
     /// Returns `true` if the agent is enabled.
     #[must_use]
     pub fn is_enabled(&self) -> bool {
@@ -108,4 +145,55 @@ impl<T: Agent> Cell<T> {
             Self::Vacant => {}
         }
     }
+
+    /// Asks the agent to stop cooperatively by closing the input side of its
+    /// port. The agent is expected to exit once its input stream ends.
+    pub fn stop(&mut self) {
+        match self {
+            Self::Enabled((port, _kill)) | Self::Disabled((port, _kill)) => {
+                port.tx.close_channel();
+            }
+            Self::Vacant => {}
+        }
+    }
+
+    /// Stops the agent and waits up to `timeout` for it to close its port,
+    /// dropping any output messages it emits meanwhile. If the agent is still
+    /// running after the timeout, it's killed when `kill` is `true`.
+    ///
+    /// Leaves the cell vacant. Returns `None` if the agent wasn't initialized.
+    pub async fn shutdown(
+        &mut self,
+        timeout: Duration,
+        kill: bool,
+    ) -> Option<ShutdownReport> {
+        self.stop();
+        let (mut port, kill_fut) = match replace(self, Self::Vacant) {
+            Self::Enabled(agent) | Self::Disabled(agent) => agent,
+            Self::Vacant => return None,
+        };
+        let mut drained = 0_usize;
+        let drain = async {
+            while port.rx.next().await.is_some() {
+                drained += 1;
+            }
+        };
+        let closed = time::timeout(timeout, drain).await.is_ok();
+        if drained > 0 {
+            tracing::debug!(
+                "Agent {} dropped {drained} output messages on shutdown",
+                T::NAME
+            );
+        }
+        let report = if closed {
+            ShutdownReport::Clean
+        } else if kill {
+            kill_fut.await;
+            ShutdownReport::Killed
+        } else {
+            ShutdownReport::TimedOut
+        };
+        tracing::info!("Agent {} shut down: {report:?}", T::NAME);
+        Some(report)
+    }
 }
@@ -2,13 +2,15 @@
 
 use super::{Agent, Kill};
 use crate::{
-    port::{self, SharedPort, SharedSerializer},
+    port::{
+        self, ConnectSharedMemoryError, HandshakeSlot, Heartbeat, HeartbeatTracker,
+        PortError, SharedPort, SharedSerializer,
+    },
     spawn_named_thread,
 };
 use close_fds::close_open_fds;
 use futures::{future::Either, prelude::*};
 use nix::{
-    errno::Errno,
     sched::{unshare, CloneFlags},
     sys::signal::{self, Signal},
     unistd::Pid,
@@ -18,17 +20,22 @@ use rkyv::{
     Serialize,
 };
 use std::{
+    collections::HashMap,
     env,
     error::Error,
-    fmt::Debug,
+    fmt::{self, Debug, Display},
     io,
     os::{
         fd::{AsRawFd as _, FromRawFd as _, OwnedFd, RawFd},
         unix::process::{parent_id, ExitStatusExt as _},
     },
     pin::pin,
-    process::{self, Stdio},
-    sync::atomic::{AtomicBool, Ordering},
+    process::{self, ExitStatus, Stdio},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, OnceLock,
+    },
+    time::Duration,
 };
 use thiserror::Error;
 use tokio::{
@@ -36,7 +43,7 @@ use tokio::{
     process::{ChildStderr, ChildStdout, Command},
     runtime,
     sync::oneshot,
-    task,
+    task, time,
 };
 
 /// Environment variable to pass extra arguments to the agent process.
@@ -44,6 +51,14 @@ pub const ARGS_ENV: &str = "AGENTWIRE_PROCESS_ARGS";
 
 const SHMEM_ENV: &str = "AGENTWIRE_PROCESS_SHMEM";
 const PARENT_PID_ENV: &str = "AGENTWIRE_PROCESS_PARENT_PID";
+const HEARTBEAT_ENV: &str = "AGENTWIRE_PROCESS_HEARTBEAT_MS";
+
+/// Number of bytes of the agent process stderr kept in [`StderrTail`].
+pub const STDERR_TAIL_SIZE: usize = 8 * 1024;
+
+/// How long to wait for the logger to read the remaining stderr output of a
+/// crashed agent process.
+const LOGGER_DRAIN_TIMEOUT: Duration = Duration::from_secs(1);
 
 static INIT_PROCESSES: AtomicBool = AtomicBool::new(false);
 
@@ -55,7 +70,10 @@ pub enum CallError<T: Debug> {
     Agent(T),
     /// Error initializing the shared memory.
     #[error("shared memory: {0}")]
-    SharedMemory(Errno),
+    SharedMemory(ConnectSharedMemoryError),
+    /// Error reading the initial state.
+    #[error("initial state: {0}")]
+    InitState(PortError),
 }
 
 /// Exit strategy returned from [`Process::exit_strategy`].
@@ -70,6 +88,51 @@ pub enum ExitStrategy {
     Retry,
 }
 
+/// How an agent process exited.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ExitInfo {
+    /// The process exited with a code.
+    Code(i32),
+    /// The process was terminated by a signal.
+    Signal {
+        /// Number of the signal.
+        signal: i32,
+        /// Whether the process dumped core.
+        core_dumped: bool,
+    },
+}
+
+/// Crash of an agent process, recorded when the process is terminated by a
+/// signal and the port is closed.
+#[derive(Clone, Debug)]
+pub struct Crash {
+    /// How the process exited.
+    pub exit: ExitInfo,
+    /// The latest output of the process to stderr, see [`StderrTail`].
+    pub stderr_tail: String,
+}
+
+/// Shared slot for the [`Crash`] of an agent process.
+#[derive(Clone, Default, Debug)]
+pub struct CrashSlot(Arc<Mutex<Option<Crash>>>);
+
+/// Bounded in-memory buffer of the latest stderr output of an agent process.
+///
+/// [`default_logger`] records every stderr line here. Custom loggers should do
+/// the same with [`stderr_tail`] to have the output attached to [`Crash`].
+#[derive(Debug)]
+pub struct StderrTail {
+    ring: Mutex<Ring>,
+}
+
+/// Fixed-size byte ring, overwriting the oldest bytes when full.
+#[derive(Debug)]
+struct Ring {
+    data: Box<[u8]>,
+    start: usize,
+    len: usize,
+}
+
 /// Additional settings for starting a new process.
 pub trait Initializer: Send {
     /// File descriptors to keep open when starting a new process.
@@ -127,36 +190,43 @@ where
         F: Fn(&'static str, ChildStdout, ChildStderr) -> Fut + Send + 'static,
         Fut: Future<Output = ()> + Send + 'static,
     {
-        assert!(
-            INIT_PROCESSES.load(Ordering::Relaxed),
-            "process-based agents are not initialized (missing call to \
-             `agentwire::agent::process::init`)"
-        );
-        let (inner, outer) = port::new();
-        let (send_kill_tx, send_kill_rx) = oneshot::channel();
-        let (wait_kill_tx, wait_kill_rx) = oneshot::channel();
-        let kill = async move {
-            let _ = send_kill_tx.send(());
-            wait_kill_rx.await.unwrap();
-            tracing::info!("Process agent {} killed", Self::NAME);
-        };
-        let spawn_process =
-            spawn_process_impl(self, inner, send_kill_rx, wait_kill_tx, logger);
-        spawn_named_thread(format!("proc-ipc-{}", Self::NAME), || {
-            let rt = runtime::Builder::new_current_thread()
-                .enable_all()
-                .build()
-                .unwrap();
-            rt.block_on(task::LocalSet::new().run_until(spawn_process));
-        });
-        (outer, kill.boxed())
+        spawn(self, logger, None)
+    }
+
+    /// Same as [`spawn_process`](Self::spawn_process), but the agent sends
+    /// heartbeats according to `heartbeat`. The heartbeats are tracked by the
+    /// returned port (see [`port::Outer::poll_unresponsive`]).
+    ///
+    /// # Panics
+    ///
+    /// If [`init`] hasn't been called yet.
+    fn spawn_process_with_heartbeat<Fut, F>(
+        self,
+        logger: F,
+        heartbeat: Heartbeat,
+    ) -> (port::Outer<Self>, Kill)
+    where
+        F: Fn(&'static str, ChildStdout, ChildStderr) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        spawn(self, logger, Some(heartbeat))
     }
 
     /// Connects to the shared memory and calls the [`run`](Self::run) method.
     fn call(shmem: OwnedFd) -> Result<(), CallError<Self::Error>> {
         let mut inner = port::RemoteInner::<Self>::from_shared_memory(shmem)
             .map_err(CallError::SharedMemory)?;
-        let agent = inner.init_state().deserialize(&mut Infallible).unwrap();
+        if let Ok(interval) = env::var(HEARTBEAT_ENV) {
+            let interval = interval
+                .parse()
+                .expect("heartbeat interval to be an integer");
+            inner.set_heartbeat_interval(Duration::from_millis(interval));
+        }
+        let agent = inner
+            .init_state()
+            .map_err(CallError::InitState)?
+            .deserialize(&mut Infallible)
+            .unwrap();
         agent.run(inner).map_err(CallError::Agent)
     }
 
@@ -174,6 +244,143 @@ where
     }
 }
 
+impl ExitInfo {
+    /// Returns `true` if the process was terminated by a signal.
+    #[must_use]
+    pub fn is_signal(self) -> bool {
+        matches!(self, Self::Signal { .. })
+    }
+}
+
+impl From<ExitStatus> for ExitInfo {
+    fn from(status: ExitStatus) -> Self {
+        match status.code() {
+            Some(code) => Self::Code(code),
+            None => Self::Signal {
+                signal: status.signal().unwrap_or_default(),
+                core_dumped: status.core_dumped(),
+            },
+        }
+    }
+}
+
+impl Display for ExitInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Code(code) => write!(f, "exit code {code}"),
+            Self::Signal {
+                signal,
+                core_dumped,
+            } => {
+                match Signal::try_from(signal) {
+                    Ok(name) => write!(f, "signal {name}")?,
+                    Err(_) => write!(f, "signal {signal}")?,
+                }
+                if core_dumped {
+                    write!(f, " (core dumped)")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl CrashSlot {
+    /// Takes the recorded crash, if any.
+    #[must_use]
+    pub fn take(&self) -> Option<Crash> {
+        self.0.lock().unwrap().take()
+    }
+
+    fn set(&self, crash: Crash) {
+        *self.0.lock().unwrap() = Some(crash);
+    }
+}
+
+impl StderrTail {
+    /// Creates an empty buffer keeping up to `capacity` bytes.
+    ///
+    /// # Panics
+    ///
+    /// If `capacity` is zero.
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "stderr tail capacity must be non-zero");
+        Self {
+            ring: Mutex::new(Ring {
+                data: vec![0; capacity].into_boxed_slice(),
+                start: 0,
+                len: 0,
+            }),
+        }
+    }
+
+    /// Appends a line, dropping the oldest output if the buffer is full.
+    pub fn push_line(&self, line: &str) {
+        let mut ring = self.ring.lock().unwrap();
+        ring.push(line.as_bytes());
+        ring.push(b"\n");
+    }
+
+    /// Removes all output.
+    pub fn clear(&self) {
+        let mut ring = self.ring.lock().unwrap();
+        ring.start = 0;
+        ring.len = 0;
+    }
+
+    /// Returns the buffered output. Invalid UTF-8, e.g. a character cut at the
+    /// start of the buffer, is replaced.
+    #[must_use]
+    pub fn contents(&self) -> String {
+        let bytes = {
+            let ring = self.ring.lock().unwrap();
+            let (head, tail) = ring.as_slices();
+            [head, tail].concat()
+        };
+        String::from_utf8_lossy(&bytes).into_owned()
+    }
+}
+
+impl Ring {
+    fn push(&mut self, mut bytes: &[u8]) {
+        let capacity = self.data.len();
+        if bytes.len() > capacity {
+            bytes = &bytes[bytes.len() - capacity..];
+        }
+        let end = (self.start + self.len) % capacity;
+        let first = bytes.len().min(capacity - end);
+        self.data[end..end + first].copy_from_slice(&bytes[..first]);
+        self.data[..bytes.len() - first].copy_from_slice(&bytes[first..]);
+        let overflow = (self.len + bytes.len()).saturating_sub(capacity);
+        self.start = (self.start + overflow) % capacity;
+        self.len = (self.len + bytes.len()).min(capacity);
+    }
+
+    fn as_slices(&self) -> (&[u8], &[u8]) {
+        let capacity = self.data.len();
+        if self.start + self.len <= capacity {
+            (&self.data[self.start..self.start + self.len], &[])
+        } else {
+            let wrapped = self.start + self.len - capacity;
+            (&self.data[self.start..], &self.data[..wrapped])
+        }
+    }
+}
+
+/// Returns the stderr buffer of the agent named `agent_name`.
+#[must_use]
+pub fn stderr_tail(agent_name: &'static str) -> Arc<StderrTail> {
+    static TAILS: OnceLock<Mutex<HashMap<&'static str, Arc<StderrTail>>>> =
+        OnceLock::new();
+    let mut tails = TAILS.get_or_init(Mutex::default).lock().unwrap();
+    Arc::clone(
+        tails
+            .entry(agent_name)
+            .or_insert_with(|| Arc::new(StderrTail::new(STDERR_TAIL_SIZE))),
+    )
+}
+
 /// Initializes process-based agents.
 ///
 /// This function must be called as early in the program lifetime as possible.
@@ -229,47 +436,126 @@ pub fn init(
 }
 
 /// Creates a default process agent logger.
+///
+/// Stderr lines are also recorded in the agent's [`stderr_tail`].
 pub async fn default_logger(
     agent_name: &'static str,
     stdout: ChildStdout,
     stderr: ChildStderr,
 ) {
+    let tail = stderr_tail(agent_name);
     let mut stdout = BufReader::new(stdout).lines();
     let mut stderr = BufReader::new(stderr).lines();
-    loop {
-        match future::select(pin!(stdout.next_line()), pin!(stderr.next_line())).await {
+    let (mut stdout_open, mut stderr_open) = (true, true);
+    // Keep reading until both streams are closed, so that the last stderr lines
+    // of a crashed agent are not lost.
+    while stdout_open || stderr_open {
+        let (read_stdout, read_stderr) = (stdout_open, stderr_open);
+        let next_stdout = async {
+            if read_stdout {
+                stdout.next_line().await
+            } else {
+                future::pending().await
+            }
+        };
+        let next_stderr = async {
+            if read_stderr {
+                stderr.next_line().await
+            } else {
+                future::pending().await
+            }
+        };
+        match future::select(pin!(next_stdout), pin!(next_stderr)).await {
             Either::Left((Ok(Some(line)), _)) => {
                 tracing::info!("[{agent_name}] <STDOUT> {line}");
             }
             Either::Right((Ok(Some(line)), _)) => {
+                tail.push_line(&line);
                 tracing::info!("[{agent_name}] <STDERR> {line}");
             }
             Either::Left((Ok(None), _)) => {
                 tracing::warn!("[{agent_name}] <STDOUT> closed");
-                break;
+                stdout_open = false;
             }
             Either::Right((Ok(None), _)) => {
                 tracing::warn!("[{agent_name}] <STDERR> closed");
-                break;
+                stderr_open = false;
             }
             Either::Left((Err(err), _)) => {
                 tracing::error!("[{agent_name}] <STDOUT> {err:#?}");
-                break;
+                stdout_open = false;
             }
             Either::Right((Err(err), _)) => {
                 tracing::error!("[{agent_name}] <STDERR> {err:#?}");
-                break;
+                stderr_open = false;
             }
         }
     }
 }
 
+fn spawn<T: Process, Fut, F>(
+    agent: T,
+    logger: F,
+    heartbeat: Option<Heartbeat>,
+) -> (port::Outer<T>, Kill)
+where
+    F: Fn(&'static str, ChildStdout, ChildStderr) -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+    <T as Archive>::Archived: Deserialize<T, Infallible>,
+    T::Input: Archive + for<'a> Serialize<SharedSerializer<'a>>,
+    T::Output: Archive + for<'a> Serialize<SharedSerializer<'a>>,
+    <T::Output as Archive>::Archived: Deserialize<T::Output, SharedDeserializeMap>,
+{
+    assert!(
+        INIT_PROCESSES.load(Ordering::Relaxed),
+        "process-based agents are not initialized (missing call to \
+         `agentwire::agent::process::init`)"
+    );
+    let (inner, mut outer) = port::new();
+    let heartbeat = heartbeat.map(HeartbeatTracker::new);
+    if let Some(tracker) = &heartbeat {
+        outer.set_heartbeat_tracker(tracker.clone());
+    }
+    let crash = CrashSlot::default();
+    outer.set_crash_slot(crash.clone());
+    let handshake = HandshakeSlot::default();
+    outer.set_handshake_slot(handshake.clone());
+    let (send_kill_tx, send_kill_rx) = oneshot::channel();
+    let (wait_kill_tx, wait_kill_rx) = oneshot::channel();
+    let kill = async move {
+        let _ = send_kill_tx.send(());
+        wait_kill_rx.await.unwrap();
+        tracing::info!("Process agent {} killed", T::NAME);
+    };
+    let spawn_process = spawn_process_impl(
+        agent,
+        inner,
+        send_kill_rx,
+        wait_kill_tx,
+        logger,
+        heartbeat,
+        crash,
+        handshake,
+    );
+    spawn_named_thread(format!("proc-ipc-{}", T::NAME), || {
+        let rt = runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(task::LocalSet::new().run_until(spawn_process));
+    });
+    (outer, kill.boxed())
+}
+
 async fn spawn_process_impl<T: Process, Fut, F>(
     init_state: T,
     mut inner: port::Inner<T>,
     mut send_kill_rx: oneshot::Receiver<()>,
     wait_kill_tx: oneshot::Sender<()>,
     logger: F,
+    heartbeat: Option<HeartbeatTracker>,
+    crash: CrashSlot,
+    handshake: HandshakeSlot,
 ) where
     F: Fn(&'static str, ChildStdout, ChildStderr) -> Fut + Send + 'static,
     Fut: Future<Output = ()> + Send + 'static,
@@ -279,9 +565,15 @@ async fn spawn_process_impl<T: Process, Fut, F>(
     <T::Output as Archive>::Archived: Deserialize<T::Output, SharedDeserializeMap>,
 {
     let mut recovered_inputs = Vec::new();
+    let stderr_tail = stderr_tail(T::NAME);
     loop {
         let (shmem_fd, close) = inner
-            .into_shared_memory(T::NAME, &init_state, recovered_inputs)
+            .into_shared_memory(
+                T::NAME,
+                &init_state,
+                recovered_inputs,
+                heartbeat.clone(),
+            )
             .expect("couldn't initialize shared memory");
         let exe =
             env::current_exe().expect("couldn't determine current executable file");
@@ -289,6 +581,10 @@ async fn spawn_process_impl<T: Process, Fut, F>(
         let initializer = T::initializer();
         let mut child_fds = initializer.keep_file_descriptors();
         child_fds.push(shmem_fd.as_raw_fd());
+        let heartbeat_env = heartbeat.as_ref().map(|tracker| {
+            let interval = tracker.heartbeat().interval.as_millis();
+            (HEARTBEAT_ENV, interval.to_string())
+        });
         let mut child = unsafe {
             Command::new(exe)
                 .arg0(format!("proc-{}", T::NAME))
@@ -301,6 +597,7 @@ async fn spawn_process_impl<T: Process, Fut, F>(
                         .unwrap_or_default(),
                 )
                 .envs(initializer.envs())
+                .envs(heartbeat_env)
                 .env(SHMEM_ENV, shmem_fd.as_raw_fd().to_string())
                 .env(PARENT_PID_ENV, process::id().to_string())
                 .stdin(Stdio::null())
@@ -314,10 +611,15 @@ async fn spawn_process_impl<T: Process, Fut, F>(
                 .spawn()
                 .expect("failed to spawn a sub-process")
         };
+        stderr_tail.clear();
         drop(shmem_fd);
         drop(initializer);
+        if let Some(tracker) = &heartbeat {
+            // Give the new process a full timeout to send its first heartbeat.
+            tracker.beat();
+        }
         let pid = Pid::from_raw(child.id().unwrap().try_into().unwrap());
-        task::spawn(logger(
+        let mut logger_task = task::spawn(logger(
             T::NAME,
             child.stdout.take().unwrap(),
             child.stderr.take().unwrap(),
@@ -331,6 +633,7 @@ async fn spawn_process_impl<T: Process, Fut, F>(
             Either::Left((status, _)) => {
                 let status = status.expect("failed to run a sub-process");
                 let (code, signal) = (status.code(), status.signal());
+                let exit = ExitInfo::from(status);
                 if signal.is_some_and(|signal| signal == libc::SIGINT) {
                     tracing::warn!("Process agent {} exited on Ctrl-C", T::NAME);
                     break;
@@ -341,10 +644,31 @@ async fn spawn_process_impl<T: Process, Fut, F>(
                      with {exit_strategy:?}",
                     T::NAME
                 );
-                (inner, recovered_inputs) =
+                let (closed_inner, inputs, handshake_error) =
                     close.await.expect("shared memory deinitialization failure");
+                (inner, recovered_inputs) = (closed_inner, inputs);
+                if let Some(err) = handshake_error {
+                    // Restarting would spawn the same incompatible binary.
+                    tracing::error!(
+                        "Process agent {} is incompatible with the broker: {err}",
+                        T::NAME
+                    );
+                    handshake.set(err);
+                    let _ = wait_kill_tx.send(());
+                    break;
+                }
                 match exit_strategy {
                     ExitStrategy::Close => {
+                        if exit.is_signal() {
+                            // Let the logger read the last words of the agent.
+                            let _ =
+                                time::timeout(LOGGER_DRAIN_TIMEOUT, &mut logger_task)
+                                    .await;
+                            crash.set(Crash {
+                                exit,
+                                stderr_tail: stderr_tail.contents(),
+                            });
+                        }
                         let _ = wait_kill_tx.send(());
                         break;
                     }
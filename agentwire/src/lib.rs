@@ -118,6 +118,25 @@
 //! }
 //! ```
 //!
+//! When the broker is no longer needed, the generated `shutdown` method stops
+//! all agents and reports how each of them terminated.
+//!
+//! ```ignore
+//! use agentwire::agent::ShutdownReport;
+//!
+//! // Close agent inputs, drain their outputs, and kill process-based agents
+//! // which are still running after the timeout.
+//! for (name, report) in broker.shutdown(Duration::from_secs(1)).await {
+//!     if report != ShutdownReport::Clean {
+//!         tracing::warn!("agent {name} didn't exit cleanly: {report:?}");
+//!     }
+//! }
+//! ```
+//!
+//! The generated `metrics` method returns the message counters and queue
+//! depths of each agent, which helps to find out which agent stalls the broker.
+//! See [`metrics`] module for more details.
+//!
 //! # Process-based agents
 //!
 //! Process-based agents are agents that run inside their own separate
@@ -156,6 +175,7 @@
 #![allow(clippy::missing_errors_doc, clippy::missing_panics_doc)]
 
 pub mod agent;
+pub mod metrics;
 pub mod port;
 pub mod testing_rt;
 
@@ -220,6 +240,10 @@ pub use agentwire_macros::test;
 ///   plan = Plan, // Plan trait for the broker (required)
 ///   error = Error, // Error type used by the generated `run` method (required)
 ///   poll_extra, // Call `poll_extra` method in the generated `run` method (optional)
+///   metrics, // Measure handler latency, see `agentwire::metrics` (optional)
+///   // Also emit the metrics as `tracing` events every 5 seconds, implies
+///   // `metrics` (optional)
+///   metrics_interval = "5s",
 /// )]
 /// pub struct MyBroker {
 ///     // Define the agents. Each agent should be annotated with the `agent`
@@ -239,6 +263,22 @@ pub use agentwire_macros::test;
 ///       init_async,
 ///       // The process-agent has a custom logger
 ///       logger = self.process_logger().await,
+///       // The process-agent sends a heartbeat every 2 seconds (`ms` and `s`
+///       // units are supported). The `run` method fails with
+///       // `BrokerError::AgentUnresponsive` if the agent misses
+///       // `heartbeat_misses` heartbeats in a row (defaults to 3).
+///       heartbeat = "2s",
+///       heartbeat_misses = 3,
+///       // The agent is re-initialized and restarted when it terminates
+///       // while the broker is running, up to `max` times per `run` call,
+///       // waiting `backoff` before each restart (defaults to no delay). The
+///       // `handle_restarted` method is called after each restart. Once the
+///       // attempts are exhausted, the `run` method fails with
+///       // `BrokerError::AgentTerminated`, or `BrokerError::AgentCrashed` if
+///       // a process-based agent was killed by a signal. A process-based
+///       // agent rejected with `BrokerError::AgentIncompatible` is never
+///       // restarted. Defaults to `never`.
+///       restart = on_failure(max = 3, backoff = "500ms"),
 ///     )]
 ///     foo: agent::Cell<Foo>,
 ///     // non-agent fields can be added as well
@@ -276,6 +316,17 @@ pub use agentwire_macros::test;
 ///         Ok(Some(Poll::Pending))
 ///     }
 ///
+///     // Implement the `handle_restarted` method if any agent has a `restart`
+///     // policy. `attempt` starts at 1.
+///     fn handle_restarted(
+///         &mut self,
+///         plan: &mut dyn Plan,
+///         name: &'static str,
+///         attempt: u32,
+///     ) -> Result<BrokerFlow, Error> {
+///         plan.handle_restarted(self, name, attempt)
+///     }
+///
 ///     // Implement a custom logger for process-based agents.
 ///     async fn process_logger(
 ///         &self,
@@ -323,6 +374,29 @@ pub enum BrokerError<T: Display> {
     /// An agent has terminated.
     #[error("agent {0} terminated")]
     AgentTerminated(&'static str),
+    /// A process-based agent was terminated by a signal.
+    #[error("agent {name} crashed with {exit}")]
+    AgentCrashed {
+        /// Name of the agent.
+        name: &'static str,
+        /// How the agent process exited.
+        exit: agent::process::ExitInfo,
+        /// The latest output of the agent process to stderr.
+        stderr_tail: String,
+    },
+    /// An agent has missed too many heartbeats in a row.
+    #[error("agent {0} is unresponsive")]
+    AgentUnresponsive(&'static str),
+    /// A process-based agent was built with an incompatible shared memory
+    /// protocol or [`SharedPort::SCHEMA_VERSION`](port::SharedPort::SCHEMA_VERSION).
+    /// The agent is not restarted.
+    #[error("agent {name} initialization: {error}")]
+    AgentIncompatible {
+        /// Name of the agent.
+        name: &'static str,
+        /// The mismatching handshakes.
+        error: port::HandshakeError,
+    },
 }
 
 fn spawn_named_thread<F, T>(name: impl Into<String>, f: F) -> thread::JoinHandle<T>
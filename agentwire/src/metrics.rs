@@ -0,0 +1,210 @@
+//! Message throughput and queue depth metrics.
+//!
+//! Every [port](crate::port) counts the messages passing through it in both
+//! directions. The counters are relaxed atomics shared between the two ends of
+//! the port, so they are always on. The difference between the sent and the
+//! received counters of a direction is the number of messages currently
+//! waiting in its queue.
+//!
+//! Handler latency is measured by the generated broker `run` method only when
+//! the broker is built with the `metrics` or `metrics_interval` option:
+//!
+//! ```ignore
+//! #[derive(Broker)]
+//! #[broker(plan = Plan, error = Error, metrics_interval = "5s")]
+//! struct MyBroker {
+//!     #[agent(task)]
+//!     foo: agent::Cell<Foo>,
+//! }
+//!
+//! let metrics = broker.metrics();
+//! println!("{:?}", metrics.agents["foo"]);
+//! ```
+
+use futures::prelude::*;
+use std::{
+    collections::BTreeMap,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+    time::Duration,
+};
+use tokio::time;
+
+/// Weight of the latest sample in the handler latency average, as a power of
+/// two divisor.
+const LATENCY_SMOOTHING_SHIFT: u32 = 3;
+
+/// Message counters shared between the two ends of a port.
+#[derive(Clone, Default, Debug)]
+pub struct PortMetrics {
+    counters: Arc<Counters>,
+}
+
+#[derive(Default, Debug)]
+struct Counters {
+    inputs_sent: AtomicU64,
+    inputs_received: AtomicU64,
+    outputs_sent: AtomicU64,
+    outputs_received: AtomicU64,
+    handler_calls: AtomicU64,
+    handler_latency_nanos: AtomicU64,
+}
+
+/// A snapshot of an agent's port metrics.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct AgentMetrics {
+    /// Number of input messages sent by the broker.
+    pub inputs_sent: u64,
+    /// Number of input messages received by the agent.
+    pub inputs_received: u64,
+    /// Number of output messages sent by the agent.
+    pub outputs_sent: u64,
+    /// Number of output messages received by the broker.
+    pub outputs_received: u64,
+    /// Number of input messages waiting to be received by the agent.
+    pub input_queue_depth: u64,
+    /// Number of output messages waiting to be received by the broker.
+    pub output_queue_depth: u64,
+    /// Number of output messages handled by the broker. Always zero if handler
+    /// latency isn't measured.
+    pub handler_calls: u64,
+    /// Rolling average of the broker handler latency. `None` if no handler
+    /// call was measured.
+    pub handler_latency: Option<Duration>,
+}
+
+/// Metrics of all initialized agents of a broker, keyed by agent name.
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+pub struct BrokerMetrics {
+    /// Per-agent metrics.
+    pub agents: BTreeMap<&'static str, AgentMetrics>,
+}
+
+/// Periodically emits broker metrics as `tracing` events. Used by the code
+/// generated for the `metrics_interval` option of the
+/// [`Broker`](crate::Broker) macro.
+#[doc(hidden)]
+pub struct MetricsTimer {
+    interval: Duration,
+    sleep: Pin<Box<time::Sleep>>,
+}
+
+impl PortMetrics {
+    pub(crate) fn input_sent(&self) {
+        self.counters.inputs_sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn input_received(&self) {
+        self.counters
+            .inputs_received
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn output_sent(&self) {
+        self.counters.outputs_sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn output_received(&self) {
+        self.counters
+            .outputs_received
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records the time the broker spent handling an output message.
+    #[doc(hidden)]
+    pub fn record_handler_latency(&self, latency: Duration) {
+        let sample = u64::try_from(latency.as_nanos()).unwrap_or(u64::MAX);
+        // Only the broker records latencies, so there is no concurrent writer.
+        let calls = self.counters.handler_calls.fetch_add(1, Ordering::Relaxed);
+        let average = if calls == 0 {
+            sample
+        } else {
+            let average = self.counters.handler_latency_nanos.load(Ordering::Relaxed);
+            if sample >= average {
+                average + ((sample - average) >> LATENCY_SMOOTHING_SHIFT)
+            } else {
+                average - ((average - sample) >> LATENCY_SMOOTHING_SHIFT)
+            }
+        };
+        self.counters
+            .handler_latency_nanos
+            .store(average, Ordering::Relaxed);
+    }
+
+    /// Returns the current values of the counters.
+    #[must_use]
+    pub fn snapshot(&self) -> AgentMetrics {
+        let Counters {
+            inputs_sent,
+            inputs_received,
+            outputs_sent,
+            outputs_received,
+            handler_calls,
+            handler_latency_nanos,
+        } = &*self.counters;
+        // Load the received counters first, so that the depths never go
+        // negative under concurrent updates.
+        let inputs_received = inputs_received.load(Ordering::Relaxed);
+        let outputs_received = outputs_received.load(Ordering::Relaxed);
+        let inputs_sent = inputs_sent.load(Ordering::Relaxed);
+        let outputs_sent = outputs_sent.load(Ordering::Relaxed);
+        let handler_calls = handler_calls.load(Ordering::Relaxed);
+        AgentMetrics {
+            inputs_sent,
+            inputs_received,
+            outputs_sent,
+            outputs_received,
+            input_queue_depth: inputs_sent.saturating_sub(inputs_received),
+            output_queue_depth: outputs_sent.saturating_sub(outputs_received),
+            handler_calls,
+            handler_latency: (handler_calls > 0).then(|| {
+                Duration::from_nanos(handler_latency_nanos.load(Ordering::Relaxed))
+            }),
+        }
+    }
+}
+
+impl BrokerMetrics {
+    /// Emits the metrics as one `tracing` event per agent.
+    pub fn trace(&self) {
+        for (name, metrics) in &self.agents {
+            tracing::info!(
+                agent = name,
+                inputs_sent = metrics.inputs_sent,
+                inputs_received = metrics.inputs_received,
+                outputs_sent = metrics.outputs_sent,
+                outputs_received = metrics.outputs_received,
+                input_queue_depth = metrics.input_queue_depth,
+                output_queue_depth = metrics.output_queue_depth,
+                handler_calls = metrics.handler_calls,
+                handler_latency = ?metrics.handler_latency,
+                "Agent metrics"
+            );
+        }
+    }
+}
+
+impl MetricsTimer {
+    /// Creates a timer ticking every `interval`.
+    #[must_use]
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            sleep: Box::pin(time::sleep(interval)),
+        }
+    }
+
+    /// Resolves once per interval.
+    pub fn poll_tick(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        if self.sleep.poll_unpin(cx).is_pending() {
+            return Poll::Pending;
+        }
+        let deadline = time::Instant::now() + self.interval;
+        self.sleep.as_mut().reset(deadline);
+        Poll::Ready(())
+    }
+}
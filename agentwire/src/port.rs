@@ -45,8 +45,13 @@
 //! simple types. If a type contains dynamic data, e.g. vectors or strings, then
 //! the buffer size should be set to the maximum possible size of the data.
 //!
+//! Every message is preceded by a header with its length and checksum, which
+//! is validated by the receiving side. When the port is established, the agent
+//! process checks that it was built with the same protocol and
+//! [`SharedPort::SCHEMA_VERSION`] as the broker, and refuses to run otherwise.
+//!
 //! ```ignore
-//! use agentwire::{Agent, Port, SharedPort};
+//! use agentwire::{Agent, Port, SharedPort, port::MESSAGE_HEADER_SIZE};
 //! use rkyv::{Archive, Deserialize, Serialize};
 //!
 //! #[derive(Archive, Serialize, Deserialize)]
@@ -70,11 +75,15 @@
 //!
 //! impl SharedPort for Foo {
 //!     const SERIALIZED_INIT_SIZE: usize =
-//!         size_of::<usize>() + size_of::<<Foo as Archive>::Archived>();
+//!         MESSAGE_HEADER_SIZE + size_of::<<Foo as Archive>::Archived>();
 //!     const SERIALIZED_INPUT_SIZE: usize =
-//!         size_of::<usize>() + size_of::<<Input as Archive>::Archived>();
+//!         MESSAGE_HEADER_SIZE + size_of::<<Input as Archive>::Archived>();
 //!     const SERIALIZED_OUTPUT_SIZE: usize =
-//!         size_of::<usize>() + size_of::<<Output as Archive>::Archived>();
+//!         MESSAGE_HEADER_SIZE + size_of::<<Output as Archive>::Archived>();
+//!
+//!     // Bump whenever the archived layout of `Foo`, `Input`, or `Output`
+//!     // changes.
+//!     const SCHEMA_VERSION: u32 = 1;
 //! }
 //!
 //! #[derive(Archive, Serialize, Deserialize)]
@@ -88,6 +97,10 @@
 //! }
 //! ```
 
+use crate::{
+    agent::process::{Crash, CrashSlot},
+    metrics::PortMetrics,
+};
 use futures::{
     channel::{
         mpsc::{self, SendError},
@@ -121,22 +134,36 @@ use rkyv::{
 use std::{
     cmp::max,
     ffi::{CString, NulError},
-    fmt::Debug,
+    fmt::{self, Debug, Display},
     io,
     marker::PhantomData,
     mem,
     num::NonZeroUsize,
     os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
-    pin::Pin,
+    pin::{pin, Pin},
     ptr, slice,
-    task::{Context, Poll},
-    time::Instant,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    task::{ready, Context, Poll},
+    time::{Duration, Instant},
 };
 use thiserror::Error;
-use tokio::task;
+use tokio::{task, time};
 
 const SCRATCH_SIZE: usize = 1024;
 
+/// Size of the header preceding every message in shared memory: the message
+/// length and its checksum, both `u32`.
+pub const MESSAGE_HEADER_SIZE: usize = 8;
+
+/// Identifies agentwire shared memory, `AGNTWIRE` in ASCII.
+const PROTOCOL_MAGIC: u64 = u64::from_be_bytes(*b"AGNTWIRE");
+
+/// Version of the shared memory layout and the message framing.
+const PROTOCOL_VERSION: u32 = 1;
+
 /// Error occured during shared memory creation.
 #[derive(Error, Debug)]
 pub enum CreateSharedMemoryError {
@@ -168,6 +195,53 @@ pub enum DestroySharedMemoryError {
     SemDestroy(io::Error),
 }
 
+/// Error occured while connecting to the shared memory from the agent process.
+#[derive(Error, Debug)]
+pub enum ConnectSharedMemoryError {
+    /// Error occured during `mmap`.
+    #[error("mmap: {0}")]
+    Mmap(Errno),
+    /// The agent process is incompatible with the broker.
+    #[error("{0}")]
+    Handshake(HandshakeError),
+}
+
+/// Error occured while reading a message from the shared memory.
+#[derive(Error, Debug)]
+pub enum PortError {
+    /// The message doesn't match its header.
+    #[error("corrupted message: {0}")]
+    Corrupted(String),
+}
+
+/// Protocol identification exchanged by the broker and the agent process when
+/// a shared port is established.
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Handshake {
+    /// Protocol magic number.
+    pub magic: u64,
+    /// Version of the shared memory layout and the message framing.
+    pub protocol_version: u32,
+    /// [`SharedPort::SCHEMA_VERSION`] of the agent.
+    pub schema_version: u32,
+}
+
+/// The agent process and the broker disagree on the shared memory protocol,
+/// e.g. because the agent was spawned from a stale binary.
+#[derive(Error, Clone, Copy, PartialEq, Eq, Debug)]
+#[error("handshake mismatch: broker has {broker}, agent has {agent}")]
+pub struct HandshakeError {
+    /// Handshake of the broker.
+    pub broker: Handshake,
+    /// Handshake of the agent process.
+    pub agent: Handshake,
+}
+
+/// Shared slot for the [`HandshakeError`] of an agent process.
+#[derive(Clone, Default, Debug)]
+pub struct HandshakeSlot(Arc<Mutex<Option<HandshakeError>>>);
+
 /// Error returned by [`Outer::send_unjam`].
 #[derive(Error, Debug)]
 pub enum SendUnjamError {
@@ -218,17 +292,25 @@ where
     <Self::Output as Archive>::Archived:
         Deserialize<Self::Output, SharedDeserializeMap>,
 {
-    /// Buffer size for input messages. Must be at least `size_of::<usize>()`
-    /// for a zero-sized input.
+    /// Buffer size for input messages. Must be at least
+    /// [`MESSAGE_HEADER_SIZE`] for a zero-sized input.
     const SERIALIZED_INPUT_SIZE: usize;
 
-    /// Buffer size for output messages. Must be at least `size_of::<usize>()`
-    /// for a zero-sized output.
+    /// Buffer size for output messages. Must be at least
+    /// [`MESSAGE_HEADER_SIZE`] for a zero-sized output.
     const SERIALIZED_OUTPUT_SIZE: usize;
 
     /// Buffer size for initial agent state. Must be at least
-    /// `size_of::<usize>()` for a zero-sized state.
+    /// [`MESSAGE_HEADER_SIZE`] for a zero-sized state.
     const SERIALIZED_INIT_SIZE: usize;
+
+    /// Version of the serialized types, compared by the broker and the agent
+    /// process when the port is established.
+    ///
+    /// Bump it whenever the archived layout of the agent state, the input, or
+    /// the output changes, so that an agent process running a stale binary is
+    /// rejected instead of misreading the messages.
+    const SCHEMA_VERSION: u32 = 0;
 }
 
 /// Input message.
@@ -268,6 +350,10 @@ pub struct Outer<T: Port> {
     pub tx: OuterTx<T>,
     /// Receiver channel for the computation unit output.
     pub rx: OuterRx<T>,
+    heartbeat: Option<HeartbeatMonitor>,
+    crash: Option<CrashSlot>,
+    handshake: Option<HandshakeSlot>,
+    metrics: PortMetrics,
 }
 
 /// A handle for bi-directional communication for the inside of the computation
@@ -278,6 +364,7 @@ pub struct Inner<T: Port> {
     pub tx: InnerTx<T>,
     /// Receiver channel for the computation unit input.
     pub rx: InnerRx<T>,
+    metrics: PortMetrics,
 }
 
 /// A handle for bi-directional communication for the inside of the computation
@@ -292,6 +379,29 @@ where
 {
     shared_memory: *mut SharedMemory<T>,
     scratch: Option<FallbackScratch<HeapScratch<SCRATCH_SIZE>, AllocScratch>>,
+    heartbeat_interval: Option<Duration>,
+}
+
+/// Heartbeat settings for an agent running in another process.
+#[derive(Clone, Copy, Debug)]
+pub struct Heartbeat {
+    /// How often the agent sends a heartbeat.
+    pub interval: Duration,
+    /// Number of consecutive heartbeats the agent can miss before it's
+    /// considered unresponsive.
+    pub max_missed: u32,
+}
+
+/// Records the time of the latest heartbeat received from an agent.
+#[derive(Clone, Debug)]
+pub struct HeartbeatTracker {
+    heartbeat: Heartbeat,
+    last_seen: Arc<Mutex<Instant>>,
+}
+
+struct HeartbeatMonitor {
+    tracker: HeartbeatTracker,
+    timer: Option<Pin<Box<time::Sleep>>>,
 }
 
 /// Sender channel for the computation unit input.
@@ -313,13 +423,19 @@ type InitialInputs = Vec<(Box<[u8]>, Instant)>;
 pub fn new<T: Port>() -> (Inner<T>, Outer<T>) {
     let (input_tx, input_rx) = mpsc::channel(T::INPUT_CAPACITY);
     let (output_tx, output_rx) = mpsc::channel(T::OUTPUT_CAPACITY);
+    let metrics = PortMetrics::default();
     let inner = Inner {
         tx: output_tx,
         rx: input_rx,
+        metrics: metrics.clone(),
     };
     let outer = Outer {
         tx: input_tx,
         rx: output_rx,
+        heartbeat: None,
+        crash: None,
+        handshake: None,
+        metrics,
     };
     (inner, outer)
 }
@@ -436,14 +552,184 @@ impl<T: Port> Outer<T> {
         let mut recv = self.rx.next();
         loop {
             select_biased! {
-                result = send => break Ok(result?),
+                result = send => {
+                    result?;
+                    self.metrics.input_sent();
+                    break Ok(());
+                }
                 item = recv => match item {
-                    Some(item) => drop(item),
+                    Some(item) => {
+                        self.metrics.output_received();
+                        drop(item);
+                    }
                     None => break Err(SendUnjamError::Closed),
                 }
             }
         }
     }
+
+    /// Returns the message counters of this channel.
+    #[must_use]
+    pub fn metrics(&self) -> &PortMetrics {
+        &self.metrics
+    }
+}
+
+impl<T: Port> Inner<T> {
+    /// Returns the message counters of this channel.
+    #[must_use]
+    pub fn metrics(&self) -> &PortMetrics {
+        &self.metrics
+    }
+}
+
+impl Heartbeat {
+    /// Time without heartbeats after which the agent is considered
+    /// unresponsive.
+    #[must_use]
+    pub fn timeout(&self) -> Duration {
+        self.interval * self.max_missed
+    }
+}
+
+impl Handshake {
+    fn new(schema_version: u32) -> Self {
+        Self {
+            magic: PROTOCOL_MAGIC,
+            protocol_version: PROTOCOL_VERSION,
+            schema_version,
+        }
+    }
+
+    /// Returns `true` if the handshake was written, i.e. it's not zeroed.
+    fn is_set(&self) -> bool {
+        self.magic != 0
+    }
+}
+
+impl Display for Handshake {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "magic {:#018x}, protocol v{}, schema v{}",
+            self.magic, self.protocol_version, self.schema_version
+        )
+    }
+}
+
+impl HandshakeSlot {
+    /// Takes the recorded handshake error, if any.
+    #[must_use]
+    pub fn take(&self) -> Option<HandshakeError> {
+        self.0.lock().unwrap().take()
+    }
+
+    pub(crate) fn set(&self, error: HandshakeError) {
+        *self.0.lock().unwrap() = Some(error);
+    }
+}
+
+impl HeartbeatTracker {
+    /// Creates a new tracker, counting the current time as the latest
+    /// heartbeat.
+    #[must_use]
+    pub fn new(heartbeat: Heartbeat) -> Self {
+        Self {
+            heartbeat,
+            last_seen: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+
+    /// Heartbeat settings.
+    #[must_use]
+    pub fn heartbeat(&self) -> Heartbeat {
+        self.heartbeat
+    }
+
+    /// Records a heartbeat received now.
+    pub fn beat(&self) {
+        *self.last_seen.lock().unwrap() = Instant::now();
+    }
+
+    /// Returns the time of the latest heartbeat.
+    #[must_use]
+    pub fn last_seen(&self) -> Instant {
+        *self.last_seen.lock().unwrap()
+    }
+}
+
+impl HeartbeatMonitor {
+    fn poll_unresponsive(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        loop {
+            let deadline = time::Instant::from_std(
+                self.tracker.last_seen() + self.tracker.heartbeat.timeout(),
+            );
+            if time::Instant::now() >= deadline {
+                return Poll::Ready(());
+            }
+            let timer = self
+                .timer
+                .get_or_insert_with(|| Box::pin(time::sleep_until(deadline)));
+            if timer.deadline() != deadline {
+                timer.as_mut().reset(deadline);
+            }
+            ready!(timer.as_mut().poll(cx));
+        }
+    }
+}
+
+impl<T: Port> Outer<T> {
+    /// Sets the tracker of the agent heartbeats, making
+    /// [`poll_unresponsive`](Self::poll_unresponsive) report missed
+    /// heartbeats.
+    pub fn set_heartbeat_tracker(&mut self, tracker: HeartbeatTracker) {
+        self.heartbeat = Some(HeartbeatMonitor {
+            tracker,
+            timer: None,
+        });
+    }
+
+    /// Returns the tracker of the agent heartbeats if set.
+    #[must_use]
+    pub fn heartbeat_tracker(&self) -> Option<&HeartbeatTracker> {
+        self.heartbeat.as_ref().map(|monitor| &monitor.tracker)
+    }
+
+    /// Resolves when the agent has missed too many heartbeats in a row. Never
+    /// resolves if the agent doesn't send heartbeats.
+    pub fn poll_unresponsive(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        match &mut self.heartbeat {
+            Some(monitor) => monitor.poll_unresponsive(cx),
+            None => Poll::Pending,
+        }
+    }
+
+    /// Sets the slot where the agent process records its crash, making
+    /// [`take_crash`](Self::take_crash) report it.
+    pub fn set_crash_slot(&mut self, slot: CrashSlot) {
+        self.crash = Some(slot);
+    }
+
+    /// Takes the crash of the agent process if it was terminated by a signal.
+    /// The crash is recorded before the port is closed.
+    #[must_use]
+    pub fn take_crash(&self) -> Option<Crash> {
+        self.crash.as_ref().and_then(CrashSlot::take)
+    }
+
+    /// Sets the slot where a failed handshake with the agent process is
+    /// recorded, making [`take_handshake_error`](Self::take_handshake_error)
+    /// report it.
+    pub fn set_handshake_slot(&mut self, slot: HandshakeSlot) {
+        self.handshake = Some(slot);
+    }
+
+    /// Takes the handshake error if the agent process was rejected as
+    /// incompatible. The error is recorded before the port is closed.
+    #[must_use]
+    pub fn take_handshake_error(&self) -> Option<HandshakeError> {
+        self.handshake.as_ref().and_then(HandshakeSlot::take)
+    }
 }
 
 impl<T: Port> Stream for Outer<T> {
@@ -453,7 +739,11 @@ impl<T: Port> Stream for Outer<T> {
         mut self: Pin<&mut Self>,
         cx: &mut Context<'_>,
     ) -> Poll<Option<Self::Item>> {
-        Pin::new(&mut self.rx).poll_next(cx)
+        let poll = Pin::new(&mut self.rx).poll_next(cx);
+        if let Poll::Ready(Some(_)) = poll {
+            self.metrics.output_received();
+        }
+        poll
     }
 }
 
@@ -474,7 +764,9 @@ impl<T: Port> Sink<Input<T>> for Outer<T> {
     }
 
     fn start_send(mut self: Pin<&mut Self>, item: Input<T>) -> Result<(), Self::Error> {
-        Pin::new(&mut self.tx).start_send(item)
+        Pin::new(&mut self.tx).start_send(item)?;
+        self.metrics.input_sent();
+        Ok(())
     }
 
     fn poll_flush(
@@ -499,7 +791,11 @@ impl<T: Port> Stream for Inner<T> {
         mut self: Pin<&mut Self>,
         cx: &mut Context<'_>,
     ) -> Poll<Option<Self::Item>> {
-        Pin::new(&mut self.rx).poll_next(cx)
+        let poll = Pin::new(&mut self.rx).poll_next(cx);
+        if let Poll::Ready(Some(_)) = poll {
+            self.metrics.input_received();
+        }
+        poll
     }
 }
 
@@ -523,7 +819,9 @@ impl<T: Port> Sink<Output<T>> for Inner<T> {
         mut self: Pin<&mut Self>,
         item: Output<T>,
     ) -> Result<(), Self::Error> {
-        Pin::new(&mut self.tx).start_send(item)
+        Pin::new(&mut self.tx).start_send(item)?;
+        self.metrics.output_sent();
+        Ok(())
     }
 
     fn poll_flush(
@@ -548,6 +846,10 @@ impl<T: Port> Sink<Output<T>> for Inner<T> {
 // 1. Input buffer 0
 // 2. Input buffer 1
 // 3. Output buffer
+//
+// The handshakes come first and the layout is fixed, so that they can be
+// compared even if the rest of the header differs between the binaries.
+#[repr(C)]
 struct SharedMemory<T>
 where
     T: SharedPort + Debug + Archive + for<'a> Serialize<SharedSerializer<'a>>,
@@ -556,6 +858,8 @@ where
     T::Output: Archive + for<'a> Serialize<SharedSerializer<'a>>,
     <T::Output as Archive>::Archived: Deserialize<T::Output, SharedDeserializeMap>,
 {
+    broker_handshake: Handshake,
+    agent_handshake: Handshake,
     input_ts: [Instant; 2],
     input_tx: sem_t,
     input_rx: sem_t,
@@ -564,6 +868,7 @@ where
     output_ts: Instant,
     output_tx: sem_t,
     output_rx: sem_t,
+    heartbeat: AtomicU64,
     _marker: PhantomData<T>,
 }
 
@@ -578,7 +883,7 @@ where
     fn size_of() -> NonZeroUsize {
         let size = mem::size_of::<Self>()
             + max(
-                mem::size_of::<usize>() + mem::size_of::<T::Archived>(),
+                MESSAGE_HEADER_SIZE + mem::size_of::<T::Archived>(),
                 T::SERIALIZED_INPUT_SIZE * 2 + T::SERIALIZED_OUTPUT_SIZE,
             );
         NonZeroUsize::new(size).expect("to always be positive")
@@ -619,13 +924,20 @@ where
                 .map_err(CreateSharedMemoryError::SemInit)?;
             sem_init(&mut (*ptr).output_rx, 1, 0)
                 .map_err(CreateSharedMemoryError::SemInit)?;
+            (*ptr).broker_handshake = Handshake::new(T::SCHEMA_VERSION);
+            (*ptr).agent_handshake = Handshake {
+                magic: 0,
+                protocol_version: 0,
+                schema_version: 0,
+            };
             (*ptr).input_count = 0;
             (*ptr).input_index = 0;
+            (*ptr).heartbeat = AtomicU64::new(0);
         }
         Ok((ptr, fd))
     }
 
-    unsafe fn from_fd(fd: OwnedFd) -> Result<*mut Self, Errno> {
+    unsafe fn from_fd(fd: OwnedFd) -> Result<*mut Self, ConnectSharedMemoryError> {
         let ptr = unsafe {
             mmap(
                 None,
@@ -634,13 +946,34 @@ where
                 MapFlags::MAP_SHARED,
                 fd.as_raw_fd(),
                 0,
-            )?
+            )
+            .map_err(ConnectSharedMemoryError::Mmap)?
             .cast::<Self>()
         };
         drop(fd);
+        let agent = Handshake::new(T::SCHEMA_VERSION);
+        let broker = unsafe {
+            (*ptr).agent_handshake = agent;
+            (*ptr).broker_handshake
+        };
+        if broker != agent {
+            unsafe {
+                let _ = munmap(ptr.cast(), Self::size_of().get());
+            }
+            return Err(ConnectSharedMemoryError::Handshake(HandshakeError {
+                broker,
+                agent,
+            }));
+        }
         Ok(ptr)
     }
 
+    /// Returns the handshake error if the agent process was rejected.
+    fn handshake_error(&self) -> Option<HandshakeError> {
+        let (broker, agent) = (self.broker_handshake, self.agent_handshake);
+        (agent.is_set() && agent != broker).then_some(HandshakeError { broker, agent })
+    }
+
     unsafe fn destroy(ptr: *mut Self) -> Result<(), DestroySharedMemoryError> {
         unsafe {
             sem_destroy(&mut (*ptr).input_tx)
@@ -700,31 +1033,54 @@ where
     <T::Output as Archive>::Archived: Deserialize<T::Output, SharedDeserializeMap>,
 {
     /// Sets up shared memory for this channel.
+    ///
+    /// If `heartbeat` is set, the heartbeats sent by the remote side are
+    /// recorded to it.
+    ///
+    /// The returned future tears the shared memory down. Besides the channel
+    /// and the pending inputs, it returns the handshake error if the remote
+    /// side rejected the shared memory as incompatible.
+    #[allow(clippy::type_complexity)]
     pub fn into_shared_memory(
         self,
         name: &str,
         init_state: &T,
         initial_inputs: InitialInputs,
+        heartbeat: Option<HeartbeatTracker>,
     ) -> Result<
         (
             OwnedFd,
-            impl Future<Output = Result<(Self, InitialInputs), DestroySharedMemoryError>>,
+            impl Future<
+                Output = Result<
+                    (Self, InitialInputs, Option<HandshakeError>),
+                    DestroySharedMemoryError,
+                >,
+            >,
         ),
         CreateSharedMemoryError,
     > {
-        let Self { tx, rx } = self;
+        let Self { tx, rx, metrics } = self;
         let (ptr, fd) = unsafe { SharedMemory::<T>::create(name)? };
         let addr = ptr as usize;
         let (stop_tx_tx, stop_tx_rx) = oneshot::channel();
         let (stop_rx_tx, stop_rx_rx) = oneshot::channel();
+        let (stop_heartbeat_tx, stop_heartbeat_rx) = oneshot::channel();
         set_init_state(addr, init_state);
-        let tx_task = spawn_shared_tx_task(tx, addr, stop_tx_rx);
-        let rx_task = spawn_shared_rx_task(rx, addr, stop_rx_rx, initial_inputs);
+        let tx_task = spawn_shared_tx_task(tx, metrics.clone(), addr, stop_tx_rx);
+        let rx_task =
+            spawn_shared_rx_task(rx, metrics.clone(), addr, stop_rx_rx, initial_inputs);
+        let heartbeat_task = heartbeat.map(|tracker| {
+            spawn_shared_heartbeat_task::<T>(tracker, addr, stop_heartbeat_rx)
+        });
         let close = async move {
             let _ = stop_tx_tx.send(());
             let _ = stop_rx_tx.send(());
+            let _ = stop_heartbeat_tx.send(());
             let tx = tx_task.await.unwrap();
             let (rx, mut inputs) = rx_task.await.unwrap();
+            if let Some(heartbeat_task) = heartbeat_task {
+                heartbeat_task.await.unwrap();
+            }
             unsafe {
                 let shared_memory = addr as *mut SharedMemory<T>;
                 assert!((*shared_memory).input_count <= 2);
@@ -738,8 +1094,9 @@ where
                     let input_ts = (*shared_memory).input_ts[i];
                     inputs.push((input, input_ts));
                 }
+                let handshake_error = (*shared_memory).handshake_error();
                 SharedMemory::destroy(shared_memory)?;
-                Ok((Self { tx, rx }, inputs))
+                Ok((Self { tx, rx, metrics }, inputs, handshake_error))
             }
         };
         Ok((fd, close))
@@ -755,16 +1112,64 @@ where
     <T::Output as Archive>::Archived: Deserialize<T::Output, SharedDeserializeMap>,
 {
     /// Creates a channel from the shared memory.
-    pub fn from_shared_memory(shmem_fd: OwnedFd) -> Result<Self, Errno> {
+    ///
+    /// Fails with [`ConnectSharedMemoryError::Handshake`] if the broker uses a
+    /// different protocol or [`SharedPort::SCHEMA_VERSION`].
+    pub fn from_shared_memory(
+        shmem_fd: OwnedFd,
+    ) -> Result<Self, ConnectSharedMemoryError> {
         Ok(RemoteInner {
             shared_memory: unsafe { SharedMemory::<T>::from_fd(shmem_fd)? },
             scratch: Some(FallbackScratch::default()),
+            heartbeat_interval: None,
         })
     }
 
+    /// Makes the blocking calls send a heartbeat at least every `interval`.
+    ///
+    /// Heartbeats are sent only from within the methods of this type, so an
+    /// agent stuck outside of them stops sending heartbeats.
+    pub fn set_heartbeat_interval(&mut self, interval: Duration) {
+        self.heartbeat_interval = Some(interval);
+        self.beat();
+    }
+
+    fn beat(&mut self) {
+        if self.heartbeat_interval.is_some() {
+            unsafe {
+                (*self.shared_memory)
+                    .heartbeat
+                    .fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    unsafe fn wait(&mut self, sem: *mut sem_t) {
+        let Some(interval) = self.heartbeat_interval else {
+            unsafe { sem_wait(sem).expect("semaphore failure") };
+            return;
+        };
+        loop {
+            self.beat();
+            match unsafe { sem_timedwait(sem, interval) } {
+                Ok(()) => break,
+                Err(err)
+                    if matches!(
+                        err.raw_os_error(),
+                        Some(libc::ETIMEDOUT | libc::EINTR)
+                    ) => {}
+                Err(err) => panic!("semaphore failure: {err}"),
+            }
+        }
+    }
+
     /// Reads the initial state.
+    ///
+    /// # Errors
+    ///
+    /// If the serialized state is corrupted.
     #[allow(clippy::missing_panics_doc)]
-    pub fn init_state(&mut self) -> &<T as Archive>::Archived {
+    pub fn init_state(&mut self) -> Result<&<T as Archive>::Archived, PortError> {
         unsafe {
             let init_state =
                 deserialize_message::<T>((*self.shared_memory).init_state());
@@ -774,32 +1179,45 @@ where
     }
 
     /// Waits for a value on the receiver half.
+    ///
+    /// # Errors
+    ///
+    /// If the received message is corrupted. The message is skipped, so the
+    /// next call receives the next message.
     #[allow(clippy::missing_panics_doc)]
-    pub fn recv(&mut self) -> ArchivedInput<'_, T> {
+    pub fn recv(&mut self) -> Result<ArchivedInput<'_, T>, PortError> {
         unsafe {
-            sem_wait(&mut (*self.shared_memory).input_rx).expect("semaphore failure");
+            self.wait(&mut (*self.shared_memory).input_rx);
             let input_index = 1 - (*self.shared_memory).input_index;
             let value = deserialize_message::<T::Input>(
                 (*self.shared_memory).input(input_index),
             );
             let source_ts = (*self.shared_memory).input_ts[input_index];
             sem_post(&mut (*self.shared_memory).input_tx).expect("semaphore failure");
-            ArchivedInput { value, source_ts }
+            Ok(ArchivedInput {
+                value: value?,
+                source_ts,
+            })
         }
     }
 
     /// Tries to receive a value on the receiver half. This function doesn't
     /// block and returns `None` if the channel is empty.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`recv`](Self::recv).
     #[allow(clippy::missing_panics_doc)]
-    pub fn try_recv(&mut self) -> Option<ArchivedInput<'_, T>> {
+    pub fn try_recv(&mut self) -> Result<Option<ArchivedInput<'_, T>>, PortError> {
         unsafe {
             if sem_getvalue(&mut (*self.shared_memory).input_rx)
                 .expect("semaphore failure")
                 > 0
             {
-                Some(self.recv())
+                self.recv().map(Some)
             } else {
-                None
+                self.beat();
+                Ok(None)
             }
         }
     }
@@ -808,7 +1226,7 @@ where
     #[allow(clippy::missing_panics_doc)]
     pub fn send(&mut self, output: &Output<T>) {
         unsafe {
-            sem_wait(&mut (*self.shared_memory).output_tx).expect("semaphore failure");
+            self.wait(&mut (*self.shared_memory).output_tx);
             serialize_message(
                 (*self.shared_memory).output(),
                 &mut self.scratch,
@@ -831,12 +1249,15 @@ where
                 self.send(output);
                 true
             } else {
+                self.beat();
                 false
             }
         }
     }
 }
 
+/// Serializes `value` into `buf`, preceded by a header with the message length
+/// and checksum.
 fn serialize_message<T>(
     buf: &mut [u8],
     scratch: &mut Option<FallbackScratch<HeapScratch<SCRATCH_SIZE>, AllocScratch>>,
@@ -844,8 +1265,9 @@ fn serialize_message<T>(
 ) where
     T: Archive + for<'a> Serialize<SharedSerializer<'a>> + Debug,
 {
+    let (header, body) = buf.split_at_mut(MESSAGE_HEADER_SIZE);
     let mut serializer = CompositeSerializer::new(
-        BufferSerializer::new(&mut buf[mem::size_of::<usize>()..]),
+        BufferSerializer::new(body),
         scratch.take().unwrap(),
         SharedSerializeMap::new(), // reuse of this map doesn't work
     );
@@ -854,17 +1276,49 @@ fn serialize_message<T>(
         .expect("failed to serialize an IPC message");
     let size = serializer.pos();
     let (_, c, _) = serializer.into_components();
-    buf[..mem::size_of::<usize>()].copy_from_slice(&size.to_ne_bytes());
+    let len = u32::try_from(size).expect("IPC message to be smaller than 4 GiB");
+    header[..4].copy_from_slice(&len.to_ne_bytes());
+    header[4..].copy_from_slice(&checksum(&body[..size]).to_ne_bytes());
     *scratch = Some(c);
 }
 
-unsafe fn deserialize_message<T>(buf: &[u8]) -> &T::Archived
+/// Validates the message header and returns the archived message.
+unsafe fn deserialize_message<T>(buf: &[u8]) -> Result<&T::Archived, PortError>
 where
     T: Archive + for<'a> Serialize<SharedSerializer<'a>>,
 {
-    let size = usize::from_ne_bytes(buf[..mem::size_of::<usize>()].try_into().unwrap());
-    let bytes = &buf[mem::size_of::<usize>()..mem::size_of::<usize>() + size];
-    unsafe { rkyv::archived_root::<T>(bytes) }
+    let bytes = message_bytes(buf)?;
+    Ok(unsafe { rkyv::archived_root::<T>(bytes) })
+}
+
+/// Returns the message bytes following the header, if they match the header.
+fn message_bytes(buf: &[u8]) -> Result<&[u8], PortError> {
+    let (header, body) = buf.split_at(MESSAGE_HEADER_SIZE);
+    let len = u32::from_ne_bytes(header[..4].try_into().unwrap());
+    let expected = u32::from_ne_bytes(header[4..].try_into().unwrap());
+    let bytes = usize::try_from(len)
+        .ok()
+        .and_then(|len| body.get(..len))
+        .ok_or_else(|| {
+            PortError::Corrupted(format!(
+                "length {len} exceeds the buffer of {} bytes",
+                body.len()
+            ))
+        })?;
+    let actual = checksum(bytes);
+    if actual != expected {
+        return Err(PortError::Corrupted(format!(
+            "checksum {actual:#010x} doesn't match the header {expected:#010x}"
+        )));
+    }
+    Ok(bytes)
+}
+
+/// FNV-1a hash of `bytes`.
+fn checksum(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c_9dc5, |hash, &byte| {
+        (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193)
+    })
 }
 
 fn set_init_state<T>(addr: usize, init_state: &T)
@@ -884,6 +1338,7 @@ where
 
 fn spawn_shared_tx_task<T>(
     mut tx: InnerTx<T>,
+    metrics: PortMetrics,
     addr: usize,
     mut stop_tx_rx: oneshot::Receiver<()>,
 ) -> task::JoinHandle<InnerTx<T>>
@@ -913,22 +1368,33 @@ where
                 sem_wait.await.unwrap();
                 break;
             }
-            let (value, source_ts) = unsafe {
+            let output = unsafe {
                 let shared_memory = addr as *mut SharedMemory<T>;
-                let archived =
-                    deserialize_message::<T::Output>((*shared_memory).output());
-                // Reuse of `SharedDeserializeMap` doesn't work
-                let value = archived
-                    .deserialize(&mut SharedDeserializeMap::new())
-                    .unwrap();
-                let source_ts = (*shared_memory).output_ts;
+                let output = deserialize_message::<T::Output>(
+                    (*shared_memory).output(),
+                )
+                .map(|archived| {
+                    // Reuse of `SharedDeserializeMap` doesn't work
+                    let value = archived
+                        .deserialize(&mut SharedDeserializeMap::new())
+                        .unwrap();
+                    (value, (*shared_memory).output_ts)
+                });
                 sem_post(&mut (*shared_memory).output_tx).expect("semaphore failure");
-                (value, source_ts)
+                output
+            };
+            let (value, source_ts) = match output {
+                Ok(output) => output,
+                Err(err) => {
+                    tracing::error!("Dropping an agent output: {err}");
+                    sem_wait = spawn_sem_wait();
+                    continue;
+                }
             };
             let mut send = tx.feed(Output { value, source_ts });
             match select(&mut stop_tx_rx, &mut send).await {
                 Either::Left((_, _)) | Either::Right((Err(_), _)) => break,
-                Either::Right((Ok(result), _)) => result,
+                Either::Right((Ok(()), _)) => metrics.output_sent(),
             }
             sem_wait = spawn_sem_wait();
         }
@@ -938,6 +1404,7 @@ where
 
 fn spawn_shared_rx_task<T>(
     mut rx: InnerRx<T>,
+    metrics: PortMetrics,
     addr: usize,
     mut stop_rx_rx: oneshot::Receiver<()>,
     mut initial_inputs: InitialInputs,
@@ -974,7 +1441,10 @@ where
             } else {
                 match select(&mut stop_rx_rx, rx.next()).await {
                     Either::Left((_, _)) | Either::Right((None, _)) => break,
-                    Either::Right((Some(input), _)) => Either::Right(input),
+                    Either::Right((Some(input), _)) => {
+                        metrics.input_received();
+                        Either::Right(input)
+                    }
                 }
             };
             unsafe {
@@ -1009,6 +1479,40 @@ where
     })
 }
 
+fn spawn_shared_heartbeat_task<T>(
+    tracker: HeartbeatTracker,
+    addr: usize,
+    mut stop_heartbeat_rx: oneshot::Receiver<()>,
+) -> task::JoinHandle<()>
+where
+    T: SharedPort + Debug + Archive + for<'a> Serialize<SharedSerializer<'a>>,
+    <T as Archive>::Archived: Deserialize<T, Infallible>,
+    T::Input: Archive + for<'a> Serialize<SharedSerializer<'a>>,
+    T::Output: Archive + for<'a> Serialize<SharedSerializer<'a>>,
+    <T::Output as Archive>::Archived: Deserialize<T::Output, SharedDeserializeMap>,
+{
+    task::spawn_local(async move {
+        let mut interval = time::interval(tracker.heartbeat().interval);
+        interval.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+        let mut last_count = 0;
+        loop {
+            if let Either::Left(_) =
+                select(&mut stop_heartbeat_rx, pin!(interval.tick())).await
+            {
+                break;
+            }
+            let count = unsafe {
+                let shared_memory = addr as *mut SharedMemory<T>;
+                (*shared_memory).heartbeat.load(Ordering::Relaxed)
+            };
+            if count != last_count {
+                last_count = count;
+                tracker.beat();
+            }
+        }
+    })
+}
+
 unsafe fn sem_init(sem: *mut sem_t, pshared: c_int, value: c_uint) -> io::Result<()> {
     let result = unsafe { libc::sem_init(sem, pshared, value) };
     if result == -1 {
@@ -1054,3 +1558,109 @@ unsafe fn sem_wait(sem: *mut sem_t) -> io::Result<()> {
         Ok(())
     }
 }
+
+// Not exposed by the `libc` crate. Available since glibc 2.30.
+extern "C" {
+    fn sem_clockwait(
+        sem: *mut sem_t,
+        clockid: libc::clockid_t,
+        abstime: *const libc::timespec,
+    ) -> c_int;
+}
+
+/// Like `sem_wait`, but fails with `ETIMEDOUT` after `timeout`. The deadline is
+/// measured on the monotonic clock, so that wall clock jumps don't affect it.
+#[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+unsafe fn sem_timedwait(sem: *mut sem_t, timeout: Duration) -> io::Result<()> {
+    let mut deadline = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    if unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut deadline) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    deadline.tv_sec += timeout.as_secs() as libc::time_t;
+    deadline.tv_nsec += libc::c_long::from(timeout.subsec_nanos() as i32);
+    if deadline.tv_nsec >= 1_000_000_000 {
+        deadline.tv_sec += 1;
+        deadline.tv_nsec -= 1_000_000_000;
+    }
+    let result = unsafe { sem_clockwait(sem, libc::CLOCK_MONOTONIC, &deadline) };
+    if result == -1 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Message buffer aligned for the archived values.
+    #[repr(align(16))]
+    struct Buffer([u8; MESSAGE_HEADER_SIZE + 16]);
+
+    fn serialize(value: u32) -> Buffer {
+        let mut buf = Buffer([0; MESSAGE_HEADER_SIZE + 16]);
+        serialize_message(&mut buf.0, &mut Some(FallbackScratch::default()), &value);
+        buf
+    }
+
+    #[test]
+    fn test_message_roundtrip() {
+        let buf = serialize(0xdead_beef);
+        let value = unsafe { deserialize_message::<u32>(&buf.0) }.unwrap();
+        assert_eq!(*value, 0xdead_beef);
+    }
+
+    #[test]
+    fn test_corrupted_message_body() {
+        let mut buf = serialize(42);
+        buf.0[MESSAGE_HEADER_SIZE] ^= 0xff;
+        let result = unsafe { deserialize_message::<u32>(&buf.0) };
+        assert!(
+            matches!(&result, Err(PortError::Corrupted(reason)) if reason.contains("checksum")),
+            "unexpected result: {result:?}"
+        );
+    }
+
+    #[test]
+    fn test_corrupted_message_length() {
+        for len in [17, u32::MAX] {
+            let mut buf = serialize(42);
+            buf.0[..4].copy_from_slice(&len.to_ne_bytes());
+            let result = unsafe { deserialize_message::<u32>(&buf.0) };
+            assert!(
+                matches!(&result, Err(PortError::Corrupted(reason)) if reason.contains("length")),
+                "{len}: unexpected result: {result:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_handshake_display() {
+        let handshake = Handshake::new(3);
+        assert!(handshake.is_set());
+        assert_eq!(
+            handshake.to_string(),
+            "magic 0x41474e5457495245, protocol v1, schema v3"
+        );
+    }
+
+    #[test]
+    fn test_sem_timedwait() {
+        let mut sem = mem::MaybeUninit::<sem_t>::uninit();
+        unsafe {
+            sem_init(sem.as_mut_ptr(), 0, 0).unwrap();
+            let start = Instant::now();
+            let err =
+                sem_timedwait(sem.as_mut_ptr(), Duration::from_millis(50)).unwrap_err();
+            assert_eq!(err.raw_os_error(), Some(libc::ETIMEDOUT));
+            assert!(start.elapsed() >= Duration::from_millis(50));
+            sem_post(sem.as_mut_ptr()).unwrap();
+            sem_timedwait(sem.as_mut_ptr(), Duration::from_millis(50)).unwrap();
+            sem_destroy(sem.as_mut_ptr()).unwrap();
+        }
+    }
+}
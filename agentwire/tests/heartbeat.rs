@@ -0,0 +1,137 @@
+use agentwire::{
+    agent::{self, Process as _},
+    port::{self, Port, SharedPort},
+    Agent, Broker, BrokerError, BrokerFlow,
+};
+use futures::prelude::*;
+use rkyv::{Archive, Deserialize, Serialize};
+use std::{
+    mem::size_of,
+    task::{Context, Poll},
+    thread,
+    time::{Duration, Instant},
+};
+use thiserror::Error;
+use tokio::time;
+
+/// Blocks forever after receiving an input.
+#[derive(Clone, Default, Archive, Serialize, Deserialize, Debug)]
+struct Blocker;
+
+impl Port for Blocker {
+    type Input = u32;
+    type Output = u32;
+
+    const INPUT_CAPACITY: usize = 0;
+    const OUTPUT_CAPACITY: usize = 0;
+}
+
+impl SharedPort for Blocker {
+    const SERIALIZED_INIT_SIZE: usize =
+        port::MESSAGE_HEADER_SIZE + size_of::<<Blocker as Archive>::Archived>();
+    const SERIALIZED_INPUT_SIZE: usize =
+        port::MESSAGE_HEADER_SIZE + size_of::<<u32 as Archive>::Archived>();
+    const SERIALIZED_OUTPUT_SIZE: usize =
+        port::MESSAGE_HEADER_SIZE + size_of::<<u32 as Archive>::Archived>();
+}
+
+impl Agent for Blocker {
+    const NAME: &'static str = "blocker";
+}
+
+#[derive(Error, Debug)]
+pub enum BlockerError {
+    #[error("port: {0}")]
+    Port(#[from] port::PortError),
+}
+
+impl agent::Process for Blocker {
+    type Error = BlockerError;
+
+    fn run(self, mut port: port::RemoteInner<Self>) -> Result<(), Self::Error> {
+        port.recv()?;
+        loop {
+            thread::park();
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum Error {}
+
+trait Plan {
+    fn handle_blocker(
+        &mut self,
+        _broker: &mut Broker,
+        _output: port::Output<Blocker>,
+    ) -> Result<BrokerFlow, Error> {
+        Ok(BrokerFlow::Continue)
+    }
+}
+
+#[derive(Broker)]
+#[broker(plan = Plan, error = Error, poll_extra)]
+struct Broker {
+    #[agent(process, heartbeat = "100ms", heartbeat_misses = 3)]
+    blocker: agent::Cell<Blocker>,
+}
+
+impl Broker {
+    fn handle_blocker(
+        &mut self,
+        plan: &mut dyn Plan,
+        output: port::Output<Blocker>,
+    ) -> Result<BrokerFlow, Error> {
+        plan.handle_blocker(self, output)
+    }
+
+    fn poll_extra(
+        &mut self,
+        _plan: &mut dyn Plan,
+        _cx: &mut Context<'_>,
+        _fence: Instant,
+    ) -> Result<Option<Poll<()>>, Error> {
+        Ok(Some(Poll::Pending))
+    }
+}
+
+fn init() {
+    agent::process::init(|name, fd| match name {
+        "blocker" => Ok(Blocker::call(fd)?),
+        _ => panic!("unregistered agent {name}"),
+    });
+}
+
+#[agentwire::test(init = init)]
+async fn test_heartbeat() {
+    struct TestPlan;
+    impl Plan for TestPlan {}
+
+    let mut broker = new_broker!();
+    let mut plan = TestPlan;
+    broker.enable_blocker().unwrap();
+
+    // An agent waiting for input keeps sending heartbeats.
+    let idle = time::timeout(Duration::from_secs(1), broker.run(&mut plan)).await;
+    assert!(idle.is_err(), "idle agent reported: {idle:?}");
+
+    broker
+        .blocker
+        .enabled()
+        .unwrap()
+        .send(port::Input::new(0))
+        .await
+        .unwrap();
+    let blocked_at = Instant::now();
+    let result = time::timeout(Duration::from_secs(5), broker.run(&mut plan))
+        .await
+        .expect("blocked agent was not detected");
+    assert!(matches!(
+        result,
+        Err(BrokerError::AgentUnresponsive("blocker"))
+    ));
+    // 3 missed heartbeats, plus up to one interval for the broker to notice.
+    assert!(blocked_at.elapsed() < Duration::from_millis(800));
+
+    broker.blocker.kill().await;
+}
@@ -0,0 +1,169 @@
+use agentwire::{
+    agent,
+    metrics::{AgentMetrics, PortMetrics},
+    port::{self, Port},
+    Agent, Broker, BrokerFlow,
+};
+use futures::{channel::mpsc::SendError, prelude::*};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+#[derive(Default)]
+struct Doubler;
+
+impl Port for Doubler {
+    type Input = u32;
+    type Output = u32;
+
+    const INPUT_CAPACITY: usize = 3;
+    const OUTPUT_CAPACITY: usize = 3;
+}
+
+impl Agent for Doubler {
+    const NAME: &'static str = "doubler";
+}
+
+impl agent::Task for Doubler {
+    type Error = SendError;
+
+    async fn run(self, mut port: port::Inner<Self>) -> Result<(), Self::Error> {
+        while let Some(x) = port.next().await {
+            port.send(x.chain(x.value * 2)).await?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum Error {}
+
+trait Plan {
+    fn handle_doubler(
+        &mut self,
+        broker: &mut Broker,
+        output: port::Output<Doubler>,
+    ) -> Result<BrokerFlow, Error>;
+}
+
+#[derive(Broker)]
+#[broker(plan = Plan, error = Error, metrics_interval = "10ms")]
+struct Broker {
+    #[agent(task)]
+    doubler: agent::Cell<Doubler>,
+}
+
+impl Broker {
+    fn handle_doubler(
+        &mut self,
+        plan: &mut dyn Plan,
+        output: port::Output<Doubler>,
+    ) -> Result<BrokerFlow, Error> {
+        plan.handle_doubler(self, output)
+    }
+}
+
+struct TestPlan {
+    results: Vec<u32>,
+}
+
+impl Plan for TestPlan {
+    fn handle_doubler(
+        &mut self,
+        _broker: &mut Broker,
+        output: port::Output<Doubler>,
+    ) -> Result<BrokerFlow, Error> {
+        self.results.push(output.value);
+        if self.results.len() == 3 {
+            Ok(BrokerFlow::Break)
+        } else {
+            Ok(BrokerFlow::Continue)
+        }
+    }
+}
+
+#[agentwire::test]
+async fn test_broker_metrics() {
+    let mut broker = new_broker!();
+    let mut plan = TestPlan {
+        results: Vec::new(),
+    };
+    assert!(broker.metrics().agents.is_empty());
+    broker.enable_doubler().unwrap();
+
+    let fence = Instant::now();
+    for x in 1..=3 {
+        broker
+            .doubler
+            .enabled()
+            .unwrap()
+            .send(port::Input::new(x))
+            .await
+            .unwrap();
+    }
+    broker.run_with_fence(&mut plan, fence).await.unwrap();
+    assert_eq!(plan.results, [2, 4, 6]);
+
+    let metrics = broker.metrics();
+    let doubler = metrics.agents["doubler"];
+    assert_eq!(doubler.inputs_sent, 3);
+    assert_eq!(doubler.inputs_received, 3);
+    assert_eq!(doubler.outputs_sent, 3);
+    assert_eq!(doubler.outputs_received, 3);
+    assert_eq!(doubler.input_queue_depth, 0);
+    assert_eq!(doubler.output_queue_depth, 0);
+    assert_eq!(doubler.handler_calls, 3);
+    assert!(doubler.handler_latency.is_some());
+
+    // Disabled agents keep reporting their metrics.
+    broker.disable_doubler();
+    assert_eq!(broker.metrics(), metrics);
+}
+
+#[agentwire::test]
+async fn test_queue_depth() {
+    let (mut inner, mut outer) = port::new::<Doubler>();
+    outer.send(port::Input::new(1)).await.unwrap();
+    outer.send(port::Input::new(2)).await.unwrap();
+    assert_eq!(
+        outer.metrics().snapshot(),
+        AgentMetrics {
+            inputs_sent: 2,
+            input_queue_depth: 2,
+            ..AgentMetrics::default()
+        }
+    );
+
+    let input = inner.next().await.unwrap();
+    inner.send(input.chain(input.value * 2)).await.unwrap();
+    assert_eq!(
+        outer.metrics().snapshot(),
+        AgentMetrics {
+            inputs_sent: 2,
+            inputs_received: 1,
+            outputs_sent: 1,
+            input_queue_depth: 1,
+            output_queue_depth: 1,
+            ..AgentMetrics::default()
+        }
+    );
+
+    assert_eq!(outer.next().await.unwrap().value, 2);
+    let metrics = inner.metrics().snapshot();
+    assert_eq!(metrics.outputs_received, 1);
+    assert_eq!(metrics.output_queue_depth, 0);
+}
+
+#[test]
+fn test_handler_latency_average() {
+    let metrics = PortMetrics::default();
+    let cases = [(800, 800), (1600, 900), (100, 800)];
+    for (i, &(sample, average)) in cases.iter().enumerate() {
+        metrics.record_handler_latency(Duration::from_micros(sample));
+        assert_eq!(
+            metrics.snapshot().handler_latency,
+            Some(Duration::from_micros(average)),
+            "{i}th case failed"
+        );
+    }
+    assert_eq!(metrics.snapshot().handler_calls, 3);
+}
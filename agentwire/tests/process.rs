@@ -1,11 +1,16 @@
 use agentwire::{
-    agent::{self, Process as _},
+    agent::{
+        self,
+        process::{ExitInfo, ExitStrategy},
+        Process as _,
+    },
     port::{self, Port, SharedPort},
-    Agent, Broker, BrokerFlow,
+    Agent, Broker, BrokerError, BrokerFlow,
 };
 use futures::prelude::*;
+use nix::sys::signal::Signal;
 use rkyv::{Archive, Deserialize, Serialize};
-use std::{mem::size_of, time::Instant};
+use std::{mem::size_of, process, time::Instant};
 use thiserror::Error;
 
 #[derive(Clone, Default, Archive, Serialize, Deserialize, Debug)]
@@ -21,11 +26,11 @@ impl Port for Doubler {
 
 impl SharedPort for Doubler {
     const SERIALIZED_INIT_SIZE: usize =
-        size_of::<usize>() + size_of::<<Doubler as Archive>::Archived>();
+        port::MESSAGE_HEADER_SIZE + size_of::<<Doubler as Archive>::Archived>();
     const SERIALIZED_INPUT_SIZE: usize =
-        size_of::<usize>() + size_of::<<u32 as Archive>::Archived>();
+        port::MESSAGE_HEADER_SIZE + size_of::<<u32 as Archive>::Archived>();
     const SERIALIZED_OUTPUT_SIZE: usize =
-        size_of::<usize>() + size_of::<<u32 as Archive>::Archived>();
+        port::MESSAGE_HEADER_SIZE + size_of::<<u32 as Archive>::Archived>();
 }
 
 impl Agent for Doubler {
@@ -33,20 +38,109 @@ impl Agent for Doubler {
 }
 
 #[derive(Error, Debug)]
-pub enum DoublerError {}
+pub enum DoublerError {
+    #[error("port: {0}")]
+    Port(#[from] port::PortError),
+}
 
 impl agent::Process for Doubler {
     type Error = DoublerError;
 
     fn run(self, mut port: port::RemoteInner<Self>) -> Result<(), Self::Error> {
         loop {
-            let input = port.recv();
+            let input = port.recv()?;
             let output = input.chain(input.value * 2);
             port.send(&output);
         }
     }
 }
 
+/// Writes a few lines to stderr and aborts.
+#[derive(Clone, Default, Archive, Serialize, Deserialize, Debug)]
+struct Aborter;
+
+impl Port for Aborter {
+    type Input = ();
+    type Output = ();
+
+    const INPUT_CAPACITY: usize = 0;
+    const OUTPUT_CAPACITY: usize = 0;
+}
+
+impl SharedPort for Aborter {
+    const SERIALIZED_INIT_SIZE: usize =
+        port::MESSAGE_HEADER_SIZE + size_of::<<Aborter as Archive>::Archived>();
+    const SERIALIZED_INPUT_SIZE: usize =
+        port::MESSAGE_HEADER_SIZE + size_of::<<() as Archive>::Archived>();
+    const SERIALIZED_OUTPUT_SIZE: usize =
+        port::MESSAGE_HEADER_SIZE + size_of::<<() as Archive>::Archived>();
+}
+
+impl Agent for Aborter {
+    const NAME: &'static str = "aborter";
+}
+
+impl agent::Process for Aborter {
+    type Error = DoublerError;
+
+    fn run(self, _port: port::RemoteInner<Self>) -> Result<(), Self::Error> {
+        for i in 1..=3 {
+            eprintln!("aborter: line {i}");
+        }
+        eprintln!("aborter: about to abort");
+        process::abort();
+    }
+
+    fn exit_strategy(_code: Option<i32>, _signal: Option<i32>) -> ExitStrategy {
+        ExitStrategy::Close
+    }
+}
+
+/// Built with schema version 1 on the broker side.
+#[derive(Clone, Default, Archive, Serialize, Deserialize, Debug)]
+struct Stale;
+
+/// Same agent as [`Stale`], but built with schema version 2 on the agent side.
+#[derive(Clone, Default, Archive, Serialize, Deserialize, Debug)]
+struct Fresh;
+
+macro_rules! skewed_agent {
+    ($agent:ident, $schema_version:literal) => {
+        impl Port for $agent {
+            type Input = u32;
+            type Output = u32;
+
+            const INPUT_CAPACITY: usize = 0;
+            const OUTPUT_CAPACITY: usize = 0;
+        }
+
+        impl SharedPort for $agent {
+            const SERIALIZED_INIT_SIZE: usize =
+                port::MESSAGE_HEADER_SIZE + size_of::<<$agent as Archive>::Archived>();
+            const SERIALIZED_INPUT_SIZE: usize =
+                port::MESSAGE_HEADER_SIZE + size_of::<<u32 as Archive>::Archived>();
+            const SERIALIZED_OUTPUT_SIZE: usize =
+                port::MESSAGE_HEADER_SIZE + size_of::<<u32 as Archive>::Archived>();
+            const SCHEMA_VERSION: u32 = $schema_version;
+        }
+
+        impl Agent for $agent {
+            const NAME: &'static str = "stale";
+        }
+
+        impl agent::Process for $agent {
+            type Error = DoublerError;
+
+            fn run(self, _port: port::RemoteInner<Self>) -> Result<(), Self::Error> {
+                unreachable!("the handshake must fail");
+            }
+        }
+    };
+}
+
+skewed_agent!(Stale, 1);
+skewed_agent!(Fresh, 2);
+
 #[derive(Error, Debug)]
 pub enum Error {}
 
@@ -56,6 +150,22 @@ trait Plan {
         broker: &mut Broker,
         output: port::Output<Doubler>,
     ) -> Result<BrokerFlow, Error>;
+
+    fn handle_aborter(
+        &mut self,
+        _broker: &mut Broker,
+        _output: port::Output<Aborter>,
+    ) -> Result<BrokerFlow, Error> {
+        Ok(BrokerFlow::Continue)
+    }
+
+    fn handle_stale(
+        &mut self,
+        _broker: &mut Broker,
+        _output: port::Output<Stale>,
+    ) -> Result<BrokerFlow, Error> {
+        Ok(BrokerFlow::Continue)
+    }
 }
 
 #[derive(Broker)]
@@ -63,6 +173,10 @@ trait Plan {
 struct Broker {
     #[agent(process)]
     doubler: agent::Cell<Doubler>,
+    #[agent(process)]
+    aborter: agent::Cell<Aborter>,
+    #[agent(process)]
+    stale: agent::Cell<Stale>,
 }
 
 impl Broker {
@@ -73,11 +187,30 @@ impl Broker {
     ) -> Result<BrokerFlow, Error> {
         plan.handle_doubler(self, output)
     }
+
+    fn handle_aborter(
+        &mut self,
+        plan: &mut dyn Plan,
+        output: port::Output<Aborter>,
+    ) -> Result<BrokerFlow, Error> {
+        plan.handle_aborter(self, output)
+    }
+
+    fn handle_stale(
+        &mut self,
+        plan: &mut dyn Plan,
+        output: port::Output<Stale>,
+    ) -> Result<BrokerFlow, Error> {
+        plan.handle_stale(self, output)
+    }
 }
 
 fn init() {
     agent::process::init(|name, fd| match name {
         "doubler" => Ok(Doubler::call(fd)?),
+        "aborter" => Ok(Aborter::call(fd)?),
+        // Simulates an agent process spawned from a stale binary.
+        "stale" => Ok(Fresh::call(fd)?),
         _ => panic!("unregistered agent {name}"),
     });
 }
@@ -115,3 +248,82 @@ async fn test_process() {
     broker.disable_doubler();
     assert_eq!(plan.result, Some(6));
 }
+
+#[agentwire::test(init = init)]
+async fn test_process_crash() {
+    struct TestPlan;
+    impl Plan for TestPlan {
+        fn handle_doubler(
+            &mut self,
+            _broker: &mut Broker,
+            _output: port::Output<Doubler>,
+        ) -> Result<BrokerFlow, Error> {
+            Ok(BrokerFlow::Continue)
+        }
+    }
+
+    let mut broker = new_broker!();
+    broker.enable_aborter().unwrap();
+    let result = broker.run(&mut TestPlan).await;
+
+    let (name, exit, stderr_tail) = match result {
+        Err(BrokerError::AgentCrashed {
+            name,
+            exit,
+            stderr_tail,
+        }) => (name, exit, stderr_tail),
+        result => panic!("unexpected result: {result:?}"),
+    };
+    assert_eq!(name, "aborter");
+    assert!(
+        matches!(exit, ExitInfo::Signal { signal, .. } if signal == Signal::SIGABRT as i32),
+        "unexpected exit: {exit:?}"
+    );
+    assert!(stderr_tail.contains("aborter: line 3\naborter: about to abort\n"));
+    broker.disable_aborter();
+}
+
+#[agentwire::test(init = init)]
+async fn test_process_schema_mismatch() {
+    struct TestPlan;
+    impl Plan for TestPlan {
+        fn handle_doubler(
+            &mut self,
+            _broker: &mut Broker,
+            _output: port::Output<Doubler>,
+        ) -> Result<BrokerFlow, Error> {
+            Ok(BrokerFlow::Continue)
+        }
+    }
+
+    let mut broker = new_broker!();
+    broker.enable_stale().unwrap();
+    let result = broker.run(&mut TestPlan).await;
+
+    let (name, error) = match result {
+        Err(BrokerError::AgentIncompatible { name, error }) => (name, error),
+        result => panic!("unexpected result: {result:?}"),
+    };
+    assert_eq!(name, "stale");
+    assert_eq!(error.broker.schema_version, 1);
+    assert_eq!(error.agent.schema_version, 2);
+    assert_eq!(error.broker.magic, error.agent.magic);
+    broker.disable_stale();
+}
+
+#[test]
+fn test_stderr_tail_is_bounded() {
+    let tail = agent::process::StderrTail::new(16);
+    assert_eq!(tail.contents(), "");
+    tail.push_line("first");
+    tail.push_line("second");
+    assert_eq!(tail.contents(), "first\nsecond\n");
+    // Wraps around, dropping the oldest bytes.
+    tail.push_line("third");
+    assert_eq!(tail.contents(), "st\nsecond\nthird\n");
+    // Longer than the whole buffer.
+    tail.push_line("0123456789abcdefghij");
+    assert_eq!(tail.contents(), "56789abcdefghij\n");
+    tail.clear();
+    assert_eq!(tail.contents(), "");
+}
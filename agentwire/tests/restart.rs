@@ -0,0 +1,196 @@
+use agentwire::{
+    agent,
+    port::{self, Port},
+    Agent, Broker, BrokerError, BrokerFlow,
+};
+use futures::{channel::mpsc::SendError, prelude::*};
+use std::{
+    io,
+    sync::atomic::{AtomicU32, Ordering},
+};
+use thiserror::Error;
+use tokio::runtime;
+
+static FLAKY_STARTS: AtomicU32 = AtomicU32::new(0);
+
+/// Panics on the first two starts, then reports the number of starts.
+#[derive(Default)]
+struct Flaky;
+
+impl Port for Flaky {
+    type Input = ();
+    type Output = u32;
+
+    const INPUT_CAPACITY: usize = 0;
+    const OUTPUT_CAPACITY: usize = 0;
+}
+
+impl Agent for Flaky {
+    const NAME: &'static str = "flaky";
+}
+
+impl agent::Thread for Flaky {
+    type Error = AgentError;
+
+    fn run(self, mut port: port::Inner<Self>) -> Result<(), Self::Error> {
+        let starts = FLAKY_STARTS.fetch_add(1, Ordering::SeqCst) + 1;
+        assert!(starts > 2, "flaky agent crashed on start {starts}");
+        let rt = runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        rt.block_on(port.send(port::Output::new(starts)))?;
+        while rt.block_on(port.next()).is_some() {}
+        Ok(())
+    }
+}
+
+/// Always panics.
+#[derive(Default)]
+struct Crasher;
+
+impl Port for Crasher {
+    type Input = ();
+    type Output = ();
+
+    const INPUT_CAPACITY: usize = 0;
+    const OUTPUT_CAPACITY: usize = 0;
+}
+
+impl Agent for Crasher {
+    const NAME: &'static str = "crasher";
+}
+
+impl agent::Thread for Crasher {
+    type Error = AgentError;
+
+    fn run(self, _port: port::Inner<Self>) -> Result<(), Self::Error> {
+        panic!("crasher agent crashed");
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum AgentError {
+    #[error("tokio runtime error")]
+    Runtime(#[from] io::Error),
+    #[error("send error")]
+    Send(#[from] SendError),
+}
+
+#[derive(Error, Debug)]
+pub enum Error {}
+
+trait Plan {
+    fn handle_flaky(
+        &mut self,
+        _broker: &mut Broker,
+        _output: port::Output<Flaky>,
+    ) -> Result<BrokerFlow, Error> {
+        Ok(BrokerFlow::Continue)
+    }
+
+    fn handle_crasher(
+        &mut self,
+        _broker: &mut Broker,
+        _output: port::Output<Crasher>,
+    ) -> Result<BrokerFlow, Error> {
+        Ok(BrokerFlow::Continue)
+    }
+
+    fn handle_restarted(
+        &mut self,
+        _broker: &mut Broker,
+        _name: &'static str,
+        _attempt: u32,
+    ) -> Result<BrokerFlow, Error> {
+        Ok(BrokerFlow::Continue)
+    }
+}
+
+#[derive(Broker)]
+#[broker(plan = Plan, error = Error)]
+struct Broker {
+    #[agent(thread, restart = on_failure(max = 3, backoff = "10ms"))]
+    flaky: agent::Cell<Flaky>,
+    #[agent(thread, restart = on_failure(max = 1))]
+    crasher: agent::Cell<Crasher>,
+}
+
+impl Broker {
+    fn handle_flaky(
+        &mut self,
+        plan: &mut dyn Plan,
+        output: port::Output<Flaky>,
+    ) -> Result<BrokerFlow, Error> {
+        plan.handle_flaky(self, output)
+    }
+
+    fn handle_crasher(
+        &mut self,
+        plan: &mut dyn Plan,
+        output: port::Output<Crasher>,
+    ) -> Result<BrokerFlow, Error> {
+        plan.handle_crasher(self, output)
+    }
+
+    fn handle_restarted(
+        &mut self,
+        plan: &mut dyn Plan,
+        name: &'static str,
+        attempt: u32,
+    ) -> Result<BrokerFlow, Error> {
+        plan.handle_restarted(self, name, attempt)
+    }
+}
+
+#[derive(Default)]
+struct TestPlan {
+    restarts: Vec<(&'static str, u32)>,
+    starts: Option<u32>,
+}
+
+impl Plan for TestPlan {
+    fn handle_flaky(
+        &mut self,
+        _broker: &mut Broker,
+        output: port::Output<Flaky>,
+    ) -> Result<BrokerFlow, Error> {
+        self.starts = Some(output.value);
+        Ok(BrokerFlow::Break)
+    }
+
+    fn handle_restarted(
+        &mut self,
+        _broker: &mut Broker,
+        name: &'static str,
+        attempt: u32,
+    ) -> Result<BrokerFlow, Error> {
+        self.restarts.push((name, attempt));
+        Ok(BrokerFlow::Continue)
+    }
+}
+
+#[agentwire::test]
+async fn test_restart_on_failure() {
+    let mut broker = new_broker!();
+    let mut plan = TestPlan::default();
+    broker.enable_flaky().unwrap();
+    broker.run(&mut plan).await.unwrap();
+
+    assert_eq!(plan.starts, Some(3));
+    assert_eq!(plan.restarts, [("flaky", 1), ("flaky", 2)]);
+    broker.disable_flaky();
+}
+
+#[agentwire::test]
+async fn test_restart_attempts_exhausted() {
+    let mut broker = new_broker!();
+    let mut plan = TestPlan::default();
+    broker.enable_crasher().unwrap();
+    let result = broker.run(&mut plan).await;
+
+    assert!(
+        matches!(result, Err(BrokerError::AgentTerminated("crasher"))),
+        "unexpected result: {result:?}"
+    );
+    assert_eq!(plan.restarts, [("crasher", 1)]);
+}
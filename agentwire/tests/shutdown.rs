@@ -0,0 +1,173 @@
+use agentwire::{
+    agent::{self, Process as _, ShutdownReport},
+    port::{self, Port, SharedPort},
+    Agent, Broker, BrokerFlow,
+};
+use futures::{channel::mpsc::SendError, prelude::*};
+use rkyv::{Archive, Deserialize, Serialize};
+use std::{mem::size_of, time::Duration};
+use thiserror::Error;
+
+/// Exits as soon as its input is closed.
+#[derive(Default)]
+struct Doubler;
+
+impl Port for Doubler {
+    type Input = u32;
+    type Output = u32;
+
+    const INPUT_CAPACITY: usize = 0;
+    const OUTPUT_CAPACITY: usize = 0;
+}
+
+impl Agent for Doubler {
+    const NAME: &'static str = "doubler";
+}
+
+impl agent::Task for Doubler {
+    type Error = SendError;
+
+    async fn run(self, mut port: port::Inner<Self>) -> Result<(), Self::Error> {
+        while let Some(x) = port.next().await {
+            port.send(x.chain(x.value * 2)).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Never exits on its own.
+#[derive(Clone, Default, Archive, Serialize, Deserialize, Debug)]
+struct Stubborn;
+
+impl Port for Stubborn {
+    type Input = u32;
+    type Output = u32;
+
+    const INPUT_CAPACITY: usize = 0;
+    const OUTPUT_CAPACITY: usize = 0;
+}
+
+impl SharedPort for Stubborn {
+    const SERIALIZED_INIT_SIZE: usize =
+        port::MESSAGE_HEADER_SIZE + size_of::<<Stubborn as Archive>::Archived>();
+    const SERIALIZED_INPUT_SIZE: usize =
+        port::MESSAGE_HEADER_SIZE + size_of::<<u32 as Archive>::Archived>();
+    const SERIALIZED_OUTPUT_SIZE: usize =
+        port::MESSAGE_HEADER_SIZE + size_of::<<u32 as Archive>::Archived>();
+}
+
+impl Agent for Stubborn {
+    const NAME: &'static str = "stubborn";
+}
+
+#[derive(Error, Debug)]
+pub enum StubbornError {
+    #[error("port: {0}")]
+    Port(#[from] port::PortError),
+}
+
+impl agent::Process for Stubborn {
+    type Error = StubbornError;
+
+    fn run(self, mut port: port::RemoteInner<Self>) -> Result<(), Self::Error> {
+        loop {
+            let input = port.recv()?;
+            let output = input.chain(*input.value);
+            port.send(&output);
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum Error {}
+
+trait Plan {
+    fn handle_doubler(
+        &mut self,
+        _broker: &mut Broker,
+        _output: port::Output<Doubler>,
+    ) -> Result<BrokerFlow, Error> {
+        Ok(BrokerFlow::Continue)
+    }
+
+    fn handle_stubborn(
+        &mut self,
+        _broker: &mut Broker,
+        _output: port::Output<Stubborn>,
+    ) -> Result<BrokerFlow, Error> {
+        Ok(BrokerFlow::Continue)
+    }
+}
+
+#[derive(Broker)]
+#[broker(plan = Plan, error = Error)]
+struct Broker {
+    #[agent(task)]
+    doubler: agent::Cell<Doubler>,
+    #[agent(process)]
+    stubborn: agent::Cell<Stubborn>,
+}
+
+impl Broker {
+    fn handle_doubler(
+        &mut self,
+        plan: &mut dyn Plan,
+        output: port::Output<Doubler>,
+    ) -> Result<BrokerFlow, Error> {
+        plan.handle_doubler(self, output)
+    }
+
+    fn handle_stubborn(
+        &mut self,
+        plan: &mut dyn Plan,
+        output: port::Output<Stubborn>,
+    ) -> Result<BrokerFlow, Error> {
+        plan.handle_stubborn(self, output)
+    }
+}
+
+fn init() {
+    agent::process::init(|name, fd| match name {
+        "stubborn" => Ok(Stubborn::call(fd)?),
+        _ => panic!("unregistered agent {name}"),
+    });
+}
+
+#[agentwire::test(init = init)]
+async fn test_shutdown_clean() {
+    let mut broker = new_broker!();
+    broker.enable_doubler().unwrap();
+
+    // The agent is blocked sending its output until the broker drains it.
+    broker
+        .doubler
+        .enabled()
+        .unwrap()
+        .send(port::Input::new(3))
+        .await
+        .unwrap();
+    let reports = broker.shutdown(Duration::from_secs(5)).await;
+
+    assert_eq!(reports, [("doubler", ShutdownReport::Clean)]);
+    assert!(!broker.doubler.is_initialized());
+}
+
+#[agentwire::test(init = init)]
+async fn test_shutdown_forced() {
+    let mut broker = new_broker!();
+    broker.enable_doubler().unwrap();
+    broker.enable_stubborn().unwrap();
+    broker.disable_doubler();
+
+    let reports = broker.shutdown(Duration::from_millis(500)).await;
+
+    assert_eq!(
+        reports,
+        [
+            ("doubler", ShutdownReport::Clean),
+            ("stubborn", ShutdownReport::Killed)
+        ]
+    );
+    assert!(!broker.doubler.is_initialized());
+    assert!(!broker.stubborn.is_initialized());
+}
@@ -2,18 +2,36 @@
 //! gdbus call --session -d org.worldcoin.AuthTokenManager1 -o '/org/worldcoin/AuthTokenManager1' -m
 //! org.freedesktop.DBus.Properties.Get org.worldcoin.AuthTokenManager1 Token
 //!
+//! Get where the current token came from, and why the last refresh failed
+//! gdbus call --session -d org.worldcoin.AuthTokenManager1 -o '/org/worldcoin/AuthTokenManager1' -m
+//! org.freedesktop.DBus.Properties.GetAll org.worldcoin.AuthTokenManager1
+//!
 //! Force token refresh
 //! gdbus call --session -d org.worldcoin.AuthTokenManager1 -o '/org/worldcoin/AuthTokenManager1' -m
 //! org.worldcoin.AuthTokenManager1.ForceTokenRefresh
 //!
+//! Force token refresh and wait until the new token is published
+//! gdbus call --session -d org.worldcoin.AuthTokenManager1 -o '/org/worldcoin/AuthTokenManager1' -m
+//! org.worldcoin.AuthTokenManager1.ForceRefresh
+//!
 //! Wait for token refresh
 //! dbus-monitor type='signal',sender='org.worldcoin.AuthTokenManager1'
 
+use std::future::Future;
+
 use zbus::interface;
 
 pub trait AuthTokenManagerT: Send + Sync + 'static {
     fn token(&self) -> zbus::fdo::Result<String>;
-    fn force_token_refresh(&mut self, ctxt: zbus::SignalContext<'_>);
+    /// Unix timestamp in seconds at which the token expires, 0 if it never does.
+    fn token_expiry(&self) -> zbus::fdo::Result<u64>;
+    /// Where the current token came from: "static", "remote" or "cached".
+    fn token_source(&self) -> zbus::fdo::Result<String>;
+    /// Why the last token refresh attempt failed, empty if it succeeded.
+    fn last_refresh_error(&self) -> zbus::fdo::Result<String>;
+    fn force_token_refresh(&self, ctxt: zbus::SignalContext<'_>);
+    /// Requests a token refresh and resolves once a new token was published.
+    fn force_refresh(&self) -> impl Future<Output = zbus::fdo::Result<()>> + Send;
 }
 
 #[derive(Debug, derive_more::From)]
@@ -32,10 +50,29 @@ impl<T: AuthTokenManagerT> AuthTokenManagerT for AuthTokenManager<T> {
         self.0.token()
     }
 
+    #[zbus(property)]
+    fn token_expiry(&self) -> zbus::fdo::Result<u64> {
+        self.0.token_expiry()
+    }
+
+    #[zbus(property)]
+    fn token_source(&self) -> zbus::fdo::Result<String> {
+        self.0.token_source()
+    }
+
+    #[zbus(property)]
+    fn last_refresh_error(&self) -> zbus::fdo::Result<String> {
+        self.0.last_refresh_error()
+    }
+
     fn force_token_refresh(
-        &mut self,
+        &self,
         #[zbus(signal_context)] ctxt: zbus::SignalContext<'_>,
     ) {
         self.0.force_token_refresh(ctxt)
     }
+
+    async fn force_refresh(&self) -> zbus::fdo::Result<()> {
+        self.0.force_refresh().await
+    }
 }
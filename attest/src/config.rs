@@ -1,12 +1,22 @@
-use std::env;
+use std::{env, path::PathBuf, time::Duration};
 
 use eyre::{self, bail};
 
+use crate::remote_api::LocalValidation;
+
 const ORB_BACKEND_ENV_VAR_NAME: &str = "ORB_BACKEND";
+const DEFAULT_TOKEN_CACHE_PATH: &str = "/usr/persistent/attest-token-cache.json";
+const DEFAULT_FORCE_REFRESH_TIMEOUT: Duration = Duration::from_secs(120);
 
 pub struct Config {
     pub auth_url: url::Url,
     pub ping_url: url::Url,
+    /// Where the short lived token is cached across restarts.
+    pub token_cache_path: PathBuf,
+    /// How long the `ForceRefresh` DBus method waits for a new token.
+    pub force_refresh_timeout: Duration,
+    /// Checks done on the static token before validating it with the backend.
+    pub static_token_validation: LocalValidation,
 }
 
 impl Config {
@@ -27,6 +37,9 @@ impl Config {
                 "https://{ping}.worldcoin.org/api/v1/orbs/{orb_id}"
             ))
             .unwrap(),
+            token_cache_path: PathBuf::from(DEFAULT_TOKEN_CACHE_PATH),
+            force_refresh_timeout: DEFAULT_FORCE_REFRESH_TIMEOUT,
+            static_token_validation: LocalValidation::default(),
         }
     }
 }
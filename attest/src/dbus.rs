@@ -2,18 +2,26 @@
 //! gdbus call --session -d org.worldcoin.AuthTokenManager1 -o '/org/worldcoin/AuthTokenManager1' -m
 //! org.freedesktop.DBus.Properties.Get org.worldcoin.AuthTokenManager1 Token
 //!
+//! Get where the current token came from, and why the last refresh failed
+//! gdbus call --session -d org.worldcoin.AuthTokenManager1 -o '/org/worldcoin/AuthTokenManager1' -m
+//! org.freedesktop.DBus.Properties.GetAll org.worldcoin.AuthTokenManager1
+//!
 //! Force token refresh
 //! gdbus call --session -d org.worldcoin.AuthTokenManager1 -o '/org/worldcoin/AuthTokenManager1' -m
 //! org.worldcoin.AuthTokenManager1.ForceTokenRefresh
 //!
+//! Force token refresh and wait until the new token is published
+//! gdbus call --session -d org.worldcoin.AuthTokenManager1 -o '/org/worldcoin/AuthTokenManager1' -m
+//! org.worldcoin.AuthTokenManager1.ForceRefresh
+//!
 //! Wait for token refresh
 //! dbus-monitor type='signal',sender='org.worldcoin.AuthTokenManager1'
 
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use eyre::WrapErr;
 use orb_attest_dbus::AuthTokenManagerT;
-use tokio::sync::Notify;
+use tokio::sync::{watch, Notify};
 use tracing::instrument;
 use zbus::ConnectionBuilder;
 
@@ -21,28 +29,83 @@ use zbus::ConnectionBuilder;
 pub type AuthTokenManagerIface = orb_attest_dbus::AuthTokenManager<AuthTokenManager>;
 
 pub struct AuthTokenManager {
-    token: Option<String>,
+    // `ForceRefresh` holds the interface's read lock while it waits for a new
+    // token, so every method takes `&self` to not queue behind it. The token is
+    // updated through a watch channel, which `ForceRefresh` also waits on.
+    state: watch::Sender<TokenState>,
     refresh_token_event: Arc<Notify>,
+    force_refresh_timeout: Duration,
+}
+
+#[derive(Default)]
+struct TokenState {
+    token: Option<String>,
+    expiry: u64,
+    source: Option<TokenSource>,
+    last_refresh_error: String,
+}
+
+/// Which path of the token refresh produced the current token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenSource {
+    /// The static token stored on the orb.
+    Static,
+    /// A short lived token freshly fetched from the backend.
+    Remote,
+    /// A short lived token loaded from the token cache.
+    Cached,
+}
+
+impl TokenSource {
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TokenSource::Static => "static",
+            TokenSource::Remote => "remote",
+            TokenSource::Cached => "cached",
+        }
+    }
 }
 
 impl AuthTokenManager {
     #[must_use]
-    pub fn new(refresh_token_event: Arc<Notify>) -> Self {
+    pub fn new(
+        refresh_token_event: Arc<Notify>,
+        force_refresh_timeout: Duration,
+    ) -> Self {
         AuthTokenManager {
-            token: None,
+            state: watch::Sender::new(TokenState::default()),
             refresh_token_event,
+            force_refresh_timeout,
         }
     }
 
-    pub fn update_token(&mut self, token: &str) {
-        self.token = Some(token.to_string());
+    /// Sets the token, its expiry as unix timestamp in seconds, 0 if it never
+    /// expires, and where it came from. Clears the last refresh error.
+    pub fn update_token(&self, token: &str, expiry: u64, source: TokenSource) {
+        self.state.send_replace(TokenState {
+            token: Some(token.to_string()),
+            expiry,
+            source: Some(source),
+            last_refresh_error: String::new(),
+        });
+    }
+
+    /// Records why a token refresh attempt failed, keeping the current token.
+    pub fn update_refresh_error(&self, error: String) {
+        // Not reported as a modification, so that `ForceRefresh` keeps waiting
+        // for a new token.
+        self.state.send_if_modified(|state| {
+            state.last_refresh_error = error;
+            false
+        });
     }
 }
 
 impl AuthTokenManagerT for AuthTokenManager {
     #[instrument(skip_all, err)]
     fn token(&self) -> zbus::fdo::Result<String> {
-        match self.token.as_deref() {
+        match self.state.borrow().token.as_deref() {
             Some("") => Err(zbus::fdo::Error::Failed(
                 "token was set, but is empty string".into(),
             )),
@@ -53,12 +116,101 @@ impl AuthTokenManagerT for AuthTokenManager {
         }
     }
 
+    #[instrument(skip_all, err)]
+    fn token_expiry(&self) -> zbus::fdo::Result<u64> {
+        let state = self.state.borrow();
+        match state.token {
+            Some(_) => Ok(state.expiry),
+            None => Err(zbus::fdo::Error::Failed(
+                "token was not yet or could not be retrieved from backend".into(),
+            )),
+        }
+    }
+
+    fn token_source(&self) -> zbus::fdo::Result<String> {
+        Ok(self
+            .state
+            .borrow()
+            .source
+            .map_or("", TokenSource::as_str)
+            .to_owned())
+    }
+
+    fn last_refresh_error(&self) -> zbus::fdo::Result<String> {
+        Ok(self.state.borrow().last_refresh_error.clone())
+    }
+
     #[instrument(skip_all)]
-    fn force_token_refresh(&mut self, _ctxt: zbus::SignalContext<'_>) {
+    fn force_token_refresh(&self, _ctxt: zbus::SignalContext<'_>) {
+        self.refresh_token_event.notify_one();
+    }
+
+    #[instrument(skip_all, err)]
+    async fn force_refresh(&self) -> zbus::fdo::Result<()> {
+        let mut published = self.state.subscribe();
         self.refresh_token_event.notify_one();
+        match tokio::time::timeout(self.force_refresh_timeout, published.changed())
+            .await
+        {
+            Ok(result) => result.map_err(|e| zbus::fdo::Error::Failed(e.to_string())),
+            Err(_) => Err(zbus::fdo::Error::TimedOut(format!(
+                "no new token was published within {}s",
+                self.force_refresh_timeout.as_secs()
+            ))),
+        }
     }
 }
 
+/// Publishes a new token, with its expiry as unix timestamp in seconds and its
+/// source, and emits the property change signals.
+///
+/// # Errors
+/// - if failed to emit the signals
+pub async fn publish_token(
+    iface_ref: &zbus::InterfaceRef<AuthTokenManagerIface>,
+    token: &str,
+    expiry: u64,
+    source: TokenSource,
+) -> eyre::Result<()> {
+    let iface = iface_ref.get().await;
+    iface.0.update_token(token, expiry, source);
+    iface
+        .token_changed(iface_ref.signal_context())
+        .await
+        .wrap_err("failed to send token_changed signal")?;
+    iface
+        .token_expiry_changed(iface_ref.signal_context())
+        .await
+        .wrap_err("failed to send token_expiry_changed signal")?;
+    iface
+        .token_source_changed(iface_ref.signal_context())
+        .await
+        .wrap_err("failed to send token_source_changed signal")?;
+    iface
+        .last_refresh_error_changed(iface_ref.signal_context())
+        .await
+        .wrap_err("failed to send last_refresh_error_changed signal")?;
+    Ok(())
+}
+
+/// Publishes why a token refresh attempt failed, and emits the property change
+/// signal.
+///
+/// # Errors
+/// - if failed to emit the signal
+pub async fn publish_refresh_error(
+    iface_ref: &zbus::InterfaceRef<AuthTokenManagerIface>,
+    error: String,
+) -> eyre::Result<()> {
+    let iface = iface_ref.get().await;
+    iface.0.update_refresh_error(error);
+    iface
+        .last_refresh_error_changed(iface_ref.signal_context())
+        .await
+        .wrap_err("failed to send last_refresh_error_changed signal")?;
+    Ok(())
+}
+
 /// Start the `AuthTokenManager1` service
 /// This service is used to provide the token to the rest of the system
 /// It is also used to :
@@ -70,8 +222,10 @@ impl AuthTokenManagerT for AuthTokenManager {
 /// - if failed to connect to the session bus or create the service
 pub async fn create_dbus_connection(
     refresh_token_event: Arc<Notify>,
+    force_refresh_timeout: Duration,
 ) -> eyre::Result<zbus::Connection> {
-    let auth_token_manager = AuthTokenManager::new(refresh_token_event);
+    let auth_token_manager =
+        AuthTokenManager::new(refresh_token_event, force_refresh_timeout);
     let dbus = ConnectionBuilder::session()
         .wrap_err("failed to establish user session dbus connection")?
         .name("org.worldcoin.AuthTokenManager1")
@@ -85,3 +239,144 @@ pub async fn create_dbus_connection(
         .wrap_err("failed to initialize the service on dbus")?;
     Ok(dbus)
 }
+
+#[cfg(test)]
+mod test {
+    use std::{sync::Arc, time::Duration};
+
+    use futures::StreamExt;
+    use orb_attest_dbus::AuthTokenManagerProxy;
+    use tokio::sync::Notify;
+
+    use super::{
+        publish_refresh_error, publish_token, AuthTokenManager, AuthTokenManagerIface,
+        TokenSource,
+    };
+
+    const PATH: &str = "/org/worldcoin/AuthTokenManager1";
+
+    async fn connect(
+        refresh_token_event: Arc<Notify>,
+    ) -> (zbus::InterfaceRef<AuthTokenManagerIface>, zbus::Connection) {
+        let (server_stream, client_stream) = tokio::net::UnixStream::pair().unwrap();
+        let server = zbus::ConnectionBuilder::unix_stream(server_stream)
+            .server(zbus::Guid::generate())
+            .unwrap()
+            .p2p()
+            .serve_at(
+                PATH,
+                AuthTokenManagerIface::from(AuthTokenManager::new(
+                    refresh_token_event,
+                    Duration::from_secs(5),
+                )),
+            )
+            .unwrap()
+            .build();
+        let client = zbus::ConnectionBuilder::unix_stream(client_stream)
+            .p2p()
+            .build();
+        let (server, client) = tokio::try_join!(server, client).unwrap();
+        let iface_ref = server
+            .object_server()
+            .interface::<_, AuthTokenManagerIface>(PATH)
+            .await
+            .unwrap();
+        (iface_ref, client)
+    }
+
+    #[tokio::test]
+    async fn force_refresh_publishes_new_token() {
+        let refresh_token_event = Arc::new(Notify::new());
+        let (iface_ref, client) = connect(refresh_token_event.clone()).await;
+        publish_token(&iface_ref, "old_token", 1000, TokenSource::Cached)
+            .await
+            .unwrap();
+
+        // Stands in for `run()`: publish a new token on every refresh request.
+        tokio::spawn(async move {
+            refresh_token_event.notified().await;
+            publish_token(&iface_ref, "new_token", 2000, TokenSource::Remote)
+                .await
+                .unwrap();
+        });
+
+        let proxy = AuthTokenManagerProxy::new(&client).await.unwrap();
+        assert_eq!(proxy.token().await.unwrap(), "old_token");
+        let mut token_changed = proxy.receive_token_changed().await;
+        // The current value is always yielded first.
+        let initial = token_changed.next().await.unwrap();
+        assert_eq!(initial.get().await.unwrap(), "old_token");
+
+        proxy.force_refresh().await.unwrap();
+
+        let changed =
+            tokio::time::timeout(Duration::from_secs(5), token_changed.next())
+                .await
+                .expect("no TokenChanged signal")
+                .unwrap();
+        assert_eq!(changed.get().await.unwrap(), "new_token");
+        assert_eq!(proxy.token_expiry().await.unwrap(), 2000);
+    }
+
+    async fn next_value(
+        stream: &mut zbus::proxy::PropertyStream<'_, String>,
+    ) -> String {
+        tokio::time::timeout(Duration::from_secs(5), stream.next())
+            .await
+            .expect("no PropertiesChanged signal")
+            .unwrap()
+            .get()
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn refresh_outcome_properties() {
+        let (iface_ref, client) = connect(Arc::new(Notify::new())).await;
+        let proxy = AuthTokenManagerProxy::new(&client).await.unwrap();
+        assert_eq!(proxy.token_source().await.unwrap(), "");
+        assert_eq!(proxy.last_refresh_error().await.unwrap(), "");
+
+        let mut source_changed = proxy.receive_token_source_changed().await;
+        let mut error_changed = proxy.receive_last_refresh_error_changed().await;
+        // The current values are always yielded first.
+        assert_eq!(next_value(&mut source_changed).await, "");
+        assert_eq!(next_value(&mut error_changed).await, "");
+
+        publish_refresh_error(&iface_ref, "failed to fetch challenge".into())
+            .await
+            .unwrap();
+        assert_eq!(
+            next_value(&mut error_changed).await,
+            "failed to fetch challenge"
+        );
+        // A failed refresh doesn't publish a token.
+        assert!(proxy.token().await.is_err());
+
+        publish_token(&iface_ref, "static_token", 0, TokenSource::Static)
+            .await
+            .unwrap();
+        assert_eq!(next_value(&mut source_changed).await, "static");
+        assert_eq!(next_value(&mut error_changed).await, "");
+        assert_eq!(proxy.token().await.unwrap(), "static_token");
+    }
+
+    #[test]
+    fn refresh_error_is_cleared_by_new_token() {
+        let manager = AuthTokenManager::new(Arc::new(Notify::new()), Duration::ZERO);
+        let mut published = manager.state.subscribe();
+
+        manager.update_refresh_error("token was rejected by the backend".into());
+        assert_eq!(
+            manager.last_refresh_error().unwrap(),
+            "token was rejected by the backend"
+        );
+        assert_eq!(manager.token_source().unwrap(), "");
+        assert!(!published.has_changed().unwrap());
+
+        manager.update_token("cached_token", 1000, TokenSource::Cached);
+        assert_eq!(manager.last_refresh_error().unwrap(), "");
+        assert_eq!(manager.token_source().unwrap(), "cached");
+        assert!(published.has_changed().unwrap());
+    }
+}
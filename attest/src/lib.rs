@@ -4,8 +4,9 @@ pub mod client;
 pub mod config;
 pub mod dbus;
 pub mod remote_api;
+pub mod token_cache;
 
-use std::sync::Arc;
+use std::{path::Path, sync::Arc, time::UNIX_EPOCH};
 
 use eyre::{self, bail, WrapErr};
 use futures::{FutureExt, StreamExt};
@@ -15,6 +16,8 @@ use tokio::{select, sync::Notify, time::sleep};
 use tracing::{info, warn};
 use url::Url;
 
+use crate::{dbus::TokenSource, remote_api::LocalValidation};
+
 const BUILD_INFO: BuildInfo = make_build_info!();
 
 const HTTP_RETRY_DELAY: std::time::Duration = std::time::Duration::from_secs(3);
@@ -31,9 +34,10 @@ pub async fn main() -> eyre::Result<()> {
 
     let force_refresh_token = Arc::new(Notify::new());
 
-    let iface_ref = setup_dbus(force_refresh_token.clone())
-        .await
-        .wrap_err("Initialization failed")?;
+    let iface_ref =
+        setup_dbus(force_refresh_token.clone(), config.force_refresh_timeout)
+            .await
+            .wrap_err("Initialization failed")?;
     let conn = iface_ref.signal_context().connection().clone();
     let run_fut = run(
         &orb_id,
@@ -41,6 +45,8 @@ pub async fn main() -> eyre::Result<()> {
         force_refresh_token.clone(),
         config.auth_url,
         config.ping_url,
+        &config.token_cache_path,
+        config.static_token_validation,
     );
 
     let mut msg_stream = zbus::MessageStream::from(conn);
@@ -57,43 +63,130 @@ pub async fn main() -> eyre::Result<()> {
     Ok(())
 }
 
-/// Return either a *proovenly working* static token, or a short lived token.
-#[tracing::instrument]
+/// Receives the outcome of token refreshes. Implemented by the dbus interface,
+/// and by a fake in tests.
+trait RefreshStatus {
+    /// Publishes a new working token.
+    async fn token_refreshed(
+        &self,
+        token: &str,
+        expiry: u64,
+        source: TokenSource,
+    ) -> eyre::Result<()>;
+
+    /// Records a failed attempt to fetch or validate a token.
+    async fn refresh_failed(&self, error: String);
+}
+
+impl RefreshStatus for zbus::InterfaceRef<dbus::AuthTokenManagerIface> {
+    async fn token_refreshed(
+        &self,
+        token: &str,
+        expiry: u64,
+        source: TokenSource,
+    ) -> eyre::Result<()> {
+        dbus::publish_token(self, token, expiry, source).await
+    }
+
+    async fn refresh_failed(&self, error: String) {
+        if let Err(e) = dbus::publish_refresh_error(self, error).await {
+            warn!(error=?e, "failed to publish the last refresh error");
+        }
+    }
+}
+
+/// Return either a *proovenly working* static or cached token, or a new short
+/// lived token, along with where it came from.
+///
+/// The cached token is only tried if `use_cache` is set. It is meant to survive
+/// restarts, later refreshes would only get back the token they replace.
+#[tracing::instrument(skip(status))]
 async fn get_working_token(
     orb_id: &str,
     auth_url: &Url,
     ping_url: &Url,
-) -> crate::remote_api::Token {
+    token_cache_path: &Path,
+    static_token_validation: &LocalValidation,
+    use_cache: bool,
+    status: &impl RefreshStatus,
+) -> (crate::remote_api::Token, TokenSource) {
+    let on_remote_error =
+        move |e: remote_api::RefreshTokenError| status.refresh_failed(e.to_string());
     select! {
-        Ok(token) = get_working_static_token(orb_id, ping_url) => token,
-        token = remote_api::get_token(orb_id, auth_url) => token,
+        Ok(token) = get_working_static_token(orb_id, ping_url, static_token_validation, status) => (token, TokenSource::Static),
+        Some(token) = get_working_cached_token(orb_id, ping_url, token_cache_path, status), if use_cache => (token, TokenSource::Cached),
+        token = remote_api::get_token(orb_id, auth_url, on_remote_error) => (token, TokenSource::Remote),
     }
 }
 
-/// Return proovenly working static token, or error if the token was rejected by the backend.
-#[tracing::instrument]
+/// Return proovenly working cached short lived token, or `None` if there is
+/// none or it was rejected by the backend.
+#[tracing::instrument(skip(status))]
+async fn get_working_cached_token(
+    orb_id: &str,
+    ping_url: &Url,
+    token_cache_path: &Path,
+    status: &impl RefreshStatus,
+) -> Option<crate::remote_api::Token> {
+    let token = token_cache::load(token_cache_path, orb_id).await?;
+    info!("got cached token {token:#?}, validating it");
+    if is_token_accepted(orb_id, &token, ping_url).await {
+        info!("Cached token is valid");
+        Some(token)
+    } else {
+        info!("Cached token was rejected, fetching a new one");
+        status
+            .refresh_failed("cached token was rejected by the backend".to_owned())
+            .await;
+        None
+    }
+}
+
+/// Return proovenly working static token, or error if the token was rejected
+/// locally or by the backend.
+#[tracing::instrument(skip(status))]
 async fn get_working_static_token(
     orb_id: &str,
     ping_url: &Url,
+    validation: &LocalValidation,
+    status: &impl RefreshStatus,
 ) -> std::io::Result<crate::remote_api::Token> {
     let token = remote_api::Token::from_usr_persistent().await?;
+    info!("got static token {token:#?}, validating it");
+    if let Err(e) = token.validate_locally(orb_id, validation) {
+        warn!("Static token rejected without contacting the backend: {e}");
+        status
+            .refresh_failed(format!("static token was rejected: {e}"))
+            .await;
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, e));
+    }
+    if is_token_accepted(orb_id, &token, ping_url).await {
+        info!("Static token is valid");
+        Ok(token)
+    } else {
+        status
+            .refresh_failed("static token was rejected by the backend".to_owned())
+            .await;
+        // TODO make this error more specific
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Other,
+            "token was rejected by the backend",
+        ))
+    }
+}
+
+/// Ask the backend whether it accepts `token`.
+async fn is_token_accepted(
+    orb_id: &str,
+    token: &crate::remote_api::Token,
+    ping_url: &Url,
+) -> bool {
     let mut failure_counter = 0;
     // Loop until we get confirmation from the backend that the token is valid
     // or not. In case of network errors, keep trying.
-    info!("got static token {token:#?}, validating it");
     loop {
-        match crate::client::validate_token(orb_id, &token, ping_url).await {
-            Ok(true) => {
-                info!("Static token is valid");
-                return Ok(token);
-            }
-            // TODO make this error more specific
-            Ok(false) => {
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    "token was rejected by the backend",
-                ));
-            }
+        match crate::client::validate_token(orb_id, token, ping_url).await {
+            Ok(accepted) => return accepted,
             Err(e) => {
                 failure_counter += 1;
                 warn!(error=?e, "Token validation has failed {} times.", failure_counter);
@@ -107,8 +200,9 @@ async fn get_working_static_token(
 #[tracing::instrument]
 async fn setup_dbus(
     force_refresh_token: Arc<Notify>,
+    force_refresh_timeout: std::time::Duration,
 ) -> eyre::Result<zbus::InterfaceRef<crate::dbus::AuthTokenManagerIface>> {
-    let dbus = dbus::create_dbus_connection(force_refresh_token)
+    let dbus = dbus::create_dbus_connection(force_refresh_token, force_refresh_timeout)
         .await
         .wrap_err("failed to create DBus connection")?;
 
@@ -127,23 +221,23 @@ async fn run(
     force_refresh_token: Arc<Notify>,
     auth_url: Url,
     ping_url: Url,
+    token_cache_path: &Path,
+    static_token_validation: LocalValidation,
 ) -> eyre::Result<()> {
+    // Only a fresh start picks up the token cached by the previous run.
+    let mut use_cache = true;
     loop {
-        let token = get_working_token(orb_id, &auth_url, &ping_url).await;
-        let token_refresh_delay = token.get_best_refresh_time();
-        // get_mut() blocks access to the iface_ref object. So we never bind its result to be safe.
-        // https://docs.rs/zbus/3.7.0/zbus/struct.InterfaceRef.html#method.get_mut
-        iface_ref
-            .get_mut()
-            .await
-            .0
-            .update_token(token.token.expose_secret());
-        iface_ref
-            .get_mut()
-            .await
-            .token_changed(iface_ref.signal_context())
-            .await
-            .wrap_err("failed to send token_changed signal")?;
+        let token_refresh_delay = refresh_token(
+            orb_id,
+            &auth_url,
+            &ping_url,
+            token_cache_path,
+            &static_token_validation,
+            use_cache,
+            &iface_ref,
+        )
+        .await?;
+        use_cache = false;
 
         //  Wait for whatever happens first: token expires or a refresh is requested
         select! {
@@ -152,3 +246,190 @@ async fn run(
         };
     }
 }
+
+/// Get a working token, cache and publish it along with where it came from.
+/// Returns how long to wait before refreshing it.
+async fn refresh_token(
+    orb_id: &str,
+    auth_url: &Url,
+    ping_url: &Url,
+    token_cache_path: &Path,
+    static_token_validation: &LocalValidation,
+    use_cache: bool,
+    status: &impl RefreshStatus,
+) -> eyre::Result<std::time::Duration> {
+    let (token, source) = get_working_token(
+        orb_id,
+        auth_url,
+        ping_url,
+        token_cache_path,
+        static_token_validation,
+        use_cache,
+        status,
+    )
+    .await;
+    if let Err(e) = token_cache::store(token_cache_path, orb_id, &token).await {
+        warn!(error=?e, "failed to update token cache {}", token_cache_path.display());
+    }
+    let expiry = token
+        .expires_at()
+        .and_then(|expires_at| expires_at.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |expiry| expiry.as_secs());
+    status
+        .token_refreshed(token.token.expose_secret(), expiry, source)
+        .await?;
+    Ok(token.get_best_refresh_time())
+}
+
+#[cfg(test)]
+mod test {
+    use std::{sync::Mutex, time::Duration};
+
+    use secrecy::{ExposeSecret, SecretString};
+    use wiremock::{
+        matchers::{method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use crate::{dbus::TokenSource, RefreshStatus};
+
+    const ORB_ID: &str = "TEST_ORB";
+
+    /// Records the outcomes instead of publishing them on dbus.
+    #[derive(Default)]
+    struct FakeStatus {
+        tokens: Mutex<Vec<(String, TokenSource)>>,
+        errors: Mutex<Vec<String>>,
+    }
+
+    impl RefreshStatus for FakeStatus {
+        async fn token_refreshed(
+            &self,
+            token: &str,
+            _expiry: u64,
+            source: TokenSource,
+        ) -> eyre::Result<()> {
+            self.tokens.lock().unwrap().push((token.to_owned(), source));
+            Ok(())
+        }
+
+        async fn refresh_failed(&self, error: String) {
+            self.errors.lock().unwrap().push(error);
+        }
+    }
+
+    async fn ping_server(status: u16) -> (MockServer, url::Url) {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/v1/orbs/TEST_ORB"))
+            .respond_with(ResponseTemplate::new(status))
+            .mount(&mock_server)
+            .await;
+        let ping_url = format!("{}/api/v1/orbs/{ORB_ID}", mock_server.uri())
+            .parse()
+            .unwrap();
+        (mock_server, ping_url)
+    }
+
+    async fn cache_token(cache_path: &std::path::Path) {
+        let token = crate::remote_api::Token::from_cache(
+            SecretString::from("token_CCCC".to_owned()),
+            Duration::from_secs(36000),
+        );
+        crate::token_cache::store(cache_path, ORB_ID, &token)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn cached_token_accepted() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache_path = dir.path().join("token-cache.json");
+        cache_token(&cache_path).await;
+        let (_server, ping_url) = ping_server(200).await;
+        let status = FakeStatus::default();
+
+        let token =
+            super::get_working_cached_token(ORB_ID, &ping_url, &cache_path, &status)
+                .await
+                .unwrap();
+        assert_eq!(token.token.expose_secret(), "token_CCCC");
+        assert!(status.errors.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn cached_token_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache_path = dir.path().join("token-cache.json");
+        cache_token(&cache_path).await;
+        let (_server, ping_url) = ping_server(401).await;
+        let status = FakeStatus::default();
+
+        assert!(super::get_working_cached_token(
+            ORB_ID,
+            &ping_url,
+            &cache_path,
+            &status
+        )
+        .await
+        .is_none());
+        assert_eq!(
+            *status.errors.lock().unwrap(),
+            ["cached token was rejected by the backend"]
+        );
+    }
+
+    #[tokio::test]
+    async fn startup_refresh_publishes_cached_token() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache_path = dir.path().join("token-cache.json");
+        cache_token(&cache_path).await;
+        let (server, ping_url) = ping_server(200).await;
+        // The auth endpoints are not mocked, fetching a new token never succeeds.
+        let auth_url = format!("{}/api/v1/", server.uri()).parse().unwrap();
+        let status = FakeStatus::default();
+
+        super::refresh_token(
+            ORB_ID,
+            &auth_url,
+            &ping_url,
+            &cache_path,
+            &crate::remote_api::LocalValidation::default(),
+            true,
+            &status,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            *status.tokens.lock().unwrap(),
+            [("token_CCCC".to_owned(), TokenSource::Cached)]
+        );
+    }
+
+    #[tokio::test]
+    async fn later_refresh_ignores_cached_token() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache_path = dir.path().join("token-cache.json");
+        cache_token(&cache_path).await;
+        let (server, ping_url) = ping_server(200).await;
+        // The auth endpoints are not mocked, fetching a new token never succeeds.
+        let auth_url = format!("{}/api/v1/", server.uri()).parse().unwrap();
+        let status = FakeStatus::default();
+
+        let refresh = super::refresh_token(
+            ORB_ID,
+            &auth_url,
+            &ping_url,
+            &cache_path,
+            &crate::remote_api::LocalValidation::default(),
+            false,
+            &status,
+        );
+        // Without the cache, the refresh keeps waiting for a new token.
+        assert!(tokio::time::timeout(Duration::from_secs(1), refresh)
+            .await
+            .is_err());
+        assert!(status.tokens.lock().unwrap().is_empty());
+        assert!(!status.errors.lock().unwrap().is_empty());
+    }
+}
@@ -1,6 +1,6 @@
-use data_encoding::BASE64;
+use data_encoding::{BASE64, BASE64URL_NOPAD};
 use ring::{digest, digest::digest};
-use secrecy::SecretString;
+use secrecy::{ExposeSecret, SecretString};
 use serde::{Deserialize, Serialize};
 use serde_with::{base64::Base64, serde_as};
 use std::num::Saturating;
@@ -9,6 +9,7 @@ use std::{
     fmt,
     io::Write,
     process::{Command, Stdio},
+    time::{SystemTime, UNIX_EPOCH},
 };
 use tokio::{
     fs::read_to_string,
@@ -34,6 +35,10 @@ const SIGNING_DELAY: time::Duration = time::Duration::from_secs(5);
 const NUMBER_OF_TOKEN_FETCH_RETRIES: u32 = NUMBER_OF_CHALLENGE_RETRIES;
 /// How long to wait before retrying to fetch the token
 const TOKEN_DELAY: time::Duration = CHALLENGE_DELAY;
+/// How long after its `exp` claim a token is still worth validating with the
+/// backend, to tolerate a skewed local clock.
+pub const DEFAULT_EXPIRY_GRACE_PERIOD: time::Duration =
+    time::Duration::from_secs(24 * 3600);
 
 /// Sometimes the signing tool fails because SE050 is not responding, the
 /// only known way to recover it is to powercycle the whole security MCU.
@@ -108,6 +113,16 @@ pub enum RefreshTokenError {
     JoinError(#[source] tokio::task::JoinError),
 }
 
+#[derive(Debug, thiserror::Error)]
+pub enum LocalValidationError {
+    #[error("token is not a JWT: {0}")]
+    Malformed(String),
+    #[error("token expired at {exp} (unix time)")]
+    Expired { exp: u64 },
+    #[error("token belongs to orb {0}")]
+    WrongOrb(String),
+}
+
 /// helper for concealing part of a secret from the log.
 /// splits the secret in three parts and print the first and last part
 fn format_secret(val: &str) -> String {
@@ -383,6 +398,63 @@ impl Token {
         self.duration / 2 - elapsed
     }
 
+    /// Wall clock time at which the token expires, or `None` for tokens that
+    /// never expire.
+    #[must_use]
+    pub fn expires_at(&self) -> Option<std::time::SystemTime> {
+        if self.duration == std::time::Duration::MAX {
+            return None;
+        }
+        let remaining = self.duration.saturating_sub(self.start_time.elapsed());
+        std::time::SystemTime::now().checked_add(remaining)
+    }
+
+    /// Return a previously fetched token that is valid for another `remaining`.
+    pub(crate) fn from_cache(
+        token: SecretString,
+        remaining: std::time::Duration,
+    ) -> Self {
+        Self {
+            token,
+            duration: remaining,
+            expiry_time: String::new(),
+            start_time: tokio::time::Instant::now(),
+        }
+    }
+
+    /// Check the token against `orb_id` and the local clock, without any network
+    /// calls.
+    ///
+    /// A token that is not a JWT only fails the check if
+    /// [`LocalValidation::reject_malformed`] is set.
+    ///
+    /// # Errors
+    /// - if the token is expired beyond the grace period, or belongs to another
+    ///   orb
+    pub fn validate_locally(
+        &self,
+        orb_id: &str,
+        validation: &LocalValidation,
+    ) -> Result<(), LocalValidationError> {
+        self.validate_locally_at(orb_id, validation, SystemTime::now())
+    }
+
+    fn validate_locally_at(
+        &self,
+        orb_id: &str,
+        validation: &LocalValidation,
+        now: SystemTime,
+    ) -> Result<(), LocalValidationError> {
+        match Claims::decode(self.token.expose_secret()) {
+            Ok(claims) => claims.check(orb_id, now, validation.expiry_grace_period),
+            Err(e) if validation.reject_malformed => Err(e),
+            Err(e) => {
+                warn!("{e}, leaving validation to the backend");
+                Ok(())
+            }
+        }
+    }
+
     /// Return a token with infinite expiration date and value from
     /// `STATIC_TOKEN_PATH` file.
     ///
@@ -401,6 +473,107 @@ impl Token {
     }
 }
 
+/// Unverified claims of a JWT token. The signature is left for the backend to
+/// check.
+#[derive(Deserialize, Debug, Default, PartialEq, Eq)]
+pub struct Claims {
+    /// Expiration time, in seconds since the unix epoch.
+    pub exp: Option<u64>,
+    /// Issue time, in seconds since the unix epoch.
+    pub iat: Option<u64>,
+    #[serde(rename = "orbId")]
+    pub orb_id: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_audience")]
+    pub aud: Vec<String>,
+}
+
+/// The `aud` claim is either a single string or an array of strings.
+fn deserialize_audience<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Audience {
+        One(String),
+        Many(Vec<String>),
+    }
+    Ok(match Audience::deserialize(deserializer)? {
+        Audience::One(aud) => vec![aud],
+        Audience::Many(aud) => aud,
+    })
+}
+
+impl Claims {
+    /// Decode the payload of `jwt` without verifying its signature.
+    ///
+    /// # Errors
+    /// - if `jwt` is not a JWT with a JSON payload
+    pub fn decode(jwt: &str) -> Result<Self, LocalValidationError> {
+        let mut parts = jwt.split('.');
+        let (Some(_header), Some(payload), Some(_signature), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(LocalValidationError::Malformed(
+                "expected three dot-separated parts".to_owned(),
+            ));
+        };
+        let payload = BASE64URL_NOPAD
+            .decode(payload.trim_end_matches('=').as_bytes())
+            .map_err(|e| LocalValidationError::Malformed(e.to_string()))?;
+        serde_json::from_slice(&payload)
+            .map_err(|e| LocalValidationError::Malformed(e.to_string()))
+    }
+
+    /// Check the claims against `orb_id` and the current time `now`.
+    ///
+    /// Claims that are absent are not checked.
+    ///
+    /// # Errors
+    /// - if the token expired more than `grace_period` before `now`
+    /// - if the token was issued for another orb
+    pub fn check(
+        &self,
+        orb_id: &str,
+        now: SystemTime,
+        grace_period: std::time::Duration,
+    ) -> Result<(), LocalValidationError> {
+        if let Some(exp) = self.exp {
+            let now = now.duration_since(UNIX_EPOCH).unwrap_or_default();
+            if now.saturating_sub(grace_period).as_secs() > exp {
+                return Err(LocalValidationError::Expired { exp });
+            }
+        }
+        if let Some(token_orb_id) = &self.orb_id {
+            if token_orb_id != orb_id {
+                return Err(LocalValidationError::WrongOrb(token_orb_id.clone()));
+            }
+        } else if !self.aud.is_empty() && !self.aud.iter().any(|aud| aud == orb_id) {
+            return Err(LocalValidationError::WrongOrb(self.aud.join(", ")));
+        }
+        Ok(())
+    }
+}
+
+/// Local checks done on a token before asking the backend about it.
+#[derive(Debug, Clone, Copy)]
+pub struct LocalValidation {
+    /// See [`DEFAULT_EXPIRY_GRACE_PERIOD`].
+    pub expiry_grace_period: std::time::Duration,
+    /// Reject tokens that can't be decoded as a JWT, instead of leaving it to
+    /// the backend.
+    pub reject_malformed: bool,
+}
+
+impl Default for LocalValidation {
+    fn default() -> Self {
+        Self {
+            expiry_grace_period: DEFAULT_EXPIRY_GRACE_PERIOD,
+            reject_malformed: false,
+        }
+    }
+}
+
 /// Try to refresh the token once, if it succeeds, return the new token.
 #[tracing::instrument]
 async fn get_token_inner(
@@ -480,13 +653,18 @@ async fn get_token_inner(
     Ok(token)
 }
 
-/// Try to refresh the token until succeeds
+/// Try to refresh the token until succeeds, passing every failed attempt to
+/// `on_error`
 ///
 /// Panics
 ///
 /// if fails to construct API URL
-#[tracing::instrument]
-pub async fn get_token(orb_id: &str, base_url: &Url) -> Token {
+#[tracing::instrument(skip(on_error))]
+pub async fn get_token<F, Fut>(orb_id: &str, base_url: &Url, mut on_error: F) -> Token
+where
+    F: FnMut(RefreshTokenError) -> Fut,
+    Fut: std::future::Future<Output = ()>,
+{
     let tokenchallenge_url = base_url.join("tokenchallenge").unwrap();
     let token_url = base_url.join("token").unwrap();
 
@@ -495,6 +673,7 @@ pub async fn get_token(orb_id: &str, base_url: &Url) -> Token {
             Ok(token) => return token,
             Err(e) => {
                 error!("failed to get token: {}", e);
+                on_error(e).await;
                 sleep(TOKEN_DELAY).await;
             }
         }
@@ -503,15 +682,17 @@ pub async fn get_token(orb_id: &str, base_url: &Url) -> Token {
 
 #[cfg(test)]
 mod test {
-    use std::os::unix::fs::PermissionsExt;
+    use std::{os::unix::fs::PermissionsExt, time::UNIX_EPOCH};
 
-    use data_encoding::BASE64;
-    use secrecy::ExposeSecret;
+    use data_encoding::{BASE64, BASE64URL_NOPAD};
+    use secrecy::{ExposeSecret, SecretString};
     use wiremock::{
         matchers::{method, path},
         Mock, MockServer, ResponseTemplate,
     };
 
+    use super::{LocalValidation, LocalValidationError};
+
     const MOCK_ORB_SIGN_ATTESTATION: &str = r#"#!/bin/sh
 printf dmFsaWRzaWduYXR1cmU=
 "#;
@@ -600,4 +781,93 @@ printf dmFsaWRzaWduYXR1cmU=
         .unwrap();
         assert_eq!(server_token, token.token.expose_secret());
     }
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn jwt(claims: &serde_json::Value) -> crate::remote_api::Token {
+        let header = BASE64URL_NOPAD.encode(br#"{"alg":"ES256","typ":"JWT"}"#);
+        let payload = BASE64URL_NOPAD.encode(claims.to_string().as_bytes());
+        crate::remote_api::Token::from_cache(
+            SecretString::from(format!("{header}.{payload}.c2lnbmF0dXJl")),
+            std::time::Duration::MAX,
+        )
+    }
+
+    fn validate(
+        token: &crate::remote_api::Token,
+        validation: &LocalValidation,
+    ) -> Result<(), LocalValidationError> {
+        let now = UNIX_EPOCH + std::time::Duration::from_secs(NOW);
+        token.validate_locally_at("TEST_ORB", validation, now)
+    }
+
+    #[test]
+    fn local_validation_accepts_valid_token() {
+        let token = jwt(&serde_json::json!({
+            "exp": NOW + 3600,
+            "iat": NOW - 3600,
+            "orbId": "TEST_ORB",
+        }));
+        validate(&token, &LocalValidation::default()).unwrap();
+
+        let token = jwt(&serde_json::json!({ "aud": ["other", "TEST_ORB"] }));
+        validate(&token, &LocalValidation::default()).unwrap();
+    }
+
+    #[test]
+    fn local_validation_rejects_expired_token() {
+        let validation = LocalValidation {
+            expiry_grace_period: std::time::Duration::from_secs(60),
+            ..LocalValidation::default()
+        };
+        let token = jwt(&serde_json::json!({ "exp": NOW - 30, "orbId": "TEST_ORB" }));
+        validate(&token, &validation).unwrap();
+
+        let token = jwt(&serde_json::json!({ "exp": NOW - 61, "orbId": "TEST_ORB" }));
+        assert!(matches!(
+            validate(&token, &validation),
+            Err(LocalValidationError::Expired { exp }) if exp == NOW - 61
+        ));
+    }
+
+    #[test]
+    fn local_validation_rejects_wrong_orb() {
+        let token = jwt(&serde_json::json!({ "exp": NOW + 3600, "orbId": "OTHER" }));
+        assert!(matches!(
+            validate(&token, &LocalValidation::default()),
+            Err(LocalValidationError::WrongOrb(orb_id)) if orb_id == "OTHER"
+        ));
+
+        let token = jwt(&serde_json::json!({ "aud": "OTHER" }));
+        assert!(matches!(
+            validate(&token, &LocalValidation::default()),
+            Err(LocalValidationError::WrongOrb(_))
+        ));
+    }
+
+    #[test]
+    fn local_validation_of_malformed_token() {
+        let strict = LocalValidation {
+            reject_malformed: true,
+            ..LocalValidation::default()
+        };
+        let not_a_jwt = crate::remote_api::Token::from_cache(
+            SecretString::from("static_token_DDDD".to_owned()),
+            std::time::Duration::MAX,
+        );
+        validate(&not_a_jwt, &LocalValidation::default()).unwrap();
+        assert!(matches!(
+            validate(&not_a_jwt, &strict),
+            Err(LocalValidationError::Malformed(_))
+        ));
+
+        let bad_payload = crate::remote_api::Token::from_cache(
+            SecretString::from("aGVhZGVy.bm90IGpzb24.c2lnbmF0dXJl".to_owned()),
+            std::time::Duration::MAX,
+        );
+        assert!(matches!(
+            validate(&bad_payload, &strict),
+            Err(LocalValidationError::Malformed(_))
+        ));
+    }
 }
@@ -0,0 +1,182 @@
+//! On-disk cache of the short lived token, so that a restart can reuse a token
+//! that is still valid instead of fetching a new one.
+
+use std::{
+    fs::Permissions,
+    io,
+    os::unix::fs::PermissionsExt as _,
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use secrecy::{ExposeSecret, SecretString};
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt as _;
+use tracing::{info, warn};
+
+use crate::remote_api::Token;
+
+/// Cached tokens closer than this to their expiry are not reused.
+const MIN_REMAINING_VALIDITY: Duration = Duration::from_secs(3600);
+
+#[derive(Serialize, Deserialize)]
+struct CachedToken {
+    orb_id: String,
+    token: String,
+    /// Unix timestamp in seconds.
+    expires_at: u64,
+}
+
+/// Persist `token` to `path`, readable only by the current user.
+///
+/// Static tokens never expire and are not cached.
+///
+/// # Errors
+/// - if failed to write the file
+pub async fn store(path: &Path, orb_id: &str, token: &Token) -> io::Result<()> {
+    let Some(expires_at) = token.expires_at() else {
+        return Ok(());
+    };
+    let cached = CachedToken {
+        orb_id: orb_id.to_owned(),
+        token: token.token.expose_secret().to_owned(),
+        expires_at: expires_at
+            .duration_since(UNIX_EPOCH)
+            .map_err(io::Error::other)?
+            .as_secs(),
+    };
+    let contents = serde_json::to_vec(&cached)?;
+
+    // Write to a temporary file first, so that a crash never leaves a partially
+    // written cache behind.
+    let tmp_path = path.with_extension("tmp");
+    let mut file = tokio::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&tmp_path)
+        .await?;
+    // `mode` only applies to newly created files.
+    file.set_permissions(Permissions::from_mode(0o600)).await?;
+    file.write_all(&contents).await?;
+    file.sync_all().await?;
+    tokio::fs::rename(&tmp_path, path).await
+}
+
+/// Load the cached token for `orb_id` from `path`.
+///
+/// Returns `None` if there is no cache, it is corrupt, belongs to another orb,
+/// or the token expires within an hour.
+pub async fn load(path: &Path, orb_id: &str) -> Option<Token> {
+    let contents = match tokio::fs::read(path).await {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return None,
+        Err(e) => {
+            warn!(error=?e, "failed to read token cache {}", path.display());
+            return None;
+        }
+    };
+    let cached: CachedToken = match serde_json::from_slice(&contents) {
+        Ok(cached) => cached,
+        Err(e) => {
+            warn!(error=?e, "ignoring corrupt token cache {}", path.display());
+            return None;
+        }
+    };
+    if cached.orb_id != orb_id {
+        info!("ignoring token cache of another orb: {}", cached.orb_id);
+        return None;
+    }
+    let remaining = (UNIX_EPOCH + Duration::from_secs(cached.expires_at))
+        .duration_since(SystemTime::now())
+        .unwrap_or_default();
+    if remaining < MIN_REMAINING_VALIDITY {
+        info!(
+            "cached token expires in {}s, ignoring it",
+            remaining.as_secs()
+        );
+        return None;
+    }
+    Some(Token::from_cache(
+        SecretString::from(cached.token),
+        remaining,
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use std::os::unix::fs::PermissionsExt as _;
+
+    use secrecy::ExposeSecret;
+
+    use super::*;
+
+    const ORB_ID: &str = "TEST_ORB";
+
+    fn token(validity: Duration) -> Token {
+        Token::from_cache(SecretString::from("token_AAAA".to_owned()), validity)
+    }
+
+    #[tokio::test]
+    async fn round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("token-cache.json");
+
+        store(&path, ORB_ID, &token(Duration::from_secs(36000)))
+            .await
+            .unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        let loaded = load(&path, ORB_ID).await.unwrap();
+        assert_eq!(loaded.token.expose_secret(), "token_AAAA");
+        let remaining = loaded
+            .expires_at()
+            .unwrap()
+            .duration_since(SystemTime::now())
+            .unwrap();
+        assert!(remaining > Duration::from_secs(35990), "{remaining:?}");
+        assert!(remaining <= Duration::from_secs(36000), "{remaining:?}");
+
+        assert!(load(&path, "OTHER_ORB").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn expired_token_is_ignored() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("token-cache.json");
+
+        store(&path, ORB_ID, &token(Duration::from_secs(1800)))
+            .await
+            .unwrap();
+        assert!(load(&path, ORB_ID).await.is_none());
+
+        let expired = serde_json::json!({
+            "orb_id": ORB_ID,
+            "token": "token_AAAA",
+            "expires_at": 1000,
+        });
+        std::fs::write(&path, expired.to_string()).unwrap();
+        assert!(load(&path, ORB_ID).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn missing_or_corrupt_cache_is_ignored() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("token-cache.json");
+
+        assert!(load(&path, ORB_ID).await.is_none());
+        std::fs::write(&path, "{\"orb_id\": \"TEST_ORB\", \"tok").unwrap();
+        assert!(load(&path, ORB_ID).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn static_token_is_not_cached() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("token-cache.json");
+
+        store(&path, ORB_ID, &token(Duration::MAX)).await.unwrap();
+        assert!(!path.exists());
+    }
+}
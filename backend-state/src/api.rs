@@ -1,10 +1,11 @@
 //! HTTP requests to the orb-manager backend. This is purely network io.
 
-use std::sync::OnceLock;
+use std::{sync::OnceLock, time::Duration};
 
 use color_eyre::{eyre::WrapErr, Result};
 use derive_more::{Display, From};
-use orb_header_parsing::time_until_max_age;
+use orb_header_parsing::CachePolicy;
+use reqwest::header::HeaderMap;
 
 use crate::state::State;
 
@@ -28,7 +29,7 @@ pub async fn get_state(id: &OrbId, token: &Token) -> Result<State> {
         .wrap_err("Error while making get request for orb state to backend")?
         .error_for_status()
         .wrap_err("http status was an error")?;
-    let expires_in = time_until_max_age(response.headers());
+    let expires_in = expires_in(response.headers());
     let body = response
         .text()
         .await
@@ -36,6 +37,17 @@ pub async fn get_state(id: &OrbId, token: &Token) -> Result<State> {
     Ok(State::new(body, expires_in))
 }
 
+/// How long the state in a response with `headers` stays valid.
+///
+/// A zero time to live, e.g. from `no-cache`, `no-store` or `Expires: 0`, is
+/// treated like no caching information. Polling again right away would
+/// hammer the backend.
+fn expires_in(headers: &HeaderMap) -> Option<Duration> {
+    CachePolicy::from_headers(headers)
+        .map(|policy| policy.time_to_live())
+        .filter(|ttl| !ttl.is_zero())
+}
+
 fn get_http_client() -> &'static reqwest::Client {
     static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
     CLIENT.get_or_init(|| {
@@ -44,3 +56,27 @@ fn get_http_client() -> &'static reqwest::Client {
             .expect("Failed to build client")
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::{HeaderValue, CACHE_CONTROL, EXPIRES};
+
+    use super::*;
+
+    #[test]
+    fn test_expires_in() {
+        let cases = [
+            (CACHE_CONTROL, "max-age=60", Some(Duration::from_secs(60))),
+            (CACHE_CONTROL, "no-cache", None),
+            (CACHE_CONTROL, "no-store", None),
+            (CACHE_CONTROL, "max-age=0", None),
+            (EXPIRES, "0", None),
+        ];
+        for (i, (name, value, expected)) in cases.into_iter().enumerate() {
+            let mut headers = HeaderMap::new();
+            headers.insert(name, HeaderValue::from_static(value));
+            assert_eq!(expires_in(&headers), expected, "{i}th case failed");
+        }
+        assert_eq!(expires_in(&HeaderMap::new()), None);
+    }
+}
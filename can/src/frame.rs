@@ -1,4 +1,4 @@
-use std::cmp::Ordering;
+use std::{cmp::Ordering, ops};
 
 /// Bit Rate Switch (second bitrate for payload data)
 pub const CANFD_BRS_FLAG: u8 = 0x01;
@@ -162,6 +162,273 @@ pub fn convert_len_to_dlc(len: Length) -> Length {
     }
 }
 
+/// Error class bits of an error frame's CAN ID, from `linux/can/error.h`.
+///
+/// Used both to decode [`ErrorFrame`]s and, as a mask, to select which error
+/// classes the kernel reports on a socket with
+/// [`FrameStream::set_error_mask`](crate::stream::FrameStream::set_error_mask).
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct ErrorMask(pub u32);
+
+impl ErrorMask {
+    /// No error frames are reported. The default of a new socket.
+    pub const NONE: ErrorMask = ErrorMask(0);
+    /// TX timeout (by netdevice driver).
+    pub const TX_TIMEOUT: ErrorMask = ErrorMask(0x0000_0001);
+    /// Lost arbitration.
+    pub const LOST_ARBITRATION: ErrorMask = ErrorMask(0x0000_0002);
+    /// Controller problems.
+    pub const CONTROLLER: ErrorMask = ErrorMask(0x0000_0004);
+    /// Protocol violations.
+    pub const PROTOCOL: ErrorMask = ErrorMask(0x0000_0008);
+    /// Transceiver status.
+    pub const TRANSCEIVER: ErrorMask = ErrorMask(0x0000_0010);
+    /// Received no ACK on transmission.
+    pub const NO_ACK: ErrorMask = ErrorMask(0x0000_0020);
+    /// Bus off.
+    pub const BUS_OFF: ErrorMask = ErrorMask(0x0000_0040);
+    /// Bus error (may flood!).
+    pub const BUS_ERROR: ErrorMask = ErrorMask(0x0000_0080);
+    /// Controller restarted.
+    pub const RESTARTED: ErrorMask = ErrorMask(0x0000_0100);
+    /// TX and RX error counters are present.
+    pub const COUNTERS: ErrorMask = ErrorMask(0x0000_0200);
+    /// Every error class.
+    pub const ALL: ErrorMask = ErrorMask(CAN_ERR_MASK);
+
+    pub fn contains(self, other: ErrorMask) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl ops::BitOr for ErrorMask {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        ErrorMask(self.0 | rhs.0)
+    }
+}
+
+impl ops::BitOrAssign for ErrorMask {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+/// Redefined CAN_ERR_FLAG from libc::CAN_ERR_FLAG, marks a frame as an error frame
+pub const CAN_ERR_FLAG: u32 = 0x2000_0000;
+/// Redefined CAN_ERR_MASK from libc::CAN_ERR_MASK
+pub const CAN_ERR_MASK: u32 = 0x1FFF_FFFF;
+
+/// Controller problems, `data[1]` of an error frame.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct ControllerProblems(pub u8);
+
+impl ControllerProblems {
+    pub const RX_OVERFLOW: ControllerProblems = ControllerProblems(0x01);
+    pub const TX_OVERFLOW: ControllerProblems = ControllerProblems(0x02);
+    /// Reached warning level for RX errors.
+    pub const RX_WARNING: ControllerProblems = ControllerProblems(0x04);
+    /// Reached warning level for TX errors.
+    pub const TX_WARNING: ControllerProblems = ControllerProblems(0x08);
+    /// Reached error passive status RX.
+    pub const RX_PASSIVE: ControllerProblems = ControllerProblems(0x10);
+    /// Reached error passive status TX.
+    pub const TX_PASSIVE: ControllerProblems = ControllerProblems(0x20);
+    /// Recovered to error active state.
+    pub const ACTIVE: ControllerProblems = ControllerProblems(0x40);
+
+    pub fn contains(self, other: ControllerProblems) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+/// Type of protocol violation, `data[2]` of an error frame.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct ProtocolViolation(pub u8);
+
+impl ProtocolViolation {
+    /// Single bit error.
+    pub const BIT: ProtocolViolation = ProtocolViolation(0x01);
+    /// Frame format error.
+    pub const FORM: ProtocolViolation = ProtocolViolation(0x02);
+    /// Bit stuffing error.
+    pub const STUFF: ProtocolViolation = ProtocolViolation(0x04);
+    /// Unable to send dominant bit.
+    pub const BIT0: ProtocolViolation = ProtocolViolation(0x08);
+    /// Unable to send recessive bit.
+    pub const BIT1: ProtocolViolation = ProtocolViolation(0x10);
+    /// Bus overload.
+    pub const OVERLOAD: ProtocolViolation = ProtocolViolation(0x20);
+    /// Active error announcement.
+    pub const ACTIVE: ProtocolViolation = ProtocolViolation(0x40);
+    /// Error occurred on transmission.
+    pub const TX: ProtocolViolation = ProtocolViolation(0x80);
+
+    pub fn contains(self, other: ProtocolViolation) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+/// Location of a protocol violation in the frame, `data[3]` of an error frame.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum ProtocolLocation {
+    Unspecified,
+    StartOfFrame,
+    Id28To21,
+    Id20To18,
+    SubstituteRtr,
+    IdentifierExtension,
+    Id17To13,
+    Id12To05,
+    Id04To00,
+    Rtr,
+    Reserved1,
+    Reserved0,
+    Dlc,
+    Data,
+    CrcSequence,
+    CrcDelimiter,
+    AckSlot,
+    AckDelimiter,
+    EndOfFrame,
+    Intermission,
+    Other(u8),
+}
+
+impl From<u8> for ProtocolLocation {
+    fn from(location: u8) -> Self {
+        match location {
+            0x00 => Self::Unspecified,
+            0x03 => Self::StartOfFrame,
+            0x02 => Self::Id28To21,
+            0x06 => Self::Id20To18,
+            0x04 => Self::SubstituteRtr,
+            0x05 => Self::IdentifierExtension,
+            0x07 => Self::Id17To13,
+            0x0F => Self::Id12To05,
+            0x0E => Self::Id04To00,
+            0x0C => Self::Rtr,
+            0x0D => Self::Reserved1,
+            0x09 => Self::Reserved0,
+            0x0B => Self::Dlc,
+            0x0A => Self::Data,
+            0x08 => Self::CrcSequence,
+            0x18 => Self::CrcDelimiter,
+            0x19 => Self::AckSlot,
+            0x1B => Self::AckDelimiter,
+            0x1A => Self::EndOfFrame,
+            0x12 => Self::Intermission,
+            other => Self::Other(other),
+        }
+    }
+}
+
+/// A single error reported by an [`ErrorFrame`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum CanError {
+    TxTimeout,
+    /// Lost arbitration at the given bit, if known.
+    LostArbitration {
+        bit: Option<u8>,
+    },
+    Controller(ControllerProblems),
+    Protocol {
+        violation: ProtocolViolation,
+        location: ProtocolLocation,
+    },
+    /// Transceiver status, `data[4]` of the error frame.
+    Transceiver(u8),
+    NoAck,
+    BusOff,
+    BusError,
+    Restarted,
+}
+
+/// TX and RX error counters of the controller.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct ErrorCounters {
+    pub tx: u8,
+    pub rx: u8,
+}
+
+/// An error frame generated by the CAN controller or driver, decoded following
+/// the layout of `linux/can/error.h`.
+///
+/// Error frames are only received after enabling their classes with
+/// [`FrameStream::set_error_mask`](crate::stream::FrameStream::set_error_mask).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ErrorFrame {
+    pub class: ErrorMask,
+    /// The errors, in the order of their class bits.
+    pub errors: Vec<CanError>,
+    pub counters: Option<ErrorCounters>,
+}
+
+impl ErrorFrame {
+    /// Decodes the CAN ID and data of an error frame. Missing data bytes are
+    /// read as zero.
+    pub fn decode(id: u32, data: &[u8]) -> Self {
+        let class = ErrorMask(id & CAN_ERR_MASK);
+        let byte = |i: usize| data.get(i).copied().unwrap_or(0);
+        let mut errors = Vec::new();
+        if class.contains(ErrorMask::TX_TIMEOUT) {
+            errors.push(CanError::TxTimeout);
+        }
+        if class.contains(ErrorMask::LOST_ARBITRATION) {
+            // 0x00 means unspecified
+            let bit = Some(byte(0)).filter(|&bit| bit != 0);
+            errors.push(CanError::LostArbitration { bit });
+        }
+        if class.contains(ErrorMask::CONTROLLER) {
+            errors.push(CanError::Controller(ControllerProblems(byte(1))));
+        }
+        if class.contains(ErrorMask::PROTOCOL) {
+            errors.push(CanError::Protocol {
+                violation: ProtocolViolation(byte(2)),
+                location: byte(3).into(),
+            });
+        }
+        if class.contains(ErrorMask::TRANSCEIVER) {
+            errors.push(CanError::Transceiver(byte(4)));
+        }
+        if class.contains(ErrorMask::NO_ACK) {
+            errors.push(CanError::NoAck);
+        }
+        if class.contains(ErrorMask::BUS_OFF) {
+            errors.push(CanError::BusOff);
+        }
+        if class.contains(ErrorMask::BUS_ERROR) {
+            errors.push(CanError::BusError);
+        }
+        if class.contains(ErrorMask::RESTARTED) {
+            errors.push(CanError::Restarted);
+        }
+        let counters = class.contains(ErrorMask::COUNTERS).then(|| ErrorCounters {
+            tx: byte(6),
+            rx: byte(7),
+        });
+        Self {
+            class,
+            errors,
+            counters,
+        }
+    }
+
+    /// Whether the controller went bus-off and stopped taking part in bus
+    /// traffic until it is restarted.
+    pub fn is_bus_off(&self) -> bool {
+        self.class.contains(ErrorMask::BUS_OFF)
+    }
+}
+
+/// An item received on a raw CAN socket.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CanItem<const N: usize> {
+    Data(Frame<N>),
+    Error(ErrorFrame),
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -197,4 +464,112 @@ mod tests {
 
         assert_eq!(std::cmp::Ordering::Greater, dominant.cmp(&recessive));
     }
+
+    #[test]
+    fn decode_error_frames() {
+        // (can_id, data, expected errors, expected counters)
+        let cases = [
+            (
+                CAN_ERR_FLAG | 0x0040,
+                [0u8; 8],
+                vec![CanError::BusOff],
+                None,
+            ),
+            (
+                CAN_ERR_FLAG | 0x0001,
+                [0u8; 8],
+                vec![CanError::TxTimeout],
+                None,
+            ),
+            (
+                CAN_ERR_FLAG | 0x0002,
+                [12, 0, 0, 0, 0, 0, 0, 0],
+                vec![CanError::LostArbitration { bit: Some(12) }],
+                None,
+            ),
+            (
+                CAN_ERR_FLAG | 0x0002,
+                [0u8; 8],
+                vec![CanError::LostArbitration { bit: None }],
+                None,
+            ),
+            (
+                CAN_ERR_FLAG | 0x0204,
+                [0, 0x28, 0, 0, 0, 0, 130, 7],
+                vec![CanError::Controller(ControllerProblems(0x28))],
+                Some(ErrorCounters { tx: 130, rx: 7 }),
+            ),
+            (
+                CAN_ERR_FLAG | 0x0088,
+                [0, 0, 0x84, 0x19, 0, 0, 0, 0],
+                vec![
+                    CanError::Protocol {
+                        violation: ProtocolViolation(0x84),
+                        location: ProtocolLocation::AckSlot,
+                    },
+                    CanError::BusError,
+                ],
+                None,
+            ),
+            (
+                CAN_ERR_FLAG | 0x0008,
+                [0, 0, 0x02, 0x1C, 0, 0, 0, 0],
+                vec![CanError::Protocol {
+                    violation: ProtocolViolation::FORM,
+                    location: ProtocolLocation::Other(0x1C),
+                }],
+                None,
+            ),
+            (
+                CAN_ERR_FLAG | 0x0130,
+                [0, 0, 0, 0, 0x04, 0, 0, 0],
+                vec![
+                    CanError::Transceiver(0x04),
+                    CanError::NoAck,
+                    CanError::Restarted,
+                ],
+                None,
+            ),
+        ];
+        for (i, (id, data, errors, counters)) in cases.into_iter().enumerate() {
+            let frame = ErrorFrame::decode(id, &data);
+            assert_eq!(
+                frame.class,
+                ErrorMask(id & CAN_ERR_MASK),
+                "{i}th case failed"
+            );
+            assert_eq!(frame.errors, errors, "{i}th case failed");
+            assert_eq!(frame.counters, counters, "{i}th case failed");
+        }
+    }
+
+    #[test]
+    fn decode_bus_off() {
+        let frame =
+            ErrorFrame::decode(CAN_ERR_FLAG | 0x0244, &[0, 0x20, 0, 0, 0, 0, 255, 0]);
+        assert!(frame.is_bus_off());
+        assert_eq!(
+            frame.errors,
+            [
+                CanError::Controller(ControllerProblems::TX_PASSIVE),
+                CanError::BusOff
+            ]
+        );
+        assert!(!ErrorFrame::decode(CAN_ERR_FLAG | 0x0004, &[0; 8]).is_bus_off());
+        // short data reads as zero
+        let frame = ErrorFrame::decode(CAN_ERR_FLAG | 0x0200, &[]);
+        assert_eq!(frame.counters, Some(ErrorCounters { tx: 0, rx: 0 }));
+    }
+
+    #[test]
+    fn error_mask_ops() {
+        let mask = ErrorMask::BUS_OFF | ErrorMask::CONTROLLER;
+        assert_eq!(mask, ErrorMask(0x44));
+        assert!(mask.contains(ErrorMask::BUS_OFF));
+        assert!(!mask.contains(ErrorMask::BUS_OFF | ErrorMask::TX_TIMEOUT));
+        assert!(ErrorMask::ALL.contains(mask));
+        let mut mask = ErrorMask::NONE;
+        mask |= ErrorMask::RESTARTED;
+        assert_eq!(mask, ErrorMask::RESTARTED);
+    }
 }
@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 pub mod addr;
 pub mod flowcontrol;
 pub mod linklayer;
@@ -12,6 +14,11 @@ pub const CAN_ISOTP_TX_STMIN: libc::c_int = 3;
 pub const CAN_ISOTP_RX_STMIN: libc::c_int = 4;
 pub const CAN_ISOTP_LL_OPTS: libc::c_int = 5;
 
+/// Options of an ISO-TP socket, applied before binding.
+///
+/// The defaults match the kernel defaults. Options left unset are not applied.
+/// Block size and the STmin sent in our flow control frames are configured
+/// through [`FlowControlOptions`](flowcontrol::FlowControlOptions).
 #[derive(Debug, Clone, Copy)]
 pub struct IsotpOptions {
     flags: u32,
@@ -20,6 +27,9 @@ pub struct IsotpOptions {
     tx_padding_content: u8,
     rx_padding_content: u8,
     rx_extended_address: u8,
+    tx_stmin: Option<Duration>,
+    rx_stmin: Option<Duration>,
+    recv_timeout: Option<Duration>,
 }
 
 impl Default for IsotpOptions {
@@ -31,10 +41,87 @@ impl Default for IsotpOptions {
             tx_padding_content: 0xCC,
             rx_padding_content: 0xCC,
             rx_extended_address: 0,
+            tx_stmin: None,
+            rx_stmin: None,
+            recv_timeout: None,
         }
     }
 }
 
+impl IsotpOptions {
+    /// Sets a `CAN_ISOTP_OPTS` flag.
+    #[must_use]
+    pub fn flag(mut self, flag: IsotpFlags) -> Self {
+        self.flags |= flag as u32;
+        self
+    }
+
+    /// Sets the frame transmission time (N_As/N_Ar).
+    #[must_use]
+    pub fn frame_txtime(mut self, txtime: Duration) -> Self {
+        self.transmission_time_nano = duration_as_nanos_u32(txtime);
+        self
+    }
+
+    /// Enables extended addressing with the given address.
+    #[must_use]
+    pub fn extended_address(mut self, address: u8) -> Self {
+        self.extended_address = address;
+        self.flag(IsotpFlags::ExtendAddr)
+    }
+
+    /// Enables a different extended address on the RX path.
+    #[must_use]
+    pub fn rx_extended_address(mut self, address: u8) -> Self {
+        self.rx_extended_address = address;
+        self.flag(IsotpFlags::RxExtendAddr)
+    }
+
+    /// Enables padding of transmitted frames with the given byte.
+    #[must_use]
+    pub fn tx_padding(mut self, content: u8) -> Self {
+        self.tx_padding_content = content;
+        self.flag(IsotpFlags::TxPadding)
+    }
+
+    /// Enables padding on the RX path, expecting the given byte.
+    #[must_use]
+    pub fn rx_padding(mut self, content: u8) -> Self {
+        self.rx_padding_content = content;
+        self.flag(IsotpFlags::RxPadding)
+    }
+
+    /// Forces a minimum separation time between transmitted consecutive frames,
+    /// ignoring the one requested by the receiver (`CAN_ISOTP_TX_STMIN`).
+    #[must_use]
+    pub fn tx_stmin(mut self, stmin: Duration) -> Self {
+        self.tx_stmin = Some(stmin);
+        self.flag(IsotpFlags::ForceTxSeparationTimeMin)
+    }
+
+    /// Ignores consecutive frames arriving faster than `stmin`
+    /// (`CAN_ISOTP_RX_STMIN`).
+    #[must_use]
+    pub fn rx_stmin(mut self, stmin: Duration) -> Self {
+        self.rx_stmin = Some(stmin);
+        self.flag(IsotpFlags::ForceRxSeparationTimeMin)
+    }
+
+    /// Makes reads fail with [`std::io::ErrorKind::WouldBlock`] after `timeout`
+    /// (`SO_RCVTIMEO`).
+    #[must_use]
+    pub fn recv_timeout(mut self, timeout: Duration) -> Self {
+        self.recv_timeout = Some(timeout);
+        self
+    }
+}
+
+/// Kernel ISO-TP times are `__u32` nanoseconds, saturate instead of wrapping.
+fn duration_as_nanos_u32(duration: Duration) -> u32 {
+    duration.as_nanos().try_into().unwrap_or(u32::MAX)
+}
+
+#[derive(Debug, Clone, Copy)]
 #[repr(u32)]
 pub enum IsotpFlags {
     /// Disables sending of Flow Control frames
@@ -60,7 +147,7 @@ pub enum IsotpFlags {
 }
 
 pub(crate) mod imp {
-    use super::IsotpOptions;
+    use super::{duration_as_nanos_u32, IsotpOptions};
 
     // 	__u32 flags;	    	/* set flags for isotp behaviour.	*/
     // 		            		/* __u32 value : flags see below	*/
@@ -115,4 +202,114 @@ pub(crate) mod imp {
             }
         }
     }
+
+    impl IsotpOptions {
+        /// `CAN_ISOTP_TX_STMIN` value, if set.
+        pub(crate) fn raw_tx_stmin(&self) -> Option<u32> {
+            self.tx_stmin.map(duration_as_nanos_u32)
+        }
+
+        /// `CAN_ISOTP_RX_STMIN` value, if set.
+        pub(crate) fn raw_rx_stmin(&self) -> Option<u32> {
+            self.rx_stmin.map(duration_as_nanos_u32)
+        }
+
+        /// `SO_RCVTIMEO` value, if set.
+        pub(crate) fn raw_recv_timeout(&self) -> Option<libc::timeval> {
+            self.recv_timeout.map(|timeout| libc::timeval {
+                tv_sec: timeout.as_secs().try_into().unwrap_or(libc::time_t::MAX),
+                tv_usec: timeout.subsec_micros() as libc::suseconds_t,
+            })
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use std::{
+            mem::{offset_of, size_of},
+            time::Duration,
+        };
+
+        use super::*;
+        use crate::isotp::{
+            flowcontrol::imp::RawFlowControlOptions,
+            linklayer::imp::RawLinkLayerOptions, IsotpFlags,
+        };
+
+        #[test]
+        fn test_raw_layouts() {
+            // struct can_isotp_options
+            assert_eq!(size_of::<RawIsotpOptions>(), 12);
+            assert_eq!(offset_of!(RawIsotpOptions, flags), 0);
+            assert_eq!(offset_of!(RawIsotpOptions, frame_txtime), 4);
+            assert_eq!(offset_of!(RawIsotpOptions, ext_address), 8);
+            assert_eq!(offset_of!(RawIsotpOptions, txpad_content), 9);
+            assert_eq!(offset_of!(RawIsotpOptions, rxpad_content), 10);
+            assert_eq!(offset_of!(RawIsotpOptions, rx_ext_address), 11);
+            // struct can_isotp_fc_options
+            assert_eq!(size_of::<RawFlowControlOptions>(), 3);
+            // struct can_isotp_ll_options
+            assert_eq!(size_of::<RawLinkLayerOptions>(), 3);
+        }
+
+        #[test]
+        fn test_default_matches_kernel() {
+            let raw = RawIsotpOptions::from(IsotpOptions::default());
+            let kernel = RawIsotpOptions::default();
+            assert_eq!(raw.flags, kernel.flags);
+            assert_eq!(raw.frame_txtime, kernel.frame_txtime);
+            assert_eq!(raw.txpad_content, kernel.txpad_content);
+            assert_eq!(raw.rxpad_content, kernel.rxpad_content);
+            assert!(IsotpOptions::default().raw_tx_stmin().is_none());
+            assert!(IsotpOptions::default().raw_rx_stmin().is_none());
+            assert!(IsotpOptions::default().raw_recv_timeout().is_none());
+        }
+
+        #[test]
+        fn test_stmin_sets_force_flags() {
+            // The kernel ignores the STmin options without the matching flag.
+            let cases = [
+                (
+                    IsotpOptions::default().tx_stmin(Duration::from_micros(100)),
+                    IsotpFlags::ForceTxSeparationTimeMin as u32,
+                ),
+                (
+                    IsotpOptions::default().rx_stmin(Duration::from_micros(100)),
+                    IsotpFlags::ForceRxSeparationTimeMin as u32,
+                ),
+            ];
+            for (i, (opts, flags)) in cases.into_iter().enumerate() {
+                let raw = RawIsotpOptions::from(opts);
+                assert_eq!(raw.flags, flags, "{i}th case failed");
+            }
+        }
+
+        #[test]
+        fn test_builder() {
+            let opts = IsotpOptions::default()
+                .flag(IsotpFlags::HalfDuplex)
+                .tx_padding(0xAA)
+                .frame_txtime(Duration::from_micros(50))
+                .tx_stmin(Duration::from_micros(500))
+                .rx_stmin(Duration::from_secs(10))
+                .recv_timeout(Duration::from_millis(1500));
+            let raw = RawIsotpOptions::from(opts);
+
+            assert_eq!(
+                raw.flags,
+                IsotpFlags::HalfDuplex as u32
+                    | IsotpFlags::TxPadding as u32
+                    | IsotpFlags::ForceTxSeparationTimeMin as u32
+                    | IsotpFlags::ForceRxSeparationTimeMin as u32
+            );
+            assert_eq!(raw.txpad_content, 0xAA);
+            assert_eq!(raw.rxpad_content, 0xCC);
+            assert_eq!(raw.frame_txtime, 50_000);
+            assert_eq!(opts.raw_tx_stmin(), Some(500_000));
+            // Saturates instead of wrapping.
+            assert_eq!(opts.raw_rx_stmin(), Some(u32::MAX));
+            let timeout = opts.raw_recv_timeout().unwrap();
+            assert_eq!((timeout.tv_sec, timeout.tv_usec), (1, 500_000));
+        }
+    }
 }
@@ -5,7 +5,7 @@ pub(crate) mod imp {
         isotp::{
             flowcontrol::imp::RawFlowControlOptions, imp::RawIsotpOptions,
             linklayer::imp::RawLinkLayerOptions, CAN_ISOTP_LL_OPTS, CAN_ISOTP_OPTS,
-            CAN_ISOTP_RECV_FC, SOL_CAN_ISOTP,
+            CAN_ISOTP_RECV_FC, CAN_ISOTP_RX_STMIN, CAN_ISOTP_TX_STMIN, SOL_CAN_ISOTP,
         },
         Error,
     };
@@ -92,4 +92,63 @@ pub(crate) mod imp {
         }
         Ok(())
     }
+
+    pub(crate) fn set_tx_stmin<T: AsRawFd>(fd: T, nanos: u32) -> Result<(), Error> {
+        set_opt(
+            fd,
+            SOL_CAN_ISOTP,
+            CAN_ISOTP_TX_STMIN,
+            "CAN_ISOTP_TX_STMIN",
+            &nanos,
+        )
+    }
+
+    pub(crate) fn set_rx_stmin<T: AsRawFd>(fd: T, nanos: u32) -> Result<(), Error> {
+        set_opt(
+            fd,
+            SOL_CAN_ISOTP,
+            CAN_ISOTP_RX_STMIN,
+            "CAN_ISOTP_RX_STMIN",
+            &nanos,
+        )
+    }
+
+    pub(crate) fn set_recv_timeout<T: AsRawFd>(
+        fd: T,
+        timeout: libc::timeval,
+    ) -> Result<(), Error> {
+        set_opt(
+            fd,
+            libc::SOL_SOCKET,
+            libc::SO_RCVTIMEO,
+            "SO_RCVTIMEO",
+            &timeout,
+        )
+    }
+
+    fn set_opt<T: AsRawFd, V>(
+        fd: T,
+        level: libc::c_int,
+        name: libc::c_int,
+        name_str: &str,
+        value: &V,
+    ) -> Result<(), Error> {
+        let ret = unsafe {
+            libc::setsockopt(
+                fd.as_raw_fd(),
+                level,
+                name,
+                value as *const V as *const libc::c_void,
+                std::mem::size_of::<V>() as u32,
+            )
+        };
+        if ret < 0 {
+            return Err(Error::Syscall {
+                syscall: "setsockopt(2)".to_string(),
+                context: Some(format!("setting {name_str}")),
+                source: io::Error::last_os_error(),
+            });
+        }
+        Ok(())
+    }
 }
@@ -93,6 +93,12 @@ impl IsotpStream<CAN_DATA_LEN> {
         IsotpStreamBuilder::new()
     }
 
+    /// Binds a stream with the given options and default flow control and link
+    /// layer options.
+    pub fn with_options(addr: CanIsotpAddr, opts: IsotpOptions) -> Result<Self, Error> {
+        Self::build().isotp_opts(opts).bind(addr)
+    }
+
     pub fn new(addr: CanIsotpAddr) -> Result<Self, Error> {
         Ok(Self {
             fd: socket::new(Type::DGRAM, Protocol::ISOTP)?,
@@ -106,6 +112,12 @@ impl IsotpStream<CANFD_DATA_LEN> {
         IsotpStreamBuilder::new()
     }
 
+    /// Binds a stream with the given options and default flow control and link
+    /// layer options.
+    pub fn with_options(addr: CanIsotpAddr, opts: IsotpOptions) -> Result<Self, Error> {
+        Self::build().isotp_opts(opts).bind(addr)
+    }
+
     pub fn new(addr: CanIsotpAddr) -> Result<Self, Error> {
         Ok(Self {
             fd: socket::new(Type::DGRAM, Protocol::ISOTP)?,
@@ -235,6 +247,15 @@ mod imp {
         socket::set_nonblocking(fd, nonblocking)?;
 
         socket_isotp::imp::set_isotp_opts(fd.as_raw_fd(), isotp_opts)?;
+        if let Some(nanos) = isotp_opts.raw_tx_stmin() {
+            socket_isotp::imp::set_tx_stmin(fd.as_raw_fd(), nanos)?;
+        }
+        if let Some(nanos) = isotp_opts.raw_rx_stmin() {
+            socket_isotp::imp::set_rx_stmin(fd.as_raw_fd(), nanos)?;
+        }
+        if let Some(timeout) = isotp_opts.raw_recv_timeout() {
+            socket_isotp::imp::set_recv_timeout(fd.as_raw_fd(), timeout)?;
+        }
         socket_isotp::imp::set_flow_control_opts(fd.as_raw_fd(), flow_control_opts)?;
         socket_isotp::imp::set_link_layer_opts(fd.as_raw_fd(), link_layer_opts)?;
 
@@ -11,7 +11,7 @@ use libc::CAN_RAW_LOOPBACK;
 use crate::{
     addr::{try_ifindex_to_ifname, RawCanAddr},
     filter::{Filter, RawFilter},
-    ifreq_siocgifmtu, Error, Protocol, Type, CAN_RAW_FD_FRAMES_ENABLE,
+    ifreq_siocgifmtu, Error, ErrorMask, Protocol, Type, CAN_RAW_FD_FRAMES_ENABLE,
     CAN_RAW_FILTER_MAX, MTU,
 };
 
@@ -71,6 +71,29 @@ pub(crate) fn loopback<T: AsRawFd>(fd: &T) -> Result<bool, Error> {
     Ok(loopback)
 }
 
+/// Selects the error classes the kernel reports as error frames on the socket
+///
+/// See `CAN_RAW_ERR_FILTER` in <https://docs.kernel.org/networking/can.html>.
+pub(crate) fn set_error_mask<T: AsRawFd>(fd: &T, mask: ErrorMask) -> Result<(), Error> {
+    let ret = unsafe {
+        libc::setsockopt(
+            fd.as_raw_fd(),
+            libc::SOL_CAN_RAW,
+            libc::CAN_RAW_ERR_FILTER,
+            std::ptr::addr_of!(mask.0).cast::<libc::c_void>(),
+            std::mem::size_of::<u32>() as u32,
+        )
+    };
+    if ret < 0 {
+        return Err(Error::Syscall {
+            syscall: "setsockopt(2)".to_string(),
+            context: Some(format!("setting CAN_RAW_ERR_FILTER to {mask:?}")),
+            source: io::Error::last_os_error(),
+        });
+    }
+    Ok(())
+}
+
 /// Set socket to nonblocking mode
 ///
 /// Subsequent `read` or `recv` calls on the bound socket may result in a `EAGAIN` or
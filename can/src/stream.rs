@@ -56,6 +56,7 @@ pub struct FrameStream<const N: usize> {
 pub struct FrameStreamBuilder<const N: usize> {
     pub(crate) nonblocking: bool,
     pub(crate) filters: Vec<Filter>,
+    pub(crate) error_mask: ErrorMask,
 }
 
 impl<const N: usize> FrameStreamBuilder<N> {
@@ -63,6 +64,7 @@ impl<const N: usize> FrameStreamBuilder<N> {
         Self {
             nonblocking: false,
             filters: vec![],
+            error_mask: ErrorMask::NONE,
         }
     }
 
@@ -75,6 +77,13 @@ impl<const N: usize> FrameStreamBuilder<N> {
         self.filters = filters;
         self
     }
+
+    /// Error classes to receive as [`ErrorFrame`]s, see
+    /// [`FrameStream::set_error_mask`].
+    pub fn error_mask(&mut self, error_mask: ErrorMask) -> &mut Self {
+        self.error_mask = error_mask;
+        self
+    }
 }
 
 impl<const N: usize> Default for FrameStreamBuilder<N> {
@@ -129,6 +138,15 @@ impl<const N: usize> FrameStream<N> {
         imp::set_filters_fd(self, filters)
     }
 
+    /// Selects the error classes the kernel reports on this socket. They are
+    /// received as [`CanItem::Error`] by [`recv_item`](Self::recv_item), and
+    /// skipped by the methods that only return data frames.
+    ///
+    /// Error frames are disabled by default.
+    pub fn set_error_mask(&self, mask: ErrorMask) -> Result<(), Error> {
+        socket::set_error_mask(self, mask)
+    }
+
     pub fn filters(&self) -> Result<Vec<Filter>, Error> {
         let ffi_filters = socket::filters(self)?;
         let mut filters = Vec::<Filter>::with_capacity(ffi_filters.len());
@@ -138,28 +156,47 @@ impl<const N: usize> FrameStream<N> {
 }
 
 impl<const N: usize> FrameStream<N> {
+    /// Receives the next data or error frame.
+    pub fn recv_item(&self, flags: c_int) -> io::Result<CanItem<N>> {
+        let mut raw = RawFrame::empty();
+        imp::recv_from(self.as_raw_fd(), &mut raw, flags, Empty)?;
+        Ok(raw.into())
+    }
+
+    /// Receives the next data frame, skipping error frames.
     pub fn recv_frame(&self, flags: c_int) -> io::Result<Frame<N>> {
         let mut frame = Frame::empty();
         self.recv(&mut frame, flags).map(|_| frame)
     }
 
+    /// Receives the next data frame, skipping error frames.
     pub fn recv(&self, frame: &mut Frame<N>, flags: c_int) -> io::Result<usize> {
-        let mut raw = RawFrame::empty();
-        let size = imp::recv_from(self.as_raw_fd(), &mut raw, flags, Empty)?;
-        let _ = std::mem::replace(frame, raw.into());
-        Ok(size)
+        loop {
+            let mut raw = RawFrame::empty();
+            let size = imp::recv_from(self.as_raw_fd(), &mut raw, flags, Empty)?;
+            if let CanItem::Data(data) = raw.into() {
+                let _ = std::mem::replace(frame, data);
+                return Ok(size);
+            }
+        }
     }
 
+    /// Receives the next data frame, skipping error frames.
     pub fn recv_from(
         &self,
         frame: &mut Frame<N>,
         flags: c_int,
         src_addr: &mut CanAddr,
     ) -> io::Result<usize> {
-        let mut raw = RawFrame::empty();
-        let size = imp::recv_from(self.as_raw_fd(), &mut raw, flags, SetMut(src_addr))?;
-        let _ = std::mem::replace(frame, raw.into());
-        Ok(size)
+        loop {
+            let mut raw = RawFrame::empty();
+            let size =
+                imp::recv_from(self.as_raw_fd(), &mut raw, flags, SetMut(src_addr))?;
+            if let CanItem::Data(data) = raw.into() {
+                let _ = std::mem::replace(frame, data);
+                return Ok(size);
+            }
+        }
     }
 
     pub fn send(&self, frame: &Frame<N>, flags: c_int) -> io::Result<usize> {
@@ -227,6 +264,166 @@ impl<const N: usize> IntoRawFd for FrameStream<N> {
     }
 }
 
+/// An asynchronous [`FrameStream`] driven by the tokio reactor.
+///
+/// The socket is switched to non-blocking mode and registered with
+/// [`AsyncFd`](tokio::io::unix::AsyncFd). Frames are received with
+/// [`recv_frame`](AsyncCanStream::recv_frame) or by polling the stream as a
+/// [`futures::Stream`], which also yields the [`ErrorFrame`]s enabled with
+/// [`set_error_mask`](AsyncCanStream::set_error_mask).
+///
+/// ```no_run
+/// # async fn example() -> Result<(), can_rs::Error> {
+/// use can_rs::{stream::FrameStream, CanItem, CAN_DATA_LEN};
+/// use futures::prelude::*;
+///
+/// let mut stream = FrameStream::<CAN_DATA_LEN>::build()
+///     .bind_async("can0".parse().unwrap())?;
+/// while let Some(item) = stream.next().await {
+///     match item? {
+///         CanItem::Data(frame) => println!("{frame:?}"),
+///         CanItem::Error(error) => println!("bus error: {error:?}"),
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "tokio")]
+pub struct AsyncCanStream<const N: usize> {
+    inner: tokio::io::unix::AsyncFd<FrameStream<N>>,
+}
+
+#[cfg(feature = "tokio")]
+impl<const N: usize> FrameStreamBuilder<N>
+where
+    [(); N]: AllowedToBind,
+{
+    /// Binds the socket like [`bind`](Self::bind) and registers it with the tokio
+    /// reactor. Filters are applied before registering, and the socket is always
+    /// non-blocking.
+    ///
+    /// Must be called from within a tokio runtime.
+    pub fn bind_async(&self, addr: CanAddr) -> Result<AsyncCanStream<N>, Error> {
+        let stream = self.bind(addr)?;
+        AsyncCanStream::new(stream)
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<const N: usize> AsyncCanStream<N> {
+    /// Wraps a bound [`FrameStream`], switching it to non-blocking mode.
+    ///
+    /// Must be called from within a tokio runtime.
+    pub fn new(stream: FrameStream<N>) -> Result<Self, Error> {
+        socket::set_nonblocking(&stream, true)?;
+        let inner = tokio::io::unix::AsyncFd::new(stream)?;
+        Ok(Self { inner })
+    }
+
+    /// Returns the underlying [`FrameStream`].
+    pub fn get_ref(&self) -> &FrameStream<N> {
+        self.inner.get_ref()
+    }
+
+    pub fn mtu(&self) -> Result<MTU, Error> {
+        self.get_ref().mtu()
+    }
+
+    pub fn set_filters(&self, filters: &[Filter]) -> Result<(), Error> {
+        self.get_ref().set_filters(filters)
+    }
+
+    pub fn filters(&self) -> Result<Vec<Filter>, Error> {
+        self.get_ref().filters()
+    }
+
+    pub fn set_error_mask(&self, mask: ErrorMask) -> Result<(), Error> {
+        self.get_ref().set_error_mask(mask)
+    }
+
+    /// Receives the next data or error frame.
+    pub async fn recv_item(&self) -> io::Result<CanItem<N>> {
+        self.inner
+            .async_io(tokio::io::Interest::READABLE, |stream| stream.recv_item(0))
+            .await
+    }
+
+    /// Receives the next data frame, skipping error frames.
+    pub async fn recv_frame(&self) -> io::Result<Frame<N>> {
+        self.inner
+            .async_io(tokio::io::Interest::READABLE, |stream| stream.recv_frame(0))
+            .await
+    }
+
+    pub async fn send_frame(&self, frame: &Frame<N>) -> io::Result<usize> {
+        self.inner
+            .async_io(tokio::io::Interest::WRITABLE, |stream| {
+                stream.send(frame, 0)
+            })
+            .await
+    }
+
+    /// Attempts to receive a data frame, skipping error frames and registering
+    /// the current task for wakeup if none is available yet.
+    pub fn poll_recv_frame(
+        &self,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<io::Result<Frame<N>>> {
+        loop {
+            if let CanItem::Data(frame) = std::task::ready!(self.poll_recv_item(cx))? {
+                return std::task::Poll::Ready(Ok(frame));
+            }
+        }
+    }
+
+    /// Attempts to receive a data or error frame, registering the current task
+    /// for wakeup if none is available yet.
+    pub fn poll_recv_item(
+        &self,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<io::Result<CanItem<N>>> {
+        loop {
+            let mut guard = std::task::ready!(self.inner.poll_read_ready(cx))?;
+            if let Ok(result) = guard.try_io(|inner| inner.get_ref().recv_item(0)) {
+                return std::task::Poll::Ready(result);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<const N: usize> futures::Stream for AsyncCanStream<N> {
+    type Item = io::Result<CanItem<N>>;
+
+    fn poll_next(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Self::Item>> {
+        self.poll_recv_item(cx).map(Some)
+    }
+}
+
+/// Reads the data of the next frame, like `impl Read for &FrameStream`.
+#[cfg(feature = "tokio")]
+impl<const N: usize> tokio::io::AsyncRead for AsyncCanStream<N> {
+    fn poll_read(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> std::task::Poll<io::Result<()>> {
+        let frame = std::task::ready!(self.poll_recv_frame(cx))?;
+        buf.put_slice(&frame.data[..(frame.len as usize)]);
+        std::task::Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<const N: usize> AsRawFd for AsyncCanStream<N> {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.as_raw_fd()
+    }
+}
+
 mod imp {
     use std::{io, os::unix::prelude::AsRawFd};
 
@@ -234,8 +431,8 @@ mod imp {
     use crate::{
         addr::CanAddr,
         filter::{Filter, RawFilter},
-        socket, Error, Frame, Id, Protocol, RawCanAddr, Type, CANFD_DATA_LEN,
-        CAN_DATA_LEN,
+        socket, CanItem, Error, ErrorFrame, ErrorMask, Frame, Id, Protocol, RawCanAddr,
+        Type, CANFD_DATA_LEN, CAN_DATA_LEN, CAN_ERR_FLAG,
     };
 
     pub trait Sealed {}
@@ -272,6 +469,9 @@ mod imp {
         socket::set_nonblocking(fd, options.nonblocking)?;
 
         set_filters_fd(fd, &options.filters)?;
+        if options.error_mask != ErrorMask::NONE {
+            socket::set_error_mask(fd, options.error_mask)?;
+        }
         socket::bind(fd.as_raw_fd(), addr)?;
         Ok(())
     }
@@ -344,6 +544,16 @@ mod imp {
         }
     }
 
+    impl<const N: usize> From<RawFrame<N>> for CanItem<N> {
+        fn from(raw: RawFrame<N>) -> Self {
+            if raw.id & CAN_ERR_FLAG != 0 {
+                CanItem::Error(ErrorFrame::decode(raw.id, &raw.data))
+            } else {
+                CanItem::Data(raw.into())
+            }
+        }
+    }
+
     #[repr(C)]
     pub(super) struct RawFrame<const N: usize> {
         id: u32,
@@ -0,0 +1,82 @@
+use std::time::Duration;
+
+use can_rs::{
+    addr::CanAddr, filter::Filter, stream::FrameStream, CanItem, Error, Frame, Id,
+    CANFD_DATA_LEN, CAN_DATA_LEN,
+};
+use futures::prelude::*;
+use tokio::time;
+
+use crate::{can_address, canfd_address, ID};
+
+/// Must also be set when running the ignored tests, so `--ignored` on a machine
+/// without the interfaces from `setup-vcan.sh` doesn't fail.
+const VCAN_TESTS_ENV: &str = "CAN_RS_VCAN_TESTS";
+
+fn vcan_available() -> bool {
+    let available = std::env::var_os(VCAN_TESTS_ENV).is_some();
+    if !available {
+        eprintln!("skipping: `{VCAN_TESTS_ENV}` is not set");
+    }
+    available
+}
+
+async fn loopback<const N: usize>(addr: CanAddr) -> Result<(), Error>
+where
+    [(); N]: can_rs::stream::AllowedToBind,
+{
+    let id = ID.with(|id| *id);
+    let mut rx = FrameStream::<N>::build()
+        .filters(vec![Filter {
+            id: Id::Standard(id),
+            mask: 0x7FF,
+        }])
+        .bind_async(addr.clone())?;
+    let tx = FrameStream::<N>::build().bind_async(addr)?;
+
+    let frame = |id, byte| Frame {
+        id: Id::Standard(id),
+        len: N as u8,
+        flags: 0,
+        data: [byte; N],
+    };
+    // Filtered out by the receiver.
+    tx.send_frame(&frame(id + 1, 0x11)).await?;
+    tx.send_frame(&frame(id, 0x22)).await?;
+    tx.send_frame(&frame(id, 0x33)).await?;
+
+    let received = time::timeout(Duration::from_secs(1), rx.recv_frame())
+        .await
+        .expect("timed out waiting for frame")?;
+    assert_eq!(received.id, Id::Standard(id));
+    assert_eq!(received.data, [0x22; N]);
+
+    let received = time::timeout(Duration::from_secs(1), rx.next())
+        .await
+        .expect("timed out waiting for frame")
+        .expect("stream ended")?;
+    let CanItem::Data(frame) = received else {
+        panic!("expected a data frame, got {received:?}");
+    };
+    assert_eq!(frame.data, [0x33; N]);
+
+    Ok(())
+}
+
+#[tokio::test]
+#[ignore = "needs vcan interface"]
+async fn async_loopback_can() -> Result<(), Error> {
+    if !vcan_available() {
+        return Ok(());
+    }
+    loopback::<CAN_DATA_LEN>(can_address()).await
+}
+
+#[tokio::test]
+#[ignore = "needs vcan interface"]
+async fn async_loopback_canfd() -> Result<(), Error> {
+    if !vcan_available() {
+        return Ok(());
+    }
+    loopback::<CANFD_DATA_LEN>(canfd_address()).await
+}
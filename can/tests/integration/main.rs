@@ -4,6 +4,8 @@ use can_rs::{addr::CanAddr, Error};
 #[cfg(feature = "isotp")]
 use can_rs::{isotp::addr::CanIsotpAddr, Id};
 
+#[cfg(feature = "tokio")]
+mod async_stream;
 mod filters;
 mod frame_stream;
 #[cfg(feature = "isotp")]
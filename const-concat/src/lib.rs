@@ -1,25 +1,261 @@
 /// Concatenates const strings. Unlike [`std::concat!()`], arguments can be full
 /// expressions rather than only string literals.
+///
+/// Integer arguments can be concatenated by prefixing them with `@int` (any
+/// signed integer type) or `@uint` (any unsigned integer type):
+///
+/// ```
+/// # use orb_const_concat::const_concat;
+/// const PORT: u16 = 8080;
+/// const OFFSET: i8 = -1;
+/// const S: &str = const_concat!("port=", @uint PORT, " offset=", @int OFFSET);
+/// assert_eq!(S, "port=8080 offset=-1");
+/// ```
+///
+/// Integers of up to 64 bits are supported. Values that don't fit the marker,
+/// such as negative `@uint` arguments, fail to compile:
+///
+/// ```compile_fail
+/// # use orb_const_concat::const_concat;
+/// const S: &str = const_concat!("offset=", @uint -1i8);
+/// ```
+///
+/// ```compile_fail
+/// # use orb_const_concat::const_concat;
+/// const S: &str = const_concat!("big=", @int u64::MAX);
+/// ```
+///
+/// ```compile_fail
+/// # use orb_const_concat::const_concat;
+/// const S: &str = const_concat!("huge=", @uint u128::MAX);
+/// ```
+///
+/// Only integers are accepted, floats fail to compile:
+///
+/// ```compile_fail
+/// # use orb_const_concat::const_concat;
+/// const S: &str = const_concat!("ratio=", @uint 1.0f64);
+/// ```
 #[macro_export]
 macro_rules! const_concat {
+    // Collect the arguments, converting integers to strings
+    (@args [$($done:expr),*]) => {
+        $crate::const_concat!(@concat $($done),*)
+    };
+    (@args [$($done:expr),*] @int $e:expr $(, $($tail:tt)*)?) => {
+        $crate::const_concat!(
+            @args [$($done,)* {
+                const __CONST_CONCAT_INT: $crate::ArrayStr<{ $crate::INT_STR_MAX_LEN }> = {
+                    let n = $e;
+                    $crate::i64_to_array_str($crate::int_to_i64(
+                        $crate::WideInt::new(&n, n as i128, n as u128),
+                    ))
+                };
+                const __CONST_CONCAT_S: &str = $crate::ArrayStr::as_str(&__CONST_CONCAT_INT);
+                __CONST_CONCAT_S
+            }]
+            $($($tail)*)?
+        )
+    };
+    (@args [$($done:expr),*] @uint $e:expr $(, $($tail:tt)*)?) => {
+        $crate::const_concat!(
+            @args [$($done,)* {
+                const __CONST_CONCAT_INT: $crate::ArrayStr<{ $crate::INT_STR_MAX_LEN }> = {
+                    let n = $e;
+                    $crate::u64_to_array_str($crate::int_to_u64(
+                        $crate::WideInt::new(&n, n as i128, n as u128),
+                    ))
+                };
+                const __CONST_CONCAT_S: &str = $crate::ArrayStr::as_str(&__CONST_CONCAT_INT);
+                __CONST_CONCAT_S
+            }]
+            $($($tail)*)?
+        )
+    };
+    (@args [$($done:expr),*] $e:expr $(, $($tail:tt)*)?) => {
+        $crate::const_concat!(@args [$($done,)* $e] $($($tail)*)?)
+    };
     // Recursive case
-    ($a:expr, $b:expr, $($tail:expr),+ $(,)?) => {
-        $crate::const_concat!($crate::const_concat!($a, $b), $($tail),+)
+    (@concat $a:expr, $b:expr, $($tail:expr),+) => {
+        $crate::const_concat!(@concat $crate::const_concat!(@concat $a, $b), $($tail),+)
     };
     // Base case
-    ($a:expr, $b:expr $(,)?) => {
-        // outer const block forces evaluation at compile time, even when assigned
-        // to a regular variable.
-        const {
-            let Ok(s) = ::core::str::from_utf8(
-                // using const block prevents dangling reference
-                &const { $crate::concat_strs($a, $b, [0; $a.len() + $b.len()]) },
-            ) else {
-                panic!("not utf8");
-            };
-            s
+    (@concat $a:expr, $b:expr) => {{
+        // Const items force evaluation at compile time, even when assigned to a
+        // regular variable. Unlike inline const blocks they don't inherit generics,
+        // so their lengths can be used in array types. Item names aren't hygienic,
+        // hence the prefix.
+        const __CONST_CONCAT_A: &str = $a;
+        const __CONST_CONCAT_B: &str = $b;
+        const __CONST_CONCAT_BUF: [u8; __CONST_CONCAT_A.len() + __CONST_CONCAT_B.len()] =
+            $crate::concat_strs(
+                __CONST_CONCAT_A,
+                __CONST_CONCAT_B,
+                [0; __CONST_CONCAT_A.len() + __CONST_CONCAT_B.len()],
+            );
+        const __CONST_CONCAT_S: &str = match ::core::str::from_utf8(&__CONST_CONCAT_BUF) {
+            Ok(s) => s,
+            Err(_) => panic!("not utf8"),
+        };
+        __CONST_CONCAT_S
+    }};
+    ($($args:tt)*) => {
+        $crate::const_concat!(@args [] $($args)*)
+    };
+}
+
+/// Maximum length of a 64-bit integer formatted in base 10, i.e. the length of
+/// `i64::MIN` and `u64::MAX`.
+pub const INT_STR_MAX_LEN: usize = 20;
+
+/// String stored inline in a fixed size buffer of `MAX` bytes, usable in const
+/// context.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArrayStr<const MAX: usize> {
+    buf: [u8; MAX],
+    len: usize,
+}
+
+impl<const MAX: usize> ArrayStr<MAX> {
+    pub const fn as_str(&self) -> &str {
+        let (bytes, _) = self.buf.split_at(self.len);
+        let Ok(s) = ::core::str::from_utf8(bytes) else {
+            panic!("not utf8");
+        };
+        s
+    }
+
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// Formats `n` in base 10.
+///
+/// # Panics
+///
+/// If `MAX` is too small to hold the digits. When evaluated in const context this
+/// is a compile error:
+///
+/// ```compile_fail
+/// # use orb_const_concat::{u64_to_array_str, ArrayStr};
+/// const S: ArrayStr<2> = u64_to_array_str(123);
+/// ```
+pub const fn u64_to_array_str<const MAX: usize>(n: u64) -> ArrayStr<MAX> {
+    format_int(n, false)
+}
+
+/// Formats `n` in base 10, with a leading `-` if negative.
+///
+/// # Panics
+///
+/// If `MAX` is too small to hold the digits and sign.
+pub const fn i64_to_array_str<const MAX: usize>(n: i64) -> ArrayStr<MAX> {
+    format_int(n.unsigned_abs(), n < 0)
+}
+
+/// Primitive integer types, the only ones accepted by `@int` and `@uint`.
+#[doc(hidden)]
+pub trait Int: Copy {
+    const SIGNED: bool;
+}
+
+macro_rules! impl_int {
+    ($signed:literal: $($ty:ty),*) => {
+        $(impl Int for $ty {
+            const SIGNED: bool = $signed;
+        })*
+    };
+}
+
+impl_int!(true: i8, i16, i32, i64, i128, isize);
+impl_int!(false: u8, u16, u32, u64, u128, usize);
+
+/// An integer argument of [`const_concat!`], widened without loss.
+#[doc(hidden)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WideInt {
+    Signed(i128),
+    Unsigned(u128),
+}
+
+impl WideInt {
+    /// Picks whichever of `as_i128` and `as_u128`, both casts of `n`, is exact.
+    pub const fn new<T: Int>(_n: &T, as_i128: i128, as_u128: u128) -> Self {
+        if T::SIGNED {
+            Self::Signed(as_i128)
+        } else {
+            Self::Unsigned(as_u128)
         }
+    }
+}
+
+/// Converts an integer argument of [`const_concat!`] to `i64`.
+///
+/// # Panics
+///
+/// If `n` doesn't fit, which is a compile error in const context.
+#[doc(hidden)]
+pub const fn int_to_i64(n: WideInt) -> i64 {
+    let fits = match n {
+        WideInt::Signed(n) => n >= i64::MIN as i128 && n <= i64::MAX as i128,
+        WideInt::Unsigned(n) => n <= i64::MAX as u128,
     };
+    assert!(fits, "integer out of range for @int");
+    match n {
+        WideInt::Signed(n) => n as i64,
+        WideInt::Unsigned(n) => n as i64,
+    }
+}
+
+/// Converts an integer argument of [`const_concat!`] to `u64`.
+///
+/// # Panics
+///
+/// If `n` is negative or doesn't fit, which is a compile error in const context.
+#[doc(hidden)]
+pub const fn int_to_u64(n: WideInt) -> u64 {
+    let fits = match n {
+        WideInt::Signed(n) => n >= 0 && n <= u64::MAX as i128,
+        WideInt::Unsigned(n) => n <= u64::MAX as u128,
+    };
+    assert!(fits, "integer out of range for @uint");
+    match n {
+        WideInt::Signed(n) => n as u64,
+        WideInt::Unsigned(n) => n as u64,
+    }
+}
+
+const fn format_int<const MAX: usize>(mut n: u64, negative: bool) -> ArrayStr<MAX> {
+    // digits in reverse order
+    let mut digits = [0; INT_STR_MAX_LEN];
+    let mut count = 0;
+    loop {
+        digits[count] = b'0' + (n % 10) as u8;
+        n /= 10;
+        count += 1;
+        if n == 0 {
+            break;
+        }
+    }
+
+    let len = count + negative as usize;
+    assert!(len <= MAX, "buffer too small for integer");
+    let mut buf = [0; MAX];
+    if negative {
+        buf[0] = b'-';
+    }
+    let mut index = 0;
+    while index < count {
+        buf[len - 1 - index] = digits[index];
+        index += 1;
+    }
+
+    ArrayStr { buf, len }
 }
 
 #[doc(hidden)]
@@ -66,4 +302,107 @@ mod test {
         const FOOBARBAZ_COMMA: &str = const_concat!(FOO, BAR, BAZ,);
         assert_eq!(FOOBARBAZ_COMMA, "foobarbaz");
     }
+
+    #[test]
+    fn test_const_concat_int() {
+        const PORT: u16 = 8080;
+        const NEG: i32 = -42;
+
+        const PORT_STR: &str = const_concat!("port=", @uint PORT);
+        assert_eq!(PORT_STR, "port=8080");
+
+        const MIXED: &str = const_concat!(@int NEG, "/", @uint PORT, "/", @int 0,);
+        assert_eq!(MIXED, "-42/8080/0");
+
+        const EXPR: &str = const_concat!("sum=", @uint PORT + 1);
+        assert_eq!(EXPR, "sum=8081");
+    }
+
+    #[test]
+    fn test_u64_to_array_str() {
+        const ZERO: ArrayStr<1> = u64_to_array_str(0);
+        assert_eq!(ZERO.as_str(), "0");
+
+        const MAX: ArrayStr<INT_STR_MAX_LEN> = u64_to_array_str(u64::MAX);
+        assert_eq!(MAX.as_str(), u64::MAX.to_string());
+        assert_eq!(MAX.len(), INT_STR_MAX_LEN);
+
+        assert_eq!(u64_to_array_str::<8>(1234).as_str(), "1234");
+    }
+
+    #[test]
+    fn test_i64_to_array_str() {
+        const ZERO: ArrayStr<1> = i64_to_array_str(0);
+        assert_eq!(ZERO.as_str(), "0");
+
+        const MIN: ArrayStr<INT_STR_MAX_LEN> = i64_to_array_str(i64::MIN);
+        assert_eq!(MIN.as_str(), i64::MIN.to_string());
+
+        const MAX: ArrayStr<INT_STR_MAX_LEN> = i64_to_array_str(i64::MAX);
+        assert_eq!(MAX.as_str(), i64::MAX.to_string());
+
+        assert_eq!(i64_to_array_str::<3>(-99).as_str(), "-99");
+    }
+
+    #[test]
+    fn test_const_concat_int_bounds() {
+        const BOUNDS: &str = const_concat!(
+            @int i64::MIN, " ", @int i64::MAX as u64, " ", @uint u64::MAX, " ", @uint 0i8
+        );
+        assert_eq!(BOUNDS, format!("{} {} {} 0", i64::MIN, i64::MAX, u64::MAX),);
+    }
+
+    #[test]
+    fn test_const_concat_int_wide_types() {
+        const WIDE: &str = const_concat!(
+            @uint u64::MAX as u128, " ", @int i64::MIN as i128, " ", @int 7usize
+        );
+        assert_eq!(WIDE, format!("{} {} 7", u64::MAX, i64::MIN));
+    }
+
+    #[test]
+    fn test_int_conversions_are_checked() {
+        use WideInt::{Signed, Unsigned};
+
+        assert_eq!(WideInt::new(&-1i8, -1, u128::MAX), Signed(-1));
+        assert_eq!(WideInt::new(&u128::MAX, -1, u128::MAX), Unsigned(u128::MAX));
+        assert_eq!(int_to_i64(Signed(-1)), -1);
+        assert_eq!(int_to_i64(Unsigned(i64::MAX as u128)), i64::MAX);
+        assert_eq!(int_to_u64(Unsigned(u64::MAX as u128)), u64::MAX);
+        assert_eq!(int_to_u64(Signed(0)), 0);
+        let out_of_i64 = [
+            Signed(i64::MAX as i128 + 1),
+            Signed(i64::MIN as i128 - 1),
+            Unsigned(i64::MAX as u128 + 1),
+            Unsigned(u128::MAX),
+        ];
+        for (i, n) in out_of_i64.into_iter().enumerate() {
+            let result = std::panic::catch_unwind(|| int_to_i64(n));
+            assert!(result.is_err(), "{i}th case failed");
+        }
+        let out_of_u64 = [
+            Signed(-1),
+            Signed(u64::MAX as i128 + 1),
+            Unsigned(u64::MAX as u128 + 1),
+            Unsigned(u128::MAX),
+        ];
+        for (i, n) in out_of_u64.into_iter().enumerate() {
+            let result = std::panic::catch_unwind(|| int_to_u64(n));
+            assert!(result.is_err(), "{i}th case failed");
+        }
+    }
+
+    #[test]
+    fn test_const_concat_in_generic_fn() {
+        fn describe<T>(_: T) -> &'static str {
+            const_concat!("a", "b", @uint 1u8, "c")
+        }
+        assert_eq!(describe(0u8), "ab1c");
+    }
+
+    #[test]
+    #[should_panic(expected = "buffer too small")]
+    fn test_buffer_too_small() {
+        let _ = i64_to_array_str::<2>(-10);
+    }
 }
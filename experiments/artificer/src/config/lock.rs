@@ -1,27 +1,30 @@
 //! Schema for artificer.lock and out.lock
 
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 use serde::{Deserialize, Serialize};
 
 use super::sources::Source;
-use super::spec::Artifact;
 use super::ArtifactName;
 
-/// The locks for a [`Spec`]. Stored in the lockfile, aka `artificer.lock`. Specifies
-/// last observed hashes for every artifact.
-#[derive(Debug, Eq, PartialEq, Serialize, Deserialize)]
+/// The locks for a [`Spec`](super::Spec). Stored in the lockfile, aka
+/// `artificer.lock`. Specifies last observed hashes for every artifact.
+#[derive(Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
 pub struct LockedSpec {
     /// The version of the overall lockfile syntax
     pub version: u8,
-    pub artifacts: HashMap<ArtifactName, Artifact>,
+    /// Sorted, to keep the lockfile diffs minimal.
+    pub artifacts: BTreeMap<ArtifactName, LockedArtifact>,
 }
 
 /// An artifact in the lock file.
-#[derive(Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub struct LockedArtifact {
+    #[serde(flatten)]
     pub source: Source,
     pub hash: cacache::Integrity,
+    /// Size of the artifact, in bytes.
+    pub size: u64,
 }
 
 #[cfg(test)]
@@ -4,9 +4,11 @@ mod lock;
 pub mod sources;
 mod spec;
 
-pub use self::lock::LockedSpec;
+pub use self::lock::{LockedArtifact, LockedSpec};
 pub use self::spec::Spec;
 
 /// `[artifacts.<artifact-name>]`. See also, [`Artifact`].
-#[derive(Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[derive(
+    Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize,
+)]
 pub struct ArtifactName(pub String);
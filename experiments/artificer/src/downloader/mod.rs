@@ -1,10 +1,28 @@
 //! Download functionality for various sources of artifacts.
 
+use std::{future::Future, pin::Pin};
+
 use color_eyre::{eyre::WrapErr, Result};
 use octocrab::Octocrab;
+use tokio::io::AsyncRead;
+
+use crate::config::sources::Source;
 
 pub mod github;
 
+/// A stream of artifact bytes.
+pub type ArtifactReader = Pin<Box<dyn AsyncRead + Send>>;
+
+/// Fetches artifacts from their [`Source`].
+pub trait Download {
+    /// Starts downloading `source`, returning the body and its total size in
+    /// bytes.
+    fn download(
+        &self,
+        source: &Source,
+    ) -> impl Future<Output = Result<(ArtifactReader, u64)>> + Send;
+}
+
 #[derive(Debug, Clone)]
 pub struct Client {
     pub octo: Octocrab,
@@ -35,3 +53,18 @@ impl Client {
         })
     }
 }
+
+impl Download for Client {
+    async fn download(&self, source: &Source) -> Result<(ArtifactReader, u64)> {
+        match source {
+            Source::Github(s) => {
+                let (reader, total_bytes) = github::download_artifact(self, s.clone())
+                    .await
+                    .wrap_err_with(|| {
+                        format!("failed to download github source: {s:?}")
+                    })?;
+                Ok((Box::into_pin(reader), total_bytes))
+            }
+        }
+    }
+}
@@ -0,0 +1,138 @@
+//! Filesystem access, relative to the project root containing `artificer.toml`.
+
+use std::path::{Path, PathBuf};
+
+use color_eyre::{eyre::WrapErr, Result};
+
+use crate::config::{ArtifactName, LockedSpec, Spec};
+
+pub const SPEC_FILE: &str = "artificer.toml";
+pub const LOCK_FILE: &str = "artificer.lock";
+
+const LOCKFILE_HEADER: &str = "# NOTE: This file is autogenerated\n";
+const PARTIAL_SUFFIX: &str = ".partial";
+
+#[derive(Debug, Clone)]
+pub struct FsIo {
+    root: PathBuf,
+    out_dir: Option<PathBuf>,
+}
+
+impl FsIo {
+    /// `out_dir` overrides the `out-dir` of the spec.
+    pub fn new(root: impl Into<PathBuf>, out_dir: Option<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            out_dir,
+        }
+    }
+
+    pub fn github_token_env(&self) -> Option<String> {
+        std::env::var("GITHUB_TOKEN").ok()
+    }
+
+    pub async fn read_spec(&self) -> Result<Spec> {
+        let path = self.root.join(SPEC_FILE);
+        let contents = tokio::fs::read_to_string(&path)
+            .await
+            .wrap_err_with(|| format!("failed to read spec file {path:?}"))?;
+        toml::from_str(&contents).wrap_err("failed to parse spec toml")
+    }
+
+    /// Reads the lockfile, or returns an empty one if it doesn't exist yet.
+    pub async fn read_lockfile(&self) -> Result<LockedSpec> {
+        let path = self.root.join(LOCK_FILE);
+        match tokio::fs::read_to_string(&path).await {
+            Ok(contents) => {
+                toml::from_str(&contents).wrap_err("failed to parse lockfile toml")
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                Ok(LockedSpec::default())
+            }
+            Err(err) => {
+                Err(err).wrap_err_with(|| format!("failed to read lock file {path:?}"))
+            }
+        }
+    }
+
+    pub async fn write_lockfile(&self, locked: &LockedSpec) -> Result<()> {
+        let contents =
+            toml::to_string(locked).wrap_err("failed to serialize lockfile")?;
+        write_atomic(
+            &self.root.join(LOCK_FILE),
+            format!("{LOCKFILE_HEADER}{contents}").as_bytes(),
+        )
+        .await
+    }
+
+    /// The directory artifacts are stored in.
+    pub fn out_dir(&self, spec: &Spec) -> PathBuf {
+        self.out_dir
+            .clone()
+            .unwrap_or_else(|| self.root.join(&spec.artificer.out_dir))
+    }
+
+    /// Path of the downloaded artifact, `out-dir/<name>/<name>`.
+    pub fn artifact_path(&self, spec: &Spec, name: &ArtifactName) -> PathBuf {
+        self.out_dir(spec).join(&name.0).join(&name.0)
+    }
+
+    /// Creates the file an artifact is downloaded to. It only takes the place
+    /// of the artifact once [`ArtifactFile::persist`] is called.
+    pub async fn create_artifact(
+        &self,
+        spec: &Spec,
+        name: &ArtifactName,
+    ) -> Result<(ArtifactFile, tokio::fs::File)> {
+        let path = self.artifact_path(spec, name);
+        let dir = path.parent().expect("artifact path has a parent");
+        tokio::fs::create_dir_all(dir)
+            .await
+            .wrap_err_with(|| format!("failed to create artifact dir {dir:?}"))?;
+        let partial = partial_path(&path);
+        let file = tokio::fs::File::create(&partial)
+            .await
+            .wrap_err_with(|| format!("failed to create {partial:?}"))?;
+        Ok((ArtifactFile { path, partial }, file))
+    }
+}
+
+/// An artifact being downloaded.
+#[derive(Debug)]
+pub struct ArtifactFile {
+    path: PathBuf,
+    partial: PathBuf,
+}
+
+impl ArtifactFile {
+    /// Atomically replaces the artifact with the downloaded file.
+    pub async fn persist(self) -> Result<PathBuf> {
+        tokio::fs::rename(&self.partial, &self.path)
+            .await
+            .wrap_err_with(|| format!("failed to move artifact to {:?}", self.path))?;
+        Ok(self.path)
+    }
+
+    /// Removes the downloaded file, leaving the artifact untouched.
+    pub async fn discard(self) -> Result<()> {
+        tokio::fs::remove_file(&self.partial)
+            .await
+            .wrap_err_with(|| format!("failed to remove {:?}", self.partial))
+    }
+}
+
+fn partial_path(path: &Path) -> PathBuf {
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(PARTIAL_SUFFIX);
+    partial.into()
+}
+
+async fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    let partial = partial_path(path);
+    tokio::fs::write(&partial, contents)
+        .await
+        .wrap_err_with(|| format!("failed to write {partial:?}"))?;
+    tokio::fs::rename(&partial, path)
+        .await
+        .wrap_err_with(|| format!("failed to move {partial:?} to {path:?}"))
+}
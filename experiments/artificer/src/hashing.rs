@@ -0,0 +1,86 @@
+//! Hashing of artifacts while they are streamed.
+
+use std::{
+    path::Path,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+use color_eyre::{eyre::WrapErr, Result};
+use ssri::{Algorithm, Integrity, IntegrityOpts};
+use tokio::io::{AsyncRead, ReadBuf};
+
+/// Wraps a reader, hashing and counting the bytes read through it.
+pub struct HashingReader<R> {
+    inner: R,
+    opts: IntegrityOpts,
+    size: u64,
+}
+
+impl<R> HashingReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            opts: IntegrityOpts::new().algorithm(Algorithm::Sha256),
+            size: 0,
+        }
+    }
+
+    /// The hash and size of everything read so far.
+    pub fn finish(self) -> (Integrity, u64) {
+        (self.opts.result(), self.size)
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for HashingReader<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let this = self.get_mut();
+        let before = buf.filled().len();
+        ready!(Pin::new(&mut this.inner).poll_read(cx, buf))?;
+        let read = &buf.filled()[before..];
+        this.opts.input(read);
+        this.size += read.len() as u64;
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Hashes the file at `path`, returning `None` if it doesn't exist.
+pub async fn hash_file(path: &Path) -> Result<Option<(Integrity, u64)>> {
+    let file = match tokio::fs::File::open(path).await {
+        Ok(file) => file,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => {
+            return Err(err).wrap_err_with(|| format!("failed to open {path:?}"))
+        }
+    };
+    let mut reader = HashingReader::new(file);
+    tokio::io::copy(&mut reader, &mut tokio::io::sink())
+        .await
+        .wrap_err_with(|| format!("failed to read {path:?}"))?;
+    Ok(Some(reader.finish()))
+}
+
+#[cfg(test)]
+mod test {
+    use tokio::io::AsyncReadExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_hash_matches_whole_input() {
+        let data = b"hello artificer".repeat(1000);
+        let mut reader = HashingReader::new(data.as_slice());
+        let mut out = Vec::new();
+        reader.read_to_end(&mut out).await.unwrap();
+
+        let (hash, size) = reader.finish();
+        assert_eq!(out, data);
+        assert_eq!(size, data.len() as u64);
+        assert_eq!(hash, Integrity::from(&data));
+        assert_eq!(hash.pick_algorithm(), Algorithm::Sha256);
+    }
+}
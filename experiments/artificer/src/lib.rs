@@ -2,30 +2,37 @@
 
 mod config;
 mod downloader;
+mod fs_io;
+mod hashing;
 
-use std::{
-    collections::HashMap,
-    fmt::Write,
-    path::{Path, PathBuf},
-    pin::pin,
-    time::Duration,
-};
+use std::{collections::HashMap, fmt::Write, path::PathBuf};
 
-use crate::downloader::Client;
-use color_eyre::{eyre::WrapErr, Result};
-use config::{sources::Source, ArtifactName};
+use crate::downloader::{Client, Download};
+use crate::fs_io::{ArtifactFile, FsIo};
+use crate::hashing::HashingReader;
+use color_eyre::{
+    eyre::{bail, ensure, WrapErr},
+    Result,
+};
+use config::{sources::Source, ArtifactName, LockedArtifact};
 use indicatif::{ProgressState, ProgressStyle};
-use tokio::io::AsyncWrite;
+use ssri::Integrity;
+use tokio::io::AsyncWriteExt;
 
-use crate::config::{LockedSpec, Spec};
+use crate::config::Spec;
 
-pub async fn run() -> Result<()> {
-    let mut fs_io = MockedFs;
-    let spec: Spec = toml::from_str(&fs_io.spec_contents().await?)
-        .wrap_err("failed to parse spec toml")?;
-    let _locked: LockedSpec = toml::from_str(&fs_io.lockfile_contents().await?)
-        .wrap_err("failed to parse lockfile toml")?;
+#[derive(Debug, Clone)]
+pub struct Args {
+    /// Directory containing `artificer.toml` and `artificer.lock`.
+    pub project_root: PathBuf,
+    /// Overrides the `out-dir` of the spec.
+    pub out_dir: Option<PathBuf>,
+    /// Fail instead of updating the lockfile or replacing modified artifacts.
+    pub frozen: bool,
+}
 
+pub async fn run(args: Args) -> Result<()> {
+    let fs_io = FsIo::new(args.project_root, args.out_dir);
     let gh_token = fs_io.github_token_env();
     if gh_token.is_some() {
         tracing::info!("Using provided github token");
@@ -34,34 +41,178 @@ pub async fn run() -> Result<()> {
     }
     let client = Client::new(gh_token)?;
 
-    let sources = spec
+    sync(&fs_io, &client, args.frozen).await
+}
+
+/// Downloads the artifacts that are missing or outdated, and records their
+/// hashes in the lockfile.
+async fn sync(fs_io: &FsIo, downloader: &impl Download, frozen: bool) -> Result<()> {
+    let spec = fs_io.read_spec().await?;
+    let mut locked = fs_io.read_lockfile().await?;
+
+    let stale: Vec<_> = locked
         .artifacts
-        .into_iter()
-        .map(|(name, art)| (name, art.source.clone()))
+        .keys()
+        .filter(|name| !spec.artifacts.contains_key(name))
+        .cloned()
         .collect();
-    let dp = DownloadPlan { sources };
-    dp.run(client, &mut fs_io).await
+    if !stale.is_empty() {
+        ensure!(
+            !frozen,
+            "lockfile has artifacts missing from the spec: {stale:?}"
+        );
+        for name in &stale {
+            locked.artifacts.remove(name);
+        }
+    }
+    let mut lock_changed = !stale.is_empty();
+
+    let mut sources = HashMap::new();
+    for (name, artifact) in &spec.artifacts {
+        let on_disk = hashing::hash_file(&fs_io.artifact_path(&spec, name))
+            .await?
+            .map(|(hash, _size)| hash);
+        let action = plan_artifact(
+            name,
+            &artifact.source,
+            locked.artifacts.get(name),
+            on_disk.as_ref(),
+            frozen,
+        )?;
+        match action {
+            Action::Skip => tracing::info!("{} is up to date", name.0),
+            Action::Download => {
+                sources.insert(name.clone(), artifact.source.clone());
+            }
+        }
+    }
+
+    let downloads = DownloadPlan { sources }
+        .run(downloader, fs_io, &spec)
+        .await?;
+    let mut result = Ok(());
+    for download in downloads {
+        let Downloaded {
+            name,
+            source,
+            file,
+            hash,
+            size,
+        } = download;
+        let previous = locked.artifacts.get(&name);
+        if let Err(err) = check_download(&name, &source, previous, &hash, frozen) {
+            file.discard().await?;
+            result = result.and(Err(err));
+            continue;
+        }
+        let path = file.persist().await?;
+        tracing::info!("downloaded {} to {path:?}", name.0);
+        let entry = LockedArtifact { source, hash, size };
+        if previous != Some(&entry) {
+            locked.artifacts.insert(name, entry);
+            lock_changed = true;
+        }
+    }
+    if lock_changed {
+        fs_io.write_lockfile(&locked).await?;
+    }
+    result
+}
+
+#[derive(Debug, Eq, PartialEq)]
+enum Action {
+    Skip,
+    Download,
+}
+
+/// Decides whether an artifact needs to be downloaded, given its lockfile entry
+/// and the hash of the artifact currently on disk.
+fn plan_artifact(
+    name: &ArtifactName,
+    source: &Source,
+    locked: Option<&LockedArtifact>,
+    on_disk: Option<&Integrity>,
+    frozen: bool,
+) -> Result<Action> {
+    let name = &name.0;
+    let Some(locked) = locked else {
+        ensure!(!frozen, "{name} is missing from the lockfile");
+        return Ok(Action::Download);
+    };
+    if locked.source != *source {
+        ensure!(!frozen, "source of {name} doesn't match the lockfile");
+        return Ok(Action::Download);
+    }
+    match on_disk {
+        Some(hash) if *hash == locked.hash => Ok(Action::Skip),
+        Some(hash) => {
+            ensure!(
+                !frozen,
+                "{name} was modified on disk: expected {}, got {hash}",
+                locked.hash
+            );
+            tracing::warn!("{name} was modified on disk, downloading it again");
+            Ok(Action::Download)
+        }
+        None => Ok(Action::Download),
+    }
+}
+
+/// Checks a downloaded artifact against its lockfile entry.
+fn check_download(
+    name: &ArtifactName,
+    source: &Source,
+    locked: Option<&LockedArtifact>,
+    hash: &Integrity,
+    frozen: bool,
+) -> Result<()> {
+    let Some(locked) = locked.filter(|locked| locked.source == *source) else {
+        return Ok(());
+    };
+    if locked.hash != *hash {
+        if frozen {
+            bail!(
+                "downloaded {} doesn't match the lockfile: expected {}, got {hash}",
+                name.0,
+                locked.hash
+            );
+        }
+        tracing::warn!(
+            "{} changed upstream: expected {}, got {hash}, updating the lockfile",
+            name.0,
+            locked.hash
+        );
+    }
+    Ok(())
 }
 
 struct DownloadPlan {
     sources: HashMap<ArtifactName, Source>,
 }
 
+/// An artifact downloaded next to its final location.
+struct Downloaded {
+    name: ArtifactName,
+    source: Source,
+    file: ArtifactFile,
+    hash: Integrity,
+    size: u64,
+}
+
 impl DownloadPlan {
-    async fn run(self, client: Client, fs_io: &mut MockedFs) -> Result<()> {
+    async fn run(
+        self,
+        downloader: &impl Download,
+        fs_io: &FsIo,
+        spec: &Spec,
+    ) -> Result<Vec<Downloaded>> {
         tracing::debug!("starting download plan");
         let multi_progress = indicatif::MultiProgress::new();
-        let mut download_tasks: tokio::task::JoinSet<Result<u64>> =
+        let mut download_tasks: tokio::task::JoinSet<Result<Downloaded>> =
             tokio::task::JoinSet::new();
         for (s_name, s) in self.sources {
-            let writer = fs_io.writer_from_artifact(&s_name, &s).await?;
-            let (reader, total_bytes) = match s {
-                Source::Github(s) => {
-                    crate::downloader::github::download_artifact(&client, s)
-                        .await
-                        .wrap_err("failed to download github source: {s:?}")?
-                }
-            };
+            let (file, mut writer) = fs_io.create_artifact(spec, &s_name).await?;
+            let (reader, total_bytes) = downloader.download(&s).await?;
             let style = ProgressStyle::with_template("{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({msg})")
         .unwrap()
         .with_key("eta", |state: &ProgressState, w: &mut dyn Write| write!(w, "{:.1}s", state.eta().as_secs_f64()).unwrap())
@@ -72,66 +223,243 @@ impl DownloadPlan {
                         .with_message(s_name.0.clone())
                         .with_style(style.clone()),
                 )
-                .wrap_async_read(Box::into_pin(reader));
+                .wrap_async_read(reader);
+            // Hash while streaming, to not read the artifact twice.
+            let mut reader = HashingReader::new(progress);
             download_tasks.spawn(async move {
-                tokio::time::sleep(Duration::from_millis(1000)).await;
-                let nbytes =
-                    tokio::io::copy(&mut pin!(progress), &mut Box::into_pin(writer))
-                        .await
-                        .wrap_err("failed to write body to writer")?;
-                assert_eq!(nbytes, total_bytes, "nbytes and total bytes didn't match");
-                Ok(nbytes)
+                let nbytes = tokio::io::copy(&mut reader, &mut writer)
+                    .await
+                    .wrap_err("failed to write body to writer")?;
+                writer
+                    .sync_all()
+                    .await
+                    .wrap_err("failed to flush artifact")?;
+                writer.shutdown().await?;
+                ensure!(
+                    nbytes == total_bytes,
+                    "expected {total_bytes} bytes for {} but got {nbytes}",
+                    s_name.0
+                );
+                let (hash, size) = reader.finish();
+                Ok(Downloaded {
+                    name: s_name,
+                    source: s,
+                    file,
+                    hash,
+                    size,
+                })
             });
         }
 
+        let mut downloads = Vec::new();
         while let Some(result) = download_tasks.join_next().await {
-            let _nbytes = result.wrap_err("task panicked")?.wrap_err("task errored")?;
+            downloads.push(result.wrap_err("task panicked")?.wrap_err("task errored")?);
         }
-        Ok(())
+        Ok(downloads)
     }
 }
 
-struct MockedFs;
-
-impl MockedFs {
-    async fn spec_contents(&self) -> Result<String> {
-        let path = Path::new(concat!(
-            env!("CARGO_MANIFEST_DIR"),
-            "/src/config/example.toml"
-        ));
-        tokio::fs::read_to_string(path)
-            .await
-            .wrap_err("failed to read spec file")
-    }
-
-    fn github_token_env(&self) -> Option<String> {
-        std::env::var("GITHUB_TOKEN").ok()
-    }
-
-    async fn lockfile_contents(&self) -> Result<String> {
-        let path = Path::new(concat!(
-            env!("CARGO_MANIFEST_DIR"),
-            "/src/config/example.lock"
-        ));
-        tokio::fs::read_to_string(path)
-            .await
-            .wrap_err("failed to read lock file")
-    }
-
-    async fn writer_from_artifact(
-        &self,
-        name: &ArtifactName,
-        _src: &Source,
-    ) -> Result<Box<dyn AsyncWrite + Send>> {
-        // TODO: Use filesystem
-        let p = PathBuf::from(std::env::var("HOME").unwrap())
-            .join("Downloads")
-            .join(&name.0);
-        let f: Box<dyn AsyncWrite + Send> = Box::new(
-            tokio::fs::File::create(p)
-                .await
-                .wrap_err("failed to create file")?,
+#[cfg(test)]
+mod test {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::config::sources::Github;
+    use crate::downloader::ArtifactReader;
+    use crate::fs_io::LOCK_FILE;
+
+    const SPEC: &str = r#"
+[artificer]
+version = "0.0.0"
+out-dir = "out"
+
+[artifacts.foo]
+source = "github"
+repo = "worldcoin/foo"
+tag = "v1"
+artifact = "foo.bin"
+
+[extractors]
+"#;
+
+    /// Serves in-memory artifacts, keyed by their github artifact name.
+    #[derive(Default)]
+    struct StubDownloader {
+        bodies: Mutex<HashMap<String, Vec<u8>>>,
+        downloads: Mutex<Vec<String>>,
+    }
+
+    impl StubDownloader {
+        fn with(artifact: &str, body: &[u8]) -> Self {
+            let stub = Self::default();
+            stub.set(artifact, body);
+            stub
+        }
+
+        fn set(&self, artifact: &str, body: &[u8]) {
+            self.bodies
+                .lock()
+                .unwrap()
+                .insert(artifact.to_owned(), body.to_vec());
+        }
+
+        fn downloads(&self) -> usize {
+            self.downloads.lock().unwrap().len()
+        }
+    }
+
+    impl Download for StubDownloader {
+        async fn download(&self, source: &Source) -> Result<(ArtifactReader, u64)> {
+            let Source::Github(github) = source;
+            let body = self.bodies.lock().unwrap()[&github.artifact].clone();
+            self.downloads.lock().unwrap().push(github.artifact.clone());
+            let len = body.len() as u64;
+            Ok((Box::pin(std::io::Cursor::new(body)), len))
+        }
+    }
+
+    fn project() -> (tempfile::TempDir, FsIo) {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join(fs_io::SPEC_FILE), SPEC).unwrap();
+        let fs_io = FsIo::new(dir.path(), None);
+        (dir, fs_io)
+    }
+
+    fn foo_path(dir: &tempfile::TempDir) -> PathBuf {
+        dir.path().join("out/foo/foo")
+    }
+
+    fn foo_source() -> Source {
+        Source::Github(Github {
+            repo: "worldcoin/foo".to_owned(),
+            tag: "v1".to_owned(),
+            artifact: "foo.bin".to_owned(),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_sync_writes_lockfile() {
+        let (dir, fs_io) = project();
+        let stub = StubDownloader::with("foo.bin", b"foo contents");
+        sync(&fs_io, &stub, false).await.unwrap();
+
+        assert_eq!(std::fs::read(foo_path(&dir)).unwrap(), b"foo contents");
+        let locked = fs_io.read_lockfile().await.unwrap();
+        let foo = &locked.artifacts[&ArtifactName("foo".to_owned())];
+        assert_eq!(foo.source, foo_source());
+        assert_eq!(foo.hash, Integrity::from(b"foo contents"));
+        assert_eq!(foo.size, 12);
+        let lockfile = std::fs::read_to_string(dir.path().join(LOCK_FILE)).unwrap();
+        assert!(lockfile.starts_with("# NOTE: This file is autogenerated"));
+    }
+
+    #[tokio::test]
+    async fn test_sync_skips_up_to_date() {
+        let (_dir, fs_io) = project();
+        let stub = StubDownloader::with("foo.bin", b"foo contents");
+        sync(&fs_io, &stub, false).await.unwrap();
+        sync(&fs_io, &stub, true).await.unwrap();
+        assert_eq!(stub.downloads(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_sync_replaces_modified_artifact() {
+        let (dir, fs_io) = project();
+        let stub = StubDownloader::with("foo.bin", b"foo contents");
+        sync(&fs_io, &stub, false).await.unwrap();
+        std::fs::write(foo_path(&dir), b"tampered").unwrap();
+
+        sync(&fs_io, &stub, false).await.unwrap();
+        assert_eq!(stub.downloads(), 2);
+        assert_eq!(std::fs::read(foo_path(&dir)).unwrap(), b"foo contents");
+    }
+
+    #[tokio::test]
+    async fn test_frozen_rejects_modified_artifact() {
+        let (dir, fs_io) = project();
+        let stub = StubDownloader::with("foo.bin", b"foo contents");
+        sync(&fs_io, &stub, false).await.unwrap();
+        std::fs::write(foo_path(&dir), b"tampered").unwrap();
+
+        assert!(sync(&fs_io, &stub, true).await.is_err());
+        assert_eq!(stub.downloads(), 1);
+        assert_eq!(std::fs::read(foo_path(&dir)).unwrap(), b"tampered");
+    }
+
+    #[tokio::test]
+    async fn test_frozen_rejects_upstream_change() {
+        let (dir, fs_io) = project();
+        let stub = StubDownloader::with("foo.bin", b"foo contents");
+        sync(&fs_io, &stub, false).await.unwrap();
+        let lockfile = std::fs::read(dir.path().join(LOCK_FILE)).unwrap();
+        std::fs::remove_file(foo_path(&dir)).unwrap();
+        stub.set("foo.bin", b"new contents");
+
+        assert!(sync(&fs_io, &stub, true).await.is_err());
+        assert!(!foo_path(&dir).exists());
+        assert!(!dir.path().join("out/foo/foo.partial").exists());
+        assert_eq!(std::fs::read(dir.path().join(LOCK_FILE)).unwrap(), lockfile);
+
+        // Without --frozen the lockfile follows upstream.
+        sync(&fs_io, &stub, false).await.unwrap();
+        let locked = fs_io.read_lockfile().await.unwrap();
+        assert_eq!(
+            locked.artifacts[&ArtifactName("foo".to_owned())].hash,
+            Integrity::from(b"new contents")
         );
-        Ok(f)
+    }
+
+    #[tokio::test]
+    async fn test_frozen_requires_lockfile() {
+        let (dir, fs_io) = project();
+        let stub = StubDownloader::with("foo.bin", b"foo contents");
+        assert!(sync(&fs_io, &stub, true).await.is_err());
+        assert_eq!(stub.downloads(), 0);
+        assert!(!dir.path().join(LOCK_FILE).exists());
+    }
+
+    #[test]
+    fn test_plan_artifact() {
+        let name = ArtifactName("foo".to_owned());
+        let source = foo_source();
+        let hash = Integrity::from(b"foo contents");
+        let other_hash = Integrity::from(b"other contents");
+        let locked = LockedArtifact {
+            source: source.clone(),
+            hash: hash.clone(),
+            size: 12,
+        };
+        let moved = LockedArtifact {
+            source: Source::Github(Github {
+                repo: "worldcoin/foo".to_owned(),
+                tag: "v0".to_owned(),
+                artifact: "foo.bin".to_owned(),
+            }),
+            ..locked.clone()
+        };
+
+        // (locked, on disk, frozen, expected action, None if it errors)
+        let cases = [
+            (None, None, false, Some(Action::Download)),
+            (None, Some(&hash), false, Some(Action::Download)),
+            (None, None, true, None),
+            (Some(&moved), Some(&hash), false, Some(Action::Download)),
+            (Some(&moved), Some(&hash), true, None),
+            (Some(&locked), Some(&hash), false, Some(Action::Skip)),
+            (Some(&locked), Some(&hash), true, Some(Action::Skip)),
+            (
+                Some(&locked),
+                Some(&other_hash),
+                false,
+                Some(Action::Download),
+            ),
+            (Some(&locked), Some(&other_hash), true, None),
+            (Some(&locked), None, false, Some(Action::Download)),
+            (Some(&locked), None, true, Some(Action::Download)),
+        ];
+        for (i, (locked, on_disk, frozen, expected)) in cases.into_iter().enumerate() {
+            let action = plan_artifact(&name, &source, locked, on_disk, frozen).ok();
+            assert_eq!(action, expected, "{i}th case failed");
+        }
     }
 }
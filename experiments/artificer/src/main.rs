@@ -1,3 +1,5 @@
+use std::path::PathBuf;
+
 use clap::Parser;
 use color_eyre::Result;
 use orb_build_info::{make_build_info, BuildInfo};
@@ -20,14 +22,29 @@ async fn main() -> Result<()> {
         )
         .init();
 
-    let _args = Cli::parse();
+    let args = Cli::parse();
 
-    artificer::run().await
+    artificer::run(artificer::Args {
+        project_root: args.project_root,
+        out_dir: args.out_dir,
+        frozen: args.frozen,
+    })
+    .await
 }
 
 #[derive(Parser, Debug)]
 #[command(about, author, version=BUILD_INFO.version, styles=make_clap_v3_styles())]
-struct Cli {}
+struct Cli {
+    /// Directory containing artificer.toml and artificer.lock.
+    #[arg(long, default_value = ".")]
+    project_root: PathBuf,
+    /// Overrides the out-dir of artificer.toml.
+    #[arg(long)]
+    out_dir: Option<PathBuf>,
+    /// Error instead of updating artificer.lock or replacing modified artifacts.
+    #[arg(long)]
+    frozen: bool,
+}
 
 /// Colors the CLI help
 fn make_clap_v3_styles() -> clap::builder::Styles {
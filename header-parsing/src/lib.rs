@@ -1,7 +1,7 @@
 #![forbid(unsafe_code)]
 
-use http::header::{AGE, CACHE_CONTROL};
-use std::time::Duration;
+use http::header::{HeaderMap, AGE, CACHE_CONTROL, DATE, EXPIRES};
+use std::time::{Duration, SystemTime};
 
 /// Parses the `max-age=<number of seconds>` value from the [`CACHE_CONTROL`] header.
 pub fn parse_max_age(cache_control_value: &http::HeaderValue) -> Option<u64> {
@@ -26,6 +26,142 @@ pub fn time_until_max_age(headers: &http::header::HeaderMap) -> Option<Duration>
     Some(Duration::from_secs(remaining_age))
 }
 
+// ---- Cache policy
+
+/// Which directives apply, see [`CachePolicy::from_headers_for`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CacheKind {
+    /// Only `max-age` applies.
+    #[default]
+    Private,
+    /// `s-maxage` takes precedence over `max-age`.
+    Shared,
+}
+
+/// How long a response may be reused, derived from its [`CACHE_CONTROL`], [`AGE`],
+/// [`EXPIRES`] and [`DATE`] headers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CachePolicy {
+    time_to_live: Duration,
+}
+
+impl CachePolicy {
+    /// Same as [`Self::from_headers_for`] with [`CacheKind::Private`].
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        Self::from_headers_for(headers, CacheKind::Private)
+    }
+
+    /// Computes the policy of a response with `headers`.
+    ///
+    /// `no-store` and `no-cache` result in a zero time to live. Otherwise the
+    /// max-age directive for `kind`, minus the `Age` header, is used. Without any
+    /// max-age directive, falls back to `Expires - Date`. Invalid or conflicting
+    /// values resolve to the shortest time to live.
+    ///
+    /// Returns `None` if the headers say nothing about caching.
+    pub fn from_headers_for(headers: &HeaderMap, kind: CacheKind) -> Option<Self> {
+        Self::from_headers_at(headers, kind, SystemTime::now())
+    }
+
+    fn from_headers_at(
+        headers: &HeaderMap,
+        kind: CacheKind,
+        now: SystemTime,
+    ) -> Option<Self> {
+        let directives = CacheDirectives::parse(headers);
+        let lifetime = if directives.no_cache {
+            Duration::ZERO
+        } else if let (CacheKind::Shared, Some(s_max_age)) =
+            (kind, directives.s_max_age)
+        {
+            s_max_age
+        } else if let Some(max_age) = directives.max_age {
+            max_age
+        } else {
+            expires_lifetime(headers, now)?
+        };
+        let age = headers
+            .get(AGE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|s| s.parse::<u64>().ok())
+            .unwrap_or(0);
+
+        Some(Self {
+            time_to_live: lifetime.saturating_sub(Duration::from_secs(age)),
+        })
+    }
+
+    /// How long the response may still be reused.
+    pub fn time_to_live(&self) -> Duration {
+        self.time_to_live
+    }
+}
+
+/// The [`CACHE_CONTROL`] directives that matter to [`CachePolicy`].
+#[derive(Debug, Default)]
+struct CacheDirectives {
+    no_cache: bool,
+    max_age: Option<Duration>,
+    s_max_age: Option<Duration>,
+}
+
+impl CacheDirectives {
+    fn parse(headers: &HeaderMap) -> Self {
+        let mut directives = Self::default();
+        for value in headers.get_all(CACHE_CONTROL) {
+            let Ok(value) = value.to_str() else {
+                directives.no_cache = true;
+                continue;
+            };
+            for directive in value.split(',').map(str::trim) {
+                let (name, arg) = match directive.split_once('=') {
+                    Some((name, arg)) => {
+                        (name.trim(), Some(arg.trim().trim_matches('"')))
+                    }
+                    None => (directive, None),
+                };
+                let slot = if name.eq_ignore_ascii_case("no-store")
+                    || name.eq_ignore_ascii_case("no-cache")
+                {
+                    directives.no_cache = true;
+                    continue;
+                } else if name.eq_ignore_ascii_case("max-age") {
+                    &mut directives.max_age
+                } else if name.eq_ignore_ascii_case("s-maxage") {
+                    &mut directives.s_max_age
+                } else {
+                    continue;
+                };
+                let seconds = arg.and_then(|arg| arg.parse::<u64>().ok()).unwrap_or(0);
+                let seconds = Duration::from_secs(seconds);
+                *slot = Some(slot.map_or(seconds, |prev| prev.min(seconds)));
+            }
+        }
+        directives
+    }
+}
+
+/// Computes `Expires - Date`, using `now` if there is no valid [`DATE`] header.
+/// An invalid [`EXPIRES`] header means the response is already expired.
+fn expires_lifetime(headers: &HeaderMap, now: SystemTime) -> Option<Duration> {
+    let date = headers
+        .get(DATE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|s| httpdate::parse_http_date(s).ok())
+        .unwrap_or(now);
+    let mut lifetime = None;
+    for value in headers.get_all(EXPIRES) {
+        let expires = value
+            .to_str()
+            .ok()
+            .and_then(|s| httpdate::parse_http_date(s).ok())
+            .and_then(|expires| expires.duration_since(date).ok())
+            .unwrap_or(Duration::ZERO);
+        lifetime = Some(lifetime.map_or(expires, |prev: Duration| prev.min(expires)));
+    }
+    lifetime
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -94,4 +230,121 @@ mod tests {
             assert_eq!(parse_max_age(&input), output, "{i}th test case failed");
         }
     }
+
+    #[test]
+    fn test_cache_policy() {
+        fn hm(headers: &[(http::HeaderName, &str)]) -> HeaderMap {
+            let mut m = HeaderMap::new();
+            for (name, value) in headers {
+                m.append(name, HeaderValue::from_str(value).unwrap());
+            }
+            m
+        }
+
+        const DATE_VALUE: &str = "Wed, 21 Oct 2015 07:28:00 GMT";
+        const IN_ONE_HOUR: &str = "Wed, 21 Oct 2015 08:28:00 GMT";
+        const ONE_HOUR_AGO: &str = "Wed, 21 Oct 2015 06:28:00 GMT";
+        let now = httpdate::parse_http_date(DATE_VALUE).unwrap();
+        let private = CacheKind::Private;
+        let shared = CacheKind::Shared;
+
+        let test_cases = [
+            (hm(&[]), private, None),
+            (hm(&[(CACHE_CONTROL, "public")]), private, None),
+            (hm(&[(CACHE_CONTROL, "max-age=10")]), private, Some(10)),
+            (hm(&[(CACHE_CONTROL, "Max-Age=10")]), private, Some(10)),
+            (hm(&[(CACHE_CONTROL, "max-age=\"10\"")]), private, Some(10)),
+            (
+                hm(&[(CACHE_CONTROL, "max-age=10"), (AGE, "3")]),
+                private,
+                Some(7),
+            ),
+            (
+                hm(&[(CACHE_CONTROL, "max-age=10"), (AGE, "30")]),
+                private,
+                Some(0),
+            ),
+            (hm(&[(CACHE_CONTROL, "no-store")]), private, Some(0)),
+            (
+                hm(&[(CACHE_CONTROL, "no-cache, max-age=10")]),
+                private,
+                Some(0),
+            ),
+            (
+                hm(&[(CACHE_CONTROL, "no-cache=\"Set-Cookie\"")]),
+                private,
+                Some(0),
+            ),
+            // s-maxage only applies to shared caches
+            (
+                hm(&[(CACHE_CONTROL, "max-age=10, s-maxage=20")]),
+                private,
+                Some(10),
+            ),
+            (
+                hm(&[(CACHE_CONTROL, "max-age=10, s-maxage=20")]),
+                shared,
+                Some(20),
+            ),
+            (hm(&[(CACHE_CONTROL, "s-maxage=20")]), private, None),
+            (hm(&[(CACHE_CONTROL, "s-maxage=20")]), shared, Some(20)),
+            (hm(&[(CACHE_CONTROL, "max-age=10")]), shared, Some(10)),
+            // invalid or conflicting values resolve to the shortest duration
+            (hm(&[(CACHE_CONTROL, "max-age=foo")]), private, Some(0)),
+            (hm(&[(CACHE_CONTROL, "max-age=-3")]), private, Some(0)),
+            (hm(&[(CACHE_CONTROL, "max-age")]), private, Some(0)),
+            (
+                hm(&[(CACHE_CONTROL, "max-age=10, max-age=5")]),
+                private,
+                Some(5),
+            ),
+            (
+                hm(&[(CACHE_CONTROL, "max-age=10"), (CACHE_CONTROL, "max-age=5")]),
+                private,
+                Some(5),
+            ),
+            // Expires fallback
+            (
+                hm(&[(EXPIRES, IN_ONE_HOUR), (DATE, DATE_VALUE)]),
+                private,
+                Some(3600),
+            ),
+            (hm(&[(EXPIRES, IN_ONE_HOUR)]), private, Some(3600)),
+            (
+                hm(&[(EXPIRES, IN_ONE_HOUR), (DATE, DATE_VALUE), (AGE, "600")]),
+                private,
+                Some(3000),
+            ),
+            (
+                hm(&[(EXPIRES, ONE_HOUR_AGO), (DATE, DATE_VALUE)]),
+                private,
+                Some(0),
+            ),
+            (hm(&[(EXPIRES, "0"), (DATE, DATE_VALUE)]), private, Some(0)),
+            (
+                hm(&[(EXPIRES, IN_ONE_HOUR), (CACHE_CONTROL, "max-age=10")]),
+                private,
+                Some(10),
+            ),
+            (
+                hm(&[(EXPIRES, IN_ONE_HOUR), (CACHE_CONTROL, "s-maxage=10")]),
+                private,
+                Some(3600),
+            ),
+            (
+                hm(&[(EXPIRES, IN_ONE_HOUR), (CACHE_CONTROL, "no-store")]),
+                private,
+                Some(0),
+            ),
+        ];
+        for (i, (input, kind, output)) in test_cases.into_iter().enumerate() {
+            let output = output.map(Duration::from_secs);
+            let policy = CachePolicy::from_headers_at(&input, kind, now);
+            assert_eq!(
+                policy.map(|p| p.time_to_live()),
+                output,
+                "{i}th case failed"
+            );
+        }
+    }
 }
@@ -1,10 +1,13 @@
-use std::process;
+use std::{process, time::Duration};
 
 use async_trait::async_trait;
 use color_eyre::eyre::{eyre, Result};
 use orb_messages::CommonAckError;
-use tokio::sync::mpsc;
-use tracing::debug;
+use tokio::{
+    sync::{broadcast, mpsc},
+    time::{self, error::Elapsed},
+};
+use tracing::{debug, warn};
 
 pub mod can;
 pub mod serial;
@@ -45,6 +48,93 @@ pub trait MessagingInterface {
     async fn send(&mut self, payload: McuPayload) -> Result<CommonAckError>;
 }
 
+/// Capacity of the [`McuClient::subscribe`] channel, slower subscribers lag.
+const SUBSCRIBE_CAPACITY: usize = 64;
+
+#[derive(thiserror::Error, Debug)]
+pub enum McuRequestError {
+    #[error("mcu refused the request: {0:?}")]
+    Nack(CommonAckError),
+    #[error("no ack received after {attempts} attempt(s)")]
+    Timeout { attempts: u16 },
+    #[error(transparent)]
+    Transport(color_eyre::Report),
+}
+
+/// Request/response helper on top of a [`MessagingInterface`].
+///
+/// Acks are correlated by the interface using the `create_ack` counter
+/// scheme; the client adds per-request timeouts, retries, and fans out
+/// unsolicited messages to any number of subscribers.
+pub struct McuClient<M> {
+    iface: M,
+    messages: broadcast::Sender<McuPayload>,
+}
+
+impl<M: MessagingInterface> McuClient<M> {
+    /// Wraps `iface`. `new_message_queue` is the receiving end of the queue
+    /// passed to the interface constructor.
+    ///
+    /// Must be called from within a tokio runtime.
+    pub fn new(
+        iface: M,
+        mut new_message_queue: mpsc::UnboundedReceiver<McuPayload>,
+    ) -> Self {
+        let (messages, _) = broadcast::channel(SUBSCRIBE_CAPACITY);
+        let messages_tx = messages.clone();
+        tokio::spawn(async move {
+            while let Some(message) = new_message_queue.recv().await {
+                // Only fails when nobody is subscribed.
+                let _ = messages_tx.send(message);
+            }
+        });
+        Self { iface, messages }
+    }
+
+    /// Receives the `FromMain`/`FromSec` payloads that are not acks.
+    pub fn subscribe(&self) -> broadcast::Receiver<McuPayload> {
+        self.messages.subscribe()
+    }
+
+    /// Returns the wrapped interface.
+    pub fn interface(&mut self) -> &mut M {
+        &mut self.iface
+    }
+
+    /// Sends `payload` and waits up to `timeout` for its ack, resending it up to
+    /// `retries` more times if no ack arrives in time.
+    ///
+    /// Only timeouts are retried; a NACK or a transport error is returned
+    /// immediately.
+    pub async fn send_with_ack(
+        &mut self,
+        payload: McuPayload,
+        timeout: Duration,
+        retries: u8,
+    ) -> Result<(), McuRequestError> {
+        let attempts = u16::from(retries) + 1;
+        for attempt in 1..=attempts {
+            match time::timeout(timeout, self.iface.send(payload.clone())).await {
+                Ok(Ok(CommonAckError::Success)) => return Ok(()),
+                Ok(Ok(ack)) => return Err(McuRequestError::Nack(ack)),
+                Ok(Err(err)) if !is_ack_timeout(&err) => {
+                    return Err(McuRequestError::Transport(err));
+                }
+                Ok(Err(_)) | Err(_) => {
+                    warn!("no ack for {payload:?} (attempt {attempt}/{attempts})");
+                }
+            }
+        }
+        Err(McuRequestError::Timeout { attempts })
+    }
+}
+
+/// Whether the interface gave up waiting for the ack, rather than failing to
+/// send.
+fn is_ack_timeout(err: &color_eyre::Report) -> bool {
+    err.chain().any(|cause| cause.is::<Elapsed>())
+}
+
 /// Create a unique ack number
 /// - prefix with process ID (16 bits, the least significant bits)
 /// - suffix with counter
@@ -156,3 +246,142 @@ fn handle_sec_mcu_message(
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::VecDeque,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+    };
+
+    use orb_messages::mcu_main::{jetson_to_mcu, mcu_to_jetson};
+
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_millis(50);
+
+    enum Reply {
+        /// Never acks.
+        Drop,
+        Ack(CommonAckError),
+        Fail,
+    }
+
+    struct FakeInterface {
+        replies: VecDeque<Reply>,
+        sent: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl MessagingInterface for FakeInterface {
+        async fn send(&mut self, _payload: McuPayload) -> Result<CommonAckError> {
+            self.sent.fetch_add(1, Ordering::SeqCst);
+            match self.replies.pop_front().expect("unexpected send") {
+                Reply::Drop => std::future::pending().await,
+                Reply::Ack(ack) => Ok(ack),
+                Reply::Fail => Err(eyre!("bus is down")),
+            }
+        }
+    }
+
+    fn client(
+        replies: impl IntoIterator<Item = Reply>,
+    ) -> (McuClient<FakeInterface>, Arc<AtomicUsize>) {
+        let sent = Arc::new(AtomicUsize::new(0));
+        let iface = FakeInterface {
+            replies: replies.into_iter().collect(),
+            sent: Arc::clone(&sent),
+        };
+        let (_tx, rx) = mpsc::unbounded_channel();
+        (McuClient::new(iface, rx), sent)
+    }
+
+    fn payload() -> McuPayload {
+        McuPayload::ToMain(jetson_to_mcu::Payload::Reboot(
+            orb_messages::mcu_main::RebootWithDelay { delay: 1 },
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_send_with_ack_retries_on_timeout() {
+        let (mut client, sent) = client([
+            Reply::Drop,
+            Reply::Drop,
+            Reply::Ack(CommonAckError::Success),
+        ]);
+        client.send_with_ack(payload(), TIMEOUT, 2).await.unwrap();
+        assert_eq!(sent.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_send_with_ack_timeout() {
+        let (mut client, sent) = client([Reply::Drop, Reply::Drop]);
+        let err = client
+            .send_with_ack(payload(), TIMEOUT, 1)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, McuRequestError::Timeout { attempts: 2 }));
+        assert_eq!(sent.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_send_with_ack_nack_is_not_retried() {
+        let (mut client, sent) = client([Reply::Ack(CommonAckError::Fail)]);
+        let err = client
+            .send_with_ack(payload(), TIMEOUT, 3)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, McuRequestError::Nack(CommonAckError::Fail)));
+        assert_eq!(sent.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_send_with_ack_transport_error() {
+        let (mut client, sent) = client([Reply::Fail]);
+        let err = client
+            .send_with_ack(payload(), TIMEOUT, 3)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, McuRequestError::Transport(_)));
+        assert_eq!(sent.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_is_ack_timeout() {
+        let elapsed = time::timeout(Duration::ZERO, std::future::pending::<()>())
+            .await
+            .unwrap_err();
+        assert!(is_ack_timeout(
+            &color_eyre::Report::new(elapsed).wrap_err("ack not received")
+        ));
+        assert!(!is_ack_timeout(&eyre!("bus is down")));
+    }
+
+    #[tokio::test]
+    async fn test_subscribe() {
+        let iface = FakeInterface {
+            replies: VecDeque::new(),
+            sent: Arc::default(),
+        };
+        let (tx, rx) = mpsc::unbounded_channel();
+        let client = McuClient::new(iface, rx);
+        let mut first = client.subscribe();
+        let mut second = client.subscribe();
+
+        let message = McuPayload::FromMain(mcu_to_jetson::Payload::Log(
+            orb_messages::mcu_main::Log::default(),
+        ));
+        tx.send(message).unwrap();
+
+        for subscriber in [&mut first, &mut second] {
+            let received = subscriber.recv().await.unwrap();
+            assert!(matches!(
+                received,
+                McuPayload::FromMain(mcu_to_jetson::Payload::Log(_))
+            ));
+        }
+    }
+}
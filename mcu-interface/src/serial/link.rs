@@ -0,0 +1,355 @@
+//! Serial link that survives the device going away.
+//!
+//! Frames are written by a background task. When a write fails, e.g. because
+//! the MCU reset or the USB-serial device detached, the task reopens the device
+//! with a capped exponential backoff. Outbound frames are buffered meanwhile and
+//! flushed in order once the link is back.
+
+use std::{collections::VecDeque, io, time::Duration};
+
+use color_eyre::eyre::{eyre, Result};
+use tokio::{
+    io::{AsyncWrite, AsyncWriteExt},
+    sync::{mpsc, watch},
+    time,
+};
+use tracing::{debug, info, warn};
+
+/// Default number of frames buffered while the link is down.
+pub const DEFAULT_BUFFER_CAPACITY: usize = 32;
+
+/// Time given to the MCU to process a frame before sending the next one.
+const FRAME_INTERVAL: Duration = Duration::from_millis(8);
+
+/// State of the serial link, as seen by the writer task.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LinkState {
+    Connected,
+    /// The device went away, outbound frames are buffered until it's reopened.
+    Reconnecting,
+}
+
+#[derive(Clone, Debug)]
+pub struct LinkConfig {
+    /// Frames buffered while reconnecting, the oldest ones are dropped first.
+    pub buffer_capacity: usize,
+    /// Delay before the first reopen attempt, doubled after each failure.
+    pub initial_backoff: Duration,
+    /// Upper bound of the delay between reopen attempts.
+    pub max_backoff: Duration,
+}
+
+impl Default for LinkConfig {
+    fn default() -> Self {
+        Self {
+            buffer_capacity: DEFAULT_BUFFER_CAPACITY,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Opens the underlying device, once on startup and again after each
+/// disconnection.
+pub trait Transport: Send + 'static {
+    type Port: AsyncWrite + Unpin + Send + 'static;
+
+    fn open(&mut self) -> io::Result<Self::Port>;
+}
+
+/// Handle to the writer task, which stops once the handle is dropped.
+pub(crate) struct Link {
+    frames: mpsc::UnboundedSender<Vec<u8>>,
+    state: watch::Receiver<LinkState>,
+}
+
+impl Link {
+    /// Spawns the writer task on the already opened `port`.
+    ///
+    /// Must be called from within a tokio runtime.
+    pub(crate) fn spawn<T: Transport>(
+        transport: T,
+        port: T::Port,
+        config: LinkConfig,
+    ) -> Self {
+        let (frames_tx, frames_rx) = mpsc::unbounded_channel();
+        let (state_tx, state_rx) = watch::channel(LinkState::Connected);
+        let writer = Writer {
+            transport,
+            config,
+            frames: frames_rx,
+            pending: VecDeque::new(),
+            state: state_tx,
+        };
+        tokio::spawn(writer.run(port));
+        Self {
+            frames: frames_tx,
+            state: state_rx,
+        }
+    }
+
+    /// Queues `frame` for writing.
+    pub(crate) fn send(&self, frame: Vec<u8>) -> Result<()> {
+        self.frames
+            .send(frame)
+            .map_err(|_| eyre!("serial writer task terminated"))
+    }
+
+    pub(crate) fn state(&self) -> watch::Receiver<LinkState> {
+        self.state.clone()
+    }
+}
+
+struct Writer<T: Transport> {
+    transport: T,
+    config: LinkConfig,
+    frames: mpsc::UnboundedReceiver<Vec<u8>>,
+    /// Frames not written yet, oldest first.
+    pending: VecDeque<Vec<u8>>,
+    state: watch::Sender<LinkState>,
+}
+
+impl<T: Transport> Writer<T> {
+    async fn run(mut self, mut port: T::Port) {
+        loop {
+            loop {
+                let frame = match self.pending.pop_front() {
+                    Some(frame) => frame,
+                    None => match self.frames.recv().await {
+                        Some(frame) => frame,
+                        None => return,
+                    },
+                };
+                if let Err(err) = write_frame(&mut port, &frame).await {
+                    warn!("serial link lost: {err}");
+                    self.pending.push_front(frame);
+                    break;
+                }
+                time::sleep(FRAME_INTERVAL).await;
+            }
+            self.state.send_replace(LinkState::Reconnecting);
+            let Some(reopened) = self.reconnect().await else {
+                return;
+            };
+            port = reopened;
+            info!(
+                "serial link restored, flushing {} frame(s)",
+                self.pending.len()
+            );
+            self.state.send_replace(LinkState::Connected);
+        }
+    }
+
+    /// Reopens the device, buffering the incoming frames meanwhile. Returns
+    /// `None` if the [`Link`] was dropped.
+    async fn reconnect(&mut self) -> Option<T::Port> {
+        let mut backoff = self.config.initial_backoff;
+        loop {
+            let sleep = time::sleep(backoff);
+            tokio::pin!(sleep);
+            loop {
+                tokio::select! {
+                    biased;
+                    frame = self.frames.recv() => self.buffer(frame?),
+                    () = &mut sleep => break,
+                }
+            }
+            match self.transport.open() {
+                Ok(port) => return Some(port),
+                Err(err) => debug!("failed to reopen serial device: {err}"),
+            }
+            backoff = (backoff * 2).min(self.config.max_backoff);
+        }
+    }
+
+    fn buffer(&mut self, frame: Vec<u8>) {
+        self.pending.push_back(frame);
+        if self.pending.len() > self.config.buffer_capacity {
+            self.pending.pop_front();
+            warn!(
+                "serial link down, dropping the oldest buffered frame (capacity: {})",
+                self.config.buffer_capacity
+            );
+        }
+    }
+}
+
+async fn write_frame(
+    port: &mut (impl AsyncWrite + Unpin),
+    frame: &[u8],
+) -> io::Result<()> {
+    port.write_all(frame).await?;
+    port.flush().await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        pin::Pin,
+        sync::{Arc, Mutex},
+        task::{Context, Poll},
+    };
+
+    use super::*;
+
+    #[derive(Default)]
+    struct Device {
+        online: bool,
+        opens: usize,
+        written: Vec<u8>,
+    }
+
+    #[derive(Clone, Default)]
+    struct FakeTransport(Arc<Mutex<Device>>);
+
+    struct FakePort(Arc<Mutex<Device>>);
+
+    impl FakeTransport {
+        fn online() -> Self {
+            let transport = Self::default();
+            transport.set_online(true);
+            transport
+        }
+
+        fn set_online(&self, online: bool) {
+            self.0.lock().unwrap().online = online;
+        }
+
+        fn opens(&self) -> usize {
+            self.0.lock().unwrap().opens
+        }
+
+        fn written(&self) -> Vec<u8> {
+            self.0.lock().unwrap().written.clone()
+        }
+    }
+
+    impl Transport for FakeTransport {
+        type Port = FakePort;
+
+        fn open(&mut self) -> io::Result<FakePort> {
+            let mut device = self.0.lock().unwrap();
+            device.opens += 1;
+            if device.online {
+                Ok(FakePort(Arc::clone(&self.0)))
+            } else {
+                Err(io::Error::from(io::ErrorKind::NotFound))
+            }
+        }
+    }
+
+    impl AsyncWrite for FakePort {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            let mut device = self.0.lock().unwrap();
+            if !device.online {
+                return Poll::Ready(Err(io::Error::from(io::ErrorKind::BrokenPipe)));
+            }
+            device.written.extend_from_slice(buf);
+            Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+        ) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+        ) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn link(transport: &FakeTransport, buffer_capacity: usize) -> Link {
+        let mut opener = transport.clone();
+        let port = opener.open().unwrap();
+        let config = LinkConfig {
+            buffer_capacity,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(4),
+        };
+        Link::spawn(opener, port, config)
+    }
+
+    async fn wait_until(condition: impl Fn() -> bool) {
+        time::timeout(Duration::from_secs(5), async {
+            while !condition() {
+                time::sleep(Duration::from_millis(1)).await;
+            }
+        })
+        .await
+        .expect("condition not met in time");
+    }
+
+    async fn wait_for_state(link: &Link, expected: LinkState) {
+        time::timeout(
+            Duration::from_secs(5),
+            link.state().wait_for(|state| *state == expected),
+        )
+        .await
+        .expect("state not reached in time")
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_writes_while_connected() {
+        let transport = FakeTransport::online();
+        let link = link(&transport, DEFAULT_BUFFER_CAPACITY);
+        link.send(vec![1, 2]).unwrap();
+        link.send(vec![3]).unwrap();
+        wait_until(|| transport.written().len() == 3).await;
+        assert_eq!(transport.written(), [1, 2, 3]);
+        assert_eq!(*link.state().borrow(), LinkState::Connected);
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_flushes_in_order() {
+        let transport = FakeTransport::online();
+        let link = link(&transport, DEFAULT_BUFFER_CAPACITY);
+        link.send(vec![1]).unwrap();
+        wait_until(|| transport.written() == [1]).await;
+
+        transport.set_online(false);
+        for frame in 2..=5 {
+            link.send(vec![frame]).unwrap();
+        }
+        wait_for_state(&link, LinkState::Reconnecting).await;
+        let opens = transport.opens();
+        wait_until(|| transport.opens() > opens + 1).await;
+
+        transport.set_online(true);
+        wait_for_state(&link, LinkState::Connected).await;
+        wait_until(|| transport.written().len() == 5).await;
+        assert_eq!(transport.written(), [1, 2, 3, 4, 5]);
+    }
+
+    #[tokio::test]
+    async fn test_buffer_drops_oldest() {
+        let transport = FakeTransport::online();
+        let link = link(&transport, 3);
+
+        transport.set_online(false);
+        for frame in 1..=5 {
+            link.send(vec![frame]).unwrap();
+        }
+        wait_for_state(&link, LinkState::Reconnecting).await;
+        // every frame has been buffered once a reopen attempt started after
+        // the sends
+        let opens = transport.opens();
+        wait_until(|| transport.opens() > opens + 1).await;
+
+        transport.set_online(true);
+        wait_until(|| transport.written().len() == 3).await;
+        assert_eq!(transport.written(), [3, 4, 5]);
+        link.send(vec![6]).unwrap();
+        wait_until(|| transport.written().len() == 4).await;
+        assert_eq!(transport.written(), [3, 4, 5, 6]);
+    }
+}
@@ -3,44 +3,80 @@ use async_trait::async_trait;
 use color_eyre::eyre::{eyre, Result};
 use orb_messages::CommonAckError;
 use prost::Message;
+use std::io;
 use std::sync::atomic::{AtomicU16, Ordering};
-use std::time::Duration;
 use std::vec;
-use tokio::io::AsyncWriteExt;
-use tokio::time;
+use tokio::sync::watch;
 use tokio_serial::{SerialPort, SerialPortBuilderExt, SerialStream};
 use tracing::debug;
 
+use self::link::Link;
+
+mod link;
+
+pub use self::link::{LinkConfig, LinkState, Transport, DEFAULT_BUFFER_CAPACITY};
+
+const BAUD_RATE: u32 = 1000000;
+
 pub struct SerialMessaging {
     device: Device,
-    port: SerialStream,
+    link: Link,
     ack_num_lsb: AtomicU16,
 }
 
+/// UART device of an MCU.
+struct SerialDevice {
+    path: &'static str,
+}
+
+impl Transport for SerialDevice {
+    type Port = SerialStream;
+
+    fn open(&mut self) -> io::Result<SerialStream> {
+        let mut port = tokio_serial::new(self.path, BAUD_RATE).open_native_async()?;
+        port.set_data_bits(tokio_serial::DataBits::Eight)?;
+        port.set_stop_bits(tokio_serial::StopBits::One)?;
+        port.set_parity(tokio_serial::Parity::None)?;
+        Ok(port)
+    }
+}
+
 impl SerialMessaging {
+    /// Opens the UART of `device`, reconnecting with the default
+    /// [`LinkConfig`] if it goes away.
+    ///
+    /// Must be called from within a tokio runtime.
     pub fn new(device: Device) -> Result<Self> {
-        let mut port = match device {
-            Device::Main => {
-                tokio_serial::new("/dev/ttyTHS0", 1000000).open_native_async()?
-            }
-            Device::Security => {
-                tokio_serial::new("/dev/ttyTHS1", 1000000).open_native_async()?
-            }
+        Self::with_config(device, LinkConfig::default())
+    }
+
+    /// Opens the UART of `device`. Fails if the device can't be opened, later
+    /// disconnections are handled according to `config`.
+    ///
+    /// Must be called from within a tokio runtime.
+    pub fn with_config(device: Device, config: LinkConfig) -> Result<Self> {
+        let path = match device {
+            Device::Main => "/dev/ttyTHS0",
+            Device::Security => "/dev/ttyTHS1",
             Device::JetsonFromMain | Device::JetsonFromSecurity => {
                 return Err(eyre!("Cannot open serial from Jetson to Jetson"));
             }
         };
-
-        port.set_data_bits(tokio_serial::DataBits::Eight)?;
-        port.set_stop_bits(tokio_serial::StopBits::One)?;
-        port.set_parity(tokio_serial::Parity::None)?;
+        let mut transport = SerialDevice { path };
+        let port = transport.open()?;
 
         Ok(Self {
             device,
-            port,
+            link: Link::spawn(transport, port, config),
             ack_num_lsb: AtomicU16::new(0),
         })
     }
+
+    /// Watches the state of the serial link, e.g. to warn the user while the
+    /// MCU is unreachable.
+    pub fn link_state(&self) -> watch::Receiver<LinkState> {
+        self.link.state()
+    }
 }
 
 #[async_trait]
@@ -101,12 +137,8 @@ impl MessagingInterface for SerialMessaging {
 
         debug!("Sending {} bytes: {:?}", bytes.len(), bytes);
 
-        self.port
-            .write_all(bytes.as_slice())
-            .await
-            .expect("unable to write test message");
-
-        time::sleep(Duration::from_millis(8)).await;
+        // Buffered while the link is down, see `LinkConfig`.
+        self.link.send(bytes)?;
 
         Ok(CommonAckError::Success)
     }
@@ -1,13 +1,24 @@
 use core::{ffi::c_void, mem::MaybeUninit, ptr};
-use std::{ffi::CStr, mem, panic::catch_unwind, ptr::NonNull};
+use std::{
+    ffi::CStr,
+    mem,
+    panic::catch_unwind,
+    ptr::NonNull,
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TrySendError},
+        Arc, Mutex, PoisonError,
+    },
+    time::Duration,
+};
 
 use log::error;
 
 use crate::{
     error::ErrorCode,
     filters::{Filter, FilterState, FlatSceneCorrectionId},
-    frame::FrameContainer,
-    frame_format::FrameFormat,
+    frame::{FrameContainer, OwnedFrame},
+    frame_format::{FrameFormat, Pixel},
+    manager::{CameraHandle, Cameras},
     sys::{self, frame_t, seekcamera_t},
     ChipId, SerialNumber,
 };
@@ -293,6 +304,135 @@ pub enum PairingStatus {
     Unpaired,
 }
 
+//------------------------//
+// ---- Frame streams ---- //
+//------------------------//
+
+/// Error returned by [`FrameStream::next_frame`].
+#[derive(thiserror::Error, Debug, Eq, PartialEq)]
+pub enum FrameStreamError {
+    /// No frame was received before the timeout elapsed.
+    #[error("timed out waiting for a frame")]
+    Timeout,
+    /// The camera was disconnected, or its callback was replaced.
+    #[error("camera disconnected")]
+    CameraDisconnected,
+    /// The SDK failed to provide the frame.
+    #[error(transparent)]
+    ErrorCode(#[from] ErrorCode),
+}
+
+/// Registers and unregisters the frame callback that feeds a [`FrameStream`].
+///
+/// The stream reports [`FrameStreamError::CameraDisconnected`] once every
+/// [`FrameSender`] handed to [`Self::register`] has been dropped.
+pub trait FrameSource<P> {
+    fn register(&mut self, sender: FrameSender<P>) -> Result<()>;
+
+    fn unregister(&mut self);
+}
+
+/// Sending half of a [`FrameStream`], used from the SDK callback thread.
+#[derive(Debug)]
+pub struct FrameSender<P> {
+    tx: SyncSender<Result<OwnedFrame<P>>>,
+}
+
+impl<P> FrameSender<P> {
+    /// Queues a frame without blocking. The frame is dropped if the stream is
+    /// full or gone.
+    pub fn send(&self, frame: Result<OwnedFrame<P>>) {
+        match self.tx.try_send(frame) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => {
+                log::debug!("Frame stream full, dropping frame")
+            }
+            Err(TrySendError::Disconnected(_)) => {}
+        }
+    }
+}
+
+/// Blocking iterator-like access to the frames of a camera.
+///
+/// Frames are copied out of the SDK callback into a bounded queue. Frames that
+/// arrive while the queue is full are dropped. The callback is unregistered when
+/// the stream is dropped.
+#[derive(Debug)]
+pub struct FrameStream<P, S: FrameSource<P>> {
+    source: S,
+    rx: Receiver<Result<OwnedFrame<P>>>,
+}
+
+impl<P, S: FrameSource<P>> FrameStream<P, S> {
+    /// Registers a callback on `source`, queueing up to `capacity` frames.
+    pub fn new(mut source: S, capacity: usize) -> Result<Self> {
+        let (tx, rx) = mpsc::sync_channel(capacity);
+        source.register(FrameSender { tx })?;
+        Ok(Self { source, rx })
+    }
+
+    /// Waits up to `timeout` for the next frame.
+    pub fn next_frame(
+        &self,
+        timeout: Duration,
+    ) -> std::result::Result<OwnedFrame<P>, FrameStreamError> {
+        match self.rx.recv_timeout(timeout) {
+            Ok(frame) => Ok(frame?),
+            Err(RecvTimeoutError::Timeout) => Err(FrameStreamError::Timeout),
+            Err(RecvTimeoutError::Disconnected) => {
+                Err(FrameStreamError::CameraDisconnected)
+            }
+        }
+    }
+}
+
+impl<P, S: FrameSource<P>> Drop for FrameStream<P, S> {
+    fn drop(&mut self) {
+        self.source.unregister();
+    }
+}
+
+/// [`FrameSource`] for a camera owned by a [`crate::manager::Manager`].
+///
+/// The manager drops the camera, and with it the registered callback, when the
+/// camera is disconnected.
+#[derive(Debug)]
+pub struct ManagedCamera {
+    cameras: Arc<Mutex<Cameras>>,
+    handle: CameraHandle,
+}
+
+impl ManagedCamera {
+    pub(crate) fn new(cameras: Arc<Mutex<Cameras>>, handle: CameraHandle) -> Self {
+        Self { cameras, handle }
+    }
+}
+
+impl<P: Pixel + Send + 'static> FrameSource<P> for ManagedCamera {
+    fn register(&mut self, sender: FrameSender<P>) -> Result<()> {
+        let mut cameras = self.cameras.lock().unwrap_or_else(PoisonError::into_inner);
+        let camera = cameras
+            .get_mut(&self.handle)
+            .ok_or(ErrorCode::DeviceNotFound)?;
+        camera.set_callback(Box::new(move |container| {
+            let frame = container
+                .get_frame::<P>()
+                .map(|frame| OwnedFrame::from(&frame));
+            sender.send(frame);
+        }))
+    }
+
+    fn unregister(&mut self) {
+        let mut cameras = self.cameras.lock().unwrap_or_else(PoisonError::into_inner);
+        // Already gone if the camera was disconnected.
+        if let Some(camera) = cameras.get_mut(&self.handle) {
+            if let Err(err) = camera.clear_callback() {
+                error!("Failed to clear camera callback: {err}");
+            }
+        }
+    }
+}
+
 //-----------------------//
 // ---- Helper code ---- //
 //-----------------------//
@@ -367,3 +507,84 @@ unsafe fn register_callback(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame_format::GrayscalePixel;
+
+    #[derive(Default)]
+    struct MockSource {
+        sender: Arc<Mutex<Option<FrameSender<GrayscalePixel>>>>,
+        unregistered: Arc<Mutex<bool>>,
+    }
+
+    impl FrameSource<GrayscalePixel> for MockSource {
+        fn register(&mut self, sender: FrameSender<GrayscalePixel>) -> Result<()> {
+            *self.sender.lock().unwrap() = Some(sender);
+            Ok(())
+        }
+
+        fn unregister(&mut self) {
+            self.sender.lock().unwrap().take();
+            *self.unregistered.lock().unwrap() = true;
+        }
+    }
+
+    fn frame(value: u8) -> OwnedFrame<GrayscalePixel> {
+        OwnedFrame::new(2, 1, vec![GrayscalePixel(value); 2])
+    }
+
+    #[test]
+    fn test_frame_stream_receives_frames() {
+        let source = MockSource::default();
+        let sender = Arc::clone(&source.sender);
+        let stream = FrameStream::new(source, 2).unwrap();
+
+        let sender = sender.lock().unwrap();
+        let sender = sender.as_ref().unwrap();
+        sender.send(Ok(frame(1)));
+        sender.send(Err(ErrorCode::DeviceCommunication));
+        // Queue is full, this one is dropped.
+        sender.send(Ok(frame(2)));
+
+        let timeout = Duration::from_millis(10);
+        assert_eq!(stream.next_frame(timeout), Ok(frame(1)));
+        assert_eq!(
+            stream.next_frame(timeout),
+            Err(FrameStreamError::ErrorCode(ErrorCode::DeviceCommunication))
+        );
+        assert_eq!(stream.next_frame(timeout), Err(FrameStreamError::Timeout));
+    }
+
+    #[test]
+    fn test_frame_stream_disconnect() {
+        let source = MockSource::default();
+        let sender = Arc::clone(&source.sender);
+        let stream = FrameStream::new(source, 2).unwrap();
+
+        sender.lock().unwrap().as_ref().unwrap().send(Ok(frame(1)));
+        // Simulates the manager dropping the camera, and its callback.
+        sender.lock().unwrap().take();
+
+        let timeout = Duration::from_millis(10);
+        assert_eq!(stream.next_frame(timeout), Ok(frame(1)));
+        assert_eq!(
+            stream.next_frame(timeout),
+            Err(FrameStreamError::CameraDisconnected)
+        );
+    }
+
+    #[test]
+    fn test_frame_stream_drop_unregisters() {
+        let source = MockSource::default();
+        let sender = Arc::clone(&source.sender);
+        let unregistered = Arc::clone(&source.unregistered);
+        let stream = FrameStream::new(source, 1).unwrap();
+        assert!(sender.lock().unwrap().is_some());
+
+        drop(stream);
+        assert!(*unregistered.lock().unwrap());
+        assert!(sender.lock().unwrap().is_none());
+    }
+}
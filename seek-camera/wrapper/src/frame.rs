@@ -92,3 +92,52 @@ impl<P: Pixel> Frame<'_, P> {
 
 unsafe impl<P> Send for Frame<'_, P> {}
 unsafe impl<P> Sync for Frame<'_, P> {}
+
+/// A copy of a [`Frame`] that outlives the SDK callback it was received in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OwnedFrame<P> {
+    width: usize,
+    height: usize,
+    pixels: Vec<P>,
+}
+
+impl<P: Pixel> OwnedFrame<P> {
+    /// Creates a frame from raw pixels, in row-major order.
+    ///
+    /// # Panics
+    /// Panics if `pixels` doesn't hold exactly `width * height` pixels.
+    pub fn new(width: usize, height: usize, pixels: Vec<P>) -> Self {
+        assert_eq!(pixels.len(), width * height, "pixel count mismatch");
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[P] {
+        &self.pixels
+    }
+
+    pub fn into_pixels(self) -> Vec<P> {
+        self.pixels
+    }
+}
+
+impl<P: Pixel> From<&Frame<'_, P>> for OwnedFrame<P> {
+    fn from(frame: &Frame<'_, P>) -> Self {
+        Self {
+            width: frame.width(),
+            height: frame.height(),
+            pixels: frame.pixels().to_vec(),
+        }
+    }
+}
@@ -5,8 +5,9 @@ pub use self::{error::ManagerError, event::Event};
 
 use self::error::Result;
 use crate::{
-    camera::{Camera, PairingStatus},
+    camera::{Camera, FrameStream, ManagedCamera, PairingStatus},
     error::ErrorCode,
+    frame_format::Pixel,
     sys, SerialNumber,
 };
 use sys::{manager_t, seekcamera_t};
@@ -89,6 +90,21 @@ impl Manager {
     pub fn cameras(&self) -> Result<MutexGuard<Cameras>> {
         self.cameras.lock().map_err(ManagerError::from)
     }
+
+    /// Streams the frames of the camera `handle`, queueing up to `capacity` of
+    /// them. Replaces any callback previously set on the camera, so only one
+    /// stream per camera should be alive at a time.
+    ///
+    /// The stream reports the camera as disconnected once the manager receives
+    /// [`Event::Disconnect`] for it.
+    pub fn frame_stream<P: Pixel + Send + 'static>(
+        &self,
+        handle: CameraHandle,
+        capacity: usize,
+    ) -> Result<FrameStream<P, ManagedCamera>> {
+        let source = ManagedCamera::new(Arc::clone(&self.cameras), handle);
+        Ok(FrameStream::new(source, capacity)?)
+    }
 }
 
 impl Drop for Manager {
@@ -0,0 +1,236 @@
+//! Storage backends of an [`EfiVarDb`](super::EfiVarDb).
+//!
+//! [`FsBackend`] talks to efivarfs (or a directory mimicking it), while
+//! [`MemoryBackend`] keeps the efivars in memory for tests and development on
+//! machines without efivarfs.
+
+use std::{
+    collections::HashMap,
+    ffi::c_int,
+    fs::{self, File},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use super::{EfiVarData, EfiVarEntry};
+use crate::ioctl;
+use crate::Error;
+
+/// Storage of efivars, addressed by their absolute path.
+pub(crate) trait EfiVarBackend: Send + Sync {
+    /// Reads the raw contents of the efivar.
+    fn read(&self, path: &Path) -> Result<Vec<u8>, Error>;
+
+    /// Writes `buffer` in a single write, creating the efivar if it doesn't exist.
+    fn write(&self, path: &Path, buffer: &[u8]) -> Result<(), Error>;
+
+    /// Creates or truncates the efivar and writes `buffer`, without any
+    /// immutability handling.
+    fn create(&self, path: &Path, buffer: &[u8]) -> Result<(), Error>;
+
+    /// Removes the efivar.
+    fn remove(&self, path: &Path) -> Result<(), Error>;
+
+    /// Lists the efivars in `dir`, in no particular order.
+    fn list(&self, dir: &Path) -> io::Result<Vec<EfiVarEntry>>;
+
+    /// Writes `new` only if the efivar currently holds `expected`, where `None`
+    /// means the efivar doesn't exist. Returns whether the write happened.
+    fn write_if(
+        &self,
+        path: &Path,
+        expected: Option<&EfiVarData>,
+        new: &EfiVarData,
+    ) -> Result<bool, Error>;
+}
+
+/// Efivars as files, as exposed by efivarfs.
+#[derive(Debug, Default)]
+pub(crate) struct FsBackend;
+
+impl EfiVarBackend for FsBackend {
+    fn read(&self, path: &Path) -> Result<Vec<u8>, Error> {
+        let mut file = File::open(path).map_err(|e| Error::open_file(path, e))?;
+        let mut buffer: Vec<u8> = vec![];
+        file.read_to_end(&mut buffer)
+            .map_err(|e| Error::read_file(path, e))?;
+        Ok(buffer)
+    }
+
+    fn write(&self, path: &Path, buffer: &[u8]) -> Result<(), Error> {
+        with_mutable(path, || write_once(path, buffer))
+    }
+
+    fn create(&self, path: &Path, buffer: &[u8]) -> Result<(), Error> {
+        let inner_file = File::create(path).map_err(|e| Error::create_file(path, e))?;
+        (&inner_file)
+            .write_all(buffer)
+            .map_err(|e| Error::write_file(path, e))?;
+        (&inner_file)
+            .flush()
+            .map_err(|e| Error::flush_file(path, e))?;
+        Ok(())
+    }
+
+    fn remove(&self, path: &Path) -> Result<(), Error> {
+        fs::remove_file(path).map_err(|e| Error::remove_efi_var(path, e))
+    }
+
+    fn list(&self, dir: &Path) -> io::Result<Vec<EfiVarEntry>> {
+        let mut vars = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            if !metadata.is_file() {
+                continue;
+            }
+            vars.push(EfiVarEntry {
+                name: entry.file_name().to_string_lossy().into_owned(),
+                size: metadata.len(),
+            });
+        }
+
+        Ok(vars)
+    }
+
+    fn write_if(
+        &self,
+        path: &Path,
+        expected: Option<&EfiVarData>,
+        new: &EfiVarData,
+    ) -> Result<bool, Error> {
+        // Keep the efivar mutable for the whole read-compare-write.
+        with_mutable(path, || {
+            let current = match self.read(path) {
+                Ok(buffer) => Some(EfiVarData::from_bytes(&buffer)?),
+                Err(Error::OpenFile { source, .. })
+                    if source.kind() == io::ErrorKind::NotFound =>
+                {
+                    None
+                }
+                Err(e) => return Err(e),
+            };
+            if current.as_ref() != expected {
+                return Ok(false);
+            }
+            write_once(path, &new.to_bytes())?;
+            Ok(true)
+        })
+    }
+}
+
+/// Runs `f` with the immutable flag of the efivar cleared, restoring the
+/// original flags afterwards. Runs `f` directly if the efivar doesn't exist.
+fn with_mutable<T>(
+    path: &Path,
+    f: impl FnOnce() -> Result<T, Error>,
+) -> Result<T, Error> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return f(),
+        Err(e) => return Err(Error::open_file(path, e)),
+    };
+
+    let original_attributes: c_int =
+        ioctl::read_file_attributes(&file).map_err(Error::GetAttributes)?;
+
+    // Make file mutable.
+    let new_attributes = original_attributes & !ioctl::IMMUTABLE_MASK;
+    ioctl::write_file_attributes(&file, new_attributes).map_err(Error::MakeMutable)?;
+
+    let result = f();
+
+    // Make file immutable again, even if `f` failed.
+    ioctl::write_file_attributes(&file, original_attributes)
+        .map_err(Error::MakeImmutable)?;
+
+    result
+}
+
+/// Opens the efivar with `O_WRONLY | O_CREAT` and writes the whole `buffer`.
+fn write_once(path: &Path, buffer: &[u8]) -> Result<(), Error> {
+    let file = File::options()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)
+        .map_err(|e| Error::open_write_file(path, e))?;
+    (&file)
+        .write_all(buffer)
+        .map_err(|e| Error::write_file(path, e))?;
+    (&file).flush().map_err(|e| Error::flush_file(path, e))
+}
+
+/// Efivars kept in memory.
+///
+/// Like efivarfs, it rejects writes shorter than the 4 attribute bytes.
+/// [`EfiVarBackend::write_if`] is atomic with respect to other writers.
+#[derive(Debug, Default)]
+pub(crate) struct MemoryBackend {
+    vars: Mutex<HashMap<PathBuf, EfiVarData>>,
+}
+
+fn not_found() -> io::Error {
+    io::ErrorKind::NotFound.into()
+}
+
+impl EfiVarBackend for MemoryBackend {
+    fn read(&self, path: &Path) -> Result<Vec<u8>, Error> {
+        self.vars
+            .lock()
+            .unwrap()
+            .get(path)
+            .map(EfiVarData::to_bytes)
+            .ok_or_else(|| Error::open_file(path, not_found()))
+    }
+
+    fn write(&self, path: &Path, buffer: &[u8]) -> Result<(), Error> {
+        let data = EfiVarData::from_bytes(buffer)?;
+        self.vars.lock().unwrap().insert(path.to_owned(), data);
+        Ok(())
+    }
+
+    fn create(&self, path: &Path, buffer: &[u8]) -> Result<(), Error> {
+        self.write(path, buffer)
+    }
+
+    fn remove(&self, path: &Path) -> Result<(), Error> {
+        self.vars
+            .lock()
+            .unwrap()
+            .remove(path)
+            .map(drop)
+            .ok_or_else(|| Error::remove_efi_var(path, not_found()))
+    }
+
+    fn list(&self, dir: &Path) -> io::Result<Vec<EfiVarEntry>> {
+        let vars = self.vars.lock().unwrap();
+        let entries = vars
+            .iter()
+            .filter(|(path, _)| path.parent() == Some(dir))
+            .filter_map(|(path, data)| {
+                Some(EfiVarEntry {
+                    name: path.file_name()?.to_string_lossy().into_owned(),
+                    size: data.to_bytes().len() as u64,
+                })
+            })
+            .collect();
+
+        Ok(entries)
+    }
+
+    fn write_if(
+        &self,
+        path: &Path,
+        expected: Option<&EfiVarData>,
+        new: &EfiVarData,
+    ) -> Result<bool, Error> {
+        let mut vars = self.vars.lock().unwrap();
+        if vars.get(path) != expected {
+            return Ok(false);
+        }
+        vars.insert(path.to_owned(), new.clone());
+        Ok(true)
+    }
+}
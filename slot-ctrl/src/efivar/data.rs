@@ -0,0 +1,220 @@
+//! Decoded efivar contents.
+//!
+//! In efivarfs every variable starts with 4 bytes of little-endian attributes,
+//! followed by the variable value.
+
+use std::fmt;
+
+use crate::Error;
+
+const ATTRIBUTES_LEN: usize = 4;
+
+/// Names of the `EFI_VARIABLE_*` attribute bits.
+const ATTRIBUTE_NAMES: [(u32, &str); 7] = [
+    (0x01, "NV"),
+    (0x02, "BS"),
+    (0x04, "RT"),
+    (0x08, "HR"),
+    (0x10, "AW"),
+    (0x20, "AT"),
+    (0x40, "AP"),
+];
+
+/// Attributes and value of an efivar.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EfiVarData {
+    /// `EFI_VARIABLE_*` attribute bits.
+    pub attributes: u32,
+    /// The variable value, without the attributes.
+    pub value: Vec<u8>,
+}
+
+impl EfiVarData {
+    /// Split raw efivarfs contents into attributes and value.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.len() < ATTRIBUTES_LEN {
+            return Err(Error::InvalidEfiVarLen);
+        }
+        let (attributes, value) = bytes.split_at(ATTRIBUTES_LEN);
+
+        Ok(Self {
+            attributes: u32::from_le_bytes(attributes.try_into().unwrap()),
+            value: value.to_vec(),
+        })
+    }
+
+    /// Raw efivarfs contents, attributes first.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(ATTRIBUTES_LEN + self.value.len());
+        bytes.extend_from_slice(&self.attributes.to_le_bytes());
+        bytes.extend_from_slice(&self.value);
+        bytes
+    }
+
+    /// The attributes as names joined by `+`, e.g. `NV+BS+RT`.
+    pub fn attribute_names(&self) -> String {
+        let mut names: Vec<String> = ATTRIBUTE_NAMES
+            .iter()
+            .filter(|(bit, _)| self.attributes & bit != 0)
+            .map(|(_, name)| (*name).to_owned())
+            .collect();
+        let known = ATTRIBUTE_NAMES.iter().fold(0, |acc, (bit, _)| acc | bit);
+        let unknown = self.attributes & !known;
+        if unknown != 0 {
+            names.push(format!("{unknown:#x}"));
+        }
+        names.join("+")
+    }
+
+    /// Read a value of exactly 2 bytes as a little-endian `u16`.
+    pub fn value_as_u16_le(&self) -> Result<u16, Error> {
+        Ok(u16::from_le_bytes(self.value_array("u16")?))
+    }
+
+    /// Read a value of exactly 4 bytes as a little-endian `u32`.
+    pub fn value_as_u32_le(&self) -> Result<u32, Error> {
+        Ok(u32::from_le_bytes(self.value_array("u32")?))
+    }
+
+    /// Read the value as a UTF-16LE string, dropping a trailing NUL terminator.
+    pub fn value_as_utf16_string(&self) -> Result<String, Error> {
+        let invalid = || Error::InvalidEfiVarValue {
+            kind: "UTF-16 string",
+            len: self.value.len(),
+        };
+        if self.value.len() % 2 != 0 {
+            return Err(invalid());
+        }
+        let mut units: Vec<u16> = self
+            .value
+            .chunks_exact(2)
+            .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+            .collect();
+        if units.last() == Some(&0) {
+            units.pop();
+        }
+        String::from_utf16(&units).map_err(|_| invalid())
+    }
+
+    fn value_array<const N: usize>(
+        &self,
+        kind: &'static str,
+    ) -> Result<[u8; N], Error> {
+        self.value
+            .as_slice()
+            .try_into()
+            .map_err(|_| Error::InvalidEfiVarValue {
+                kind,
+                len: self.value.len(),
+            })
+    }
+}
+
+/// Renders the attributes by name, the value as hex and every decoding that
+/// fits the value.
+impl fmt::Display for EfiVarData {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "attributes: {} ({:#010x})",
+            self.attribute_names(),
+            self.attributes
+        )?;
+        write!(f, "value ({} bytes):", self.value.len())?;
+        for byte in &self.value {
+            write!(f, " {byte:02x}")?;
+        }
+        writeln!(f)?;
+        if let Ok(value) = self.value_as_u16_le() {
+            writeln!(f, "as u16: {value}")?;
+        }
+        if let Ok(value) = self.value_as_u32_le() {
+            writeln!(f, "as u32: {value}")?;
+        }
+        if let Ok(value) = self.value_as_utf16_string() {
+            if !value.is_empty() && !value.chars().any(char::is_control) {
+                writeln!(f, "as UTF-16: {value:?}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data(attributes: u32, value: &[u8]) -> EfiVarData {
+        EfiVarData {
+            attributes,
+            value: value.to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_bytes_round_trip() {
+        let bytes = [0x07, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00];
+        let parsed = EfiVarData::from_bytes(&bytes).unwrap();
+        assert_eq!(parsed, data(7, &[0x01, 0x00, 0x00, 0x00]));
+        assert_eq!(parsed.to_bytes(), bytes);
+
+        assert!(matches!(
+            EfiVarData::from_bytes(&[0x07, 0x00]),
+            Err(Error::InvalidEfiVarLen)
+        ));
+    }
+
+    #[test]
+    fn test_attribute_names() {
+        assert_eq!(data(0x07, &[]).attribute_names(), "NV+BS+RT");
+        assert_eq!(data(0x47, &[]).attribute_names(), "NV+BS+RT+AP");
+        assert_eq!(data(0x102, &[]).attribute_names(), "BS+0x100");
+        assert_eq!(data(0, &[]).attribute_names(), "");
+    }
+
+    #[test]
+    fn test_value_as_integers() {
+        let var = data(7, &[0x01, 0x02, 0x00, 0x00]);
+        assert_eq!(var.value_as_u32_le().unwrap(), 0x0201);
+        assert!(matches!(
+            var.value_as_u16_le(),
+            Err(Error::InvalidEfiVarValue {
+                kind: "u16",
+                len: 4
+            })
+        ));
+
+        let var = data(7, &[0x34, 0x12]);
+        assert_eq!(var.value_as_u16_le().unwrap(), 0x1234);
+        assert!(var.value_as_u32_le().is_err());
+    }
+
+    #[test]
+    fn test_value_as_utf16_string() {
+        let var = data(7, &[b'o', 0, b'r', 0, b'b', 0, 0, 0]);
+        assert_eq!(var.value_as_utf16_string().unwrap(), "orb");
+        assert!(data(7, &[b'o', 0, b'r']).value_as_utf16_string().is_err());
+        // unpaired surrogate
+        assert!(data(7, &[0x00, 0xd8]).value_as_utf16_string().is_err());
+    }
+
+    #[test]
+    fn test_display() {
+        let var = data(7, &[0x01, 0x00, 0x00, 0x00]);
+        assert_eq!(
+            var.to_string(),
+            "attributes: NV+BS+RT (0x00000007)\n\
+             value (4 bytes): 01 00 00 00\n\
+             as u32: 1\n"
+        );
+
+        let var = data(7, &[b'h', 0, b'i', 0]);
+        assert_eq!(
+            var.to_string(),
+            "attributes: NV+BS+RT (0x00000007)\n\
+             value (4 bytes): 68 00 69 00\n\
+             as u32: 6881384\n\
+             as UTF-16: \"hi\"\n"
+        );
+    }
+}
@@ -8,18 +8,21 @@
 //! [efivar Documentation](https://www.kernel.org/doc/html/latest/filesystems/efivarfs.html)
 
 use std::{
-    ffi::c_int,
-    fs::{self, File},
-    io::{self, Read, Write},
+    fs, io,
     path::{Path, PathBuf},
+    sync::Arc,
 };
 use thiserror::Error;
 
+mod backend;
 pub mod bootchain;
+pub mod data;
 pub mod rootfs;
 
-use crate::ioctl;
+pub use data::EfiVarData;
+
 use crate::Error;
+use backend::{EfiVarBackend, FsBackend, MemoryBackend};
 
 // Slots.
 pub const SLOT_A: u8 = 0;
@@ -39,10 +42,13 @@ pub enum EfiVarDbErr {
     FailedCanonicalization(#[from] io::Error),
     #[error("EfiVar path cannot be absolute. Given '{0:?}'")]
     VarPathCannotBeAbsolute(PathBuf),
+    #[error("Failed listing efivars in '{path:?}': {source}")]
+    ListVars { path: PathBuf, source: io::Error },
 }
 
 pub struct EfiVarDb {
     path: PathBuf,
+    backend: Arc<dyn EfiVarBackend>,
 }
 
 impl EfiVarDb {
@@ -52,7 +58,22 @@ impl EfiVarDb {
         let path = rootfs_path.as_ref().join(EFIVARS_PATH);
         let path = fs::canonicalize(path)?;
 
-        Ok(Self { path })
+        Ok(Self {
+            path,
+            backend: Arc::new(FsBackend),
+        })
+    }
+
+    /// Returns an empty [`EfiVarDb`] that keeps its efivars in memory, for tests
+    /// and development on machines without efivarfs.
+    ///
+    /// Its [`path`](Self::path) is `/sys/firmware/efi/efivars`, but nothing is
+    /// read from or written to the filesystem.
+    pub fn in_memory() -> Self {
+        Self {
+            path: Path::new("/").join(EFIVARS_PATH),
+            backend: Arc::new(MemoryBackend::default()),
+        }
     }
 
     pub fn get_var(
@@ -66,19 +87,44 @@ impl EfiVarDb {
 
         let path = self.path.join(relative_path);
 
-        Ok(EfiVar { path })
+        Ok(EfiVar {
+            path,
+            backend: Arc::clone(&self.backend),
+        })
     }
 
     /// Returns the filesystem path to this [`EfiVarDb`].
     pub fn path(&self) -> &Path {
         self.path.as_path()
     }
+
+    /// Lists all efivars in the db, sorted by name, without reading them.
+    pub fn list_vars(&self) -> Result<Vec<EfiVarEntry>, EfiVarDbErr> {
+        let list_err = |source| EfiVarDbErr::ListVars {
+            path: self.path.clone(),
+            source,
+        };
+        let mut vars = self.backend.list(&self.path).map_err(list_err)?;
+        vars.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(vars)
+    }
+}
+
+/// An efivar found by [`EfiVarDb::list_vars`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EfiVarEntry {
+    /// File name of the efivar, `{Name}-{VendorGuid}`.
+    pub name: String,
+    /// Size in bytes, including the 4 attribute bytes.
+    pub size: u64,
 }
 
 /// Efivar representation.
 pub struct EfiVar {
     // Path to efivar.
     path: PathBuf,
+    backend: Arc<dyn EfiVarBackend>,
 }
 
 impl EfiVar {
@@ -86,12 +132,12 @@ impl EfiVar {
     ///
     /// Errors: i/o specific on file operations and `InvalidEfiVarLen` if the data length is invalid.
     pub fn read(&self) -> Result<Vec<u8>, Error> {
-        let mut file =
-            File::open(&self.path).map_err(|e| Error::open_file(&self.path, e))?;
-        let mut buffer: Vec<u8> = vec![];
-        file.read_to_end(&mut buffer)
-            .map_err(|e| Error::read_file(&self.path, e))?;
-        Ok(buffer)
+        self.backend.read(&self.path)
+    }
+
+    /// Read the efivar and split it into attributes and value.
+    pub fn read_data(&self) -> Result<EfiVarData, Error> {
+        EfiVarData::from_bytes(&self.read()?)
     }
 
     /// Read the efivar data from a `path`.
@@ -103,59 +149,39 @@ impl EfiVar {
         Ok(buf)
     }
 
-    /// Validates the expected data length and writes the current buffer.
+    /// Writes the `buffer`, creating the efivar if it doesn't exist yet.
     ///
-    /// Errors: i/o specific `Error`s on file operations and `InvalidEfiVarLen` if the data length is invalid.
+    /// The buffer must hold the attributes followed by the value, and is written
+    /// in a single write as efivarfs requires.
+    ///
+    /// Errors: i/o specific `Error`s on file operations.
     pub fn write(&self, buffer: &[u8]) -> Result<(), Error> {
-        let file_read =
-            File::open(&self.path).map_err(|e| Error::open_file(&self.path, e))?;
-
-        let original_attributes: c_int =
-            ioctl::read_file_attributes(&file_read).map_err(Error::GetAttributes)?;
-
-        // Make file mutable.
-        let new_attributes = original_attributes & !ioctl::IMMUTABLE_MASK;
-        ioctl::write_file_attributes(&file_read, new_attributes)
-            .map_err(Error::MakeMutable)?;
-
-        // Open file for writing and write buffer.
-        let file_write = File::options()
-            .write(true)
-            .open(&self.path)
-            .map_err(|e| Error::open_write_file(&self.path, e))?;
-        (&file_write)
-            .write_all(buffer)
-            .map_err(|e| Error::write_file(&self.path, e))?;
-        (&file_write)
-            .flush()
-            .map_err(|e| Error::flush_file(&self.path, e))?;
-
-        // Make file immutable again.
-        ioctl::write_file_attributes(&file_read, original_attributes)
-            .map_err(Error::MakeImmutable)?;
+        self.backend.write(&self.path, buffer)
+    }
 
-        Ok(())
+    /// Writes `new` only if the efivar currently holds `expected`, where `None`
+    /// means the efivar doesn't exist. Returns whether the write happened.
+    ///
+    /// Errors: i/o specific `Error`s on file operations and `InvalidEfiVarLen` if the
+    /// current contents are not a valid efivar.
+    pub fn write_if(
+        &self,
+        expected: Option<&EfiVarData>,
+        new: &EfiVarData,
+    ) -> Result<bool, Error> {
+        self.backend.write_if(&self.path, expected, new)
     }
 
     /// Create a new efivar and write the `buffer`.
     ///
     /// Errors: i/o specific `Error`s on file operations and `InvalidEfiVarLen` if the data length is invalid.
     pub fn create_and_write(&self, buffer: &[u8]) -> Result<(), Error> {
-        let inner_file =
-            File::create(&self.path).map_err(|e| Error::create_file(&self.path, e))?;
-        (&inner_file)
-            .write_all(buffer)
-            .map_err(|e| Error::write_file(&self.path, e))?;
-        (&inner_file)
-            .flush()
-            .map_err(|e| Error::flush_file(&self.path, e))?;
-        Ok(())
+        self.backend.create(&self.path, buffer)
     }
 
     /// Remove UEFI variable
     pub fn remove(&self) -> Result<(), Error> {
-        std::fs::remove_file(&self.path)
-            .map_err(|e| Error::remove_efi_var(&self.path, e))
+        self.backend.remove(&self.path)
     }
 }
 
@@ -166,3 +192,232 @@ fn is_valid_buffer(buffer: &[u8], expected_length: usize) -> Result<(), Error> {
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn fake_db(vars: &[(&str, &[u8])]) -> (TempDir, EfiVarDb) {
+        let tempdir = TempDir::new().unwrap();
+        let db_path = tempdir.path().join(EFIVARS_PATH);
+        fs::create_dir_all(&db_path).unwrap();
+        for (name, contents) in vars {
+            fs::write(db_path.join(name), contents).unwrap();
+        }
+        let db = EfiVarDb::from_rootfs(tempdir.path()).unwrap();
+        (tempdir, db)
+    }
+
+    #[test]
+    fn test_list_vars() {
+        let (_tempdir, db) = fake_db(&[
+            (
+                "RootfsStatusSlotA-781e084c-a330-417c-b678-38e696380cb9",
+                &[7, 0, 0, 0, 0, 0, 0, 0],
+            ),
+            (
+                "BootChainFwCurrent-781e084c-a330-417c-b678-38e696380cb9",
+                &[7, 0, 0, 0, 1, 0, 0, 0],
+            ),
+            ("Empty-8be4df61-93ca-11d2-aa0d-00e098032b8c", &[]),
+        ]);
+        fs::create_dir(db.path().join("not-a-var")).unwrap();
+
+        let vars = db.list_vars().unwrap();
+        let listed: Vec<(&str, u64)> = vars
+            .iter()
+            .map(|var| (var.name.as_str(), var.size))
+            .collect();
+        assert_eq!(
+            listed,
+            [
+                ("BootChainFwCurrent-781e084c-a330-417c-b678-38e696380cb9", 8),
+                ("Empty-8be4df61-93ca-11d2-aa0d-00e098032b8c", 0),
+                ("RootfsStatusSlotA-781e084c-a330-417c-b678-38e696380cb9", 8),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_read_data() {
+        let (_tempdir, db) = fake_db(&[
+            (
+                "Counter-8be4df61-93ca-11d2-aa0d-00e098032b8c",
+                &[7, 0, 0, 0, 5, 0],
+            ),
+            ("Short-8be4df61-93ca-11d2-aa0d-00e098032b8c", &[7, 0]),
+        ]);
+
+        let data = db
+            .get_var("Counter-8be4df61-93ca-11d2-aa0d-00e098032b8c")
+            .unwrap()
+            .read_data()
+            .unwrap();
+        assert_eq!(data.attribute_names(), "NV+BS+RT");
+        assert_eq!(data.value_as_u16_le().unwrap(), 5);
+
+        let short = db
+            .get_var("Short-8be4df61-93ca-11d2-aa0d-00e098032b8c")
+            .unwrap()
+            .read_data();
+        assert!(matches!(short, Err(Error::InvalidEfiVarLen)));
+    }
+
+    fn memory_db(vars: &[(&str, &[u8])]) -> EfiVarDb {
+        let db = EfiVarDb::in_memory();
+        for (name, contents) in vars {
+            db.get_var(name)
+                .unwrap()
+                .create_and_write(contents)
+                .unwrap();
+        }
+        db
+    }
+
+    /// Runs `test` against a filesystem and an in-memory db, both holding `vars`.
+    fn for_each_backend(vars: &[(&str, &[u8])], test: impl Fn(&EfiVarDb)) {
+        let (_tempdir, db) = fake_db(vars);
+        test(&db);
+        test(&memory_db(vars));
+    }
+
+    fn data(value: &[u8]) -> EfiVarData {
+        EfiVarData {
+            attributes: 7,
+            value: value.to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_write_if_hit() {
+        let name = "Counter-8be4df61-93ca-11d2-aa0d-00e098032b8c";
+        for_each_backend(&[(name, &[7, 0, 0, 0, 1, 0])], |db| {
+            let var = db.get_var(name).unwrap();
+
+            assert!(var.write_if(Some(&data(&[1, 0])), &data(&[2, 0])).unwrap());
+            assert_eq!(var.read_data().unwrap(), data(&[2, 0]));
+        });
+    }
+
+    #[test]
+    fn test_write_if_miss() {
+        let name = "Counter-8be4df61-93ca-11d2-aa0d-00e098032b8c";
+        for_each_backend(&[(name, &[7, 0, 0, 0, 3, 0])], |db| {
+            let var = db.get_var(name).unwrap();
+
+            assert!(!var.write_if(Some(&data(&[1, 0])), &data(&[2, 0])).unwrap());
+            assert!(!var.write_if(None, &data(&[2, 0])).unwrap());
+            assert_eq!(var.read_data().unwrap(), data(&[3, 0]));
+        });
+    }
+
+    #[test]
+    fn test_write_if_missing_var() {
+        let name = "New-8be4df61-93ca-11d2-aa0d-00e098032b8c";
+        for_each_backend(&[], |db| {
+            let var = db.get_var(name).unwrap();
+
+            assert!(!var.write_if(Some(&data(&[1, 0])), &data(&[2, 0])).unwrap());
+            assert!(var.read().is_err());
+
+            assert!(var.write_if(None, &data(&[2, 0])).unwrap());
+            assert_eq!(var.read_data().unwrap(), data(&[2, 0]));
+        });
+    }
+
+    #[test]
+    fn test_write_and_remove() {
+        let name = "Counter-8be4df61-93ca-11d2-aa0d-00e098032b8c";
+        for_each_backend(&[], |db| {
+            let var = db.get_var(name).unwrap();
+
+            var.write(&[7, 0, 0, 0, 1, 0]).unwrap();
+            assert_eq!(var.read().unwrap(), [7, 0, 0, 0, 1, 0]);
+            let listed: Vec<EfiVarEntry> = db.list_vars().unwrap();
+            assert_eq!(
+                listed,
+                [EfiVarEntry {
+                    name: name.to_owned(),
+                    size: 6
+                }]
+            );
+
+            var.remove().unwrap();
+            assert!(matches!(
+                var.read(),
+                Err(Error::OpenFile { source, .. })
+                    if source.kind() == io::ErrorKind::NotFound
+            ));
+            assert!(var.remove().is_err());
+            assert!(db.list_vars().unwrap().is_empty());
+        });
+    }
+
+    #[test]
+    fn test_in_memory_rejects_short_writes() {
+        let db = EfiVarDb::in_memory();
+        let var = db
+            .get_var("Short-8be4df61-93ca-11d2-aa0d-00e098032b8c")
+            .unwrap();
+
+        assert!(matches!(var.write(&[7, 0]), Err(Error::InvalidEfiVarLen)));
+        assert!(var.read().is_err());
+    }
+
+    #[test]
+    fn test_in_memory_concurrent_write_if() {
+        const THREADS: u16 = 8;
+        const INCREMENTS: u16 = 100;
+        let name = "Counter-8be4df61-93ca-11d2-aa0d-00e098032b8c";
+        let db = memory_db(&[(name, &[7, 0, 0, 0, 0, 0])]);
+
+        std::thread::scope(|s| {
+            for _ in 0..THREADS {
+                s.spawn(|| {
+                    let var = db.get_var(name).unwrap();
+                    let mut done = 0;
+                    while done < INCREMENTS {
+                        let current = var.read_data().unwrap();
+                        let next = current.value_as_u16_le().unwrap() + 1;
+                        if var
+                            .write_if(Some(&current), &data(&next.to_le_bytes()))
+                            .unwrap()
+                        {
+                            done += 1;
+                        }
+                    }
+                });
+            }
+        });
+
+        let counter = db.get_var(name).unwrap().read_data().unwrap();
+        assert_eq!(counter.value_as_u16_le().unwrap(), THREADS * INCREMENTS);
+    }
+
+    #[test]
+    fn test_in_memory_concurrent_writes() {
+        let db = EfiVarDb::in_memory();
+        let name = |i| format!("Var{i}-8be4df61-93ca-11d2-aa0d-00e098032b8c");
+
+        std::thread::scope(|s| {
+            for i in 0..8u8 {
+                let (db, name) = (&db, &name);
+                s.spawn(move || {
+                    let var = db.get_var(name(i)).unwrap();
+                    for value in 0..=i {
+                        var.write(&[7, 0, 0, 0, value]).unwrap();
+                    }
+                });
+            }
+        });
+
+        let vars = db.list_vars().unwrap();
+        assert_eq!(vars.len(), 8);
+        for (i, entry) in (0..8u8).zip(&vars) {
+            assert_eq!(entry.name, name(i), "{i}th case failed");
+            let var = db.get_var(&entry.name).unwrap();
+            assert_eq!(var.read_data().unwrap().value, [i], "{i}th case failed");
+        }
+    }
+}
@@ -2,6 +2,7 @@
 
 #![allow(clippy::missing_errors_doc)]
 
+use serde::Serialize;
 use std::{
     fmt, io,
     path::{Path, PathBuf},
@@ -10,6 +11,8 @@ use std::{
 mod efivar;
 mod ioctl;
 pub mod program;
+mod switch;
+pub mod watch;
 
 pub mod test_utils;
 
@@ -19,7 +22,8 @@ use efivar::{
     ROOTFS_STATUS_UPD_IN_PROCESS, SLOT_A, SLOT_B,
 };
 
-pub use crate::efivar::{EfiVar, EfiVarDb};
+pub use crate::efivar::{EfiVar, EfiVarData, EfiVarDb, EfiVarDbErr, EfiVarEntry};
+pub use crate::switch::{RollbackError, SlotEfiVar, SlotSwitchGuard};
 
 /// Error definition for library.
 #[allow(missing_docs)]
@@ -49,12 +53,23 @@ pub enum Error {
     RemoveEfiVar { path: PathBuf, source: io::Error },
     #[error("failed reading efivar, invalid data length")]
     InvalidEfiVarLen,
+    #[error("efivar value of {len} bytes is not a valid {kind}")]
+    InvalidEfiVarValue { kind: &'static str, len: usize },
     #[error("invalid slot configuration")]
     InvalidSlotData,
     #[error("invalid rootfs status")]
     InvalidRootFsStatusData,
     #[error("invalid retry counter({counter}), exceeding the maximum ({max})")]
     ExceedingRetryCount { counter: u8, max: u8 },
+    #[error(
+        "failed switching to slot {slot}: {source}{}",
+        .rollback.as_ref().map(|e| format!(", {e}")).unwrap_or_default()
+    )]
+    SwitchSlot {
+        slot: Slot,
+        source: Box<Error>,
+        rollback: Option<RollbackError>,
+    },
 }
 
 #[allow(missing_docs)]
@@ -104,7 +119,8 @@ impl Error {
 }
 
 /// Representation of the slot.
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
 #[repr(u8)]
 pub enum Slot {
     /// The Slot A is represented as 0.
@@ -124,7 +140,7 @@ impl fmt::Display for Slot {
 }
 
 /// Representation of the rootfs status.
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize)]
 #[repr(u8)]
 pub enum RootFsStatus {
     /// Default status of the rootfs.
@@ -177,6 +193,45 @@ impl TryFrom<u8> for RootFsStatus {
     }
 }
 
+/// Snapshot of the complete slot and rootfs state.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize)]
+pub struct StatusReport {
+    /// The current active slot.
+    pub current_slot: Slot,
+    /// The slot set for the next boot.
+    pub next_boot_slot: Slot,
+    /// Rootfs state of slot A.
+    pub slot_a: SlotStatus,
+    /// Rootfs state of slot B.
+    pub slot_b: SlotStatus,
+    /// The maximum retry count before fallback.
+    pub max_retry_count: u8,
+}
+
+/// Rootfs state of a single slot.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize)]
+pub struct SlotStatus {
+    /// The rootfs status of the slot.
+    pub rootfs_status: RootFsStatus,
+    /// The remaining boot retries of the slot.
+    pub retry_count: u8,
+}
+
+impl fmt::Display for StatusReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "current slot: {}", self.current_slot)?;
+        writeln!(f, "next boot slot: {}", self.next_boot_slot)?;
+        for (slot, status) in [(Slot::A, self.slot_a), (Slot::B, self.slot_b)] {
+            writeln!(
+                f,
+                "slot {slot}: rootfs status {:?}, retry count {}/{}",
+                status.rootfs_status, status.retry_count, self.max_retry_count
+            )?;
+        }
+        Ok(())
+    }
+}
+
 pub struct OrbSlotCtrl {
     bootchain: BootChainEfiVars,
     rootfs: RootfsEfiVars,
@@ -190,6 +245,24 @@ impl OrbSlotCtrl {
         })
     }
 
+    /// Collect the complete slot and rootfs state into a [`StatusReport`].
+    pub fn status_report(&self) -> Result<StatusReport, Error> {
+        let slot_status = |slot| -> Result<SlotStatus, Error> {
+            Ok(SlotStatus {
+                rootfs_status: self.get_rootfs_status(slot)?,
+                retry_count: self.get_retry_count(slot)?,
+            })
+        };
+
+        Ok(StatusReport {
+            current_slot: self.get_current_slot()?,
+            next_boot_slot: self.get_next_boot_slot()?,
+            slot_a: slot_status(Slot::A)?,
+            slot_b: slot_status(Slot::B)?,
+            max_retry_count: self.get_max_retry_count()?,
+        })
+    }
+
     /// Get the current active slot.
     pub fn get_current_slot(&self) -> Result<Slot, Error> {
         match self.bootchain.get_current_boot_slot()? {
@@ -267,6 +340,12 @@ impl OrbSlotCtrl {
         self.rootfs.get_max_retry_count()
     }
 
+    /// Set the retry `count` for a certain `slot`. Fails if `count` exceeds the
+    /// maximum retry count.
+    pub fn set_retry_count(&self, count: u8, slot: Slot) -> Result<(), Error> {
+        self.rootfs.set_retry_count(count, slot as u8)
+    }
+
     /// Reset the retry counter to the maximum for the current active slot.
     pub fn reset_current_retry_count_to_max(&self) -> Result<(), Error> {
         let max_count = self.rootfs.get_max_retry_count()?;
@@ -1,7 +1,16 @@
-use crate::OrbSlotCtrl;
+use crate::{
+    watch::{StatusWatcher, Update},
+    OrbSlotCtrl, RootFsStatus, Slot,
+};
 use clap::{Parser, Subcommand};
 use orb_build_info::{make_build_info, BuildInfo};
-use std::{env, process::exit};
+use std::{
+    env,
+    process::exit,
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
 const BUILD_INFO: BuildInfo = make_build_info!();
 
@@ -28,13 +37,50 @@ enum Commands {
     /// Set slot for the next boot.
     #[command(name = "set", short_flag = 's')]
     SetNextSlot { slot: String },
-    /// Rootfs status controls.
+    /// Rootfs status controls. Prints a full status report if no subcommand is given.
     Status {
         /// Control the inactive slot instead of the active.
         #[arg(long = "inactive", short = 'i')]
         inactive: bool,
+        /// Print the full status report as JSON. Ignored if a subcommand is given.
+        #[arg(long = "json")]
+        json: bool,
         #[command(subcommand)]
-        subcmd: StatusCommands,
+        subcmd: Option<StatusCommands>,
+    },
+    /// Set the rootfs status of a slot, printing the previous and new status.
+    SetStatus {
+        /// The slot to write, either A/a/0 or B/b/1.
+        #[arg(long = "slot")]
+        slot: String,
+        /// The new rootfs status. See `status list` for the accepted aliases.
+        #[arg(long = "status")]
+        status: String,
+    },
+    /// Set the retry counter of a slot, printing the previous and new count.
+    SetRetry {
+        /// The slot to write, either A/a/0 or B/b/1.
+        #[arg(long = "slot")]
+        slot: String,
+        /// The new retry count. Must not exceed the maximum retry count.
+        #[arg(long = "count")]
+        count: u8,
+    },
+    /// Print the slot and rootfs state whenever it changes.
+    Watch {
+        /// Polling interval in milliseconds.
+        #[arg(long = "interval-ms", default_value_t = 1000)]
+        interval_ms: u64,
+        /// Print one JSON object per line.
+        #[arg(long = "json")]
+        json: bool,
+        /// Exit once the rootfs status of the watched slot becomes this status.
+        #[arg(long = "until-status")]
+        until_status: Option<String>,
+        /// Watch the status of the inactive slot for `--until-status`
+        /// instead of the active.
+        #[arg(long = "inactive", short = 'i')]
+        inactive: bool,
     },
     /// Get the git commit used for this build.
     #[command(name = "git", short_flag = 'g')]
@@ -63,7 +109,162 @@ enum StatusCommands {
     ListStatusVariants,
 }
 
-fn check_running_as_root(error: crate::Error) {
+/// Parses a slot or one of its aliases.
+fn parse_slot(slot: &str) -> Option<Slot> {
+    match slot.to_lowercase().as_str() {
+        // Slot A alias.
+        "a" | "0" => Some(Slot::A),
+        // Slot B alias.
+        "b" | "1" => Some(Slot::B),
+        _ => None,
+    }
+}
+
+/// Parses a rootfs status or one of its aliases.
+fn parse_rootfs_status(status: &str) -> Option<RootFsStatus> {
+    match status.to_lowercase().as_str() {
+        // Status Normal alias.
+        "normal" | "0" => Some(RootFsStatus::Normal),
+        // Status UpdateInProcess alias.
+        "updateinprocess" | "updinprocess" | "upd-in-process" | "1" => {
+            Some(RootFsStatus::UpdateInProcess)
+        }
+        // Status UpdateDone alias.
+        "updatedone" | "upddone" | "upd-done" | "2" => Some(RootFsStatus::UpdateDone),
+        // Status Unbootable alias.
+        "unbootable" | "3" => Some(RootFsStatus::Unbootable),
+        _ => None,
+    }
+}
+
+fn exit_invalid_status() -> ! {
+    exit_set_error(SetError::InvalidStatus)
+}
+
+/// Reasons for `set-status` and `set-retry` to fail.
+#[derive(thiserror::Error, Debug)]
+enum SetError {
+    #[error("Invalid slot provided, please use either A/a/0 or B/b/1.")]
+    InvalidSlot,
+    #[error(
+        "Invalid status provided. For a full list of available rootfs status run:\n\
+         slot-ctrl status --list"
+    )]
+    InvalidStatus,
+    #[error("Invalid retry count {count}, the maximum retry count is {max}.")]
+    ExceedingRetryCount { count: u8, max: u8 },
+    #[error(transparent)]
+    EfiVar(#[from] crate::Error),
+}
+
+/// Sets the rootfs status of `slot`, returning the previous and new status.
+///
+/// Nothing is written if `slot` or `status` is invalid.
+fn set_status(
+    orb_slot_ctrl: &OrbSlotCtrl,
+    slot: &str,
+    status: &str,
+) -> Result<(RootFsStatus, RootFsStatus), SetError> {
+    let slot = parse_slot(slot).ok_or(SetError::InvalidSlot)?;
+    let status = parse_rootfs_status(status).ok_or(SetError::InvalidStatus)?;
+    let previous = orb_slot_ctrl.get_rootfs_status(slot)?;
+    orb_slot_ctrl.set_rootfs_status(status, slot)?;
+    Ok((previous, status))
+}
+
+/// Sets the retry count of `slot`, returning the previous and new count.
+///
+/// Nothing is written if `slot` is invalid or `count` exceeds the maximum retry
+/// count.
+fn set_retry(
+    orb_slot_ctrl: &OrbSlotCtrl,
+    slot: &str,
+    count: u8,
+) -> Result<(u8, u8), SetError> {
+    let slot = parse_slot(slot).ok_or(SetError::InvalidSlot)?;
+    let max = orb_slot_ctrl.get_max_retry_count()?;
+    if count > max {
+        return Err(SetError::ExceedingRetryCount { count, max });
+    }
+    let previous = orb_slot_ctrl.get_retry_count(slot)?;
+    orb_slot_ctrl.set_retry_count(count, slot)?;
+    Ok((previous, count))
+}
+
+/// Exits with the error of a rejected `set-status` or `set-retry`.
+fn exit_set_error(error: SetError) -> ! {
+    match error {
+        SetError::EfiVar(e) => check_running_as_root(e),
+        error => {
+            println!("{error}");
+            exit(1)
+        }
+    }
+}
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_sigint(_: libc::c_int) {
+    INTERRUPTED.store(true, Ordering::Relaxed);
+}
+
+/// Polls the slot and rootfs state every `interval` until interrupted or until
+/// `until` returns `true` for an update.
+fn watch(
+    orb_slot_ctrl: &OrbSlotCtrl,
+    interval: Duration,
+    json: bool,
+    until: impl Fn(&Update) -> bool,
+) -> eyre::Result<()> {
+    // Stop polling on Ctrl-C instead of being killed mid-line.
+    // SAFETY: `on_sigint` only does a relaxed store to an `AtomicBool`, which is
+    // lock-free and therefore async-signal-safe. It has the `extern "C"` signature
+    // `signal` expects.
+    unsafe { libc::signal(libc::SIGINT, on_sigint as libc::sighandler_t) };
+    let mut watcher = StatusWatcher::new(orb_slot_ctrl);
+    while !INTERRUPTED.load(Ordering::Relaxed) {
+        let polled_at = Instant::now();
+        if let Some(update) = watcher.poll()? {
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs_f64();
+            if json {
+                let line = serde_json::json!({
+                    "timestamp": timestamp,
+                    "report": update.report,
+                    "changes": update.changes,
+                });
+                println!("{line}");
+            } else if update.changes.is_empty() {
+                let report = update.report.to_string();
+                println!("[{timestamp:.3}] {}", report.trim_end().replace('\n', ", "));
+            } else {
+                let changes = update
+                    .changes
+                    .iter()
+                    .map(|change| {
+                        format!("{}: {} -> {}", change.field, change.old, change.new)
+                    })
+                    .collect::<Vec<_>>();
+                println!("[{timestamp:.3}] {}", changes.join(", "));
+            }
+            if until(&update) {
+                break;
+            }
+        }
+        // Sleep in short steps to react to Ctrl-C quickly.
+        while !INTERRUPTED.load(Ordering::Relaxed) {
+            let Some(remaining) = interval.checked_sub(polled_at.elapsed()) else {
+                break;
+            };
+            thread::sleep(remaining.min(Duration::from_millis(100)));
+        }
+    }
+    Ok(())
+}
+
+fn check_running_as_root(error: crate::Error) -> ! {
     let uid = rustix::process::getuid();
     let euid = rustix::process::geteuid();
     if !(uid.is_root() && euid.is_root()) {
@@ -82,104 +283,125 @@ pub fn run(orb_slot_ctrl: &OrbSlotCtrl, cli: Cli) -> eyre::Result<()> {
             println!("{}", orb_slot_ctrl.get_next_boot_slot()?);
         }
         Commands::SetNextSlot { slot } => {
-            let slot = match slot.to_lowercase().as_str() {
-                // Slot A alias.
-                "a" | "0" => crate::Slot::A,
-                // Slot B alias.
-                "b" | "1" => crate::Slot::B,
-                _ => {
-                    println!(
-                        "Invalid slot provided, please use either A/a/0 or B/b/1."
-                    );
-                    exit(1)
-                }
+            let Some(slot) = parse_slot(&slot) else {
+                exit_set_error(SetError::InvalidSlot)
             };
             if let Err(e) = orb_slot_ctrl.set_next_boot_slot(slot) {
                 check_running_as_root(e);
             };
         }
-        Commands::Status { inactive, subcmd } => {
-            match subcmd {
-                StatusCommands::GetRootfsStatus => {
-                    if inactive {
-                        println!(
-                            "{:?}",
-                            orb_slot_ctrl.get_rootfs_status(
-                                orb_slot_ctrl.get_inactive_slot()?
-                            )?
-                        );
-                    } else {
-                        println!("{:?}", orb_slot_ctrl.get_current_rootfs_status()?);
-                    }
+        Commands::Status {
+            inactive: _,
+            json,
+            subcmd: None,
+        } => {
+            let report = orb_slot_ctrl.status_report()?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                print!("{report}");
+            }
+        }
+        Commands::Status {
+            inactive,
+            json: _,
+            subcmd: Some(subcmd),
+        } => match subcmd {
+            StatusCommands::GetRootfsStatus => {
+                if inactive {
+                    println!(
+                        "{:?}",
+                        orb_slot_ctrl
+                            .get_rootfs_status(orb_slot_ctrl.get_inactive_slot()?)?
+                    );
+                } else {
+                    println!("{:?}", orb_slot_ctrl.get_current_rootfs_status()?);
                 }
-                StatusCommands::SetRootfsStatus { status } => {
-                    let status = match status.to_lowercase().as_str() {
-                        // Status Normal alias.
-                        "normal" | "0" => crate::RootFsStatus::Normal,
-                        // Status UpdateInProcess alias.
-                        "updateinprocess" | "updinprocess" | "1" => {
-                            crate::RootFsStatus::UpdateInProcess
-                        }
-                        // Status UpdateDone alias.
-                        "updatedone" | "upddone" | "2" => {
-                            crate::RootFsStatus::UpdateDone
-                        }
-                        // Status Unbootable alias.
-                        "unbootable" | "3" => crate::RootFsStatus::Unbootable,
-                        _ => {
-                            println!("Invalid status provided. For a full list of available rootfs status run:");
-                            println!("slot-ctrl status --list");
-                            exit(1)
-                        }
-                    };
-                    if inactive {
-                        if let Err(e) = orb_slot_ctrl.set_rootfs_status(
-                            status,
-                            orb_slot_ctrl.get_inactive_slot()?,
-                        ) {
-                            check_running_as_root(e);
-                        }
-                    } else if let Err(e) =
-                        orb_slot_ctrl.set_current_rootfs_status(status)
+            }
+            StatusCommands::SetRootfsStatus { status } => {
+                let Some(status) = parse_rootfs_status(&status) else {
+                    exit_invalid_status()
+                };
+                if inactive {
+                    if let Err(e) = orb_slot_ctrl
+                        .set_rootfs_status(status, orb_slot_ctrl.get_inactive_slot()?)
                     {
                         check_running_as_root(e);
                     }
+                } else if let Err(e) = orb_slot_ctrl.set_current_rootfs_status(status) {
+                    check_running_as_root(e);
                 }
-                StatusCommands::GetRetryCounter => {
-                    if inactive {
-                        println!(
-                            "{}",
-                            orb_slot_ctrl
-                                .get_retry_count(orb_slot_ctrl.get_inactive_slot()?)?
-                        );
-                    } else {
-                        println!("{}", orb_slot_ctrl.get_current_retry_count()?);
-                    }
-                }
-                StatusCommands::GetMaxRetryCounter => {
-                    println!("{}", orb_slot_ctrl.get_max_retry_count()?);
+            }
+            StatusCommands::GetRetryCounter => {
+                if inactive {
+                    println!(
+                        "{}",
+                        orb_slot_ctrl
+                            .get_retry_count(orb_slot_ctrl.get_inactive_slot()?)?
+                    );
+                } else {
+                    println!("{}", orb_slot_ctrl.get_current_retry_count()?);
                 }
-                StatusCommands::ResetRetryCounter => {
-                    if inactive {
-                        if let Err(e) = orb_slot_ctrl.reset_retry_count_to_max(
-                            orb_slot_ctrl.get_inactive_slot()?,
-                        ) {
-                            check_running_as_root(e);
-                        }
-                    } else if let Err(e) =
-                        orb_slot_ctrl.reset_current_retry_count_to_max()
+            }
+            StatusCommands::GetMaxRetryCounter => {
+                println!("{}", orb_slot_ctrl.get_max_retry_count()?);
+            }
+            StatusCommands::ResetRetryCounter => {
+                if inactive {
+                    if let Err(e) = orb_slot_ctrl
+                        .reset_retry_count_to_max(orb_slot_ctrl.get_inactive_slot()?)
                     {
                         check_running_as_root(e);
                     }
-                }
-                StatusCommands::ListStatusVariants => {
-                    println!("Available Rootfs status variants with their aliases):");
-                    println!("  Normal (normal, 0)");
-                    println!("  UpdateInProcess (updateinprocess, updinprocess, 1)");
-                    println!("  UpdateDone (updatedone, upddone, 2)");
-                    println!("  Unbootable (unbootable, 3)");
+                } else if let Err(e) = orb_slot_ctrl.reset_current_retry_count_to_max()
+                {
+                    check_running_as_root(e);
                 }
             }
+            StatusCommands::ListStatusVariants => {
+                println!("Available Rootfs status variants with their aliases):");
+                println!("  Normal (normal, 0)");
+                println!("  UpdateInProcess (updateinprocess, updinprocess, upd-in-process, 1)");
+                println!("  UpdateDone (updatedone, upddone, upd-done, 2)");
+                println!("  Unbootable (unbootable, 3)");
+            }
+        },
+        Commands::SetStatus { slot, status } => {
+            let (previous, status) = set_status(orb_slot_ctrl, &slot, &status)
+                .unwrap_or_else(|e| exit_set_error(e));
+            println!("{previous:?} -> {status:?}");
+        }
+        Commands::SetRetry { slot, count } => {
+            let (previous, count) = set_retry(orb_slot_ctrl, &slot, count)
+                .unwrap_or_else(|e| exit_set_error(e));
+            println!("{previous} -> {count}");
+        }
+        Commands::Watch {
+            interval_ms,
+            json,
+            until_status,
+            inactive,
+        } => {
+            let until_status = until_status.map(|status| {
+                parse_rootfs_status(&status).unwrap_or_else(|| exit_invalid_status())
+            });
+            let slot = if inactive {
+                orb_slot_ctrl.get_inactive_slot()?
+            } else {
+                orb_slot_ctrl.get_current_slot()?
+            };
+            watch(
+                orb_slot_ctrl,
+                Duration::from_millis(interval_ms),
+                json,
+                |update| {
+                    let slot_status = match slot {
+                        Slot::A => update.report.slot_a,
+                        Slot::B => update.report.slot_b,
+                    };
+                    until_status == Some(slot_status.rootfs_status)
+                },
+            )?;
         }
         Commands::GitDescribe => {
             println!("{}", BUILD_INFO.git.describe);
@@ -188,3 +410,114 @@ pub fn run(orb_slot_ctrl: &OrbSlotCtrl, cli: Cli) -> eyre::Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{FakeOrb, FakeVar};
+
+    #[test]
+    fn test_set_status() {
+        let fx = FakeOrb::builder()
+            .rootfs_status(Slot::B, RootFsStatus::UpdateInProcess)
+            .build();
+        let cases = [
+            (
+                "b",
+                "upd-done",
+                RootFsStatus::UpdateInProcess,
+                RootFsStatus::UpdateDone,
+            ),
+            (
+                "B",
+                "unbootable",
+                RootFsStatus::UpdateDone,
+                RootFsStatus::Unbootable,
+            ),
+            ("1", "0", RootFsStatus::Unbootable, RootFsStatus::Normal),
+            (
+                "a",
+                "upd-in-process",
+                RootFsStatus::Normal,
+                RootFsStatus::UpdateInProcess,
+            ),
+        ];
+        for (i, (slot, status, previous, new)) in cases.into_iter().enumerate() {
+            let result = set_status(&fx.slot_ctrl, slot, status).unwrap();
+            assert_eq!(result, (previous, new), "{i}th case failed");
+            let slot = parse_slot(slot).unwrap();
+            assert_eq!(
+                fx.slot_ctrl.get_rootfs_status(slot).unwrap(),
+                new,
+                "{i}th case failed"
+            );
+        }
+    }
+
+    #[test]
+    fn test_set_retry() {
+        let fx = FakeOrb::builder()
+            .max_retry_count(3)
+            .retry_count(Slot::A, 1)
+            .build();
+        let cases = [("a", 3, 1), ("0", 0, 3), ("b", 2, 0)];
+        for (i, (slot, count, previous)) in cases.into_iter().enumerate() {
+            let result = set_retry(&fx.slot_ctrl, slot, count).unwrap();
+            assert_eq!(result, (previous, count), "{i}th case failed");
+            let slot = parse_slot(slot).unwrap();
+            assert_eq!(
+                fx.slot_ctrl.get_retry_count(slot).unwrap(),
+                count,
+                "{i}th case failed"
+            );
+        }
+    }
+
+    #[test]
+    fn test_set_rejections() {
+        let fx = FakeOrb::builder()
+            .max_retry_count(3)
+            .retry_count(Slot::A, 2)
+            .build();
+        let cases = [
+            (
+                set_status(&fx.slot_ctrl, "c", "normal").map(drop),
+                "Invalid slot provided, please use either A/a/0 or B/b/1.",
+            ),
+            (
+                set_status(&fx.slot_ctrl, "a", "broken").map(drop),
+                "Invalid status provided. For a full list of available rootfs \
+                 status run:\nslot-ctrl status --list",
+            ),
+            (
+                set_retry(&fx.slot_ctrl, "2", 1).map(drop),
+                "Invalid slot provided, please use either A/a/0 or B/b/1.",
+            ),
+            (
+                set_retry(&fx.slot_ctrl, "a", 4).map(drop),
+                "Invalid retry count 4, the maximum retry count is 3.",
+            ),
+        ];
+        for (i, (result, message)) in cases.into_iter().enumerate() {
+            assert_eq!(
+                result.unwrap_err().to_string(),
+                message,
+                "{i}th case failed"
+            );
+        }
+        // None of the rejected requests touched the efivars.
+        assert_eq!(
+            fx.slot_ctrl.get_rootfs_status(Slot::A).unwrap(),
+            RootFsStatus::Normal
+        );
+        assert_eq!(fx.slot_ctrl.get_retry_count(Slot::A).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_set_status_fails_on_missing_efivar() {
+        let fx = FakeOrb::builder().build();
+        fx.var(FakeVar::RootfsStatus(Slot::B)).remove().unwrap();
+        let result = set_status(&fx.slot_ctrl, "b", "normal");
+        assert!(matches!(result, Err(SetError::EfiVar(_))));
+    }
+}
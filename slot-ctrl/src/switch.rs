@@ -0,0 +1,177 @@
+//! Switching the boot slot as a single operation that can be rolled back.
+
+use std::fmt;
+
+use crate::{Error, OrbSlotCtrl, RootFsStatus, Slot};
+
+/// An efivar that is written while switching slots.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlotEfiVar {
+    /// `BootChainFwNext`.
+    NextBootSlot,
+    /// `RootfsStatusSlot{A,B}`.
+    RootfsStatus(Slot),
+    /// `RootfsRetryCount{A,B}`.
+    RetryCount(Slot),
+}
+
+impl fmt::Display for SlotEfiVar {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SlotEfiVar::NextBootSlot => write!(f, "next boot slot"),
+            SlotEfiVar::RootfsStatus(slot) => write!(f, "rootfs status of slot {slot}"),
+            SlotEfiVar::RetryCount(slot) => write!(f, "retry count of slot {slot}"),
+        }
+    }
+}
+
+/// The efivars that could not be restored while rolling back a slot switch.
+#[derive(Debug)]
+pub struct RollbackError {
+    pub failures: Vec<(SlotEfiVar, Error)>,
+}
+
+impl fmt::Display for RollbackError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "failed restoring efivars:")?;
+        for (var, err) in &self.failures {
+            write!(f, " [{var}: {err}]")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for RollbackError {}
+
+/// Values of the efivars before a slot switch.
+#[derive(Debug, Clone, Copy)]
+struct Snapshot {
+    next_boot_slot: Slot,
+    rootfs_status: RootFsStatus,
+    retry_count: u8,
+}
+
+/// An uncommitted slot switch, returned by [`OrbSlotCtrl::switch_to_slot`].
+///
+/// Dropping the guard without calling [`SlotSwitchGuard::commit`] restores the
+/// previous next boot slot, rootfs status and retry count. Dropping can't report
+/// efivars that failed to be restored, call [`SlotSwitchGuard::rollback`] to get
+/// them.
+#[must_use = "the slot switch is rolled back unless `commit` is called"]
+pub struct SlotSwitchGuard<'a> {
+    slot_ctrl: &'a OrbSlotCtrl,
+    slot: Slot,
+    previous: Snapshot,
+    written: Vec<SlotEfiVar>,
+    done: bool,
+}
+
+impl SlotSwitchGuard<'_> {
+    /// The slot that was switched to.
+    pub fn slot(&self) -> Slot {
+        self.slot
+    }
+
+    /// Keeps the slot switch.
+    pub fn commit(mut self) {
+        self.done = true;
+    }
+
+    /// Restores the previous efivars, reporting every efivar that could not be
+    /// restored.
+    pub fn rollback(mut self) -> Result<(), RollbackError> {
+        self.done = true;
+        self.restore()
+    }
+
+    /// Restores the written efivars in reverse order, so that the next boot slot is
+    /// reverted first. Keeps going on failure to restore as much as possible.
+    fn restore(&self) -> Result<(), RollbackError> {
+        let bootchain = &self.slot_ctrl.bootchain;
+        let rootfs = &self.slot_ctrl.rootfs;
+        let failures: Vec<(SlotEfiVar, Error)> =
+            self.written
+                .iter()
+                .rev()
+                .filter_map(|&var| {
+                    let result = match var {
+                        SlotEfiVar::NextBootSlot => bootchain
+                            .set_next_boot_slot(self.previous.next_boot_slot as u8),
+                        SlotEfiVar::RootfsStatus(slot) => rootfs.set_rootfs_status(
+                            self.previous.rootfs_status as u8,
+                            slot as u8,
+                        ),
+                        SlotEfiVar::RetryCount(slot) => rootfs
+                            .set_retry_count(self.previous.retry_count, slot as u8),
+                    };
+                    result.err().map(|err| (var, err))
+                })
+                .collect();
+
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(RollbackError { failures })
+        }
+    }
+}
+
+impl Drop for SlotSwitchGuard<'_> {
+    fn drop(&mut self) {
+        if !self.done {
+            // Best effort, `rollback` is the way to learn about failures.
+            let _ = self.restore();
+        }
+    }
+}
+
+impl OrbSlotCtrl {
+    /// Switch to `slot` for the next boot.
+    ///
+    /// Marks the rootfs of `slot` as [`RootFsStatus::UpdateDone`], resets its retry
+    /// count to the maximum and then sets it as the next boot slot. The switch only
+    /// sticks once [`SlotSwitchGuard::commit`] is called.
+    ///
+    /// If one of the writes fails, the ones already done are rolled back before
+    /// returning [`Error::SwitchSlot`].
+    pub fn switch_to_slot(&self, slot: Slot) -> Result<SlotSwitchGuard<'_>, Error> {
+        let previous = Snapshot {
+            next_boot_slot: self.get_next_boot_slot()?,
+            rootfs_status: self.get_rootfs_status(slot)?,
+            retry_count: self.get_retry_count(slot)?,
+        };
+        let mut guard = SlotSwitchGuard {
+            slot_ctrl: self,
+            slot,
+            previous,
+            written: Vec::with_capacity(3),
+            done: false,
+        };
+
+        let writes: [(SlotEfiVar, &dyn Fn() -> Result<(), Error>); 3] = [
+            (SlotEfiVar::RootfsStatus(slot), &|| {
+                self.set_rootfs_status(RootFsStatus::UpdateDone, slot)
+            }),
+            (SlotEfiVar::RetryCount(slot), &|| {
+                self.reset_retry_count_to_max(slot)
+            }),
+            (SlotEfiVar::NextBootSlot, &|| {
+                self.bootchain.set_next_boot_slot(slot as u8)
+            }),
+        ];
+        for (var, write) in writes {
+            // Record the var before writing, a failed write may still have
+            // partially modified it.
+            guard.written.push(var);
+            if let Err(err) = write() {
+                return Err(Error::SwitchSlot {
+                    slot,
+                    source: Box::new(err),
+                    rollback: guard.rollback().err(),
+                });
+            }
+        }
+
+        Ok(guard)
+    }
+}
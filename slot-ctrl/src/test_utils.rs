@@ -1,88 +1,157 @@
-use std::fs;
-
 use crate::{
     efivar::{bootchain::BootChainEfiVars, rootfs::RootfsEfiVars},
-    EfiVarDb, OrbSlotCtrl, Slot,
+    EfiVar, EfiVarDb, OrbSlotCtrl, RootFsStatus, Slot,
 };
-use tempfile::TempDir;
 
-/// A Fixture that initializes fake EfiVars.
-/// Both Rootfs slots are normal by default.
-pub struct Fixture {
-    _tempdir: TempDir,
+/// An efivar written by [`FakeOrb`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FakeVar {
+    CurrentSlot,
+    NextSlot,
+    RootfsStatus(Slot),
+    RetryCount(Slot),
+    RetryCountMax,
+}
+
+/// A fake Orb with a pre-populated, in-memory efivar db.
+///
+/// Use [`FakeOrb::var`] or [`FakeOrb::write_var`] to change the efivars behind the
+/// back of `slot_ctrl` mid-test.
+pub struct FakeOrb {
     pub db: EfiVarDb,
     pub slot_ctrl: OrbSlotCtrl,
-    pub bootchain: BootChainEfiVars,
-    pub rootfs: RootfsEfiVars,
+    bootchain: BootChainEfiVars,
+    rootfs: RootfsEfiVars,
 }
 
-impl Fixture {
-    pub fn new(current_and_next_slot: Slot, max_retry_count: u8) -> Self {
-        let tempdir = TempDir::new_in("/tmp").unwrap();
-        let db_path = tempdir.path().join("sys/firmware/efi/efivars/");
-        fs::create_dir_all(&db_path).unwrap();
-
-        let db = EfiVarDb::from_rootfs(&tempdir).unwrap();
-        let bootchain = BootChainEfiVars::new(&db).unwrap();
-        let rootfs = RootfsEfiVars::new(&db).unwrap();
-        let slot_ctrl = OrbSlotCtrl::new(&db).unwrap();
-
-        let slot = match current_and_next_slot {
-            Slot::A => 0x00,
-            Slot::B => 0x01,
-        };
+/// Builder for [`FakeOrb`].
+///
+/// By default the Orb boots from slot A, both slots are `Normal` with a retry
+/// count of 0, and the max retry count is 3.
+#[derive(Debug, Clone)]
+pub struct FakeOrbBuilder {
+    current_slot: Slot,
+    next_slot: Option<Slot>,
+    max_retry_count: u8,
+    status_a: RootFsStatus,
+    status_b: RootFsStatus,
+    retry_count_a: u8,
+    retry_count_b: u8,
+}
 
-        bootchain
-            .current
-            .create_and_write(&[0x07, 0x00, 0x00, 0x00, slot, 0x00, 0x00, 0x00])
-            .unwrap();
+impl Default for FakeOrbBuilder {
+    fn default() -> Self {
+        Self {
+            current_slot: Slot::A,
+            next_slot: None,
+            max_retry_count: 3,
+            status_a: RootFsStatus::Normal,
+            status_b: RootFsStatus::Normal,
+            retry_count_a: 0,
+            retry_count_b: 0,
+        }
+    }
+}
 
-        // Initialize next boot slot to assumed default value from Efi
-        bootchain
-            .next
-            .create_and_write(&[0x07, 0x00, 0x00, 0x00, slot, 0x00, 0x00, 0x00])
-            .unwrap();
+impl FakeOrbBuilder {
+    /// Sets the slot the Orb is currently booted from.
+    #[must_use]
+    pub fn current_slot(mut self, slot: Slot) -> Self {
+        self.current_slot = slot;
+        self
+    }
 
-        rootfs
-            .retry_count_a
-            .create_and_write(&[0x07, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00])
-            .unwrap();
+    /// Sets the next boot slot. Defaults to the current slot.
+    #[must_use]
+    pub fn next_slot(mut self, slot: Slot) -> Self {
+        self.next_slot = Some(slot);
+        self
+    }
 
-        rootfs
-            .retry_count_b
-            .create_and_write(&[0x07, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00])
-            .unwrap();
+    /// Sets the max retry count shared by both slots.
+    #[must_use]
+    pub fn max_retry_count(mut self, count: u8) -> Self {
+        self.max_retry_count = count;
+        self
+    }
 
-        rootfs
-            .retry_count_max
-            .create_and_write(&[
-                0x07,
-                0x00,
-                0x00,
-                0x00,
-                max_retry_count,
-                0x00,
-                0x00,
-                0x00,
-            ])
-            .unwrap();
+    /// Sets the rootfs status of `slot`.
+    #[must_use]
+    pub fn rootfs_status(mut self, slot: Slot, status: RootFsStatus) -> Self {
+        match slot {
+            Slot::A => self.status_a = status,
+            Slot::B => self.status_b = status,
+        }
+        self
+    }
 
-        rootfs
-            .status_a
-            .create_and_write(&[0x07, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00])
-            .unwrap();
+    /// Sets the retry count of `slot`.
+    #[must_use]
+    pub fn retry_count(mut self, slot: Slot, count: u8) -> Self {
+        match slot {
+            Slot::A => self.retry_count_a = count,
+            Slot::B => self.retry_count_b = count,
+        }
+        self
+    }
 
-        rootfs
-            .status_b
-            .create_and_write(&[0x07, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00])
-            .unwrap();
+    pub fn build(self) -> FakeOrb {
+        let db = EfiVarDb::in_memory();
+        let bootchain = BootChainEfiVars::new(&db).unwrap();
+        let rootfs = RootfsEfiVars::new(&db).unwrap();
+        let slot_ctrl = OrbSlotCtrl::new(&db).unwrap();
 
-        Self {
-            _tempdir: tempdir,
+        let orb = FakeOrb {
             db,
             slot_ctrl,
             bootchain,
             rootfs,
+        };
+
+        let slot_value = |slot| match slot {
+            Slot::A => 0x00,
+            Slot::B => 0x01,
+        };
+        let next_slot = self.next_slot.unwrap_or(self.current_slot);
+        let values = [
+            (FakeVar::CurrentSlot, slot_value(self.current_slot)),
+            (FakeVar::NextSlot, slot_value(next_slot)),
+            (FakeVar::RetryCount(Slot::A), self.retry_count_a),
+            (FakeVar::RetryCount(Slot::B), self.retry_count_b),
+            (FakeVar::RetryCountMax, self.max_retry_count),
+            (FakeVar::RootfsStatus(Slot::A), self.status_a as u8),
+            (FakeVar::RootfsStatus(Slot::B), self.status_b as u8),
+        ];
+        for (var, value) in values {
+            orb.write_var(var, value);
+        }
+
+        orb
+    }
+}
+
+impl FakeOrb {
+    pub fn builder() -> FakeOrbBuilder {
+        FakeOrbBuilder::default()
+    }
+
+    /// Handle to the efivar backing `var`, e.g. to remove it mid-test.
+    pub fn var(&self, var: FakeVar) -> &EfiVar {
+        match var {
+            FakeVar::CurrentSlot => &self.bootchain.current,
+            FakeVar::NextSlot => &self.bootchain.next,
+            FakeVar::RootfsStatus(Slot::A) => &self.rootfs.status_a,
+            FakeVar::RootfsStatus(Slot::B) => &self.rootfs.status_b,
+            FakeVar::RetryCount(Slot::A) => &self.rootfs.retry_count_a,
+            FakeVar::RetryCount(Slot::B) => &self.rootfs.retry_count_b,
+            FakeVar::RetryCountMax => &self.rootfs.retry_count_max,
         }
     }
+
+    /// Overwrites `var` with `value`, creating it if it was removed.
+    pub fn write_var(&self, var: FakeVar, value: u8) {
+        self.var(var)
+            .create_and_write(&[0x07, 0x00, 0x00, 0x00, value, 0x00, 0x00, 0x00])
+            .unwrap();
+    }
 }
@@ -0,0 +1,82 @@
+//! Change detection for the `watch` subcommand.
+
+use crate::{Error, OrbSlotCtrl, StatusReport};
+use serde::Serialize;
+
+/// A value of the [`StatusReport`] that differs between two polls.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize)]
+pub struct Change {
+    /// Name of the changed value, e.g. `slot_b.rootfs_status`.
+    pub field: &'static str,
+    /// Previous value.
+    pub old: String,
+    /// New value.
+    pub new: String,
+}
+
+/// Result of a [`StatusWatcher::poll`] that observed something new.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize)]
+pub struct Update {
+    /// The state after the change.
+    pub report: StatusReport,
+    /// Values that changed since the previous update. Empty for the first one.
+    pub changes: Vec<Change>,
+}
+
+/// Lists the values that differ between `old` and `new`.
+#[must_use]
+pub fn diff(old: &StatusReport, new: &StatusReport) -> Vec<Change> {
+    let fields = |report: &StatusReport| {
+        [
+            ("current_slot", report.current_slot.to_string()),
+            ("next_boot_slot", report.next_boot_slot.to_string()),
+            (
+                "slot_a.rootfs_status",
+                format!("{:?}", report.slot_a.rootfs_status),
+            ),
+            ("slot_a.retry_count", report.slot_a.retry_count.to_string()),
+            (
+                "slot_b.rootfs_status",
+                format!("{:?}", report.slot_b.rootfs_status),
+            ),
+            ("slot_b.retry_count", report.slot_b.retry_count.to_string()),
+            ("max_retry_count", report.max_retry_count.to_string()),
+        ]
+    };
+    fields(old)
+        .into_iter()
+        .zip(fields(new))
+        .filter(|((_, old), (_, new))| old != new)
+        .map(|((field, old), (_, new))| Change { field, old, new })
+        .collect()
+}
+
+/// Polls the slot and rootfs state and reports it when it changes.
+pub struct StatusWatcher<'a> {
+    slot_ctrl: &'a OrbSlotCtrl,
+    last: Option<StatusReport>,
+}
+
+impl<'a> StatusWatcher<'a> {
+    /// Creates a watcher. Its first poll always reports the current state.
+    #[must_use]
+    pub fn new(slot_ctrl: &'a OrbSlotCtrl) -> Self {
+        Self {
+            slot_ctrl,
+            last: None,
+        }
+    }
+
+    /// Reads the state, returning `None` if nothing changed since the previous
+    /// update.
+    pub fn poll(&mut self) -> Result<Option<Update>, Error> {
+        let report = self.slot_ctrl.status_report()?;
+        let changes = match &self.last {
+            Some(last) if *last == report => return Ok(None),
+            Some(last) => diff(last, &report),
+            None => Vec::new(),
+        };
+        self.last = Some(report.clone());
+        Ok(Some(Update { report, changes }))
+    }
+}
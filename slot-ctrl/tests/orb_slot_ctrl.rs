@@ -1,23 +1,33 @@
-use orb_slot_ctrl::test_utils::Fixture;
-use orb_slot_ctrl::{RootFsStatus, Slot};
+use orb_slot_ctrl::test_utils::{FakeOrb, FakeVar};
+use orb_slot_ctrl::watch::{self, Change, StatusWatcher};
+use orb_slot_ctrl::{RootFsStatus, Slot, SlotEfiVar, SlotStatus, StatusReport};
 
 #[test]
 fn it_gets_current_slot() {
-    let fx = Fixture::new(Slot::A, 5);
+    let fx = FakeOrb::builder()
+        .current_slot(Slot::A)
+        .max_retry_count(5)
+        .build();
     let slot = fx.slot_ctrl.get_current_slot().unwrap();
     assert_eq!(slot, Slot::A)
 }
 
 #[test]
 fn it_gets_inactive_slot() {
-    let fx = Fixture::new(Slot::B, 5);
+    let fx = FakeOrb::builder()
+        .current_slot(Slot::B)
+        .max_retry_count(5)
+        .build();
     let slot = fx.slot_ctrl.get_inactive_slot().unwrap();
     assert_eq!(slot, Slot::A)
 }
 
 #[test]
 fn it_gets_and_sets_next_boot_slot() {
-    let fx = Fixture::new(Slot::B, 5);
+    let fx = FakeOrb::builder()
+        .current_slot(Slot::B)
+        .max_retry_count(5)
+        .build();
     let slot = fx.slot_ctrl.get_next_boot_slot().unwrap();
     assert_eq!(slot, Slot::B);
 
@@ -28,7 +38,10 @@ fn it_gets_and_sets_next_boot_slot() {
 
 #[test]
 fn it_gets_and_sets_current_rootfs_status() {
-    let fx = Fixture::new(Slot::A, 5);
+    let fx = FakeOrb::builder()
+        .current_slot(Slot::A)
+        .max_retry_count(5)
+        .build();
     let status = fx.slot_ctrl.get_current_rootfs_status().unwrap();
     assert_eq!(status, RootFsStatus::Normal);
 
@@ -42,7 +55,10 @@ fn it_gets_and_sets_current_rootfs_status() {
 
 #[test]
 fn it_gets_and_sets_current_rootfs_status_on_specific_slot() {
-    let fx = Fixture::new(Slot::A, 5);
+    let fx = FakeOrb::builder()
+        .current_slot(Slot::A)
+        .max_retry_count(5)
+        .build();
     let status = fx.slot_ctrl.get_rootfs_status(Slot::B).unwrap();
     assert_eq!(status, RootFsStatus::Normal);
 
@@ -56,7 +72,10 @@ fn it_gets_and_sets_current_rootfs_status_on_specific_slot() {
 
 #[test]
 fn it_gets_and_resets_current_retry_count_to_max() {
-    let fx = Fixture::new(Slot::A, 5);
+    let fx = FakeOrb::builder()
+        .current_slot(Slot::A)
+        .max_retry_count(5)
+        .build();
     let count = fx.slot_ctrl.get_current_retry_count().unwrap();
     assert_eq!(count, 0);
 
@@ -67,7 +86,10 @@ fn it_gets_and_resets_current_retry_count_to_max() {
 
 #[test]
 fn it_gets_and_resets_current_retry_count_to_max_on_specific_slot() {
-    let fx = Fixture::new(Slot::A, 5);
+    let fx = FakeOrb::builder()
+        .current_slot(Slot::A)
+        .max_retry_count(5)
+        .build();
     let count = fx.slot_ctrl.get_retry_count(Slot::B).unwrap();
     assert_eq!(count, 0);
 
@@ -75,3 +97,195 @@ fn it_gets_and_resets_current_retry_count_to_max_on_specific_slot() {
     let count = fx.slot_ctrl.get_retry_count(Slot::B).unwrap();
     assert_eq!(count, 5);
 }
+
+#[test]
+fn it_reports_full_status() {
+    let fx = FakeOrb::builder()
+        .current_slot(Slot::B)
+        .max_retry_count(5)
+        .build();
+    fx.slot_ctrl.set_next_boot_slot(Slot::A).unwrap();
+    fx.slot_ctrl
+        .set_rootfs_status(RootFsStatus::UpdateDone, Slot::A)
+        .unwrap();
+
+    let report = fx.slot_ctrl.status_report().unwrap();
+    assert_eq!(
+        report,
+        StatusReport {
+            current_slot: Slot::B,
+            next_boot_slot: Slot::A,
+            slot_a: SlotStatus {
+                rootfs_status: RootFsStatus::UpdateDone,
+                retry_count: 5,
+            },
+            slot_b: SlotStatus {
+                rootfs_status: RootFsStatus::Normal,
+                retry_count: 0,
+            },
+            max_retry_count: 5,
+        }
+    );
+
+    let json = serde_json::to_value(&report).unwrap();
+    assert_eq!(json["current_slot"], "b");
+    assert_eq!(json["next_boot_slot"], "a");
+    assert_eq!(json["slot_a"]["rootfs_status"], "UpdateDone");
+    assert_eq!(json["slot_a"]["retry_count"], 5);
+}
+
+#[test]
+fn it_keeps_committed_slot_switch() {
+    let fx = FakeOrb::builder()
+        .current_slot(Slot::A)
+        .max_retry_count(5)
+        .build();
+    let guard = fx.slot_ctrl.switch_to_slot(Slot::B).unwrap();
+    assert_eq!(guard.slot(), Slot::B);
+    guard.commit();
+
+    assert_eq!(fx.slot_ctrl.get_next_boot_slot().unwrap(), Slot::B);
+    assert_eq!(
+        fx.slot_ctrl.get_rootfs_status(Slot::B).unwrap(),
+        RootFsStatus::UpdateDone
+    );
+    assert_eq!(fx.slot_ctrl.get_retry_count(Slot::B).unwrap(), 5);
+}
+
+#[test]
+fn it_rolls_back_dropped_slot_switch() {
+    let fx = FakeOrb::builder()
+        .current_slot(Slot::A)
+        .max_retry_count(5)
+        .build();
+    let before = fx.slot_ctrl.status_report().unwrap();
+
+    let guard = fx.slot_ctrl.switch_to_slot(Slot::B).unwrap();
+    assert_eq!(fx.slot_ctrl.get_next_boot_slot().unwrap(), Slot::B);
+    drop(guard);
+
+    assert_eq!(fx.slot_ctrl.status_report().unwrap(), before);
+}
+
+#[test]
+fn it_reports_efivars_that_failed_to_roll_back() {
+    let fx = FakeOrb::builder()
+        .current_slot(Slot::A)
+        .max_retry_count(5)
+        .build();
+    let guard = fx.slot_ctrl.switch_to_slot(Slot::B).unwrap();
+    fx.var(FakeVar::RootfsStatus(Slot::B)).remove().unwrap();
+
+    let err = guard.rollback().unwrap_err();
+    let failed: Vec<SlotEfiVar> = err.failures.iter().map(|(var, _)| *var).collect();
+    assert_eq!(failed, [SlotEfiVar::RootfsStatus(Slot::B)]);
+    assert_eq!(fx.slot_ctrl.get_next_boot_slot().unwrap(), Slot::A);
+    assert_eq!(fx.slot_ctrl.get_retry_count(Slot::B).unwrap(), 0);
+}
+
+#[test]
+fn it_diffs_status_reports() {
+    let fx = FakeOrb::builder()
+        .current_slot(Slot::A)
+        .max_retry_count(5)
+        .build();
+    let before = fx.slot_ctrl.status_report().unwrap();
+    assert!(watch::diff(&before, &before).is_empty());
+
+    fx.slot_ctrl.set_next_boot_slot(Slot::B).unwrap();
+    fx.slot_ctrl
+        .set_rootfs_status(RootFsStatus::UpdateDone, Slot::B)
+        .unwrap();
+    let after = fx.slot_ctrl.status_report().unwrap();
+
+    let change = |field, old: &str, new: &str| Change {
+        field,
+        old: old.to_string(),
+        new: new.to_string(),
+    };
+    assert_eq!(
+        watch::diff(&before, &after),
+        [
+            change("next_boot_slot", "a", "b"),
+            change("slot_b.rootfs_status", "Normal", "UpdateDone"),
+            change("slot_b.retry_count", "0", "5"),
+        ]
+    );
+}
+
+#[test]
+fn it_watches_status_changes() {
+    let fx = FakeOrb::builder()
+        .current_slot(Slot::A)
+        .max_retry_count(5)
+        .build();
+    let mut watcher = StatusWatcher::new(&fx.slot_ctrl);
+
+    let first = watcher.poll().unwrap().unwrap();
+    assert_eq!(first.report, fx.slot_ctrl.status_report().unwrap());
+    assert!(first.changes.is_empty());
+    assert_eq!(watcher.poll().unwrap(), None);
+
+    fx.slot_ctrl
+        .set_rootfs_status(RootFsStatus::UpdateInProcess, Slot::B)
+        .unwrap();
+    let update = watcher.poll().unwrap().unwrap();
+    assert_eq!(
+        update.report.slot_b.rootfs_status,
+        RootFsStatus::UpdateInProcess
+    );
+    assert_eq!(
+        update.changes,
+        [Change {
+            field: "slot_b.rootfs_status",
+            old: "Normal".to_string(),
+            new: "UpdateInProcess".to_string(),
+        }]
+    );
+    assert_eq!(watcher.poll().unwrap(), None);
+}
+
+#[test]
+fn it_reads_preset_slot_state() {
+    let fx = FakeOrb::builder()
+        .current_slot(Slot::A)
+        .next_slot(Slot::B)
+        .max_retry_count(3)
+        .rootfs_status(Slot::B, RootFsStatus::UpdateDone)
+        .retry_count(Slot::B, 2)
+        .build();
+
+    let report = fx.slot_ctrl.status_report().unwrap();
+    assert_eq!(
+        report,
+        StatusReport {
+            current_slot: Slot::A,
+            next_boot_slot: Slot::B,
+            slot_a: SlotStatus {
+                rootfs_status: RootFsStatus::Normal,
+                retry_count: 0,
+            },
+            slot_b: SlotStatus {
+                rootfs_status: RootFsStatus::UpdateDone,
+                retry_count: 2,
+            },
+            max_retry_count: 3,
+        }
+    );
+}
+
+#[test]
+fn it_sees_efivars_changed_mid_test() {
+    let fx = FakeOrb::builder()
+        .current_slot(Slot::A)
+        .next_slot(Slot::B)
+        .build();
+    assert_eq!(fx.slot_ctrl.get_retry_count(Slot::A).unwrap(), 0);
+
+    fx.write_var(FakeVar::RetryCount(Slot::A), 1);
+    assert_eq!(fx.slot_ctrl.get_retry_count(Slot::A).unwrap(), 1);
+
+    // A missing next boot slot falls back to the current slot.
+    fx.var(FakeVar::NextSlot).remove().unwrap();
+    assert_eq!(fx.slot_ctrl.get_next_boot_slot().unwrap(), Slot::A);
+}
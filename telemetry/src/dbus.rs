@@ -0,0 +1,46 @@
+//! Dbus interface to change the log filter at runtime.
+
+use zbus::{interface, SignalContext};
+
+use crate::{FilterError, TelemetryHandle};
+
+/// Serves `org.worldcoin.Telemetry1`, conventionally at `/org/worldcoin/Telemetry1`.
+pub struct Interface {
+    handle: TelemetryHandle,
+}
+
+impl Interface {
+    pub fn new(handle: TelemetryHandle) -> Self {
+        Self { handle }
+    }
+}
+
+#[interface(name = "org.worldcoin.Telemetry1")]
+impl Interface {
+    /// The directives of the active global log filter.
+    #[zbus(property)]
+    fn log_filter(&self) -> String {
+        self.handle.current_filter()
+    }
+
+    /// Replaces the global log filter with `directives`, using the `RUST_LOG`
+    /// syntax. Invalid directives are rejected and leave the filter unchanged.
+    async fn set_log_filter(
+        &self,
+        #[zbus(signal_context)] ctxt: SignalContext<'_>,
+        directives: String,
+    ) -> zbus::fdo::Result<()> {
+        self.handle
+            .set_filter_directives(&directives)
+            .map_err(|err| match err {
+                FilterError::Parse(_) => {
+                    zbus::fdo::Error::InvalidArgs(format!("{err}: {directives}"))
+                }
+                FilterError::Reload(_) => zbus::fdo::Error::Failed(err.to_string()),
+            })?;
+        tracing::info!(%directives, "changed log filter");
+        self.log_filter_changed(&ctxt).await?;
+
+        Ok(())
+    }
+}
@@ -1,14 +1,26 @@
-use std::io::IsTerminal as _;
+#[cfg(feature = "dbus")]
+pub mod dbus;
+mod rolling_file;
 
-use tracing::level_filters::LevelFilter;
+use std::{fmt, io::IsTerminal as _, path::Path};
+
+use rolling_file::{RollingFileConfig, RollingFileWriter};
+
+use tracing::{level_filters::LevelFilter, Subscriber};
 use tracing_subscriber::{
-    layer::SubscriberExt as _, util::SubscriberInitExt as _, EnvFilter,
+    filter::ParseError, layer::SubscriberExt as _, registry::LookupSpan, reload,
+    util::SubscriberInitExt as _, EnvFilter, Layer, Registry,
 };
 
+type BoxedLayer<S> = Box<dyn Layer<S> + Send + Sync + 'static>;
+
 #[derive(Debug)]
 pub struct TelemetryConfig {
     syslog_identifier: Option<String>,
     global_filter: EnvFilter,
+    journald_filter: Option<EnvFilter>,
+    stderr_filter: Option<EnvFilter>,
+    rolling_file: Option<RollingFileConfig>,
 }
 
 impl TelemetryConfig {
@@ -22,6 +34,9 @@ impl TelemetryConfig {
             global_filter: EnvFilter::builder()
                 .with_default_directive(LevelFilter::INFO.into())
                 .from_env_lossy(),
+            journald_filter: None,
+            stderr_filter: None,
+            rolling_file: None,
         }
     }
 
@@ -46,7 +61,57 @@ impl TelemetryConfig {
         }
     }
 
-    pub fn try_init(self) -> Result<(), tracing_subscriber::util::TryInitError> {
+    /// Use a dedicated filter for journald instead of the global filter.
+    ///
+    /// The global filter does not apply to journald once this is set, so this can be
+    /// both more or less verbose than the global filter.
+    #[must_use]
+    pub fn with_journald_filter(self, filter: EnvFilter) -> Self {
+        Self {
+            journald_filter: Some(filter),
+            ..self
+        }
+    }
+
+    /// Use a dedicated filter for stderr instead of the global filter.
+    ///
+    /// The global filter does not apply to stderr once this is set, so this can be
+    /// both more or less verbose than the global filter.
+    #[must_use]
+    pub fn with_stderr_filter(self, filter: EnvFilter) -> Self {
+        Self {
+            stderr_filter: Some(filter),
+            ..self
+        }
+    }
+
+    /// Additionally writes logs as JSON lines to the file at `path`.
+    ///
+    /// Once the file would exceed `max_bytes`, it is rotated to `<path>.1`, with
+    /// older files shifted to `<path>.2` and so on. At most `max_files` rotated
+    /// files are kept. Each record is written before the logging call returns, so
+    /// nothing is lost if the program exits or panics right after.
+    #[must_use]
+    pub fn with_rolling_file(
+        self,
+        path: impl AsRef<Path>,
+        max_bytes: u64,
+        max_files: usize,
+    ) -> Self {
+        Self {
+            rolling_file: Some(RollingFileConfig {
+                path: path.as_ref().to_owned(),
+                max_bytes,
+                max_files,
+            }),
+            ..self
+        }
+    }
+
+    /// Like [`Self::init`], but returns an error instead of panicking.
+    pub fn try_init(
+        self,
+    ) -> Result<TelemetryHandle, tracing_subscriber::util::TryInitError> {
         let registry = tracing_subscriber::registry();
         // The type is only there to get it to compile.
         let tokio_console_layer: Option<tracing_subscriber::layer::Identity> = None;
@@ -72,12 +137,40 @@ impl TelemetryConfig {
             .is_none()
             .then(|| tracing_subscriber::fmt::layer().with_writer(std::io::stderr));
         assert!(stderr_layer.is_some() || journald_layer.is_some());
-        registry
-            .with(tokio_console_layer)
-            .with(stderr_layer)
-            .with(journald_layer)
-            .with(self.global_filter)
-            .try_init()
+        let rolling_file = self.rolling_file.and_then(|cfg| {
+            let path = cfg.path.clone();
+            RollingFileWriter::spawn(cfg)
+                .inspect_err(|err| {
+                    eprintln!(
+                        "failed opening rolling log file {}. \
+                        will not log to it: {err}",
+                        path.display()
+                    );
+                })
+                .ok()
+        });
+        let rolling_file_layer = rolling_file
+            .clone()
+            .map(|writer| tracing_subscriber::fmt::layer().json().with_writer(writer));
+
+        let mut sinks: Vec<(BoxedLayer<_>, Option<EnvFilter>)> = Vec::new();
+        sinks.push((tokio_console_layer.boxed(), None));
+        if let Some(layer) = stderr_layer {
+            sinks.push((layer.boxed(), self.stderr_filter));
+        }
+        if let Some(layer) = journald_layer {
+            sinks.push((layer.boxed(), self.journald_filter));
+        }
+        if let Some(layer) = rolling_file_layer {
+            sinks.push((layer.boxed(), None));
+        }
+        let (layers, global_filter) = with_sink_filters(sinks, self.global_filter);
+        registry.with(layers).try_init()?;
+
+        Ok(TelemetryHandle {
+            global_filter,
+            rolling_file,
+        })
     }
 
     /// Initializes the telemetry config. Call this only once, at the beginning of the
@@ -85,7 +178,228 @@ impl TelemetryConfig {
     ///
     /// Calling this more than once or when another tracing subscriber is registered
     /// will cause a panic.
-    pub fn init(self) {
+    ///
+    /// The returned handle can be used to change the global filter at runtime. Keep
+    /// it until the program exits when logging to a rolling file, dropping it
+    /// flushes the file.
+    pub fn init(self) -> TelemetryHandle {
         self.try_init().expect("failed to initialize orb-telemetry")
     }
 }
+
+/// Handle to the telemetry installed by [`TelemetryConfig::try_init`].
+///
+/// Allows changing the global filter while the program is running, without
+/// restarting it. Sinks with a dedicated filter are not affected.
+///
+/// Dropping the handle, or any of its clones, waits until the queued records are
+/// written to the rolling file.
+#[derive(Debug, Clone)]
+pub struct TelemetryHandle {
+    global_filter: reload::Handle<EnvFilter, Registry>,
+    rolling_file: Option<RollingFileWriter>,
+}
+
+impl TelemetryHandle {
+    /// Blocks until every event logged so far has been written to the rolling
+    /// file, if there is one.
+    pub fn flush(&self) {
+        if let Some(rolling_file) = &self.rolling_file {
+            rolling_file.flush();
+        }
+    }
+
+    /// Replaces the global filter.
+    pub fn set_filter(&self, filter: EnvFilter) -> Result<(), FilterError> {
+        self.global_filter
+            .reload(filter)
+            .map_err(FilterError::Reload)
+    }
+
+    /// Parses `directives` using the `RUST_LOG` syntax and uses them as the global
+    /// filter.
+    ///
+    /// The active filter is left untouched if the directives are invalid.
+    pub fn set_filter_directives(&self, directives: &str) -> Result<(), FilterError> {
+        let filter = EnvFilter::builder()
+            .parse(directives)
+            .map_err(FilterError::Parse)?;
+        self.set_filter(filter)
+    }
+
+    /// The directives of the active global filter.
+    pub fn current_filter(&self) -> String {
+        self.global_filter
+            .with_current(ToString::to_string)
+            .unwrap_or_default()
+    }
+}
+
+impl Drop for TelemetryHandle {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+/// Error returned when changing the global filter through a [`TelemetryHandle`].
+#[derive(Debug)]
+pub enum FilterError {
+    /// The filter directives could not be parsed.
+    Parse(ParseError),
+    /// The subscriber that owns the filter no longer exists.
+    Reload(reload::Error),
+}
+
+impl fmt::Display for FilterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Parse(_) => f.write_str("invalid filter directives"),
+            Self::Reload(_) => f.write_str("failed to reload the global filter"),
+        }
+    }
+}
+
+impl std::error::Error for FilterError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Parse(err) => Some(err),
+            Self::Reload(err) => Some(err),
+        }
+    }
+}
+
+/// Applies each sink's own filter, falling back to `global_filter` for the sinks
+/// that don't have one.
+///
+/// `global_filter` is installed behind a reload layer, whose handle is returned.
+fn with_sink_filters<S>(
+    sinks: Vec<(BoxedLayer<S>, Option<EnvFilter>)>,
+    global_filter: EnvFilter,
+) -> (Vec<BoxedLayer<S>>, reload::Handle<EnvFilter, S>)
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    let mut layers = Vec::with_capacity(sinks.len() + 1);
+    let mut unfiltered = Vec::new();
+    for (layer, filter) in sinks {
+        match filter {
+            Some(filter) => layers.push(layer.with_filter(filter).boxed()),
+            None => unfiltered.push(layer),
+        }
+    }
+    let (global_filter, handle) = reload::Layer::new(global_filter);
+    layers.push(unfiltered.with_filter(global_filter).boxed());
+
+    (layers, handle)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use tracing::{Event, Level};
+    use tracing_subscriber::layer::Context;
+
+    use super::*;
+
+    /// Records the level of every event it receives.
+    #[derive(Debug, Clone, Default)]
+    struct CaptureLayer(Arc<Mutex<Vec<Level>>>);
+
+    impl<S: Subscriber> Layer<S> for CaptureLayer {
+        fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+            self.0.lock().unwrap().push(*event.metadata().level());
+        }
+    }
+
+    impl CaptureLayer {
+        fn levels(&self) -> Vec<Level> {
+            self.0.lock().unwrap().clone()
+        }
+    }
+
+    #[test]
+    fn test_sink_filter_overrides_global_filter() {
+        let verbose = CaptureLayer::default();
+        let quiet = CaptureLayer::default();
+        let fallback = CaptureLayer::default();
+        let (layers, _handle) = with_sink_filters(
+            vec![
+                (verbose.clone().boxed(), Some(EnvFilter::new("debug"))),
+                (quiet.clone().boxed(), Some(EnvFilter::new("warn"))),
+                (fallback.clone().boxed(), None),
+            ],
+            EnvFilter::new("info"),
+        );
+        let subscriber = tracing_subscriber::registry().with(layers);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::trace!("trace");
+            tracing::debug!("debug");
+            tracing::info!("info");
+            tracing::warn!("warn");
+        });
+
+        assert_eq!(verbose.levels(), [Level::DEBUG, Level::INFO, Level::WARN]);
+        assert_eq!(quiet.levels(), [Level::WARN]);
+        assert_eq!(fallback.levels(), [Level::INFO, Level::WARN]);
+    }
+
+    #[test]
+    fn test_global_filter_reload() {
+        let capture = CaptureLayer::default();
+        let quiet = CaptureLayer::default();
+        let (layers, global_filter) = with_sink_filters(
+            vec![
+                (capture.clone().boxed(), None),
+                (quiet.clone().boxed(), Some(EnvFilter::new("warn"))),
+            ],
+            EnvFilter::new("info"),
+        );
+        let handle = TelemetryHandle {
+            global_filter,
+            rolling_file: None,
+        };
+        let subscriber = tracing_subscriber::registry().with(layers);
+        tracing::subscriber::with_default(subscriber, || {
+            assert_eq!(handle.current_filter(), "info");
+            tracing::debug!("dropped");
+            tracing::info!("recorded");
+
+            handle.set_filter_directives("debug").unwrap();
+            assert_eq!(handle.current_filter(), "debug");
+            tracing::trace!("dropped");
+            tracing::debug!("recorded");
+
+            handle.set_filter(EnvFilter::new("error")).unwrap();
+            tracing::warn!("dropped");
+            tracing::error!("recorded");
+        });
+
+        assert_eq!(capture.levels(), [Level::INFO, Level::DEBUG, Level::ERROR]);
+        // Sinks with their own filter are not affected by the global filter.
+        assert_eq!(quiet.levels(), [Level::WARN, Level::ERROR]);
+    }
+
+    #[test]
+    fn test_invalid_directives_keep_active_filter() {
+        let capture = CaptureLayer::default();
+        let (layers, global_filter) = with_sink_filters(
+            vec![(capture.clone().boxed(), None)],
+            EnvFilter::new("warn"),
+        );
+        let handle = TelemetryHandle {
+            global_filter,
+            rolling_file: None,
+        };
+        let subscriber = tracing_subscriber::registry().with(layers);
+        tracing::subscriber::with_default(subscriber, || {
+            let err = handle.set_filter_directives("foo=notalevel").unwrap_err();
+            assert!(matches!(err, FilterError::Parse(_)));
+            assert_eq!(handle.current_filter(), "warn");
+            tracing::info!("dropped");
+            tracing::warn!("recorded");
+        });
+
+        assert_eq!(capture.levels(), [Level::WARN]);
+    }
+}
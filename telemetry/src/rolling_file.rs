@@ -0,0 +1,283 @@
+//! A size-based rolling log file, written from a dedicated thread so that logging
+//! never does file IO or rotation on the caller's thread.
+//!
+//! Queued records are written before the program exits as long as the
+//! [`crate::TelemetryHandle`] is kept alive until then, see
+//! [`RollingFileWriter::flush`].
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::mpsc,
+};
+
+use tracing_subscriber::fmt::MakeWriter;
+
+#[derive(Debug, Clone)]
+pub(crate) struct RollingFileConfig {
+    pub path: PathBuf,
+    pub max_bytes: u64,
+    pub max_files: usize,
+}
+
+/// The log file at `path`, plus up to `max_files` rotated files named `path.1`
+/// (newest) to `path.N` (oldest).
+#[derive(Debug)]
+struct RollingFile {
+    cfg: RollingFileConfig,
+    file: File,
+    len: u64,
+}
+
+impl RollingFile {
+    fn open(cfg: RollingFileConfig) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&cfg.path)?;
+        let len = file.metadata()?.len();
+
+        Ok(Self { cfg, file, len })
+    }
+
+    /// Writes a single record, rotating first if it would not fit in the current
+    /// file. Records are never split across files.
+    fn write_record(&mut self, record: &[u8]) -> io::Result<()> {
+        if self.len > 0 && self.len + record.len() as u64 > self.cfg.max_bytes {
+            self.rotate()?;
+        }
+        self.file.write_all(record)?;
+        self.len += record.len() as u64;
+
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        let max_files = self.cfg.max_files;
+        if max_files > 0 {
+            remove_if_exists(&rotated_path(&self.cfg.path, max_files))?;
+            for idx in (1..max_files).rev() {
+                rename_if_exists(
+                    &rotated_path(&self.cfg.path, idx),
+                    &rotated_path(&self.cfg.path, idx + 1),
+                )?;
+            }
+            std::fs::rename(&self.cfg.path, rotated_path(&self.cfg.path, 1))?;
+        }
+        self.file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&self.cfg.path)?;
+        self.len = 0;
+
+        Ok(())
+    }
+}
+
+fn rotated_path(path: &Path, idx: usize) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(format!(".{idx}"));
+    path.into()
+}
+
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match std::fs::remove_file(path) {
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+fn rename_if_exists(from: &Path, to: &Path) -> io::Result<()> {
+    match std::fs::rename(from, to) {
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+/// Number of records that can be queued before logging blocks on the writer thread.
+const QUEUE_CAPACITY: usize = 1024;
+
+#[derive(Debug)]
+enum Command {
+    Record(Vec<u8>),
+    /// Acknowledged once every record queued before it has been written.
+    Flush(mpsc::SyncSender<()>),
+}
+
+/// [`MakeWriter`] that forwards each formatted event to the writer thread.
+///
+/// The thread exits once every `RollingFileWriter` has been dropped.
+#[derive(Debug, Clone)]
+pub(crate) struct RollingFileWriter {
+    tx: mpsc::SyncSender<Command>,
+}
+
+impl RollingFileWriter {
+    /// Opens the log file and spawns the thread that writes to it.
+    pub fn spawn(cfg: RollingFileConfig) -> io::Result<Self> {
+        let mut file = RollingFile::open(cfg)?;
+        let (tx, rx) = mpsc::sync_channel(QUEUE_CAPACITY);
+        std::thread::Builder::new()
+            .name("orb-telemetry-file".to_owned())
+            .spawn(move || {
+                for command in rx {
+                    match command {
+                        Command::Record(record) => {
+                            if let Err(err) = file.write_record(&record) {
+                                eprintln!("failed writing to rolling log file: {err}");
+                            }
+                        }
+                        Command::Flush(ack) => {
+                            let _ = ack.send(());
+                        }
+                    }
+                }
+            })?;
+
+        Ok(Self { tx })
+    }
+
+    /// Blocks until every record logged so far has been written to the file.
+    pub fn flush(&self) {
+        let (ack_tx, ack_rx) = mpsc::sync_channel(1);
+        if self.tx.send(Command::Flush(ack_tx)).is_ok() {
+            // Fails only if the writer thread is gone.
+            let _ = ack_rx.recv();
+        }
+    }
+}
+
+impl<'a> MakeWriter<'a> for RollingFileWriter {
+    type Writer = RecordWriter;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        RecordWriter {
+            buf: Vec::new(),
+            tx: self.tx.clone(),
+        }
+    }
+}
+
+/// Buffers one formatted event and sends it to the writer thread on drop.
+#[derive(Debug)]
+pub(crate) struct RecordWriter {
+    buf: Vec<u8>,
+    tx: mpsc::SyncSender<Command>,
+}
+
+impl Write for RecordWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for RecordWriter {
+    fn drop(&mut self) {
+        if !self.buf.is_empty() {
+            // Blocks if the queue is full, rather than losing the record. The writer
+            // thread only exits once all senders are gone, so this can't fail in
+            // practice.
+            let _ = self.tx.send(Command::Record(std::mem::take(&mut self.buf)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::layer::SubscriberExt as _;
+
+    fn cfg(dir: &Path, max_bytes: u64, max_files: usize) -> RollingFileConfig {
+        RollingFileConfig {
+            path: dir.join("test.log"),
+            max_bytes,
+            max_files,
+        }
+    }
+
+    #[test]
+    fn test_rotation_keeps_at_most_max_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = cfg(dir.path(), 10, 2);
+        let mut file = RollingFile::open(cfg.clone()).unwrap();
+        for record in ["aaaaaaaa\n", "bbbbbbbb\n", "cccccccc\n", "dddddddd\n"] {
+            file.write_record(record.as_bytes()).unwrap();
+        }
+
+        let read = |path: PathBuf| std::fs::read_to_string(path).unwrap();
+        assert_eq!(read(cfg.path.clone()), "dddddddd\n");
+        assert_eq!(read(rotated_path(&cfg.path, 1)), "cccccccc\n");
+        assert_eq!(read(rotated_path(&cfg.path, 2)), "bbbbbbbb\n");
+        assert!(!rotated_path(&cfg.path, 3).exists());
+    }
+
+    #[test]
+    fn test_no_max_files_truncates() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = cfg(dir.path(), 4, 0);
+        let mut file = RollingFile::open(cfg.clone()).unwrap();
+        file.write_record(b"abc\n").unwrap();
+        file.write_record(b"def\n").unwrap();
+
+        assert_eq!(std::fs::read_to_string(&cfg.path).unwrap(), "def\n");
+        assert!(!rotated_path(&cfg.path, 1).exists());
+    }
+
+    #[test]
+    fn test_json_layer_rotates() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = cfg(dir.path(), 512, 3);
+        let writer = RollingFileWriter::spawn(cfg.clone()).unwrap();
+        let subscriber = tracing_subscriber::registry().with(
+            tracing_subscriber::fmt::layer()
+                .json()
+                .with_writer(writer.clone()),
+        );
+        tracing::subscriber::with_default(subscriber, || {
+            for idx in 0..50 {
+                tracing::info!(idx, "filling up the log file");
+            }
+        });
+        writer.flush();
+
+        let current = std::fs::read_to_string(&cfg.path).unwrap();
+        let rotated = std::fs::read_to_string(rotated_path(&cfg.path, 1)).unwrap();
+        assert!(current.len() as u64 <= cfg.max_bytes);
+        assert!(rotated.len() as u64 <= cfg.max_bytes);
+        for line in current.lines().chain(rotated.lines()) {
+            assert!(line.starts_with('{') && line.ends_with('}'), "{line}");
+        }
+        assert!(current.contains("\"idx\":49"));
+        assert!(!rotated_path(&cfg.path, 4).exists());
+    }
+
+    #[test]
+    fn test_flush_waits_for_queued_records() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = cfg(dir.path(), 1 << 20, 1);
+        let writer = RollingFileWriter::spawn(cfg.clone()).unwrap();
+        let subscriber = tracing_subscriber::registry().with(
+            tracing_subscriber::fmt::layer()
+                .json()
+                .with_writer(writer.clone()),
+        );
+        tracing::subscriber::with_default(subscriber, || {
+            // More than fits in the queue, so logging has to wait for the thread.
+            for idx in 0..2 * QUEUE_CAPACITY {
+                tracing::info!(idx, "early boot");
+            }
+            // Still inside the subscriber, the writer thread is still running.
+            writer.flush();
+            let current = std::fs::read_to_string(&cfg.path).unwrap();
+            assert_eq!(current.lines().count(), 2 * QUEUE_CAPACITY);
+            assert!(current.contains("\"idx\":2047"), "{current}");
+        });
+    }
+}
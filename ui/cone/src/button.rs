@@ -1,7 +1,8 @@
-use crate::{ButtonState, ConeEvent, CONE_FTDI_BUTTON_INDEX};
+use crate::{ButtonGesture, ButtonState, ConeEvent};
 use color_eyre::eyre;
-use ftdi_embedded_hal::libftd2xx::{BitMode, Ft4232h, Ftdi, FtdiCommon};
+use ftdi_embedded_hal::libftd2xx::{BitMode, DeviceInfo, Ft4232h, Ftdi, FtdiCommon};
 use std::cmp::PartialEq;
+use std::time::{Duration, Instant};
 use tokio::select;
 use tokio::sync::{broadcast, oneshot};
 use tokio::task::JoinHandle;
@@ -12,7 +13,17 @@ const BUTTON_GPIO_PIN: u8 = 0;
 const BUTTON_GPIO_MASK: u8 = 1 << BUTTON_GPIO_PIN;
 /// Only the button pin is an input (set to 0), the rest are set to outputs (set to 1)
 const BUTTON_GPIO_DIRECTION: u8 = !(1 << BUTTON_GPIO_PIN);
-const BUTTON_POLL_INTERVAL_MS: u64 = 50;
+/// Polled often enough for the debounce window to span several samples.
+const BUTTON_POLL_INTERVAL_MS: u64 = 10;
+
+/// Default time the button must stay in a new state before the transition is
+/// accepted.
+pub const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(30);
+/// Default maximum time between the release of a click and the next press for
+/// the two clicks to form a [`ButtonGesture::DoubleClick`].
+pub const DEFAULT_DOUBLE_CLICK_WINDOW: Duration = Duration::from_millis(400);
+/// Default time the button must be held to emit a [`ButtonGesture::LongPress`].
+pub const DEFAULT_LONG_PRESS: Duration = Duration::from_millis(1500);
 
 /// Handle that can be used to join on errors from the [`Button`] task.
 ///
@@ -27,6 +38,31 @@ pub struct Button {
     pub kill_tx: oneshot::Sender<()>,
 }
 
+/// Timings used by the [`Button`] task to turn GPIO samples into events.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ButtonConfig {
+    /// See [`DEFAULT_DEBOUNCE`].
+    pub debounce: Duration,
+    /// See [`DEFAULT_DOUBLE_CLICK_WINDOW`].
+    pub double_click_window: Duration,
+    /// See [`DEFAULT_LONG_PRESS`].
+    pub long_press: Duration,
+    /// Also send every transition of the GPIO, before debouncing, as
+    /// [`ConeEvent::Button`]. Meant for diagnostics.
+    pub raw_events: bool,
+}
+
+impl Default for ButtonConfig {
+    fn default() -> Self {
+        Self {
+            debounce: DEFAULT_DEBOUNCE,
+            double_click_window: DEFAULT_DOUBLE_CLICK_WINDOW,
+            long_press: DEFAULT_LONG_PRESS,
+            raw_events: false,
+        }
+    }
+}
+
 impl PartialEq for ButtonState {
     fn eq(&self, other: &Self) -> bool {
         matches!(
@@ -37,14 +73,175 @@ impl PartialEq for ButtonState {
     }
 }
 
+/// Turns GPIO samples into [`ConeEvent`]s, independently of how and when the
+/// samples are taken.
+#[derive(Debug)]
+struct ButtonMachine {
+    config: ButtonConfig,
+    /// Last sampled state, to report raw transitions.
+    raw: ButtonState,
+    /// Accepted state, after debouncing.
+    stable: ButtonState,
+    /// State differing from `stable`, and when it was first sampled.
+    candidate: Option<(ButtonState, Instant)>,
+    gesture: Gesture,
+}
+
+#[derive(Debug, Copy, Clone)]
+enum Gesture {
+    Idle,
+    /// The button is held. `second` is set if the press follows a click within
+    /// the double click window.
+    Pressed {
+        since: Instant,
+        long_press_sent: bool,
+        second: bool,
+    },
+    /// A click was released, waiting for a second one.
+    Clicked {
+        released_at: Instant,
+    },
+}
+
+impl ButtonMachine {
+    fn new(config: ButtonConfig) -> Self {
+        Self {
+            config,
+            raw: ButtonState::Released,
+            stable: ButtonState::Released,
+            candidate: None,
+            gesture: Gesture::Idle,
+        }
+    }
+
+    /// Processes a GPIO sample taken at `now`, returning the resulting events.
+    fn sample(&mut self, state: ButtonState, now: Instant) -> Vec<ConeEvent> {
+        let mut events = Vec::new();
+        if self.config.raw_events && state != self.raw {
+            events.push(ConeEvent::Button(state));
+        }
+        self.raw = state;
+
+        self.check_timeouts(now, &mut events);
+        if let Some(changed_at) = self.debounce(state, now) {
+            self.transition(state, changed_at, &mut events);
+        }
+        events
+    }
+
+    /// Returns when the button changed state, once the new state has been held
+    /// for the debounce window.
+    fn debounce(&mut self, state: ButtonState, now: Instant) -> Option<Instant> {
+        if state == self.stable {
+            self.candidate = None;
+            return None;
+        }
+        match self.candidate {
+            Some((candidate, since)) if candidate == state => {
+                if now.duration_since(since) >= self.config.debounce {
+                    self.stable = state;
+                    self.candidate = None;
+                    Some(since)
+                } else {
+                    None
+                }
+            }
+            _ => {
+                self.candidate = Some((state, now));
+                if self.config.debounce.is_zero() {
+                    self.debounce(state, now)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    fn check_timeouts(&mut self, now: Instant, events: &mut Vec<ConeEvent>) {
+        match self.gesture {
+            Gesture::Pressed {
+                since,
+                long_press_sent: false,
+                second,
+            } if now.duration_since(since) >= self.config.long_press => {
+                if second {
+                    events.push(ConeEvent::ButtonGesture(ButtonGesture::Click));
+                }
+                events.push(ConeEvent::ButtonGesture(ButtonGesture::LongPress {
+                    duration: now.duration_since(since),
+                }));
+                self.gesture = Gesture::Pressed {
+                    since,
+                    long_press_sent: true,
+                    second,
+                };
+            }
+            Gesture::Clicked { released_at }
+                if now.duration_since(released_at)
+                    > self.config.double_click_window =>
+            {
+                events.push(ConeEvent::ButtonGesture(ButtonGesture::Click));
+                self.gesture = Gesture::Idle;
+            }
+            _ => {}
+        }
+    }
+
+    fn transition(
+        &mut self,
+        state: ButtonState,
+        at: Instant,
+        events: &mut Vec<ConeEvent>,
+    ) {
+        self.gesture = match (self.gesture, state) {
+            (Gesture::Idle, ButtonState::Pressed) => Gesture::Pressed {
+                since: at,
+                long_press_sent: false,
+                second: false,
+            },
+            (Gesture::Clicked { .. }, ButtonState::Pressed) => Gesture::Pressed {
+                since: at,
+                long_press_sent: false,
+                second: true,
+            },
+            (
+                Gesture::Pressed {
+                    since,
+                    long_press_sent: true,
+                    ..
+                },
+                ButtonState::Released,
+            ) => {
+                events.push(ConeEvent::ButtonGesture(
+                    ButtonGesture::LongPressReleased {
+                        duration: at.duration_since(since),
+                    },
+                ));
+                Gesture::Idle
+            }
+            (Gesture::Pressed { second: true, .. }, ButtonState::Released) => {
+                events.push(ConeEvent::ButtonGesture(ButtonGesture::DoubleClick));
+                Gesture::Idle
+            }
+            (Gesture::Pressed { second: false, .. }, ButtonState::Released) => {
+                Gesture::Clicked { released_at: at }
+            }
+            // the debouncer only reports actual changes
+            (gesture, _) => gesture,
+        };
+    }
+}
+
 /// Poll the button state.
-/// Events are sent to the event queue when the button is pressed or released
+/// Events are sent to the event queue according to the [`ButtonConfig`].
 impl Button {
     pub(crate) fn spawn(
+        device: &DeviceInfo,
+        config: ButtonConfig,
         event_queue: broadcast::Sender<ConeEvent>,
     ) -> eyre::Result<(Self, ButtonJoinHandle)> {
         let mut device: Ft4232h =
-            Ftdi::with_index(CONE_FTDI_BUTTON_INDEX)?.try_into()?;
+            Ftdi::with_serial_number(&device.serial_number)?.try_into()?;
         device.set_bit_mode(BUTTON_GPIO_DIRECTION, BitMode::AsyncBitbang)?;
         tracing::debug!("Button GPIO initialized");
 
@@ -52,8 +249,7 @@ impl Button {
 
         // spawn a thread to poll the button
         let thread_handle = tokio::task::spawn_blocking(move || {
-            // keep state so that we send an event only on state change
-            let mut last_state = ButtonState::Released;
+            let mut machine = ButtonMachine::new(config);
             let rt = tokio::runtime::Handle::current();
             loop {
                 let interval = rt.block_on(async {
@@ -73,14 +269,11 @@ impl Button {
                                     ButtonState::Released
                                 };
 
-                                if state != last_state {
-                                    if let Err(e) =
-                                        event_queue.send(ConeEvent::Button(state))
-                                    {
+                                for event in machine.sample(state, Instant::now()) {
+                                    if let Err(e) = event_queue.send(event) {
                                         tracing::debug!("Error sending event: {e:?} - no receiver? stopping producer");
                                         return Ok(());
                                     }
-                                    last_state = state;
                                 }
                             }
                             Err(e) => {
@@ -99,3 +292,192 @@ impl Button {
         Ok((Button { kill_tx }, ButtonJoinHandle(thread_handle)))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_PERIOD: Duration = Duration::from_millis(BUTTON_POLL_INTERVAL_MS);
+
+    /// Feeds the machine with samples every [`SAMPLE_PERIOD`], each `(count,
+    /// pressed)` step of the script repeating a sample `count` times, and
+    /// returns the events with the time they were emitted at.
+    fn run(config: ButtonConfig, script: &[(u32, bool)]) -> Vec<(Duration, ConeEvent)> {
+        let start = Instant::now();
+        let mut machine = ButtonMachine::new(config);
+        let mut elapsed = Duration::ZERO;
+        let mut events = Vec::new();
+        for &(count, pressed) in script {
+            let state = if pressed {
+                ButtonState::Pressed
+            } else {
+                ButtonState::Released
+            };
+            for _ in 0..count {
+                for event in machine.sample(state, start + elapsed) {
+                    events.push((elapsed, event));
+                }
+                elapsed += SAMPLE_PERIOD;
+            }
+        }
+        events
+    }
+
+    fn gestures(config: ButtonConfig, script: &[(u32, bool)]) -> Vec<ButtonGesture> {
+        run(config, script)
+            .into_iter()
+            .filter_map(|(_, event)| match event {
+                ConeEvent::ButtonGesture(gesture) => Some(gesture),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn test_gestures() {
+        let config = ButtonConfig::default();
+        let cases: &[(&[(u32, bool)], &[ButtonGesture])] = &[
+            // nothing happens
+            (&[(100, false)], &[]),
+            // bounces shorter than the debounce window are ignored
+            (&[(2, true), (1, false), (2, true), (100, false)], &[]),
+            // a single click
+            (&[(10, true), (100, false)], &[ButtonGesture::Click]),
+            // a bouncy single click
+            (
+                &[
+                    (1, true),
+                    (1, false),
+                    (10, true),
+                    (1, false),
+                    (1, true),
+                    (100, false),
+                ],
+                &[ButtonGesture::Click],
+            ),
+            // a double click
+            (
+                &[(10, true), (20, false), (10, true), (100, false)],
+                &[ButtonGesture::DoubleClick],
+            ),
+            // two clicks too far apart
+            (
+                &[(10, true), (50, false), (10, true), (100, false)],
+                &[ButtonGesture::Click, ButtonGesture::Click],
+            ),
+            // a long press
+            (
+                &[(200, true), (100, false)],
+                &[
+                    ButtonGesture::LongPress { duration: ms(1500) },
+                    ButtonGesture::LongPressReleased { duration: ms(2000) },
+                ],
+            ),
+            // a click followed by a long press
+            (
+                &[(10, true), (20, false), (200, true), (100, false)],
+                &[
+                    ButtonGesture::Click,
+                    ButtonGesture::LongPress { duration: ms(1500) },
+                    ButtonGesture::LongPressReleased { duration: ms(2000) },
+                ],
+            ),
+            // three quick clicks
+            (
+                &[
+                    (10, true),
+                    (20, false),
+                    (10, true),
+                    (20, false),
+                    (10, true),
+                    (100, false),
+                ],
+                &[ButtonGesture::DoubleClick, ButtonGesture::Click],
+            ),
+        ];
+        for (i, (script, expected)) in cases.iter().enumerate() {
+            assert_eq!(&gestures(config, script), expected, "{i}th case failed");
+        }
+    }
+
+    #[test]
+    fn test_event_timing() {
+        let events = run(ButtonConfig::default(), &[(200, true), (10, false)]);
+        let times: Vec<_> = events.iter().map(|(elapsed, _)| *elapsed).collect();
+        // long press detected once held for 1.5s, release accepted after the
+        // debounce window
+        assert_eq!(times, [ms(1500), ms(2030)]);
+
+        let events = run(ButtonConfig::default(), &[(10, true), (100, false)]);
+        let times: Vec<_> = events.iter().map(|(elapsed, _)| *elapsed).collect();
+        // a click is only reported once the double click window has elapsed
+        assert_eq!(times, [ms(510)]);
+    }
+
+    #[test]
+    fn test_custom_timings() {
+        let config = ButtonConfig {
+            debounce: Duration::ZERO,
+            double_click_window: ms(100),
+            long_press: ms(500),
+            raw_events: false,
+        };
+        let cases: &[(&[(u32, bool)], &[ButtonGesture])] = &[
+            // without debouncing, a single sample is a click
+            (&[(1, true), (100, false)], &[ButtonGesture::Click]),
+            (
+                &[(1, true), (5, false), (1, true), (100, false)],
+                &[ButtonGesture::DoubleClick],
+            ),
+            (
+                &[(1, true), (20, false), (1, true), (100, false)],
+                &[ButtonGesture::Click, ButtonGesture::Click],
+            ),
+            (
+                &[(60, true), (1, false)],
+                &[
+                    ButtonGesture::LongPress { duration: ms(500) },
+                    ButtonGesture::LongPressReleased { duration: ms(600) },
+                ],
+            ),
+        ];
+        for (i, (script, expected)) in cases.iter().enumerate() {
+            assert_eq!(&gestures(config, script), expected, "{i}th case failed");
+        }
+    }
+
+    #[test]
+    fn test_raw_events() {
+        let script = [(1, true), (1, false), (10, true), (100, false)];
+        let raw = |config| -> Vec<ButtonState> {
+            run(config, &script)
+                .into_iter()
+                .filter_map(|(_, event)| match event {
+                    ConeEvent::Button(state) => Some(state),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        assert!(raw(ButtonConfig::default()).is_empty());
+        let config = ButtonConfig {
+            raw_events: true,
+            ..ButtonConfig::default()
+        };
+        // bounces included
+        assert_eq!(
+            raw(config),
+            [
+                ButtonState::Pressed,
+                ButtonState::Released,
+                ButtonState::Pressed,
+                ButtonState::Released,
+            ]
+        );
+        assert_eq!(gestures(config, &script), [ButtonGesture::Click]);
+    }
+}
@@ -0,0 +1,312 @@
+//! Locates the cone's FTDI interfaces.
+//!
+//! The cone exposes a single FT4232H: each of its four ports (A to D) shows up as
+//! a separate FTDI device whose serial number is the chip serial number with the
+//! port letter appended, and whose description ends with that same letter.
+//! Enumeration order isn't stable, so ports are matched by serial number rather
+//! than by index.
+//!
+//! A second FT4232H is usually connected to the same host. It keeps the stock
+//! "Quad RS232-HS" description, while the cone's EEPROM is programmed with
+//! [`CONE_FTDI_DESCRIPTION`]. Cones with an unprogrammed EEPROM are still found
+//! when they are the only FT4232H connected.
+
+use color_eyre::eyre;
+use ftdi_embedded_hal::libftd2xx::{self, DeviceInfo, DeviceType};
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+/// Environment variable used to select a cone by its FT4232H serial number
+/// (without the port letter), when several cones are connected to the host.
+///
+/// The selected chip doesn't need the cone description, so that bench setups with
+/// an unprogrammed EEPROM work too.
+pub const CONE_FTDI_SERIAL_ENV: &str = "CONE_FTDI_SERIAL";
+
+/// Product description in the cone's FT4232H EEPROM. Ports show up as
+/// `"<description> A"` to `"<description> D"`.
+pub const CONE_FTDI_DESCRIPTION: &str = "Cone";
+
+const PORT_LCD: char = 'A';
+const PORT_LED: char = 'B';
+const PORT_AUX: char = 'C';
+const PORT_BUTTON: char = 'D';
+
+/// The FTDI interfaces of a cone, mapped to their role.
+#[derive(Debug, Clone)]
+pub struct ConeInterfaces {
+    /// FT4232H serial number, without the port letter.
+    pub serial_number: String,
+    pub lcd: DeviceInfo,
+    pub led: DeviceInfo,
+    /// Not used by the cone, only reset on startup.
+    pub aux: DeviceInfo,
+    pub button: DeviceInfo,
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum DiscoveryError {
+    #[error("no cone found: cone not connected?")]
+    NotFound,
+    #[error("no cone found with serial number {0}")]
+    SerialNotFound(String),
+    #[error("several cones found ({}), set CONE_FTDI_SERIAL", .0.join(", "))]
+    Ambiguous(Vec<String>),
+    #[error("cone {serial_number} is missing port {port}")]
+    MissingPort { serial_number: String, port: char },
+}
+
+/// Lists the FTDI devices connected to the host and resolves the cone interfaces,
+/// honoring [`CONE_FTDI_SERIAL_ENV`].
+pub fn discover() -> eyre::Result<ConeInterfaces> {
+    let devices = libftd2xx::list_devices()?;
+    for device in devices.iter() {
+        tracing::trace!("FTDI device: {:?}", device);
+    }
+    let serial_number = std::env::var(CONE_FTDI_SERIAL_ENV).ok();
+    let interfaces = find_cone(&devices, serial_number.as_deref())?;
+    tracing::debug!("Cone found: {}", interfaces.serial_number);
+
+    Ok(interfaces)
+}
+
+/// Resolves the cone interfaces from a list of FTDI devices.
+///
+/// If `serial_number` is `None`, exactly one chip with the cone description must
+/// be present, or, if none has it, exactly one FT4232H. Otherwise the FT4232H with
+/// that serial number is used, whatever its description.
+pub fn find_cone(
+    devices: &[DeviceInfo],
+    serial_number: Option<&str>,
+) -> Result<ConeInterfaces, DiscoveryError> {
+    // chip serial number -> port letter -> device
+    let mut chips: BTreeMap<&str, BTreeMap<char, &DeviceInfo>> = BTreeMap::new();
+    let mut stock_chips: BTreeMap<&str, BTreeMap<char, &DeviceInfo>> = BTreeMap::new();
+    for device in devices {
+        let Some((chip, port)) = split_port(device) else {
+            continue;
+        };
+        if serial_number.is_none() && !has_cone_description(device, port) {
+            stock_chips.entry(chip).or_default().insert(port, device);
+            continue;
+        }
+        chips.entry(chip).or_default().insert(port, device);
+    }
+    if chips.is_empty() && !stock_chips.is_empty() {
+        tracing::warn!("no FT4232H programmed as a cone, trying stock FT4232H");
+        chips = stock_chips;
+    }
+
+    let (chip, ports) = match serial_number {
+        Some(serial_number) => chips
+            .remove_entry(serial_number)
+            .ok_or_else(|| DiscoveryError::SerialNotFound(serial_number.to_owned()))?,
+        None if chips.len() > 1 => {
+            return Err(DiscoveryError::Ambiguous(
+                chips.keys().map(|chip| chip.to_string()).collect(),
+            ));
+        }
+        None => chips.pop_first().ok_or(DiscoveryError::NotFound)?,
+    };
+
+    let port = |port: char| {
+        ports
+            .get(&port)
+            .map(|device| (*device).clone())
+            .ok_or_else(|| DiscoveryError::MissingPort {
+                serial_number: chip.to_owned(),
+                port,
+            })
+    };
+
+    Ok(ConeInterfaces {
+        serial_number: chip.to_owned(),
+        lcd: port(PORT_LCD)?,
+        led: port(PORT_LED)?,
+        aux: port(PORT_AUX)?,
+        button: port(PORT_BUTTON)?,
+    })
+}
+
+/// Splits an FT4232H port serial number into the chip serial number and the port
+/// letter. Returns `None` for any other device.
+fn split_port(device: &DeviceInfo) -> Option<(&str, char)> {
+    if device.device_type != DeviceType::FT4232H {
+        return None;
+    }
+    let port = device.serial_number.chars().last()?;
+    if !('A'..='D').contains(&port) || !device.description.ends_with(port) {
+        return None;
+    }
+    let chip = &device.serial_number[..device.serial_number.len() - 1];
+    if chip.is_empty() {
+        return None;
+    }
+
+    Some((chip, port))
+}
+
+/// Whether `device` is port `port` of a chip programmed as a cone.
+fn has_cone_description(device: &DeviceInfo, port: char) -> bool {
+    device.description == format!("{CONE_FTDI_DESCRIPTION} {port}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ft4232h(chip: &str, port: char) -> DeviceInfo {
+        DeviceInfo {
+            port_open: false,
+            speed: None,
+            device_type: DeviceType::FT4232H,
+            vendor_id: 0x0403,
+            product_id: 0x6011,
+            serial_number: format!("{chip}{port}"),
+            description: format!("{CONE_FTDI_DESCRIPTION} {port}"),
+        }
+    }
+
+    fn cone(chip: &str) -> Vec<DeviceInfo> {
+        ['A', 'B', 'C', 'D']
+            .into_iter()
+            .map(|port| ft4232h(chip, port))
+            .collect()
+    }
+
+    /// An FT4232H with the stock EEPROM.
+    fn stock_ft4232h(chip: &str) -> Vec<DeviceInfo> {
+        cone(chip)
+            .into_iter()
+            .map(|device| DeviceInfo {
+                description: format!(
+                    "Quad RS232-HS {}",
+                    device.serial_number.chars().last().unwrap()
+                ),
+                ..device
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_find_single_cone() {
+        // enumeration order doesn't matter
+        let mut devices = cone("FT1234");
+        devices.reverse();
+        devices.insert(
+            2,
+            DeviceInfo {
+                device_type: DeviceType::FT232R,
+                serial_number: "A10ABCDE".to_string(),
+                description: "FT232R USB UART".to_string(),
+                ..ft4232h("", 'A')
+            },
+        );
+
+        let cone = find_cone(&devices, None).unwrap();
+        assert_eq!(cone.serial_number, "FT1234");
+        assert_eq!(cone.lcd.serial_number, "FT1234A");
+        assert_eq!(cone.led.serial_number, "FT1234B");
+        assert_eq!(cone.aux.serial_number, "FT1234C");
+        assert_eq!(cone.button.serial_number, "FT1234D");
+    }
+
+    #[test]
+    fn test_no_cone() {
+        assert_eq!(find_cone(&[], None).unwrap_err(), DiscoveryError::NotFound);
+        assert_eq!(
+            find_cone(&[], Some("FT1234")).unwrap_err(),
+            DiscoveryError::SerialNotFound("FT1234".to_string())
+        );
+    }
+
+    #[test]
+    fn test_missing_port() {
+        let devices: Vec<_> = cone("FT1234")
+            .into_iter()
+            .filter(|d| !d.serial_number.ends_with('B'))
+            .collect();
+
+        assert_eq!(
+            find_cone(&devices, None).unwrap_err(),
+            DiscoveryError::MissingPort {
+                serial_number: "FT1234".to_string(),
+                port: 'B'
+            }
+        );
+    }
+
+    #[test]
+    fn test_multiple_cones() {
+        let devices = [cone("FT5678"), cone("FT1234")].concat();
+
+        assert_eq!(
+            find_cone(&devices, None).unwrap_err(),
+            DiscoveryError::Ambiguous(vec!["FT1234".to_string(), "FT5678".to_string()])
+        );
+
+        let cone = find_cone(&devices, Some("FT5678")).unwrap();
+        assert_eq!(cone.serial_number, "FT5678");
+        assert_eq!(cone.lcd.serial_number, "FT5678A");
+        assert_eq!(cone.button.serial_number, "FT5678D");
+
+        assert_eq!(
+            find_cone(&devices, Some("FT0000")).unwrap_err(),
+            DiscoveryError::SerialNotFound("FT0000".to_string())
+        );
+    }
+
+    #[test]
+    fn test_description_must_match_port() {
+        let mut devices = cone("FT1234");
+        devices[0].description = format!("{CONE_FTDI_DESCRIPTION} B");
+
+        assert_eq!(
+            find_cone(&devices, None).unwrap_err(),
+            DiscoveryError::MissingPort {
+                serial_number: "FT1234".to_string(),
+                port: 'A'
+            }
+        );
+    }
+
+    #[test]
+    fn test_other_ft4232h_next_to_cone() {
+        // Interleaved, like two chips enumerated in arbitrary order.
+        let devices: Vec<_> = stock_ft4232h("FT5678")
+            .into_iter()
+            .zip(cone("FT1234"))
+            .flat_map(|(other, cone)| [other, cone])
+            .collect();
+
+        let cone = find_cone(&devices, None).unwrap();
+        assert_eq!(cone.serial_number, "FT1234");
+        assert_eq!(cone.lcd.serial_number, "FT1234A");
+        assert_eq!(cone.button.serial_number, "FT1234D");
+
+        // An explicit serial number also selects a chip with the stock EEPROM.
+        let cone = find_cone(&devices, Some("FT5678")).unwrap();
+        assert_eq!(cone.serial_number, "FT5678");
+        assert_eq!(cone.led.serial_number, "FT5678B");
+
+        // Several chips with the stock EEPROM can't be told apart.
+        let devices = [stock_ft4232h("FT5678"), stock_ft4232h("FT1234")].concat();
+        assert_eq!(
+            find_cone(&devices, None).unwrap_err(),
+            DiscoveryError::Ambiguous(vec!["FT1234".to_string(), "FT5678".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_single_stock_ft4232h() {
+        let mut devices = stock_ft4232h("FT1234");
+        devices.reverse();
+
+        let cone = find_cone(&devices, None).unwrap();
+        assert_eq!(cone.serial_number, "FT1234");
+        assert_eq!(cone.lcd.serial_number, "FT1234A");
+        assert_eq!(cone.led.serial_number, "FT1234B");
+        assert_eq!(cone.aux.serial_number, "FT1234C");
+        assert_eq!(cone.button.serial_number, "FT1234D");
+    }
+}
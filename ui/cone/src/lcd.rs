@@ -1,11 +1,12 @@
-use crate::CONE_FTDI_LCD_INDEX;
 use color_eyre::eyre;
 use color_eyre::eyre::Context;
+use embedded_graphics::mono_font::{ascii::FONT_10X20, MonoTextStyle};
 use embedded_graphics::pixelcolor::Rgb565;
 use embedded_graphics::primitives::{PrimitiveStyleBuilder, Rectangle};
+use embedded_graphics::text::{Alignment, Baseline, Text, TextStyleBuilder};
 use embedded_graphics::{image::Image, prelude::*};
 use ftdi_embedded_hal::eh1::digital::OutputPin;
-use ftdi_embedded_hal::libftd2xx::{Ft4232h, Ftdi, FtdiCommon};
+use ftdi_embedded_hal::libftd2xx::{DeviceInfo, Ft4232h, Ftdi, FtdiCommon};
 use ftdi_embedded_hal::{Delay, SpiDevice};
 use gc9a01::{mode::BufferedGraphics, prelude::*, Gc9a01, SPIDisplayInterface};
 use image::{ImageFormat, Luma};
@@ -33,6 +34,13 @@ pub struct LcdJoinHandle(pub JoinHandle<eyre::Result<()>>);
 /// never be a blocker.
 const LCD_COMMAND_CHANNEL_SIZE: usize = 2;
 
+/// Maximum number of characters per line of text, so that the text
+/// fits within the round display with [`FONT_10X20`].
+const LCD_TEXT_MAX_CHARS: usize = 16;
+/// Maximum number of lines of text, extra lines are replaced by an ellipsis.
+const LCD_TEXT_MAX_LINES: usize = 8;
+const LCD_TEXT_ELLIPSIS: &str = "...";
+
 /// Lcd handle to send commands to the LCD screen.
 ///
 /// The LCD is controlled by a separate task.
@@ -53,6 +61,14 @@ pub enum LcdCommand {
     ImageBmp(Vec<u8>, Rgb565),
     /// Fill the LCD with a color
     Fill(Rgb565),
+    /// Display lines of text, centered on the screen.
+    /// Lines too long for the display are wrapped and the text is truncated
+    /// with an ellipsis if it doesn't fit.
+    Text {
+        lines: Vec<String>,
+        fg: Rgb565,
+        bg: Rgb565,
+    },
 }
 
 #[derive(Error, Debug)]
@@ -117,12 +133,14 @@ impl TryFrom<String> for LcdCommand {
 }
 
 impl Lcd {
-    pub(crate) fn spawn() -> eyre::Result<(Lcd, LcdJoinHandle)> {
+    pub(crate) fn spawn(device: &DeviceInfo) -> eyre::Result<(Lcd, LcdJoinHandle)> {
         let (cmd_tx, mut cmd_rx) = mpsc::channel(LCD_COMMAND_CHANNEL_SIZE);
         let (kill_tx, kill_rx) = oneshot::channel();
 
-        let task_handle =
-            task::spawn_blocking(move || do_lcd_update(&mut cmd_rx, kill_rx));
+        let serial_number = device.serial_number.clone();
+        let task_handle = task::spawn_blocking(move || {
+            do_lcd_update(&serial_number, &mut cmd_rx, kill_rx)
+        });
 
         Ok((Lcd { cmd_tx, kill_tx }, LcdJoinHandle(task_handle)))
     }
@@ -134,11 +152,12 @@ impl Lcd {
 
 /// Entry point for the lcd update task
 fn do_lcd_update(
+    serial_number: &str,
     cmd_rx: &mut mpsc::Receiver<LcdCommand>,
     mut kill_rx: oneshot::Receiver<()>,
 ) -> eyre::Result<()> {
     let mut delay = Delay::new();
-    let mut device: Ft4232h = Ftdi::with_index(CONE_FTDI_LCD_INDEX)?.try_into()?;
+    let mut device: Ft4232h = Ftdi::with_serial_number(serial_number)?.try_into()?;
     device.reset().wrap_err("Failed to reset")?;
     let hal = ftdi_embedded_hal::FtHal::init_freq(device, 30_000_000)?;
     let spi = Box::pin(hal.spi_device(3)?);
@@ -211,6 +230,14 @@ fn do_lcd_update(
                     tracing::warn!("{e:?}");
                 }
             }
+            Some(LcdCommand::Text { lines, fg, bg }) => {
+                if let Err(e) = fill_color(&mut display, bg) {
+                    tracing::warn!("{e:?}");
+                }
+                if let Err(e) = draw_text(&mut display, &lines, fg) {
+                    tracing::warn!("{e:?}");
+                }
+            }
             None => {
                 // cmd channel closed or kill_rx received
                 let _ = bl.set_low();
@@ -224,6 +251,71 @@ fn do_lcd_update(
     }
 }
 
+fn draw_text(
+    display: &mut LcdDisplayDriver,
+    lines: &[String],
+    color: Rgb565,
+) -> eyre::Result<()> {
+    let character_style = MonoTextStyle::new(&FONT_10X20, color);
+    let text_style = TextStyleBuilder::new()
+        .alignment(Alignment::Center)
+        .baseline(Baseline::Middle)
+        .build();
+
+    let lines = layout_text(lines, LCD_TEXT_MAX_CHARS, LCD_TEXT_MAX_LINES);
+    let line_height = FONT_10X20.character_size.height as i32;
+    let x = DisplayResolution240x240::WIDTH as i32 / 2;
+    // vertical center of the first line, so that the text block is centered
+    let y = (DisplayResolution240x240::HEIGHT as i32
+        - line_height * lines.len() as i32
+        + line_height)
+        / 2;
+    for (i, line) in lines.iter().enumerate() {
+        let position = Point::new(x, y + line_height * i as i32);
+        Text::with_text_style(line, position, character_style, text_style)
+            .draw(display)
+            .map_err(|e| eyre::eyre!("Error drawing text: {e:?}"))?;
+    }
+
+    Ok(())
+}
+
+/// Wraps `lines` on word boundaries so that each line is at most `max_chars` long,
+/// words longer than a line are split.
+/// If the result doesn't fit in `max_lines`, the last line is truncated with an
+/// ellipsis.
+fn layout_text(lines: &[String], max_chars: usize, max_lines: usize) -> Vec<String> {
+    let mut wrapped = Vec::new();
+    for line in lines {
+        let mut current = String::new();
+        for word in line.split_whitespace() {
+            let word: Vec<char> = word.chars().collect();
+            for chunk in word.chunks(max_chars) {
+                let current_len = current.chars().count();
+                if current_len > 0 && current_len + 1 + chunk.len() > max_chars {
+                    wrapped.push(std::mem::take(&mut current));
+                }
+                if !current.is_empty() {
+                    current.push(' ');
+                }
+                current.extend(chunk);
+            }
+        }
+        // keep empty lines, used as spacing
+        wrapped.push(current);
+    }
+
+    if wrapped.len() > max_lines {
+        wrapped.truncate(max_lines);
+        if let Some(last) = wrapped.last_mut() {
+            let keep = max_chars.saturating_sub(LCD_TEXT_ELLIPSIS.len());
+            *last = last.chars().take(keep).collect::<String>() + LCD_TEXT_ELLIPSIS;
+        }
+    }
+
+    wrapped
+}
+
 fn fill_color(display: &mut LcdDisplayDriver, color: Rgb565) -> eyre::Result<()> {
     Rectangle::new(
         Point::new(0, 0),
@@ -236,3 +328,53 @@ fn fill_color(display: &mut LcdDisplayDriver, color: Rgb565) -> eyre::Result<()>
     .draw(display)
     .map_err(|e| eyre::eyre!("Error drawing the rectangle: {e:?}"))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|l| l.to_string()).collect()
+    }
+
+    #[test]
+    fn test_layout_text_short_lines() {
+        let text = lines(&["UPDATING 42%", "", "NO NETWORK"]);
+        assert_eq!(layout_text(&text, 16, 8), text);
+    }
+
+    #[test]
+    fn test_layout_text_wraps_words() {
+        let text = lines(&["please wait while the orb is updating"]);
+        assert_eq!(
+            layout_text(&text, 16, 8),
+            lines(&["please wait", "while the orb is", "updating"])
+        );
+    }
+
+    #[test]
+    fn test_layout_text_splits_long_words() {
+        let text = lines(&["id: 0123456789abcdef0123"]);
+        assert_eq!(
+            layout_text(&text, 16, 8),
+            lines(&["id:", "0123456789abcdef", "0123"])
+        );
+    }
+
+    #[test]
+    fn test_layout_text_clamps_with_ellipsis() {
+        let text: Vec<String> = (0..20).map(|i| format!("line number {i}")).collect();
+        let layout = layout_text(&text, 16, 8);
+
+        assert_eq!(layout.len(), 8);
+        assert_eq!(layout[6], "line number 6");
+        assert_eq!(layout[7], "line number 7...");
+        assert!(layout.iter().all(|l| l.chars().count() <= 16));
+    }
+
+    #[test]
+    fn test_layout_text_empty() {
+        assert!(layout_text(&[], 16, 8).is_empty());
+        assert_eq!(layout_text(&lines(&["   "]), 16, 8), lines(&[""]));
+    }
+}
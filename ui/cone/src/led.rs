@@ -1,14 +1,25 @@
-use crate::CONE_FTDI_LED_INDEX;
 use color_eyre::eyre;
 use color_eyre::eyre::{eyre, Context};
 use ftdi_embedded_hal::eh1::spi::SpiBus;
-use ftdi_embedded_hal::libftd2xx::{Ft4232h, Ftdi, FtdiCommon};
+use ftdi_embedded_hal::libftd2xx::{DeviceInfo, Ft4232h, Ftdi, FtdiCommon};
 use orb_rgb::Argb;
+use std::time::Duration;
 use tokio::sync::{mpsc, oneshot};
 use tokio::task;
+use tokio::time::Instant;
 
 pub const CONE_LED_COUNT: usize = 64;
 
+/// Default maximum number of frames per second written to the LED strip.
+pub const DEFAULT_MAX_FPS: u32 = 60;
+/// Default cap on the estimated current drawn by the LED strip, in milliamps.
+pub const DEFAULT_MAX_CURRENT_MA: u32 = 1000;
+/// Approximate current drawn by a single color channel of an APA102 LED, at full
+/// value and full global brightness, in microamps.
+const CHANNEL_MAX_CURRENT_UA: u32 = 20_000;
+/// Maximum value of the APA102 global brightness field.
+const MAX_DIMMING: u8 = 0x1F;
+
 /// LED strip handle.
 /// To send new values to the LED strip.
 #[derive(Debug)]
@@ -16,6 +27,47 @@ pub struct LedStrip {
     /// Used to signal that the task should be cleanly terminated.
     pub kill_tx: oneshot::Sender<()>,
     tx: mpsc::Sender<[Argb; CONE_LED_COUNT]>,
+    control_tx: mpsc::Sender<LedControl>,
+}
+
+/// Limits applied by the LED strip task to the frames it receives.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct LedConfig {
+    /// Frames received faster than this are coalesced, only the latest one is
+    /// written.
+    pub max_fps: u32,
+    /// Frames whose estimated current is above this are dimmed down to it.
+    pub max_current_ma: u32,
+}
+
+impl Default for LedConfig {
+    fn default() -> Self {
+        Self {
+            max_fps: DEFAULT_MAX_FPS,
+            max_current_ma: DEFAULT_MAX_CURRENT_MA,
+        }
+    }
+}
+
+/// Changes the [`LedConfig`] of a running LED strip task.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LedControl {
+    SetMaxFps(u32),
+    SetMaxCurrent { milliamps: u32 },
+}
+
+impl LedConfig {
+    fn apply(&mut self, control: LedControl) {
+        match control {
+            LedControl::SetMaxFps(max_fps) => self.max_fps = max_fps,
+            LedControl::SetMaxCurrent { milliamps } => self.max_current_ma = milliamps,
+        }
+    }
+
+    /// Minimum time between two writes to the LED strip.
+    fn frame_period(&self) -> Duration {
+        Duration::from_secs(1) / self.max_fps.max(1)
+    }
 }
 
 #[derive(Debug)]
@@ -25,18 +77,24 @@ pub struct LedJoinHandle(pub task::JoinHandle<eyre::Result<()>>);
 /// If the receiver is full, the frame should be dropped, so that any new frame containing
 /// the latest state can be sent once the receiver is ready to receive them.
 const LED_CHANNEL_SIZE: usize = 2;
+const LED_CONTROL_CHANNEL_SIZE: usize = 4;
 
 impl LedStrip {
-    pub(crate) fn spawn() -> eyre::Result<(Self, LedJoinHandle)> {
+    pub(crate) fn spawn(
+        device: &DeviceInfo,
+        mut config: LedConfig,
+    ) -> eyre::Result<(Self, LedJoinHandle)> {
         let (tx, mut rx) = mpsc::channel(LED_CHANNEL_SIZE);
+        let (control_tx, mut control_rx) = mpsc::channel(LED_CONTROL_CHANNEL_SIZE);
         let (kill_tx, mut kill_rx) = oneshot::channel();
+        let serial_number = device.serial_number.clone();
 
         // spawn receiver thread
         // where SPI communication happens
         let task = task::spawn_blocking(move || {
             let spi = {
                 let mut device: Ft4232h =
-                    Ftdi::with_index(CONE_FTDI_LED_INDEX)?.try_into()?;
+                    Ftdi::with_serial_number(&serial_number)?.try_into()?;
                 device.reset().wrap_err("Failed to reset")?;
                 let hal = ftdi_embedded_hal::FtHal::init_freq(device, 3_000_000)?;
                 hal.spi()?
@@ -45,41 +103,118 @@ impl LedStrip {
             let mut led = Apa102 { spi };
 
             let rt = tokio::runtime::Handle::current();
+            let mut last_update: Option<Instant> = None;
             loop {
-                // todo do we want to update the LED strip at a fixed rate?
-                // todo do we want to only take the last message and ignore previous ones
                 let msg = rt.block_on(async {
-                    tokio::select! {
-                        _ = &mut kill_rx => {
-                            tracing::trace!("led task killed");
-                            None
+                    loop {
+                        tokio::select! {
+                            _ = &mut kill_rx => {
+                                tracing::trace!("led task killed");
+                                return None;
+                            }
+                            Some(control) = control_rx.recv() => {
+                                tracing::debug!("led strip control: {:?}", control);
+                                config.apply(control);
+                            }
+                            msg = rx.recv() => return msg,
                         }
-                        msg = rx.recv() => msg,
                     }
                 });
-
-                match msg {
-                    Some(values) => {
-                        tracing::trace!("led strip values: {:?}", values);
-                        if let Err(e) = led.spi_rgb_led_update_rgb(&values) {
-                            tracing::debug!("Failed to update LED strip: {e}");
-                        } else {
-                            tracing::trace!("LED strip updated");
+                let Some(mut values) = msg else {
+                    return Ok(());
+                };
+
+                // Hold the frame back until the frame rate allows writing it,
+                // then only keep the latest frame received meanwhile.
+                if let Some(last_update) = last_update {
+                    let next_update = last_update + config.frame_period();
+                    let killed = rt.block_on(async {
+                        tokio::select! {
+                            _ = &mut kill_rx => true,
+                            () = tokio::time::sleep_until(next_update) => false,
                         }
+                    });
+                    if killed {
+                        tracing::trace!("led task killed");
+                        return Ok(());
                     }
-                    None => return Ok(()),
+                }
+                while let Ok(control) = control_rx.try_recv() {
+                    config.apply(control);
+                }
+                while let Ok(newer) = rx.try_recv() {
+                    values = newer;
+                }
+                last_update = Some(Instant::now());
+
+                limit_current(&mut values, config.max_current_ma);
+                tracing::trace!("led strip values: {:?}", values);
+                if let Err(e) = led.spi_rgb_led_update_rgb(&values) {
+                    tracing::debug!("Failed to update LED strip: {e}");
+                } else {
+                    tracing::trace!("LED strip updated");
                 }
             }
         });
 
         tracing::debug!("LED strip initialized");
 
-        Ok((LedStrip { tx, kill_tx }, LedJoinHandle(task)))
+        Ok((
+            LedStrip {
+                tx,
+                control_tx,
+                kill_tx,
+            },
+            LedJoinHandle(task),
+        ))
     }
 
     pub fn tx(&self) -> &mpsc::Sender<[Argb; CONE_LED_COUNT]> {
         &self.tx
     }
+
+    /// Changes the maximum frame rate of the running LED strip task.
+    pub fn set_max_fps(&self, max_fps: u32) -> eyre::Result<()> {
+        self.control_tx
+            .try_send(LedControl::SetMaxFps(max_fps))
+            .wrap_err("unable to send led control")
+    }
+
+    /// Changes the current cap of the running LED strip task.
+    pub fn set_max_current(&self, milliamps: u32) -> eyre::Result<()> {
+        self.control_tx
+            .try_send(LedControl::SetMaxCurrent { milliamps })
+            .wrap_err("unable to send led control")
+    }
+}
+
+/// Estimates the current drawn by the LED strip to display `pixels`, in
+/// microamps. Quiescent current is not included.
+fn estimate_current_ua(pixels: &[Argb]) -> u32 {
+    pixels
+        .iter()
+        .map(|pixel| {
+            let dimming = u32::from(pixel.0.unwrap_or(MAX_DIMMING) & MAX_DIMMING);
+            let channels = u32::from(pixel.1) + u32::from(pixel.2) + u32::from(pixel.3);
+            channels * dimming * CHANNEL_MAX_CURRENT_UA
+                / (u32::from(u8::MAX) * u32::from(MAX_DIMMING))
+        })
+        .sum()
+}
+
+/// Scales `pixels` down, if needed, so that their estimated current stays below
+/// `max_current_ma`.
+fn limit_current(pixels: &mut [Argb], max_current_ma: u32) {
+    let max_current_ua = u64::from(max_current_ma) * 1000;
+    let current_ua = u64::from(estimate_current_ua(pixels));
+    if current_ua <= max_current_ua {
+        return;
+    }
+    tracing::trace!("scaling LED strip down from {current_ua}uA to {max_current_ua}uA");
+    let scale = |value: u8| (u64::from(value) * max_current_ua / current_ua) as u8;
+    for pixel in pixels.iter_mut() {
+        *pixel = Argb(pixel.0, scale(pixel.1), scale(pixel.2), scale(pixel.3));
+    }
 }
 
 /// APA102 LEDs
@@ -248,4 +383,64 @@ mod tests {
         // Check end frame (at least 8 bytes of 0xFF)
         assert_eq!(&written[written.len() - 5..], &[0xFF; 5]);
     }
+
+    #[test]
+    fn test_estimate_current() {
+        assert_eq!(
+            estimate_current_ua(&[Argb(None, 0, 0, 0); CONE_LED_COUNT]),
+            0
+        );
+        // full white: 3 channels at 20mA
+        assert_eq!(estimate_current_ua(&[Argb(None, 255, 255, 255)]), 60_000);
+        assert_eq!(
+            estimate_current_ua(&[Argb(Some(MAX_DIMMING), 255, 255, 255)]),
+            60_000
+        );
+        assert_eq!(
+            estimate_current_ua(&[Argb(None, 255, 255, 255); CONE_LED_COUNT]),
+            60_000 * CONE_LED_COUNT as u32
+        );
+        // a single channel, at full and reduced global brightness
+        assert_eq!(estimate_current_ua(&[Argb(None, 255, 0, 0)]), 20_000);
+        assert_eq!(estimate_current_ua(&[Argb(Some(0), 255, 0, 0)]), 0);
+        assert_eq!(estimate_current_ua(&[Argb(Some(10), 0, 255, 0)]), 6_451);
+        assert_eq!(estimate_current_ua(&[Argb(None, 0, 0, 51)]), 4_000);
+    }
+
+    #[test]
+    fn test_limit_current() {
+        // below the cap, unchanged
+        let mut pixels = [Argb(Some(10), 255, 128, 64); CONE_LED_COUNT];
+        let original = pixels;
+        limit_current(&mut pixels, DEFAULT_MAX_CURRENT_MA);
+        assert_eq!(pixels, original);
+
+        // full white is 3840mA, scaled down to a quarter
+        let mut pixels = [Argb(None, 255, 255, 255); CONE_LED_COUNT];
+        limit_current(&mut pixels, 960);
+        assert!(pixels.iter().all(|p| *p == Argb(None, 63, 63, 63)));
+        assert!(estimate_current_ua(&pixels) <= 960_000);
+
+        // hue and global brightness are preserved
+        let mut pixels = [Argb(Some(20), 200, 100, 0); CONE_LED_COUNT];
+        limit_current(&mut pixels, 500);
+        assert!(pixels.iter().all(|p| *p == Argb(Some(20), 102, 51, 0)));
+        assert!(estimate_current_ua(&pixels) <= 500_000);
+
+        let mut pixels = [Argb(None, 255, 255, 255); CONE_LED_COUNT];
+        limit_current(&mut pixels, 0);
+        assert!(pixels.iter().all(|p| *p == Argb(None, 0, 0, 0)));
+    }
+
+    #[test]
+    fn test_frame_period() {
+        let mut config = LedConfig::default();
+        assert_eq!(config.frame_period(), Duration::from_secs(1) / 60);
+        config.apply(LedControl::SetMaxFps(10));
+        assert_eq!(config.frame_period(), Duration::from_millis(100));
+        config.apply(LedControl::SetMaxFps(0));
+        assert_eq!(config.frame_period(), Duration::from_secs(1));
+        config.apply(LedControl::SetMaxCurrent { milliamps: 200 });
+        assert_eq!(config.max_current_ma, 200);
+    }
 }
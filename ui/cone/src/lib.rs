@@ -1,25 +1,20 @@
 pub mod button;
+pub mod discovery;
 pub mod lcd;
 pub mod led;
 
-use crate::button::{Button, ButtonJoinHandle};
-use crate::lcd::{Lcd, LcdJoinHandle};
-use crate::led::{LedJoinHandle, LedStrip};
+use crate::button::{Button, ButtonConfig, ButtonJoinHandle};
+use crate::discovery::ConeInterfaces;
+use crate::lcd::{Lcd, LcdCommand, LcdJoinHandle};
+use crate::led::{LedConfig, LedJoinHandle, LedStrip};
 use color_eyre::eyre;
 use color_eyre::eyre::Context;
+use embedded_graphics::pixelcolor::Rgb565;
 use ftdi_embedded_hal::libftd2xx::{Ft4232h, Ftdi, FtdiCommon};
 use futures::FutureExt;
+use std::time::Duration;
 use tokio::sync::broadcast;
 
-const CONE_FTDI_DEVICE_COUNT: usize = 8;
-
-// FIXME: FTDI adapters aren't guaranteed to enumerate in the same order every time.
-// Find a way to access a specific ftdi adapter with either a serial number of some other
-// disambiguating info.
-const CONE_FTDI_LCD_INDEX: i32 = 4;
-const CONE_FTDI_LED_INDEX: i32 = 5;
-const CONE_FTDI_BUTTON_INDEX: i32 = 7;
-
 #[derive(Debug)]
 #[allow(dead_code)]
 enum Status {
@@ -64,6 +59,20 @@ pub enum ButtonState {
     Released,
 }
 
+/// Button events built from the debounced button state, see [`ButtonConfig`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ButtonGesture {
+    /// The button was pressed and released once.
+    Click,
+    /// The button was clicked twice in a row.
+    DoubleClick,
+    /// The button has been held for `duration`, and is still held.
+    LongPress { duration: Duration },
+    /// The button was released after a [`ButtonGesture::LongPress`], having been
+    /// held for `duration`.
+    LongPressReleased { duration: Duration },
+}
+
 #[derive(Debug, Copy, Clone)]
 pub enum ConeState {
     Connected,
@@ -73,29 +82,45 @@ pub enum ConeState {
 #[derive(Debug, Copy, Clone)]
 pub enum ConeEvent {
     Cone(ConeState),
+    /// Raw button transitions, only sent if [`ButtonConfig::raw_events`] is set.
     Button(ButtonState),
+    ButtonGesture(ButtonGesture),
 }
 
 impl Cone {
     /// Create a new Cone instance.
+    ///
+    /// The cone is looked up with [`discovery::discover`], and the LED strip and
+    /// the button use the default [`LedConfig`] and [`ButtonConfig`].
     pub fn spawn(
         event_queue: broadcast::Sender<ConeEvent>,
     ) -> eyre::Result<(Self, ConeJoinHandle)> {
-        if ftdi_embedded_hal::libftd2xx::list_devices()?.len() != CONE_FTDI_DEVICE_COUNT
-        {
-            return Err(eyre::eyre!(
-                "FTDI device count mismatch: cone not connected?"
-            ));
-        } else {
-            let mut device: Ft4232h = Ftdi::with_index(6)?
+        let interfaces = discovery::discover()?;
+        Self::spawn_with_interfaces(
+            &interfaces,
+            LedConfig::default(),
+            ButtonConfig::default(),
+            event_queue,
+        )
+    }
+
+    /// Create a new Cone instance from already resolved FTDI interfaces.
+    pub fn spawn_with_interfaces(
+        interfaces: &ConeInterfaces,
+        led_config: LedConfig,
+        button_config: ButtonConfig,
+        event_queue: broadcast::Sender<ConeEvent>,
+    ) -> eyre::Result<(Self, ConeJoinHandle)> {
+        let mut device: Ft4232h =
+            Ftdi::with_serial_number(&interfaces.aux.serial_number)?
                 .try_into()
                 .wrap_err("Failed to initialize FTDI device")?;
-            device.reset().wrap_err("Failed to reset")?;
-        }
+        device.reset().wrap_err("Failed to reset")?;
 
-        let (lcd, lcd_handle) = Lcd::spawn()?;
-        let (led_strip, led_handle) = LedStrip::spawn()?;
-        let (button, button_handle) = Button::spawn(event_queue.clone())?;
+        let (lcd, lcd_handle) = Lcd::spawn(&interfaces.lcd)?;
+        let (led_strip, led_handle) = LedStrip::spawn(&interfaces.led, led_config)?;
+        let (button, button_handle) =
+            Button::spawn(&interfaces.button, button_config, event_queue.clone())?;
 
         let cone = Cone {
             lcd,
@@ -111,4 +136,18 @@ impl Cone {
 
         Ok((cone, handle))
     }
+
+    /// Queue lines of text to be displayed on the LCD, rendered by the LCD task.
+    /// See [`LcdCommand::Text`].
+    pub fn queue_lcd_text(
+        &self,
+        lines: Vec<String>,
+        fg: Rgb565,
+        bg: Rgb565,
+    ) -> eyre::Result<()> {
+        self.lcd
+            .tx()
+            .try_send(LcdCommand::Text { lines, fg, bg })
+            .wrap_err("unable to send text to lcd")
+    }
 }
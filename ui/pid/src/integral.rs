@@ -4,19 +4,51 @@ use std::ops::Range;
 
 /// Approximation of the integral using Riemann sum. See
 /// <https://en.wikipedia.org/wiki/Riemann_sum>.
+///
+/// The sum can optionally be bounded with [`RiemannSum::set_limits`], in which
+/// case it never accumulates beyond the limits.
 #[derive(Clone, Default, Debug)]
 pub struct RiemannSum {
     sum: f64,
+    limits: Option<(f64, f64)>,
 }
 
 impl RiemannSum {
     /// Adds a new partition of the target function. Returns the current
     /// integral value.
     pub fn add(&mut self, x: f64, dt: f64) -> f64 {
-        self.sum += x * dt;
+        self.sum = self.peek(x, dt);
         self.sum
     }
 
+    /// Returns the integral value [`RiemannSum::add`] would return for the
+    /// given partition, without accumulating it.
+    #[must_use]
+    pub fn peek(&self, x: f64, dt: f64) -> f64 {
+        let sum = self.sum + x * dt;
+        match self.limits {
+            Some((min, max)) => sum.clamp(min, max),
+            None => sum,
+        }
+    }
+
+    /// Returns the current integral value.
+    #[must_use]
+    pub fn value(&self) -> f64 {
+        self.sum
+    }
+
+    /// Bounds the accumulated sum to `min..=max`.
+    ///
+    /// # Panics
+    ///
+    /// If `min` is greater than `max`.
+    pub fn set_limits(&mut self, min: f64, max: f64) {
+        assert!(min <= max, "integral limits must not be inverted");
+        self.limits = Some((min, max));
+        self.sum = self.sum.clamp(min, max);
+    }
+
     /// Resets the sum.
     pub fn reset(&mut self) {
         self.sum = 0.0;
@@ -50,6 +82,18 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_limits() {
+        let mut integral = RiemannSum::default();
+        integral.set_limits(-1.0, 2.0);
+        for _ in 0..100 {
+            integral.add(1.0, 0.1);
+        }
+        assert_abs_diff_eq!(integral.value(), 2.0);
+        assert_abs_diff_eq!(integral.peek(-5.0, 0.1), 1.5);
+        assert_abs_diff_eq!(integral.add(-100.0, 0.1), -1.0);
+    }
+
     #[test]
     fn test_trigonometry() {
         // Definite integral from 1 to 3 of cos(x) dx is -0.7.
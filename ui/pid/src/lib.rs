@@ -1,7 +1,6 @@
 //! Universal [PID controller](https://en.wikipedia.org/wiki/PID_controller).
 //!
-//! This implementation has minimal constant memory footprint of only 80
-//! bytes. It implements Proportional, Integral, Derivative terms, and also an
+//! This implementation has minimal constant memory footprint. It implements Proportional, Integral, Derivative terms, and also an
 //! adjustable low-pass filter for Derivative term.
 //!
 //! If one of the terms is not needed, it's preferable not to set it at all, as
@@ -24,6 +23,21 @@
 //! average interval between the PID controller updates, and `N` is an empiric
 //! constant in order of `20`.
 //!
+//! By default the *Derivative* term is computed on the error, so a step change
+//! of the setpoint produces a one-sample spike of the control variable
+//! ("derivative kick"). With [`Pid::with_derivative_on_measurement`] it is
+//! computed on the negated process value instead, which has the same damping
+//! effect while the setpoint is constant, but ignores setpoint changes.
+//!
+//! # Limits
+//!
+//! The control variable can be clamped with [`Pid::with_output_limits`], to
+//! match the range of the actuator. While the output is saturated, the
+//! *Integral* term stops accumulating in the direction of the saturation
+//! (conditional integration), so that the controller recovers as soon as the
+//! setpoint becomes reachable again instead of overshooting. The accumulated
+//! integral itself can also be bounded with [`Pid::with_integral_limits`].
+//!
 //! # Variable Names
 //!
 //! `setpoint` (SP) is the desired value, the PID controller should eventually
@@ -79,6 +93,8 @@ pub struct Pid {
     integral: Option<f64>,
     derivative: Option<f64>,
     rc: f64,
+    output_limits: Option<(f64, f64)>,
+    derivative_on_measurement: bool,
     filter: LowPassFilter,
     sum: RiemannSum,
 }
@@ -120,6 +136,39 @@ impl Pid {
         self
     }
 
+    /// Sets whether the derivative term is computed on the process value
+    /// instead of the error. This method takes self by value and allows
+    /// chaining.
+    #[must_use]
+    pub fn with_derivative_on_measurement(mut self, enabled: bool) -> Self {
+        self.set_derivative_on_measurement(enabled);
+        self
+    }
+
+    /// Sets the limits of the control variable. This method takes self by value
+    /// and allows chaining.
+    ///
+    /// # Panics
+    ///
+    /// If `min` is greater than `max`.
+    #[must_use]
+    pub fn with_output_limits(mut self, min: f64, max: f64) -> Self {
+        self.set_output_limits(min, max);
+        self
+    }
+
+    /// Sets the limits of the accumulated integral, before applying the integral
+    /// gain. This method takes self by value and allows chaining.
+    ///
+    /// # Panics
+    ///
+    /// If `min` is greater than `max`.
+    #[must_use]
+    pub fn with_integral_limits(mut self, min: f64, max: f64) -> Self {
+        self.set_integral_limits(min, max);
+        self
+    }
+
     /// Sets the proportional gain. This method takes self by mutable reference
     /// and allows chaining.
     pub fn set_proportional(&mut self, proportional: f64) -> &mut Self {
@@ -153,6 +202,43 @@ impl Pid {
         self
     }
 
+    /// Sets whether the derivative term is computed on the process value
+    /// instead of the error. This method takes self by mutable reference and
+    /// allows chaining.
+    ///
+    /// Changing the mode resets the low-pass filter, as its state is specific to
+    /// the differentiated signal.
+    pub fn set_derivative_on_measurement(&mut self, enabled: bool) -> &mut Self {
+        if self.derivative_on_measurement != enabled {
+            self.filter.reset();
+        }
+        self.derivative_on_measurement = enabled;
+        self
+    }
+
+    /// Sets the limits of the control variable. This method takes self by
+    /// mutable reference and allows chaining.
+    ///
+    /// # Panics
+    ///
+    /// If `min` is greater than `max`.
+    pub fn set_output_limits(&mut self, min: f64, max: f64) -> &mut Self {
+        assert!(min <= max, "output limits must not be inverted");
+        self.output_limits = Some((min, max));
+        self
+    }
+
+    /// Sets the limits of the accumulated integral, before applying the integral
+    /// gain. This method takes self by mutable reference and allows chaining.
+    ///
+    /// # Panics
+    ///
+    /// If `min` is greater than `max`.
+    pub fn set_integral_limits(&mut self, min: f64, max: f64) -> &mut Self {
+        self.sum.set_limits(min, max);
+        self
+    }
+
     /// Resets the accumulated state.
     pub fn reset(&mut self) {
         self.filter.reset();
@@ -161,20 +247,149 @@ impl Pid {
 
     /// Advances the PID loop with new `setpoint` and `process` variables, and
     /// the time passed since last invocation `dt`. Returns calculated control
-    /// variable.
+    /// variable, clamped to the output limits if set.
     pub fn advance(&mut self, setpoint: f64, process: f64, dt: f64) -> f64 {
         let error = setpoint - process;
         let mut control = 0.0;
         if let Some(proportional) = self.proportional {
             control += proportional * error;
         }
-        if let Some(integral) = self.integral {
-            control += integral * self.sum.add(error, dt);
-        }
         if let Some(derivative) = self.derivative {
+            // The negated process value has the same slope as the error while the
+            // setpoint is constant.
+            let x = if self.derivative_on_measurement {
+                -process
+            } else {
+                error
+            };
             control +=
-                derivative * self.filter.add_slope(error, dt, self.rc).unwrap_or(0.0);
+                derivative * self.filter.add_slope(x, dt, self.rc).unwrap_or(0.0);
+        }
+        if let Some(integral) = self.integral {
+            let unclamped = control + integral * self.sum.peek(error, dt);
+            // Don't wind up the integral while it only pushes the output further
+            // into saturation.
+            let saturated = self.output_limits.is_some_and(|(min, max)| {
+                unclamped > max && error > 0.0 || unclamped < min && error < 0.0
+            });
+            let sum = if saturated {
+                self.sum.value()
+            } else {
+                self.sum.add(error, dt)
+            };
+            control += integral * sum;
+        }
+        match self.output_limits {
+            Some((min, max)) => control.clamp(min, max),
+            None => control,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_abs_diff_eq;
+
+    const DT: f64 = 0.01;
+
+    /// Runs `pid` against a process proportional to the actuator value, where the
+    /// actuator saturates at `0.0..=1.0`. The setpoint is first unreachable for
+    /// many steps, then becomes reachable. Returns the control values after the
+    /// setpoint change.
+    fn simulate_saturated_actuator(pid: &mut Pid) -> Vec<f64> {
+        let process = |control: f64| 5.0 * control.clamp(0.0, 1.0);
+        let mut control = 0.0;
+        for _ in 0..1000 {
+            control = pid.advance(10.0, process(control), DT);
+        }
+        (0..10)
+            .map(|_| {
+                control = pid.advance(2.0, process(control), DT);
+                control
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_anti_windup_recovers() {
+        let mut pid = Pid::default()
+            .with_proportional(0.1)
+            .with_integral(1.0)
+            .with_output_limits(0.0, 1.0);
+        let controls = simulate_saturated_actuator(&mut pid);
+
+        // Out of saturation right after the setpoint change, and settling at the
+        // actuator value needed to reach the setpoint.
+        assert!(controls.iter().all(|&c| c < 1.0));
+        assert_abs_diff_eq!(controls[9], 0.4, epsilon = 0.05);
+    }
+
+    #[test]
+    fn test_windup_without_limits() {
+        let mut pid = Pid::default().with_proportional(0.1).with_integral(1.0);
+        let controls = simulate_saturated_actuator(&mut pid);
+
+        // The integral wound up and the control is still saturated.
+        assert!(controls.iter().all(|&c| c > 1.0));
+    }
+
+    #[test]
+    fn test_derivative_kick() {
+        let advance_step = |pid: &mut Pid| {
+            pid.advance(0.0, 0.5, DT);
+            pid.advance(1.0, 0.5, DT)
+        };
+
+        let mut on_error = Pid::default().with_derivative(1.0);
+        assert_abs_diff_eq!(advance_step(&mut on_error), 1.0 / DT);
+
+        let mut on_measurement = Pid::default()
+            .with_derivative(1.0)
+            .with_derivative_on_measurement(true);
+        assert_abs_diff_eq!(advance_step(&mut on_measurement), 0.0);
+    }
+
+    #[test]
+    fn test_derivative_on_measurement_damping() {
+        // With a constant setpoint both modes produce the same control.
+        let mut on_error = Pid::default().with_derivative(1.0).with_filter(DT);
+        let mut on_measurement = Pid::default()
+            .with_derivative(1.0)
+            .with_filter(DT)
+            .with_derivative_on_measurement(true);
+        for i in 0..100 {
+            let process = (i as f64 * DT).sin();
+            assert_abs_diff_eq!(
+                on_error.advance(1.0, process, DT),
+                on_measurement.advance(1.0, process, DT),
+                epsilon = 1e-9
+            );
         }
-        control
+    }
+
+    #[test]
+    fn test_output_limits() {
+        let mut pid = Pid::default()
+            .with_proportional(2.0)
+            .with_output_limits(-1.0, 1.0);
+        assert_abs_diff_eq!(pid.advance(10.0, 0.0, DT), 1.0);
+        assert_abs_diff_eq!(pid.advance(-10.0, 0.0, DT), -1.0);
+        assert_abs_diff_eq!(pid.advance(0.25, 0.0, DT), 0.5);
+    }
+
+    #[test]
+    fn test_integral_limits() {
+        let mut pid = Pid::default()
+            .with_integral(2.0)
+            .with_integral_limits(-0.5, 0.5);
+        let mut control = 0.0;
+        for _ in 0..1000 {
+            control = pid.advance(1.0, 0.0, DT);
+        }
+        assert_abs_diff_eq!(control, 1.0);
+
+        pid.reset();
+        assert_abs_diff_eq!(pid.advance(1.0, 0.0, DT), 2.0 * DT);
     }
 }
@@ -2,6 +2,10 @@ use serde::{Deserialize, Serialize};
 use std::ops;
 use std::ops::Add;
 
+pub mod palette;
+
+pub use palette::{Palette, PaletteError};
+
 /// RGB LED color.
 #[derive(Eq, PartialEq, Copy, Clone, Default, Debug, Serialize, Deserialize)]
 pub struct Argb(
@@ -14,43 +18,140 @@ pub struct Argb(
 impl Argb {
     pub fn lerp(self, other: Self, t: f64) -> Self {
         let t = t.clamp(0.0, 1.0);
-        self * (1.0 - t) + other * t
+        self.scale_with_cutoff(1.0 - t) + other.scale_with_cutoff(t)
+    }
+
+    /// Scales the color like `self * rhs`, but turns the LED off when a color
+    /// with several components would be left with a single one.
+    ///
+    /// At low brightness, the rounding residue of a mixed color is often a
+    /// single primary color, which looks wrong, so prefer turning the LED off.
+    pub fn scale_with_cutoff(self, rhs: f64) -> Self {
+        let res = self * rhs;
+        if self.component_count() > 1 && res.component_count() == 1 {
+            Argb::OFF
+        } else {
+            res
+        }
+    }
+
+    fn component_count(self) -> usize {
+        [self.1, self.2, self.3].iter().filter(|&&c| c != 0).count()
+    }
+
+    /// Creates a color from hue (degrees), saturation and value (both `0.0..=1.0`).
+    #[allow(
+        clippy::many_single_char_names,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
+    pub fn from_hsv(h: f64, s: f64, v: f64, dimming: Option<u8>) -> Self {
+        let h = h.rem_euclid(360.0) / 60.0;
+        let s = s.clamp(0.0, 1.0);
+        let v = v.clamp(0.0, 1.0);
+        let c = v * s;
+        let x = c * (1.0 - (h % 2.0 - 1.0).abs());
+        let (r, g, b) = match h as u8 {
+            0 => (c, x, 0.0),
+            1 => (x, c, 0.0),
+            2 => (0.0, c, x),
+            3 => (0.0, x, c),
+            4 => (x, 0.0, c),
+            _ => (c, 0.0, x),
+        };
+        let m = v - c;
+        Argb(
+            dimming,
+            from_unit(r + m),
+            from_unit(g + m),
+            from_unit(b + m),
+        )
+    }
+
+    /// Returns hue (degrees, `0.0..360.0`), saturation and value (both
+    /// `0.0..=1.0`). The dimming value is ignored.
+    pub fn to_hsv(self) -> (f64, f64, f64) {
+        let (r, g, b) = (to_unit(self.1), to_unit(self.2), to_unit(self.3));
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        let delta = max - min;
+        let h = if delta == 0.0 {
+            0.0
+        } else if max == r {
+            60.0 * ((g - b) / delta).rem_euclid(6.0)
+        } else if max == g {
+            60.0 * ((b - r) / delta + 2.0)
+        } else {
+            60.0 * ((r - g) / delta + 4.0)
+        };
+        let s = if max == 0.0 { 0.0 } else { delta / max };
+        (h, s, max)
+    }
+
+    /// Interpolates in HSV space, along the shortest arc of the hue circle.
+    ///
+    /// Avoids the muddy mid-tones of [`Argb::lerp`] when fading between
+    /// saturated colors. The dimming value of `self` is kept.
+    pub fn lerp_hsv(self, other: Self, t: f64) -> Self {
+        let t = t.clamp(0.0, 1.0);
+        let (h0, s0, v0) = self.to_hsv();
+        let (h1, s1, v1) = other.to_hsv();
+        // A grey has no hue of its own, take the one of the other color.
+        let h0 = if s0 == 0.0 { h1 } else { h0 };
+        let h1 = if s1 == 0.0 { h0 } else { h1 };
+        let mut dh = h1 - h0;
+        if dh > 180.0 {
+            dh -= 360.0;
+        } else if dh < -180.0 {
+            dh += 360.0;
+        }
+        Self::from_hsv(h0 + dh * t, s0 + (s1 - s0) * t, v0 + (v1 - v0) * t, self.0)
+    }
+
+    /// Interpolates each channel in linear light, using `gamma` to decode and
+    /// re-encode the channel values (e.g. `2.2`).
+    ///
+    /// The dimming value of `self` is kept.
+    pub fn lerp_gamma(self, other: Self, t: f64, gamma: f64) -> Self {
+        let t = t.clamp(0.0, 1.0);
+        let mix = |a: u8, b: u8| {
+            let a = to_unit(a).powf(gamma);
+            let b = to_unit(b).powf(gamma);
+            from_unit((a + (b - a) * t).powf(gamma.recip()))
+        };
+        Argb(
+            self.0,
+            mix(self.1, other.1),
+            mix(self.2, other.2),
+            mix(self.3, other.3),
+        )
     }
 }
+
+fn to_unit(channel: u8) -> f64 {
+    f64::from(channel) / f64::from(u8::MAX)
+}
+
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn from_unit(value: f64) -> u8 {
+    (value.clamp(0.0, 1.0) * f64::from(u8::MAX)).round() as u8
+}
 impl ops::Mul<f64> for Argb {
     type Output = Self;
 
+    /// Scales each color component by `rhs`, keeping the dimming value.
     #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
     fn mul(self, rhs: f64) -> Self::Output {
-        // at low brightness (low rhs)
-        // prefer to turn LED off if the resulting color has only 1 component
-        // with an initial color that has more than 1 component
-        if ((self.1 != 0) as u8) + ((self.2 != 0) as u8) + ((self.3 != 0) as u8) > 1 {
-            let res = Argb(
-                self.0,
-                ((f64::from(self.1) * rhs) as u8).clamp(0, u8::MAX),
-                ((f64::from(self.2) * rhs) as u8).clamp(0, u8::MAX),
-                ((f64::from(self.3) * rhs) as u8).clamp(0, u8::MAX),
-            );
-            // if result color has only 1 component, prefer to turn LED off
-            if ((res.1 != 0) as u8) + ((res.2 != 0) as u8) + ((res.3 != 0) as u8) == 1 {
-                Argb::OFF
-            } else {
-                res
-            }
-        } else {
-            Argb(
-                self.0,
-                ((f64::from(self.1) * rhs) as u8).clamp(0, u8::MAX),
-                ((f64::from(self.2) * rhs) as u8).clamp(0, u8::MAX),
-                ((f64::from(self.3) * rhs) as u8).clamp(0, u8::MAX),
-            )
-        }
+        Argb(
+            self.0,
+            (f64::from(self.1) * rhs) as u8,
+            (f64::from(self.2) * rhs) as u8,
+            (f64::from(self.3) * rhs) as u8,
+        )
     }
 }
 
 impl ops::MulAssign<f64> for Argb {
-    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
     fn mul_assign(&mut self, rhs: f64) {
         *self = *self * rhs;
     }
@@ -152,3 +253,122 @@ impl Argb {
         self.0 == Some(0) || (self.1 == 0 && self.2 == 0 && self.3 == 0)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-6, "{a} != {b}");
+    }
+
+    #[test]
+    fn test_hsv_known_values() {
+        let cases = [
+            (Argb::FULL_RED, (0.0, 1.0, 1.0)),
+            (Argb::FULL_GREEN, (120.0, 1.0, 1.0)),
+            (Argb::FULL_BLUE, (240.0, 1.0, 1.0)),
+            (Argb::FULL_WHITE, (0.0, 0.0, 1.0)),
+            (Argb::FULL_BLACK, (0.0, 0.0, 0.0)),
+            (Argb(None, 255, 255, 0), (60.0, 1.0, 1.0)),
+            (Argb(None, 255, 0, 255), (300.0, 1.0, 1.0)),
+        ];
+        for (color, (h, s, v)) in cases {
+            let hsv = color.to_hsv();
+            assert_close(hsv.0, h);
+            assert_close(hsv.1, s);
+            assert_close(hsv.2, v);
+            assert_eq!(Argb::from_hsv(h, s, v, None), color);
+        }
+
+        let (_, s, v) = Argb(None, 51, 51, 51).to_hsv();
+        assert_close(s, 0.0);
+        assert_close(v, 0.2);
+        assert_eq!(Argb::from_hsv(0.0, 0.0, 0.2, None), Argb(None, 51, 51, 51));
+        // hue wraps around
+        assert_eq!(Argb::from_hsv(360.0, 1.0, 1.0, None), Argb::FULL_RED);
+        assert_eq!(Argb::from_hsv(-120.0, 1.0, 1.0, None), Argb::FULL_BLUE);
+    }
+
+    #[test]
+    fn test_hsv_round_trip() {
+        for r in (0..=255).step_by(5) {
+            for g in (0..=255).step_by(5) {
+                for b in (0..=255).step_by(5) {
+                    let color = Argb(Some(7), r, g, b);
+                    let (h, s, v) = color.to_hsv();
+                    let back = Argb::from_hsv(h, s, v, color.0);
+                    assert_eq!(back.0, color.0);
+                    for (x, y) in [(back.1, r), (back.2, g), (back.3, b)] {
+                        assert!(x.abs_diff(y) <= 1, "{color:?} -> {back:?}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_lerp_hsv() {
+        let red = Argb(Some(3), 255, 0, 0);
+        let blue = Argb(Some(9), 0, 0, 255);
+        // shortest arc from red (0) to blue (240) goes through magenta (300)
+        assert_eq!(red.lerp_hsv(blue, 0.5), Argb(Some(3), 255, 0, 255));
+        assert_eq!(red.lerp_hsv(blue, 0.0), red);
+        assert_eq!(red.lerp_hsv(blue, 1.0), Argb(Some(3), 0, 0, 255));
+        // fading from a grey keeps the hue of the other color
+        let faded = Argb::FULL_BLACK.lerp_hsv(Argb::FULL_GREEN, 0.5);
+        assert_eq!(faded, Argb(None, 64, 128, 64));
+        assert_close(faded.to_hsv().0, 120.0);
+    }
+
+    #[test]
+    fn test_lerp_gamma() {
+        let black = Argb(Some(1), 0, 0, 0);
+        let white = Argb(Some(2), 255, 255, 255);
+        assert_eq!(black.lerp_gamma(white, 0.0, 2.2), black);
+        assert_eq!(
+            black.lerp_gamma(white, 1.0, 2.2),
+            Argb(Some(1), 255, 255, 255)
+        );
+        // half of the light is brighter than half of the encoded value
+        let mid = black.lerp_gamma(white, 0.5, 2.2);
+        assert_eq!(mid, Argb(Some(1), 186, 186, 186));
+        // gamma 1.0 is a plain linear interpolation
+        assert_eq!(
+            black.lerp_gamma(white, 0.5, 1.0),
+            Argb(Some(1), 128, 128, 128)
+        );
+    }
+
+    #[test]
+    fn test_mul() {
+        let color = Argb(Some(5), 100, 3, 0);
+        assert_eq!(color * 0.5, Argb(Some(5), 50, 1, 0));
+        assert_eq!(color * 0.1, Argb(Some(5), 10, 0, 0));
+        assert_eq!(color * 0.0, Argb(Some(5), 0, 0, 0));
+        assert_eq!(color * 3.0, Argb(Some(5), 255, 9, 0));
+        let mut assigned = color;
+        assigned *= 0.1;
+        assert_eq!(assigned, color * 0.1);
+    }
+
+    #[test]
+    fn test_scale_with_cutoff() {
+        let color = Argb(Some(5), 100, 3, 0);
+        assert_eq!(color.scale_with_cutoff(0.5), Argb(Some(5), 50, 1, 0));
+        // a single component would be left, turn off instead
+        assert_eq!(color.scale_with_cutoff(0.1), Argb::OFF);
+        // a color with a single component is just scaled
+        let red = Argb(None, 100, 0, 0);
+        assert_eq!(red.scale_with_cutoff(0.1), Argb(None, 10, 0, 0));
+        // all components gone is not a single component
+        assert_eq!(color.scale_with_cutoff(0.0), Argb(Some(5), 0, 0, 0));
+    }
+
+    #[test]
+    fn test_lerp_unchanged() {
+        let amber = Argb::PEARL_USER_AMBER;
+        let white = Argb::PEARL_USER_SIGNUP;
+        assert_eq!(amber.lerp(white, 0.5), Argb(None, 26, 21, 15));
+    }
+}
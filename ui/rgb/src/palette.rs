@@ -0,0 +1,295 @@
+//! Named colors, which can be overridden at runtime from a TOML or JSON file.
+//!
+//! A palette file maps keys to colors:
+//!
+//! ```toml
+//! "diamond.ring.user_capture" = { dim = 10, r = 120, g = 100, b = 4 }
+//! "pearl.ring.user_capture" = { r = 30, g = 20, b = 0 }
+//! ```
+//!
+//! Keys missing from the file keep their built-in value, see [`DEFAULTS`].
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::Argb;
+
+macro_rules! defaults {
+    ($($key:literal => $color:ident,)*) => {
+        /// Built-in colors of the palette, keyed by name.
+        pub const DEFAULTS: &[(&str, Argb)] = &[$(($key, Argb::$color),)*];
+    };
+}
+
+defaults! {
+    "off" => OFF,
+    "operator.dev" => OPERATOR_DEV,
+    "full.red" => FULL_RED,
+    "full.green" => FULL_GREEN,
+    "full.blue" => FULL_BLUE,
+    "full.white" => FULL_WHITE,
+    "full.black" => FULL_BLACK,
+
+    "pearl.operator.amber" => PEARL_OPERATOR_AMBER,
+    "pearl.operator.default" => PEARL_OPERATOR_DEFAULT,
+    "pearl.operator.versions_deprecated" => PEARL_OPERATOR_VERSIONS_DEPRECATED,
+    "pearl.operator.versions_outdated" => PEARL_OPERATOR_VERSIONS_OUTDATED,
+    "pearl.user.amber" => PEARL_USER_AMBER,
+    "pearl.user.qr_scan" => PEARL_USER_QR_SCAN,
+    "pearl.user.red" => PEARL_USER_RED,
+    "pearl.user.signup" => PEARL_USER_SIGNUP,
+    "pearl.user.flash" => PEARL_USER_FLASH,
+    "pearl.ring.operator_qr_scan" => PEARL_RING_OPERATOR_QR_SCAN,
+    "pearl.ring.operator_qr_scan_spinner" => PEARL_RING_OPERATOR_QR_SCAN_SPINNER,
+    "pearl.ring.operator_qr_scan_spinner_operator_based" =>
+        PEARL_RING_OPERATOR_QR_SCAN_SPINNER_OPERATOR_BASED,
+    "pearl.ring.wifi_qr_scan" => PEARL_RING_WIFI_QR_SCAN,
+    "pearl.ring.wifi_qr_scan_spinner" => PEARL_RING_WIFI_QR_SCAN_SPINNER,
+    "pearl.ring.user_qr_scan" => PEARL_RING_USER_QR_SCAN,
+    "pearl.ring.user_qr_scan_spinner" => PEARL_RING_USER_QR_SCAN_SPINNER,
+    "pearl.ring.user_capture" => PEARL_RING_USER_CAPTURE,
+    "pearl.ring.error_salmon" => PEARL_RING_ERROR_SALMON,
+    "pearl.center.summon_user_amber" => PEARL_CENTER_SUMMON_USER_AMBER,
+    "pearl.center.user_capture" => PEARL_CENTER_USER_CAPTURE,
+
+    "diamond.operator.amber" => DIAMOND_OPERATOR_AMBER,
+    "diamond.operator.default" => DIAMOND_OPERATOR_DEFAULT,
+    "diamond.operator.versions_deprecated" => DIAMOND_OPERATOR_VERSIONS_DEPRECATED,
+    "diamond.operator.versions_outdated" => DIAMOND_OPERATOR_VERSIONS_OUTDATED,
+    "diamond.ring.wifi_qr_scan" => DIAMOND_RING_WIFI_QR_SCAN,
+    "diamond.ring.wifi_qr_scan_spinner" => DIAMOND_RING_WIFI_QR_SCAN_SPINNER,
+    "diamond.ring.operator_qr_scan" => DIAMOND_RING_OPERATOR_QR_SCAN,
+    "diamond.ring.operator_qr_scan_spinner" => DIAMOND_RING_OPERATOR_QR_SCAN_SPINNER,
+    "diamond.ring.operator_qr_scan_spinner_operator_based" =>
+        DIAMOND_RING_OPERATOR_QR_SCAN_SPINNER_OPERATOR_BASED,
+    "diamond.ring.user_qr_scan" => DIAMOND_RING_USER_QR_SCAN,
+    "diamond.ring.user_qr_scan_spinner" => DIAMOND_RING_USER_QR_SCAN_SPINNER,
+    "diamond.ring.user_capture" => DIAMOND_RING_USER_CAPTURE,
+    "diamond.ring.error_salmon" => DIAMOND_RING_ERROR_SALMON,
+    "diamond.center.summon_user_amber" => DIAMOND_CENTER_SUMMON_USER_AMBER,
+    "diamond.cone.amber" => DIAMOND_CONE_AMBER,
+}
+
+/// Color as written in a palette file. `dim` is only used on Diamond Orbs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ColorDef {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dim: Option<u8>,
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl From<Argb> for ColorDef {
+    fn from(Argb(dim, r, g, b): Argb) -> Self {
+        Self { dim, r, g, b }
+    }
+}
+
+impl From<ColorDef> for Argb {
+    fn from(ColorDef { dim, r, g, b }: ColorDef) -> Self {
+        Argb(dim, r, g, b)
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum PaletteError {
+    #[error("failed to read palette file {path:?}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("failed to parse TOML palette")]
+    Toml(#[from] toml::de::Error),
+    #[error("failed to parse JSON palette")]
+    Json(#[from] serde_json::Error),
+    #[error("unknown palette key {0:?}")]
+    UnknownKey(String),
+    #[error("dimming value {dim} of {key:?} is above {}", Argb::DIMMING_MAX_VALUE)]
+    Dimming { key: String, dim: u8 },
+}
+
+/// Colors by name, falling back to the built-in [`DEFAULTS`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Palette {
+    colors: HashMap<&'static str, Argb>,
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self {
+            colors: DEFAULTS.iter().copied().collect(),
+        }
+    }
+}
+
+impl Palette {
+    /// Loads overrides from `path`, parsed as JSON if it has a `.json`
+    /// extension, and as TOML otherwise.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, PaletteError> {
+        let path = path.as_ref();
+        let contents =
+            std::fs::read_to_string(path).map_err(|source| PaletteError::Io {
+                path: path.to_owned(),
+                source,
+            })?;
+        if path.extension().is_some_and(|ext| ext == "json") {
+            Self::from_json(&contents)
+        } else {
+            Self::from_toml(&contents)
+        }
+    }
+
+    pub fn from_toml(s: &str) -> Result<Self, PaletteError> {
+        Self::with_overrides(toml::from_str(s)?)
+    }
+
+    pub fn from_json(s: &str) -> Result<Self, PaletteError> {
+        Self::with_overrides(serde_json::from_str(s)?)
+    }
+
+    fn with_overrides(
+        overrides: BTreeMap<String, ColorDef>,
+    ) -> Result<Self, PaletteError> {
+        let mut palette = Self::default();
+        for (key, color) in overrides {
+            if let Some(dim) = color.dim.filter(|&dim| dim > Argb::DIMMING_MAX_VALUE) {
+                return Err(PaletteError::Dimming { key, dim });
+            }
+            let Some((&key, _)) = palette.colors.get_key_value(key.as_str()) else {
+                return Err(PaletteError::UnknownKey(key));
+            };
+            palette.colors.insert(key, color.into());
+        }
+        Ok(palette)
+    }
+
+    /// Returns the color named `key`.
+    ///
+    /// Keys are checked when loading, so an unknown key is a bug: it panics in
+    /// debug builds and turns the LED off otherwise.
+    pub fn get(&self, key: &str) -> Argb {
+        let color = self.colors.get(key).copied();
+        debug_assert!(color.is_some(), "unknown palette key {key:?}");
+        color.unwrap_or(Argb::OFF)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults() {
+        let palette = Palette::default();
+        for &(key, color) in DEFAULTS {
+            assert_eq!(palette.get(key), color, "{key}");
+        }
+        assert_eq!(
+            palette.get("diamond.ring.user_capture"),
+            Argb::DIAMOND_RING_USER_CAPTURE
+        );
+        let mut keys: Vec<_> = DEFAULTS.iter().map(|(key, _)| key).collect();
+        keys.sort_unstable();
+        keys.dedup();
+        assert_eq!(keys.len(), DEFAULTS.len(), "duplicate default keys");
+    }
+
+    #[test]
+    fn test_toml_overrides() {
+        let palette = Palette::from_toml(
+            r#"
+            "diamond.ring.user_capture" = { dim = 10, r = 1, g = 2, b = 3 }
+            "pearl.ring.user_capture" = { r = 4, g = 5, b = 6 }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            palette.get("diamond.ring.user_capture"),
+            Argb(Some(10), 1, 2, 3)
+        );
+        assert_eq!(palette.get("pearl.ring.user_capture"), Argb(None, 4, 5, 6));
+        // missing keys fall back to the built-in colors
+        assert_eq!(palette.get("diamond.cone.amber"), Argb::DIAMOND_CONE_AMBER);
+    }
+
+    #[test]
+    fn test_json_overrides() {
+        let palette = Palette::from_json(
+            r#"{ "full.red": { "dim": 31, "r": 200, "g": 0, "b": 0 } }"#,
+        )
+        .unwrap();
+        assert_eq!(palette.get("full.red"), Argb(Some(31), 200, 0, 0));
+        assert_eq!(palette.get("full.green"), Argb::FULL_GREEN);
+    }
+
+    #[test]
+    fn test_load() {
+        let dir = std::env::temp_dir()
+            .join(format!("orb-rgb-palette-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let toml_path = dir.join("palette.toml");
+        let json_path = dir.join("palette.json");
+        std::fs::write(&toml_path, "\"off\" = { r = 1, g = 1, b = 1 }").unwrap();
+        std::fs::write(&json_path, r#"{ "off": { "r": 2, "g": 2, "b": 2 } }"#).unwrap();
+
+        assert_eq!(
+            Palette::load(&toml_path).unwrap().get("off"),
+            Argb(None, 1, 1, 1)
+        );
+        assert_eq!(
+            Palette::load(&json_path).unwrap().get("off"),
+            Argb(None, 2, 2, 2)
+        );
+        assert!(matches!(
+            Palette::load(dir.join("missing.toml")),
+            Err(PaletteError::Io { .. })
+        ));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_validation_errors() {
+        let cases = [
+            (
+                r#""ring.unknown" = { r = 1, g = 2, b = 3 }"#,
+                "unknown palette key \"ring.unknown\"",
+            ),
+            (
+                r#""off" = { dim = 32, r = 1, g = 2, b = 3 }"#,
+                "dimming value 32 of \"off\" is above 31",
+            ),
+            (
+                r#""off" = { r = 1, g = 2, b = 3, a = 4 }"#,
+                "failed to parse TOML palette",
+            ),
+            (
+                r#""off" = { r = 1, g = 2 }"#,
+                "failed to parse TOML palette",
+            ),
+            (
+                r#""off" = { r = 256, g = 2, b = 3 }"#,
+                "failed to parse TOML palette",
+            ),
+        ];
+        for (i, (input, expected)) in cases.into_iter().enumerate() {
+            let err = Palette::from_toml(input).unwrap_err();
+            assert_eq!(err.to_string(), expected, "{i}th case failed");
+        }
+    }
+
+    #[test]
+    fn test_color_def_round_trip() {
+        for &(key, color) in DEFAULTS {
+            assert_eq!(Argb::from(ColorDef::from(color)), color, "{key}");
+        }
+    }
+}
@@ -134,7 +134,7 @@ impl<const N: usize> Animation for Alert<N> {
                     // starts at intensity 1
                     (t * (PI / 2.0) / period).cos()
                 };
-                color = self.target_color * intensity;
+                color = self.target_color.scale_with_cutoff(intensity);
                 break;
             }
         }
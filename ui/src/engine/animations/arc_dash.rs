@@ -79,7 +79,9 @@ impl<const N: usize> Animation for ArcDash<N> {
     ) -> AnimationState {
         let mut current_color = self.color;
         if let Some(phase) = &mut self.wave_phase {
-            current_color *= (1.0 - phase.cos()) / 2.0 * (1.0 - WAVE_MIN) + WAVE_MIN;
+            current_color = current_color.scale_with_cutoff(
+                (1.0 - phase.cos()) / 2.0 * (1.0 - WAVE_MIN) + WAVE_MIN,
+            );
             *phase = (*phase + dt * WAVE_SPEED) % (PI * 2.0);
         } else if let Some(phase) = &mut self.flash_phase {
             if N == PEARL_RING_LED_COUNT {
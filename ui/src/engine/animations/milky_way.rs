@@ -156,7 +156,7 @@ impl<const N: usize> Animation for MilkyWay<N> {
                 self.phase += dt;
                 let scaling_factor = (self.transition_time / duration * PI / 2.0).cos();
                 for (led, background_led) in frame.iter_mut().zip(&self.frame) {
-                    *led = *background_led * scaling_factor;
+                    *led = background_led.scale_with_cutoff(scaling_factor);
                 }
                 if self.phase >= duration {
                     AnimationState::Finished
@@ -169,7 +169,7 @@ impl<const N: usize> Animation for MilkyWay<N> {
                 self.phase += dt;
                 let scaling_factor = (self.transition_time / duration * PI / 2.0).sin();
                 for (led, background_led) in frame.iter_mut().zip(&self.frame) {
-                    *led = *background_led * scaling_factor;
+                    *led = background_led.scale_with_cutoff(scaling_factor);
                 }
                 if self.phase >= duration {
                     self.transition = None;
@@ -50,10 +50,12 @@ pub fn render_lines<const FRAME_SIZE: usize, const RANGES_COUNT: usize>(
                 }
                 *led = foreground;
                 if start_fill < one_led_rad || end_fill < one_led_rad {
-                    *led *= ((start_fill.min(one_led_rad) + end_fill.min(one_led_rad)
-                        - one_led_rad)
-                        / one_led_rad)
-                        .powf(GAMMA);
+                    *led = led.scale_with_cutoff(
+                        ((start_fill.min(one_led_rad) + end_fill.min(one_led_rad)
+                            - one_led_rad)
+                            / one_led_rad)
+                            .powf(GAMMA),
+                    );
                 }
                 continue 'leds;
             }
@@ -74,10 +76,12 @@ pub fn render_lines<const FRAME_SIZE: usize, const RANGES_COUNT: usize>(
                 }
                 *led = foreground;
                 if start_fill < one_led_rad || end_fill < one_led_rad {
-                    *led *= ((start_fill.min(one_led_rad) + end_fill.min(one_led_rad)
-                        - one_led_rad)
-                        / one_led_rad)
-                        .powf(GAMMA);
+                    *led = led.scale_with_cutoff(
+                        ((start_fill.min(one_led_rad) + end_fill.min(one_led_rad)
+                            - one_led_rad)
+                            / one_led_rad)
+                            .powf(GAMMA),
+                    );
                 }
                 continue 'leds;
             }
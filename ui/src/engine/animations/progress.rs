@@ -120,7 +120,8 @@ impl<const N: usize> Animation for Progress<N> {
 
         tracing::trace!("scaling: {scaling_factor}");
         if !idle {
-            self.shape.render(frame, self.color * scaling_factor);
+            self.shape
+                .render(frame, self.color.scale_with_cutoff(scaling_factor));
         }
 
         self.shape.progress = self.shape.progress
@@ -95,7 +95,7 @@ impl<const N: usize> Shape<N> {
 
     #[allow(clippy::cast_precision_loss, clippy::match_on_vec_items)]
     pub fn render(&self, frame: &mut RingFrame<N>, color: Argb) {
-        let pulse_color = color * ((1.0 - self.phase.cos()) / 2.0);
+        let pulse_color = color.scale_with_cutoff((1.0 - self.phase.cos()) / 2.0);
         for (led_index, led) in frame.iter_mut().enumerate() {
             *led = match self.pattern[self.segment_index(led_index)] {
                 Segment::Off => Argb::OFF,
@@ -166,13 +166,13 @@ impl<const N: usize> Animation for SimpleSpinner<N> {
                                 * (head_tail_scale))
                                 as i32) as u8,
                     );
-                    *led = c * scaling_factor;
+                    *led = c.scale_with_cutoff(scaling_factor);
                 } else if (((led_index + led_spinner_count) % N) < led_index
                     && (i < ((led_index + led_spinner_count) % N) || i > led_index))
                     || (((led_index + led_spinner_count) % N) > led_index
                         && (i < ((led_index + led_spinner_count) % N) && i > led_index))
                 {
-                    *led = self.color * scaling_factor;
+                    *led = self.color.scale_with_cutoff(scaling_factor);
                 } else if i == (led_index + led_spinner_count) % N {
                     let c = Argb(
                         self.color.0,
@@ -189,9 +189,9 @@ impl<const N: usize> Animation for SimpleSpinner<N> {
                                 * head_tail_scale) as i32)
                             as u8,
                     );
-                    *led = c * scaling_factor;
+                    *led = c.scale_with_cutoff(scaling_factor);
                 } else {
-                    *led = background * scaling_factor;
+                    *led = background.scale_with_cutoff(scaling_factor);
                 }
             }
         }
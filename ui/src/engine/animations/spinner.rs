@@ -231,8 +231,8 @@ impl<const N: usize> Shape<N> {
         }
         render_lines(
             frame,
-            self.background * self.color_scale,
-            self.color * self.color_scale,
+            self.background.scale_with_cutoff(self.color_scale),
+            self.color.scale_with_cutoff(self.color_scale),
             &ranges,
         );
     }
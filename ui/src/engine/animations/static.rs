@@ -100,7 +100,7 @@ impl<const N: usize> Animation for Static<N> {
             };
 
             for led in frame {
-                *led = color * scaling_factor;
+                *led = color.scale_with_cutoff(scaling_factor);
             }
         }
 
@@ -170,7 +170,7 @@ impl<const N: usize> Animation for Wave<N> {
                 } else {
                     // pearl's ring or diamond
                     for led in frame.iter_mut() {
-                        *led = self.color * intensity;
+                        *led = self.color.scale_with_cutoff(intensity);
                     }
                 }
             } else {
@@ -191,9 +191,9 @@ impl Animation for Idle {
         };
 
         if !idle {
-            frame[4] = color_battery * multiplier;
-            frame[3] = wlan_color * wlan_m;
-            frame[2] = internet_color * internet_m;
+            frame[4] = color_battery.scale_with_cutoff(multiplier);
+            frame[3] = wlan_color.scale_with_cutoff(wlan_m);
+            frame[2] = internet_color.scale_with_cutoff(internet_m);
             frame[1] = color_default;
             frame[0] = color_default;
         }
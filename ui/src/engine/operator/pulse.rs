@@ -102,7 +102,7 @@ impl Animation for Pulse {
                         return AnimationState::Finished;
                     }
                 }
-                self.color * intensity
+                self.color.scale_with_cutoff(intensity)
             } else {
                 // solid
                 self.color
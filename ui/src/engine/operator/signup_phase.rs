@@ -277,7 +277,8 @@ impl Animation for SignupPhase {
                         engine::OrbType::Pearl => Argb::PEARL_OPERATOR_AMBER,
                         engine::OrbType::Diamond => Argb::DIAMOND_OPERATOR_AMBER,
                     };
-                    animated_frame[i] = color * self.warning_pulse_ph_rad.sin();
+                    animated_frame[i] =
+                        color.scale_with_cutoff(self.warning_pulse_ph_rad.sin());
                 }
                 // wait for warning animation to finish before we either restart
                 // the animation or end it if no warning set
@@ -1,3 +1,34 @@
+use std::process::Command;
+
+/// Lower bound for the build timestamp when the git history isn't available, e.g.
+/// in nix builds. Bump it from time to time. 2026-10-01T00:00:00Z.
+const MIN_BUILD_TIMESTAMP: u64 = 1_790_812_800;
+
 fn main() {
-    orb_build_info::initialize().expect("failed to initialize")
+    orb_build_info::initialize().expect("failed to initialize");
+
+    // Lower bound for the system clock, see `checks::time_sync`. Derived from the
+    // commit being built instead of the wall clock, so builds stay reproducible.
+    // `SOURCE_DATE_EPOCH` is not used: nix sets it to 1980.
+    let timestamp = git_commit_timestamp()
+        .unwrap_or(MIN_BUILD_TIMESTAMP)
+        .max(MIN_BUILD_TIMESTAMP);
+    println!("cargo:rustc-env=UPDATE_VERIFIER_BUILD_TIMESTAMP={timestamp}");
+}
+
+/// Commit time of `HEAD`, or `None` outside of a git checkout.
+fn git_commit_timestamp() -> Option<u64> {
+    let output = Command::new("git")
+        .args(["log", "-1", "--format=%ct"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    std::str::from_utf8(&output.stdout)
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
 }
@@ -0,0 +1,86 @@
+use color_eyre::eyre::{self, WrapErr as _};
+use zbus::blocking::{Connection, Proxy};
+
+use super::{Check, CheckOutcome};
+
+/// Connection states accepted by default.
+pub const DEFAULT_ACCEPTED_STATES: &[&str] = &["Connected", "PartiallyConnected"];
+
+/// Checks that the orb is connected, according to the connection daemon.
+pub struct Connectivity {
+    accepted_states: Vec<String>,
+}
+
+impl Connectivity {
+    pub fn new(accepted_states: Vec<String>) -> Self {
+        Self { accepted_states }
+    }
+
+    fn connection_state() -> eyre::Result<String> {
+        let connection = Connection::system()?;
+        let proxy: Proxy<'_> = zbus::blocking::proxy::Builder::new(&connection)
+            .interface("org.worldcoin.Connd1")?
+            .path("/org/worldcoin/Connd1")?
+            .destination("org.worldcoin.Connd1")?
+            .build()?;
+
+        proxy
+            .call("ConnectionState", &())
+            .wrap_err("failed to get connection state from connd")
+    }
+}
+
+impl Check for Connectivity {
+    fn name(&self) -> &'static str {
+        "connectivity"
+    }
+
+    fn check(&self) -> eyre::Result<CheckOutcome> {
+        let state = Self::connection_state()?;
+        Ok(outcome(&state, &self.accepted_states))
+    }
+}
+
+fn outcome(state: &str, accepted_states: &[String]) -> CheckOutcome {
+    if accepted_states.iter().any(|accepted| accepted == state) {
+        CheckOutcome::Pass
+    } else {
+        CheckOutcome::Fail(format!(
+            "connection state is {state}, expected one of {accepted_states:?}"
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn default_states() -> Vec<String> {
+        DEFAULT_ACCEPTED_STATES
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn test_accepted_states_pass() {
+        assert_eq!(outcome("Connected", &default_states()), CheckOutcome::Pass);
+        assert_eq!(
+            outcome("PartiallyConnected", &default_states()),
+            CheckOutcome::Pass
+        );
+    }
+
+    #[test]
+    fn test_other_states_fail() {
+        assert!(matches!(
+            outcome("Disconnected", &default_states()),
+            CheckOutcome::Fail(_)
+        ));
+        assert!(matches!(
+            outcome("PartiallyConnected", &["Connected".to_string()]),
+            CheckOutcome::Fail(_)
+        ));
+        assert!(matches!(outcome("Connected", &[]), CheckOutcome::Fail(_)));
+    }
+}
@@ -0,0 +1,88 @@
+use std::path::PathBuf;
+
+use color_eyre::eyre::{self, WrapErr as _};
+use nix::sys::statvfs;
+
+use super::{Check, CheckOutcome};
+
+/// Mount point of the data partition.
+pub const DATA_MOUNT_POINT: &str = "/usr/persistent";
+/// Free space required on the data partition.
+pub const DEFAULT_MIN_FREE_BYTES: u64 = 50 * 1024 * 1024;
+
+/// Checks that a filesystem has enough free space left.
+pub struct DiskSpace {
+    mount_point: PathBuf,
+    min_free_bytes: u64,
+}
+
+impl DiskSpace {
+    pub fn new(mount_point: impl Into<PathBuf>, min_free_bytes: u64) -> Self {
+        Self {
+            mount_point: mount_point.into(),
+            min_free_bytes,
+        }
+    }
+
+    /// Checks the data partition with the default threshold.
+    pub fn data_partition() -> Self {
+        Self::new(DATA_MOUNT_POINT, DEFAULT_MIN_FREE_BYTES)
+    }
+
+    fn free_bytes(&self) -> eyre::Result<u64> {
+        let stats = statvfs::statvfs(&self.mount_point).wrap_err_with(|| {
+            format!("failed to get statvfs at `{}`", self.mount_point.display())
+        })?;
+        let piece_size = if stats.fragment_size() == 0 {
+            stats.block_size()
+        } else {
+            stats.fragment_size()
+        };
+        #[allow(clippy::useless_conversion)] // the field types differ between targets
+        Ok(u64::from(stats.blocks_available()) * u64::from(piece_size))
+    }
+}
+
+impl Check for DiskSpace {
+    fn name(&self) -> &'static str {
+        "disk space"
+    }
+
+    fn check(&self) -> eyre::Result<CheckOutcome> {
+        let free_bytes = self.free_bytes()?;
+        if free_bytes < self.min_free_bytes {
+            return Ok(CheckOutcome::Fail(format!(
+                "only {free_bytes} bytes free at `{}`, need at least {}",
+                self.mount_point.display(),
+                self.min_free_bytes
+            )));
+        }
+        Ok(CheckOutcome::Pass)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_passes_with_enough_space() {
+        let dir = tempfile::tempdir().unwrap();
+        let check = DiskSpace::new(dir.path(), 0);
+        assert_eq!(check.check().unwrap(), CheckOutcome::Pass);
+    }
+
+    #[test]
+    fn test_fails_without_enough_space() {
+        let dir = tempfile::tempdir().unwrap();
+        let check = DiskSpace::new(dir.path(), u64::MAX);
+        assert!(matches!(check.check().unwrap(), CheckOutcome::Fail(_)));
+    }
+
+    #[test]
+    fn test_errors_on_missing_mount_point() {
+        let dir = tempfile::tempdir().unwrap();
+        let check = DiskSpace::new(dir.path().join("does-not-exist"), 0);
+        assert!(check.check().is_err());
+    }
+}
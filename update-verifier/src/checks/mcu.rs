@@ -74,6 +74,16 @@ pub enum Error {
 pub struct Mcu {
     bus: String,
     remote: Device,
+    /// Reads the firmware versions, replaced in tests.
+    read_versions: fn(&Mcu) -> Result<Versions, Error>,
+}
+
+/// Firmware versions of a microcontroller.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Versions {
+    expected: semver::Version,
+    primary: semver::Version,
+    secondary: Option<semver::Version>,
 }
 
 impl Mcu {
@@ -81,6 +91,7 @@ impl Mcu {
         Self {
             bus: "can0".to_string(),
             remote: Device::Main,
+            read_versions: Mcu::read_versions,
         }
     }
 
@@ -89,9 +100,33 @@ impl Mcu {
         Self {
             bus: "can0".to_string(),
             remote: Device::Security,
+            read_versions: Mcu::read_versions,
         }
     }
 
+    /// Reads the expected version and the versions of both slots over CAN.
+    fn read_versions(&self) -> Result<Versions, Error> {
+        let expected =
+            semver::Version::parse(self.expected_version()?.trim_start_matches('v'))
+                .map_err(|err| Error::Other(err.to_string()))?;
+
+        let mut mcu_stream =
+            MessageStream::new(self.remote, &self.bus).map_err(|err| {
+                Error::StreamInitialization {
+                    remote: self.remote,
+                    bus: self.bus.clone(),
+                    error: err,
+                }
+            })?;
+
+        let (primary, secondary) = self.get_versions(&mut mcu_stream)?;
+        Ok(Versions {
+            expected,
+            primary,
+            secondary,
+        })
+    }
+
     /// Get versions in primary and secondary slots
     /// Returns a tuple of primary and secondary firmware versions
     /// Primary version is mandatory, otherwise an error is returned.
@@ -251,10 +286,44 @@ impl Mcu {
 }
 
 impl super::Check for Mcu {
-    type Error = Error;
+    fn name(&self) -> &'static str {
+        match self.remote {
+            Device::Security => "security mcu version",
+            _ => "main mcu version",
+        }
+    }
 
-    const NAME: &'static str = "mcu version";
+    fn failure_category(&self) -> crate::report::Failure {
+        crate::report::Failure::McuMismatch
+    }
 
+    fn check(&self) -> color_eyre::eyre::Result<super::CheckOutcome> {
+        let outcome = match self.check_versions() {
+            Ok(()) => super::CheckOutcome::Pass,
+            Err(
+                e @ (Error::RecoverableVersionMismatch(..)
+                | Error::SecondaryIsMoreRecent(_)),
+            ) => super::CheckOutcome::Recoverable(e.to_string()),
+            // Reported as a `Failure::McuMismatch`, see `failure_category`.
+            Err(e @ Error::UnableToRecover(_)) => {
+                super::CheckOutcome::Fail(e.to_string())
+            }
+            // On any other error, we skip the check.
+            Err(e) => super::CheckOutcome::Warn(format!(
+                "{e}. The microcontroller might not be compatible, but is going to be \
+                 used anyway."
+            )),
+        };
+        Ok(outcome)
+    }
+
+    fn recover(&self) -> color_eyre::eyre::Result<()> {
+        self.reboot_for_update()?;
+        Ok(())
+    }
+}
+
+impl Mcu {
     /// Checks microcontroller firmware versions after an update.
     /// Two slots are used on the microcontroller to store firmware images: the primary (running image)
     /// and secondary. Slots are switched during an update.
@@ -267,21 +336,12 @@ impl super::Check for Mcu {
     ///    by using only the major and minor number. The slot is selected accordingly and the device is
     ///    rebooted if the best image is in secondary slot.
     /// 3. If none of the above, the most recent version is used by comparing the semver.
-    fn check(&self) -> Result<(), Self::Error> {
-        let expected_version =
-            semver::Version::parse(self.expected_version()?.trim_start_matches('v'))
-                .map_err(|err| Error::Other(err.to_string()))?;
-
-        let mut mcu_stream =
-            MessageStream::new(self.remote, &self.bus).map_err(|err| {
-                Error::StreamInitialization {
-                    remote: self.remote,
-                    bus: self.bus.clone(),
-                    error: err,
-                }
-            })?;
-
-        let (primary_app, secondary_app) = self.get_versions(&mut mcu_stream)?;
+    fn check_versions(&self) -> Result<(), Error> {
+        let Versions {
+            expected: expected_version,
+            primary: primary_app,
+            secondary: secondary_app,
+        } = (self.read_versions)(self)?;
         info!(
             "Mcu primary app: {:?}, secondary app: {:?}, expected: {}",
             primary_app, secondary_app, expected_version
@@ -728,3 +788,104 @@ fn decode_protobuf(remote: Device, frame: Frame<CANFD_DATA_LEN>) -> Option<Paylo
         _ => None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::super::{run_checks, Check, CheckOutcome, Verdict};
+    use super::*;
+    use crate::report::{ChecksFailed, Report};
+
+    fn v(version: &str) -> semver::Version {
+        semver::Version::parse(version).unwrap()
+    }
+
+    fn fake_mcu(
+        remote: Device,
+        read_versions: fn(&Mcu) -> Result<Versions, Error>,
+    ) -> Mcu {
+        Mcu {
+            bus: String::new(),
+            remote,
+            read_versions,
+        }
+    }
+
+    #[test]
+    fn test_check_outcomes() {
+        let cases: [(fn(&Mcu) -> Result<Versions, Error>, &str); 5] = [
+            (
+                |_| {
+                    Ok(Versions {
+                        expected: v("1.2.3"),
+                        primary: v("1.2.3"),
+                        secondary: None,
+                    })
+                },
+                "pass",
+            ),
+            (
+                |_| {
+                    Ok(Versions {
+                        expected: v("1.2.3"),
+                        primary: v("1.1.0"),
+                        secondary: Some(v("1.2.3")),
+                    })
+                },
+                "recoverable",
+            ),
+            (
+                |_| {
+                    Ok(Versions {
+                        expected: v("1.2.3"),
+                        primary: v("0.9.0"),
+                        secondary: None,
+                    })
+                },
+                "fail",
+            ),
+            (
+                |_| {
+                    Ok(Versions {
+                        expected: v("1.2.3"),
+                        primary: v("1.2.0"),
+                        secondary: None,
+                    })
+                },
+                "pass",
+            ),
+            (|_| Err(Error::Other("no reply".to_string())), "warn"),
+        ];
+        for remote in [Device::Main, Device::Security] {
+            for (i, (read_versions, expected)) in cases.iter().enumerate() {
+                let outcome = fake_mcu(remote, *read_versions).check().unwrap();
+                let outcome = match outcome {
+                    CheckOutcome::Pass => "pass",
+                    CheckOutcome::Warn(_) => "warn",
+                    CheckOutcome::Recoverable(_) => "recoverable",
+                    CheckOutcome::Fail(_) => "fail",
+                };
+                assert_eq!(outcome, *expected, "{i}th case failed for {remote:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_mismatch_exit_code() {
+        for remote in [Device::Main, Device::Security] {
+            let checks: Vec<Box<dyn Check>> = vec![Box::new(fake_mcu(remote, |_| {
+                Ok(Versions {
+                    expected: v("2.0.0"),
+                    primary: v("1.0.0"),
+                    secondary: None,
+                })
+            }))];
+            let mut report = Report::default();
+            let Verdict::Unhealthy(failed) =
+                run_checks(&checks, false, &mut report.checks).unwrap()
+            else {
+                panic!("mismatch of {remote:?} mcu wasn't a failure");
+            };
+            assert_eq!(report.finish(&Err(ChecksFailed(failed).into())), 10);
+        }
+    }
+}
@@ -1,26 +1,318 @@
 //! A common health check module.
 
+pub mod connectivity;
+pub mod disk;
 pub mod mcu;
+pub mod time_sync;
 
-use tracing::{info, instrument};
+use std::time::Instant;
+
+use color_eyre::eyre::{self, WrapErr};
+use tracing::{error, info, instrument, warn};
+
+use crate::report::{CheckFrameworkError, CheckRecord, Failure};
+
+/// The result of a health check that could be performed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CheckOutcome {
+    /// The module is healthy.
+    Pass,
+    /// The module is not healthy, but the system is still usable.
+    Warn(String),
+    /// The module can be fixed by [`Check::recover`], which reboots the orb.
+    Recoverable(String),
+    /// The module is not healthy and the current slot should not be booted again.
+    Fail(String),
+}
 
 /// A common health check trait.
 pub trait Check {
-    type Error;
-
     /// Name of module.
-    const NAME: &'static str;
+    fn name(&self) -> &'static str;
 
     /// Perform the actual health check for a module.
-    fn check(&self) -> Result<(), Self::Error> {
+    ///
+    /// An `Err` means the check could not be performed at all.
+    fn check(&self) -> eyre::Result<CheckOutcome>;
+
+    /// Fix a [`CheckOutcome::Recoverable`] module. Expected to reboot the orb.
+    fn recover(&self) -> eyre::Result<()> {
         Ok(())
     }
 
-    #[instrument(fields(module=Self::NAME), skip_all)]
-    fn run_check(&self) -> Result<(), Self::Error> {
-        info!("performing health check for {}", Self::NAME);
-        self.check()?;
-        info!("health check succeeded");
-        Ok(())
+    /// Exit code category when this check fails.
+    fn failure_category(&self) -> Failure {
+        Failure::ChecksFailed
+    }
+
+    #[instrument(fields(module = self.name()), skip_all)]
+    fn run_check(&self) -> eyre::Result<CheckOutcome> {
+        info!("performing health check for {}", self.name());
+        let outcome = self.check()?;
+        match &outcome {
+            CheckOutcome::Pass => info!("health check succeeded"),
+            CheckOutcome::Warn(reason) => warn!("health check degraded: {reason}"),
+            CheckOutcome::Recoverable(reason) => {
+                warn!("health check failed, but is recoverable: {reason}");
+            }
+            CheckOutcome::Fail(reason) => error!("health check failed: {reason}"),
+        }
+        Ok(outcome)
+    }
+}
+
+/// How a failure of an optional check is handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum FailureMode {
+    /// Failures are only logged, the check is reported as degraded.
+    Warn,
+    /// Failures make the slot unbootable, like any other check.
+    Block,
+}
+
+/// Wraps a check, applying a [`FailureMode`] to its failures.
+pub struct WithFailureMode<C> {
+    check: C,
+    mode: FailureMode,
+}
+
+impl<C: Check> WithFailureMode<C> {
+    pub fn new(check: C, mode: FailureMode) -> Self {
+        Self { check, mode }
+    }
+}
+
+impl<C: Check> Check for WithFailureMode<C> {
+    fn name(&self) -> &'static str {
+        self.check.name()
+    }
+
+    fn check(&self) -> eyre::Result<CheckOutcome> {
+        let outcome = self.check.check()?;
+        Ok(match (outcome, self.mode) {
+            (CheckOutcome::Fail(reason), FailureMode::Warn) => {
+                error!("{} check failed, ignoring: {reason}", self.name());
+                CheckOutcome::Warn(reason)
+            }
+            (outcome, _) => outcome,
+        })
+    }
+
+    fn recover(&self) -> eyre::Result<()> {
+        self.check.recover()
+    }
+
+    fn failure_category(&self) -> Failure {
+        self.check.failure_category()
+    }
+}
+
+/// The aggregated result of running all health checks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    /// No check failed.
+    Healthy,
+    /// A check is being recovered, the orb is about to reboot.
+    Recovering(&'static str),
+    /// The named checks failed.
+    Unhealthy(Vec<&'static str>),
+}
+
+/// Runs all `checks` in order, appending their results to `records`.
+///
+/// Checks that can't be performed are skipped, they never make the slot unbootable.
+/// The first [`CheckOutcome::Recoverable`] check is recovered immediately and no
+/// further checks are run, unless `dry_run` is set.
+pub fn run_checks(
+    checks: &[Box<dyn Check>],
+    dry_run: bool,
+    records: &mut Vec<CheckRecord>,
+) -> eyre::Result<Verdict> {
+    let mut failed = Vec::new();
+    for check in checks {
+        let start = Instant::now();
+        let outcome = check.run_check();
+        records.push(CheckRecord::new(
+            check.name(),
+            &outcome,
+            start.elapsed(),
+            Some(check.failure_category()),
+        ));
+        match outcome {
+            Ok(CheckOutcome::Pass | CheckOutcome::Warn(_)) => {}
+            Ok(CheckOutcome::Recoverable(_)) if dry_run => {
+                warn!("Dry-run: skipping recovery of {}", check.name());
+            }
+            Ok(CheckOutcome::Recoverable(_)) => {
+                info!("recovering {}", check.name());
+                check.recover().wrap_err(CheckFrameworkError)?;
+                return Ok(Verdict::Recovering(check.name()));
+            }
+            Ok(CheckOutcome::Fail(_)) => failed.push(check.name()),
+            Err(e) => {
+                warn!("skipping health check for {}: {e:?}", check.name());
+            }
+        }
+    }
+
+    if failed.is_empty() {
+        Ok(Verdict::Healthy)
+    } else {
+        Ok(Verdict::Unhealthy(failed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, rc::Rc};
+
+    use color_eyre::eyre::eyre;
+
+    use super::*;
+    use crate::report::CheckStatus;
+
+    #[derive(Default)]
+    struct Fake {
+        name: &'static str,
+        outcome: Option<CheckOutcome>,
+        recovered: Rc<Cell<bool>>,
+    }
+
+    fn fake(name: &'static str, outcome: Option<CheckOutcome>) -> Box<dyn Check> {
+        Box::new(Fake {
+            name,
+            outcome,
+            ..Default::default()
+        })
+    }
+
+    impl Check for Fake {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        fn check(&self) -> eyre::Result<CheckOutcome> {
+            self.outcome.clone().ok_or_else(|| eyre!("can't check"))
+        }
+
+        fn recover(&self) -> eyre::Result<()> {
+            self.recovered.set(true);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_all_passing_checks_are_healthy() {
+        let checks = vec![
+            fake("pass", Some(CheckOutcome::Pass)),
+            fake("warn", Some(CheckOutcome::Warn("meh".into()))),
+            fake("error", None),
+        ];
+        assert_eq!(
+            run_checks(&checks, false, &mut Vec::new()).unwrap(),
+            Verdict::Healthy
+        );
+    }
+
+    #[test]
+    fn test_records() {
+        let checks = vec![
+            fake("pass", Some(CheckOutcome::Pass)),
+            fake("fail", Some(CheckOutcome::Fail("bad".into()))),
+            fake("error", None),
+        ];
+        let mut records = Vec::new();
+        run_checks(&checks, false, &mut records).unwrap();
+        let records: Vec<_> = records
+            .iter()
+            .map(|record| (record.name, record.status, record.message.as_str()))
+            .collect();
+        assert_eq!(
+            records,
+            [
+                ("pass", CheckStatus::Pass, ""),
+                ("fail", CheckStatus::Fail, "bad"),
+                ("error", CheckStatus::Error, "can't check"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_failing_checks_are_collected() {
+        let checks = vec![
+            fake("fail1", Some(CheckOutcome::Fail("bad".into()))),
+            fake("pass", Some(CheckOutcome::Pass)),
+            fake("fail2", Some(CheckOutcome::Fail("bad".into()))),
+        ];
+        assert_eq!(
+            run_checks(&checks, false, &mut Vec::new()).unwrap(),
+            Verdict::Unhealthy(vec!["fail1", "fail2"])
+        );
+    }
+
+    #[test]
+    fn test_recoverable_check_stops_early() {
+        let recovered = Rc::new(Cell::new(false));
+        let checks: Vec<Box<dyn Check>> = vec![
+            Box::new(Fake {
+                name: "recoverable",
+                outcome: Some(CheckOutcome::Recoverable("x".into())),
+                recovered: recovered.clone(),
+            }),
+            fake("fail", Some(CheckOutcome::Fail("bad".into()))),
+        ];
+        assert_eq!(
+            run_checks(&checks, false, &mut Vec::new()).unwrap(),
+            Verdict::Recovering("recoverable")
+        );
+        assert!(recovered.get());
+    }
+
+    #[test]
+    fn test_failure_mode() {
+        let check = |outcome, mode| -> Box<dyn Check> {
+            Box::new(WithFailureMode::new(
+                Fake {
+                    name: "optional",
+                    outcome: Some(outcome),
+                    ..Default::default()
+                },
+                mode,
+            ))
+        };
+        let fail = CheckOutcome::Fail("bad".into());
+
+        let checks = vec![check(fail.clone(), FailureMode::Warn)];
+        assert_eq!(
+            run_checks(&checks, false, &mut Vec::new()).unwrap(),
+            Verdict::Healthy
+        );
+
+        let checks = vec![
+            check(fail, FailureMode::Block),
+            check(CheckOutcome::Pass, FailureMode::Block),
+        ];
+        assert_eq!(
+            run_checks(&checks, false, &mut Vec::new()).unwrap(),
+            Verdict::Unhealthy(vec!["optional"])
+        );
+    }
+
+    #[test]
+    fn test_dry_run_skips_recovery() {
+        let recovered = Rc::new(Cell::new(false));
+        let checks: Vec<Box<dyn Check>> = vec![
+            Box::new(Fake {
+                name: "recoverable",
+                outcome: Some(CheckOutcome::Recoverable("x".into())),
+                recovered: recovered.clone(),
+            }),
+            fake("fail", Some(CheckOutcome::Fail("bad".into()))),
+        ];
+        assert_eq!(
+            run_checks(&checks, true, &mut Vec::new()).unwrap(),
+            Verdict::Unhealthy(vec!["fail"])
+        );
+        assert!(!recovered.get());
     }
 }
@@ -0,0 +1,112 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use color_eyre::eyre;
+use tracing::warn;
+use zbus::blocking::{Connection, Proxy};
+
+use super::{Check, CheckOutcome};
+
+/// Unix timestamp of the commit this binary was built from, set by the build
+/// script.
+const BUILD_TIMESTAMP: &str = env!("UPDATE_VERIFIER_BUILD_TIMESTAMP");
+
+/// Checks that the system clock is sane: it can't be earlier than the build time
+/// of the running software, and should be synchronized over NTP.
+pub struct TimeSync {
+    min_time: SystemTime,
+}
+
+impl TimeSync {
+    pub fn new(min_time: SystemTime) -> Self {
+        Self { min_time }
+    }
+
+    /// Uses the build time of this binary as the earliest sane time.
+    pub fn since_build() -> Self {
+        let secs = BUILD_TIMESTAMP
+            .parse()
+            .expect("build script emits a valid timestamp");
+        Self::new(UNIX_EPOCH + Duration::from_secs(secs))
+    }
+
+    fn ntp_synchronized() -> eyre::Result<bool> {
+        let connection = Connection::system()?;
+        let proxy: Proxy<'_> = zbus::blocking::proxy::Builder::new(&connection)
+            .interface("org.freedesktop.timedate1")?
+            .path("/org/freedesktop/timedate1")?
+            .destination("org.freedesktop.timedate1")?
+            .build()?;
+
+        Ok(proxy.get_property("NTPSynchronized")?)
+    }
+}
+
+impl Check for TimeSync {
+    fn name(&self) -> &'static str {
+        "time sync"
+    }
+
+    fn check(&self) -> eyre::Result<CheckOutcome> {
+        let synchronized = Self::ntp_synchronized()
+            .inspect_err(|e| warn!("failed to get NTP synchronization status: {e:?}"))
+            .ok();
+        Ok(outcome(SystemTime::now(), self.min_time, synchronized))
+    }
+}
+
+fn outcome(
+    now: SystemTime,
+    min_time: SystemTime,
+    synchronized: Option<bool>,
+) -> CheckOutcome {
+    if let Ok(behind) = min_time.duration_since(now) {
+        if !behind.is_zero() {
+            return CheckOutcome::Fail(format!(
+                "system clock is {}s earlier than the build time",
+                behind.as_secs()
+            ));
+        }
+    }
+    match synchronized {
+        Some(false) => CheckOutcome::Warn("system clock is not synchronized".into()),
+        Some(true) | None => CheckOutcome::Pass,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: Duration = Duration::from_secs(3600);
+
+    #[test]
+    fn test_build_timestamp_is_valid() {
+        let check = TimeSync::since_build();
+        assert!(check.min_time <= SystemTime::now());
+    }
+
+    #[test]
+    fn test_clock_after_build_passes() {
+        let build = UNIX_EPOCH + 1000 * HOUR;
+        assert_eq!(outcome(build, build, Some(true)), CheckOutcome::Pass);
+        assert_eq!(outcome(build + HOUR, build, None), CheckOutcome::Pass);
+    }
+
+    #[test]
+    fn test_clock_before_build_fails() {
+        let build = UNIX_EPOCH + 1000 * HOUR;
+        assert!(matches!(
+            outcome(build - HOUR, build, Some(true)),
+            CheckOutcome::Fail(_)
+        ));
+    }
+
+    #[test]
+    fn test_unsynchronized_clock_warns() {
+        let build = UNIX_EPOCH + 1000 * HOUR;
+        assert!(matches!(
+            outcome(build + HOUR, build, Some(false)),
+            CheckOutcome::Warn(_)
+        ));
+    }
+}
@@ -1,24 +1,60 @@
 //! The update verifier crate provides methods to check the system health of the Orb.
 #![warn(clippy::pedantic, missing_docs)]
 
-use crate::checks::mcu::{Error, Mcu};
-use crate::checks::Check;
+use crate::checks::{
+    connectivity::Connectivity, disk::DiskSpace, mcu::Mcu, time_sync::TimeSync, Check,
+    Verdict, WithFailureMode,
+};
+use crate::report::{Action, ChecksFailed, Report};
 use color_eyre::eyre;
 use orb_build_info::{make_build_info, BuildInfo};
 use orb_slot_ctrl::OrbSlotCtrl;
 use tracing::{error, info, instrument, warn};
 
 mod checks;
+pub mod report;
+
+pub use crate::checks::{connectivity::DEFAULT_ACCEPTED_STATES, FailureMode};
 
 #[allow(missing_docs)]
 pub const BUILD_INFO: BuildInfo = make_build_info!();
 
-/// Performs the system health check.
+/// Configuration of the optional health checks.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Failure mode of the connectivity check, `None` skips it.
+    pub connectivity_check: Option<FailureMode>,
+    /// Connection states the connectivity check accepts.
+    pub accepted_connection_states: Vec<String>,
+    /// Failure mode of the time sync check, `None` skips it.
+    pub time_sync_check: Option<FailureMode>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            connectivity_check: Some(FailureMode::Warn),
+            accepted_connection_states: DEFAULT_ACCEPTED_STATES
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+            time_sync_check: Some(FailureMode::Warn),
+        }
+    }
+}
+
+/// Performs the system health check, recording the executed checks and the
+/// action taken on the current slot in `report`.
 ///
 /// # Errors
 /// Can throw errors of `slot-ctrl` library or when calling system health checks.
-#[instrument(err, skip(orb_slot_ctrl))]
-pub fn run_health_check(orb_slot_ctrl: OrbSlotCtrl) -> eyre::Result<()> {
+/// [`report::Failure::from_error`] categorizes them.
+#[instrument(err, skip(orb_slot_ctrl, report))]
+pub fn run_health_check(
+    orb_slot_ctrl: OrbSlotCtrl,
+    config: &Config,
+    report: &mut Report,
+) -> eyre::Result<()> {
     // get runtime environment variable to force health check
     let dry_run = std::env::var("UPDATE_VERIFIER_DRY_RUN").is_ok();
 
@@ -31,6 +67,7 @@ pub fn run_health_check(orb_slot_ctrl: OrbSlotCtrl) -> eyre::Result<()> {
             dry_run
         );
 
+        let mut checks: Vec<Box<dyn Check>> = Vec::new();
         // In case rootfs status is NOT Normal, and we know it's the first boot attempt
         // by checking the retry counter
         // we check that the main microcontroller version is compatible with the
@@ -45,34 +82,53 @@ pub fn run_health_check(orb_slot_ctrl: OrbSlotCtrl) -> eyre::Result<()> {
             // on each successful execution, but we might want to check the
             // health check logic multiple times
             if retry_count >= (max_retry_count - 1) {
-                match Mcu::main().run_check() {
-                    Ok(()) => {}
-                    Err(
-                        Error::RecoverableVersionMismatch(..)
-                        | Error::SecondaryIsMoreRecent(_),
-                    ) => {
-                        info!("Activating and rebooting for mcu update retry");
-                        if dry_run {
-                            warn!("Dry-run: skipping mcu update retry");
-                        } else {
-                            Mcu::main().reboot_for_update()?;
-                            return Ok(());
-                        }
-                    }
-                    Err(e) => {
-                        error!("Main MCU version check failed: {}", e);
-                        warn!("The main microcontroller might not be compatible, but is going to be used anyway.");
-                    }
-                }
+                checks.push(Box::new(Mcu::main()));
             }
         } else {
             warn!("Could not get retry count or max retry count, skipping main MCU version check");
         }
+        checks.push(Box::new(DiskSpace::data_partition()));
+        if let Some(mode) = config.connectivity_check {
+            let check = Connectivity::new(config.accepted_connection_states.clone());
+            checks.push(Box::new(WithFailureMode::new(check, mode)));
+        } else {
+            info!("skipping connectivity check");
+        }
+        if let Some(mode) = config.time_sync_check {
+            checks.push(Box::new(WithFailureMode::new(
+                TimeSync::since_build(),
+                mode,
+            )));
+        } else {
+            info!("skipping time sync check");
+        }
+
+        match checks::run_checks(&checks, dry_run, &mut report.checks)? {
+            Verdict::Healthy => {}
+            Verdict::Recovering(check) => {
+                info!("rebooting to recover {check}");
+                return Ok(());
+            }
+            Verdict::Unhealthy(failed) => {
+                error!("system health checks failed: {failed:?}");
+                if dry_run {
+                    warn!("Dry-run: not marking rootfs as Unbootable");
+                } else {
+                    info!("setting rootfs status to Unbootable");
+                    orb_slot_ctrl.set_current_rootfs_status(
+                        orb_slot_ctrl::RootFsStatus::Unbootable,
+                    )?;
+                    report.action = Action::SetUnbootable;
+                }
+                return Err(ChecksFailed(failed).into());
+            }
+        }
 
         info!("system health is OK");
 
         info!("setting rootfs status to Normal");
         orb_slot_ctrl.set_current_rootfs_status(orb_slot_ctrl::RootFsStatus::Normal)?;
+        report.action = Action::MarkedOk;
     }
 
     info!("setting retry counter to maximum for future boot attempts");
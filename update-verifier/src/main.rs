@@ -4,18 +4,62 @@ use clap::{
 };
 use color_eyre::eyre::{self, Context};
 use orb_slot_ctrl::{EfiVarDb, OrbSlotCtrl};
-use orb_update_verifier::BUILD_INFO;
-use tracing::error;
+use orb_update_verifier::{
+    report::{Report, DEFAULT_RESULT_FILE},
+    Config, FailureMode, BUILD_INFO, DEFAULT_ACCEPTED_STATES,
+};
+use std::{path::PathBuf, process::ExitCode};
+use tracing::{error, warn};
 
 const SYSLOG_IDENTIFIER: &str = "worldcoin-update-verifier";
+const EXIT_CODES_HELP: &str = "\
+Exit codes:
+  0   system is healthy, or health checks were skipped
+  1   other error
+  10  main or security MCU version mismatch
+  11  slot-ctrl error, reading or writing the slot state failed
+  12  check framework error, a check couldn't be recovered
+  13  other health checks failed";
 
 #[derive(Parser, Debug)]
 #[clap(
     version = BUILD_INFO.version,
     about,
     styles = clap_v3_styles(),
+    after_help = EXIT_CODES_HELP,
 )]
-struct Cli {}
+struct Cli {
+    /// Skip the connectivity check, e.g. on bench setups without networking.
+    #[clap(long)]
+    skip_connectivity_check: bool,
+    /// Whether a failed connectivity check makes the slot unbootable.
+    #[clap(long, value_enum, default_value_t = FailureMode::Warn)]
+    connectivity_check_mode: FailureMode,
+    /// Connection states accepted by the connectivity check.
+    #[clap(long, value_delimiter = ',', default_values = DEFAULT_ACCEPTED_STATES)]
+    accepted_connection_states: Vec<String>,
+    /// Skip the time sync check.
+    #[clap(long)]
+    skip_time_sync_check: bool,
+    /// Whether a failed time sync check makes the slot unbootable.
+    #[clap(long, value_enum, default_value_t = FailureMode::Warn)]
+    time_sync_check_mode: FailureMode,
+    /// JSON file summarizing the executed checks and the action taken.
+    #[clap(long, default_value = DEFAULT_RESULT_FILE)]
+    result_file: PathBuf,
+}
+
+impl Cli {
+    fn config(self) -> Config {
+        Config {
+            connectivity_check: (!self.skip_connectivity_check)
+                .then_some(self.connectivity_check_mode),
+            accepted_connection_states: self.accepted_connection_states,
+            time_sync_check: (!self.skip_time_sync_check)
+                .then_some(self.time_sync_check_mode),
+        }
+    }
+}
 
 fn clap_v3_styles() -> Styles {
     Styles::styled()
@@ -25,20 +69,33 @@ fn clap_v3_styles() -> Styles {
         .placeholder(AnsiColor::Green.on_default())
 }
 
-fn main() -> color_eyre::Result<()> {
+fn main() -> color_eyre::Result<ExitCode> {
     color_eyre::install()?;
     orb_telemetry::TelemetryConfig::new()
         .with_journald(SYSLOG_IDENTIFIER)
         .init();
-    run().inspect_err(|error| error!(?error, "failed to run update-verifier"))
-}
+    let cli = Cli::parse();
+    let result_file = cli.result_file.clone();
 
-fn run() -> eyre::Result<()> {
-    let _args = Cli::parse();
+    let mut report = Report::default();
+    let result = run(cli.config(), &mut report)
+        .inspect_err(|error| error!(?error, "failed to run update-verifier"));
+    let exit_code = report.finish(&result);
+    // The result file is informational, failing to write it doesn't change the
+    // outcome.
+    if let Err(error) = report.write(&result_file) {
+        warn!(?error, "failed to write result file");
+    }
+    if let Err(error) = result {
+        eprintln!("Error: {error:?}");
+    }
+    Ok(ExitCode::from(exit_code))
+}
 
+fn run(config: Config, report: &mut Report) -> eyre::Result<()> {
     let efi_var_db = EfiVarDb::from_rootfs("/")?;
     let orb_slot_ctrl = OrbSlotCtrl::new(&efi_var_db)?;
-    orb_update_verifier::run_health_check(orb_slot_ctrl)
+    orb_update_verifier::run_health_check(orb_slot_ctrl, &config, report)
         .wrap_err("update verifier encountered error while checking system health")?;
 
     Ok(())
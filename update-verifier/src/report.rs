@@ -0,0 +1,350 @@
+//! Machine-readable summary of a verifier run, and the process exit codes.
+
+use std::{io::Write, path::Path, time::Duration};
+
+use color_eyre::eyre::{self, WrapErr};
+use serde::Serialize;
+
+use crate::checks::CheckOutcome;
+
+/// Default path of the result file.
+pub const DEFAULT_RESULT_FILE: &str = "/run/update-verifier/result.json";
+
+/// Why the verifier failed, each mapped to a distinct process exit code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+#[repr(u8)]
+pub enum Failure {
+    /// Any error not covered by the other categories.
+    Other = 1,
+    /// The main or security MCU doesn't run the expected firmware.
+    McuMismatch = 10,
+    /// Opening, reading or writing the slot and rootfs state failed.
+    SlotCtrl = 11,
+    /// A health check couldn't be run or recovered.
+    CheckFramework = 12,
+    /// A health check other than the MCU ones failed.
+    ChecksFailed = 13,
+}
+
+impl Failure {
+    /// Categorizes an error returned by the verifier, using the checks recorded
+    /// in `report`.
+    #[must_use]
+    pub fn from_error(error: &eyre::Report, report: &Report) -> Self {
+        if error.downcast_ref::<orb_slot_ctrl::Error>().is_some()
+            || error.downcast_ref::<orb_slot_ctrl::EfiVarDbErr>().is_some()
+        {
+            Self::SlotCtrl
+        } else if error.downcast_ref::<CheckFrameworkError>().is_some() {
+            Self::CheckFramework
+        } else if error.downcast_ref::<ChecksFailed>().is_some() {
+            let mcu_failed = report.checks.iter().any(|check| {
+                check.status == CheckStatus::Fail
+                    && check.category == Some(Failure::McuMismatch)
+            });
+            if mcu_failed {
+                Self::McuMismatch
+            } else {
+                Self::ChecksFailed
+            }
+        } else {
+            Self::Other
+        }
+    }
+
+    /// The process exit code.
+    #[must_use]
+    pub fn exit_code(self) -> u8 {
+        self as u8
+    }
+}
+
+/// Error of the verifier when health checks failed.
+#[derive(Debug, thiserror::Error)]
+#[error("system health checks failed: {0:?}")]
+pub struct ChecksFailed(pub Vec<&'static str>);
+
+/// Context of errors raised while running or recovering a health check.
+#[derive(Debug, thiserror::Error)]
+#[error("health check framework failed")]
+pub struct CheckFrameworkError;
+
+/// Status of an executed check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    /// See [`CheckOutcome::Pass`].
+    Pass,
+    /// See [`CheckOutcome::Warn`].
+    Warn,
+    /// See [`CheckOutcome::Recoverable`].
+    Recoverable,
+    /// See [`CheckOutcome::Fail`].
+    Fail,
+    /// The check couldn't be performed, and was skipped.
+    Error,
+}
+
+/// Result of a single executed check.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CheckRecord {
+    /// Name of the check.
+    pub name: &'static str,
+    /// Outcome of the check.
+    pub status: CheckStatus,
+    /// Human readable reason, empty for passing checks.
+    pub message: String,
+    /// Time taken by the check.
+    #[serde(rename = "duration_ms", serialize_with = "serialize_millis")]
+    pub duration: Duration,
+    /// Exit code category of a failure of this check.
+    #[serde(skip)]
+    pub category: Option<Failure>,
+}
+
+impl CheckRecord {
+    pub(crate) fn new(
+        name: &'static str,
+        outcome: &eyre::Result<CheckOutcome>,
+        duration: Duration,
+        category: Option<Failure>,
+    ) -> Self {
+        let (status, message) = match outcome {
+            Ok(CheckOutcome::Pass) => (CheckStatus::Pass, String::new()),
+            Ok(CheckOutcome::Warn(reason)) => (CheckStatus::Warn, reason.clone()),
+            Ok(CheckOutcome::Recoverable(reason)) => {
+                (CheckStatus::Recoverable, reason.clone())
+            }
+            Ok(CheckOutcome::Fail(reason)) => (CheckStatus::Fail, reason.clone()),
+            Err(e) => (CheckStatus::Error, format!("{e:#}")),
+        };
+        Self {
+            name,
+            status,
+            message,
+            duration,
+            category,
+        }
+    }
+}
+
+/// What the verifier did with the current slot.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    /// The rootfs status was set to `Normal`.
+    MarkedOk,
+    /// The rootfs status wasn't changed, e.g. on a dry run, on a recovery
+    /// reboot, or when it already was `Normal`.
+    #[default]
+    LeftUnchanged,
+    /// The rootfs status was set to `Unbootable`.
+    SetUnbootable,
+}
+
+/// Summary of a verifier run, written to the result file.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Report {
+    /// Executed checks, in order.
+    pub checks: Vec<CheckRecord>,
+    /// Final action taken on the current slot.
+    pub action: Action,
+    /// Category of the failure, `None` on success.
+    pub failure: Option<Failure>,
+    /// Exit code of the process, see [`Failure`].
+    pub exit_code: u8,
+    /// The error of a failed run.
+    pub error: Option<String>,
+}
+
+impl Report {
+    /// Records the result of the run, returning the process exit code.
+    pub fn finish(&mut self, result: &eyre::Result<()>) -> u8 {
+        match result {
+            Ok(()) => {
+                self.failure = None;
+                self.exit_code = 0;
+                self.error = None;
+            }
+            Err(e) => {
+                let failure = Failure::from_error(e, self);
+                self.failure = Some(failure);
+                self.exit_code = failure.exit_code();
+                self.error = Some(format!("{e:#}"));
+            }
+        }
+        self.exit_code
+    }
+
+    /// Atomically writes the report as JSON to `path`, creating its parent
+    /// directory if needed.
+    ///
+    /// # Errors
+    /// Fails if the file or its directory can't be written.
+    pub fn write(&self, path: &Path) -> eyre::Result<()> {
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        std::fs::create_dir_all(dir)
+            .wrap_err_with(|| format!("failed to create {}", dir.display()))?;
+        let mut file = tempfile::NamedTempFile::new_in(dir)
+            .wrap_err("failed to create temporary result file")?;
+        serde_json::to_writer_pretty(&mut file, self)
+            .wrap_err("failed to serialize result")?;
+        file.write_all(b"\n")?;
+        file.as_file().sync_all()?;
+        file.persist(path)
+            .wrap_err_with(|| format!("failed to persist {}", path.display()))?;
+        Ok(())
+    }
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn serialize_millis<S: serde::Serializer>(
+    duration: &Duration,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u64(duration.as_millis() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use color_eyre::eyre::eyre;
+
+    use super::*;
+
+    fn record(
+        name: &'static str,
+        status: CheckStatus,
+        category: Failure,
+    ) -> CheckRecord {
+        CheckRecord {
+            name,
+            status,
+            message: String::new(),
+            duration: Duration::ZERO,
+            category: Some(category),
+        }
+    }
+
+    #[test]
+    fn test_serialization() {
+        let outcomes = [
+            ("main mcu version", Ok(CheckOutcome::Pass)),
+            ("disk space", Ok(CheckOutcome::Fail("10 MiB left".into()))),
+            ("connectivity", Err(eyre!("connd is down"))),
+        ];
+        let mut report = Report {
+            checks: outcomes
+                .iter()
+                .map(|(name, outcome)| {
+                    CheckRecord::new(name, outcome, Duration::from_millis(42), None)
+                })
+                .collect(),
+            action: Action::SetUnbootable,
+            ..Report::default()
+        };
+        let exit_code = report.finish(&Err(ChecksFailed(vec!["disk space"]).into()));
+        assert_eq!(exit_code, 13);
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "checks": [
+                    {
+                        "name": "main mcu version",
+                        "status": "pass",
+                        "message": "",
+                        "duration_ms": 42,
+                    },
+                    {
+                        "name": "disk space",
+                        "status": "fail",
+                        "message": "10 MiB left",
+                        "duration_ms": 42,
+                    },
+                    {
+                        "name": "connectivity",
+                        "status": "error",
+                        "message": "connd is down",
+                        "duration_ms": 42,
+                    },
+                ],
+                "action": "set_unbootable",
+                "failure": "checks_failed",
+                "exit_code": 13,
+                "error": "system health checks failed: [\"disk space\"]",
+            })
+        );
+    }
+
+    #[test]
+    fn test_exit_codes() {
+        let mcu_failed = Report {
+            checks: vec![
+                record("disk space", CheckStatus::Fail, Failure::ChecksFailed),
+                record("main mcu version", CheckStatus::Fail, Failure::McuMismatch),
+            ],
+            ..Report::default()
+        };
+        let mcu_passed = Report {
+            checks: vec![
+                record("disk space", CheckStatus::Fail, Failure::ChecksFailed),
+                record("main mcu version", CheckStatus::Pass, Failure::McuMismatch),
+            ],
+            ..Report::default()
+        };
+        let slot_ctrl_error = || {
+            eyre::Report::new(orb_slot_ctrl::Error::InvalidSlotData)
+                .wrap_err("failed to set rootfs status")
+        };
+        let efivar_db_error = || {
+            eyre::Report::new(orb_slot_ctrl::EfiVarDbErr::VarPathCannotBeAbsolute(
+                "/efivars".into(),
+            ))
+        };
+        let cases: [(eyre::Result<()>, &Report, u8); 8] = [
+            (Ok(()), &mcu_failed, 0),
+            (Err(ChecksFailed(vec![]).into()), &mcu_failed, 10),
+            (Err(ChecksFailed(vec![]).into()), &mcu_passed, 13),
+            (Err(slot_ctrl_error()), &mcu_failed, 11),
+            (Err(efivar_db_error()), &mcu_passed, 11),
+            (
+                Err(eyre!("reboot failed").wrap_err(CheckFrameworkError)),
+                &mcu_passed,
+                12,
+            ),
+            (Err(eyre!("something else")), &mcu_passed, 1),
+            (
+                Err(eyre::Report::new(ChecksFailed(vec![])).wrap_err("context")),
+                &mcu_passed,
+                13,
+            ),
+        ];
+        for (i, (result, report, expected)) in cases.into_iter().enumerate() {
+            let mut report = report.clone();
+            assert_eq!(report.finish(&result), expected, "{i}th case failed");
+            assert_eq!(report.exit_code, expected, "{i}th case failed");
+            assert_eq!(report.error.is_some(), result.is_err(), "{i}th case failed");
+        }
+    }
+
+    #[test]
+    fn test_write_is_atomic() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested/result.json");
+        let mut report = Report::default();
+        report.finish(&Ok(()));
+        report.write(&path).unwrap();
+        let written: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!(written["action"], "left_unchanged");
+        assert_eq!(written["exit_code"], 0);
+        // only the result file is left behind
+        let entries = std::fs::read_dir(path.parent().unwrap()).unwrap().count();
+        assert_eq!(entries, 1);
+    }
+}
@@ -1,7 +1,11 @@
-use std::{fmt, ops::Deref};
+use std::{fmt, fmt::Write, ops::Deref, path::PathBuf};
+
+use thiserror::Error;
+
+const QR_PREFIX: &str = "WIFI:";
 
 /// WiFi network credentials.
-#[derive(Debug)]
+#[derive(Clone, Eq, PartialEq, Debug)]
 pub struct Credentials {
     /// Network SSID.
     pub ssid: String,
@@ -13,7 +17,7 @@ pub struct Credentials {
 }
 
 /// Authentication type.
-#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[derive(Clone, Eq, PartialEq, Debug)]
 pub enum AuthType {
     /// WEP encryption.
     Wep,
@@ -21,6 +25,16 @@ pub enum AuthType {
     Wpa,
     /// Pure WPA3-SAE.
     Sae,
+    /// WPA2-Enterprise (802.1X). Uses its own credentials instead of
+    /// [`Credentials::password`].
+    Eap {
+        /// EAP identity, usually the username.
+        identity: String,
+        /// EAP password.
+        password: Password,
+        /// Path to the CA certificate used to validate the authentication server.
+        ca_cert: Option<PathBuf>,
+    },
     /// Unencrypted.
     Nopass,
 }
@@ -54,3 +68,435 @@ impl PartialEq<&str> for Password {
         self.0 == *other
     }
 }
+
+/// Error parsing a WiFi QR code string, see [`Credentials::from_qr_str`].
+#[derive(Error, Debug, Clone, Eq, PartialEq)]
+pub enum QrParseError {
+    #[error("missing `WIFI:` prefix")]
+    MissingPrefix,
+    #[error("field {index} has no `:` separating its name from its value")]
+    MissingSeparator { index: usize },
+    #[error("field `{field}` is not terminated by `;`")]
+    Unterminated { field: String },
+    #[error("field `{field}` ends with a dangling `\\`")]
+    DanglingEscape { field: String },
+    #[error("field `{field}` is repeated")]
+    Duplicate { field: String },
+    #[error("unexpected data after the `;;` terminator")]
+    TrailingData,
+    #[error("missing `S` (SSID) field")]
+    MissingSsid,
+    #[error("unknown `T` (authentication type) value `{0}`")]
+    UnknownAuthType(String),
+    #[error("invalid `H` (hidden) value `{0}`, expected `true` or `false`")]
+    InvalidHidden(String),
+    #[error("missing `{field}` field required by WPA2-EAP")]
+    MissingEapField { field: &'static str },
+}
+
+impl Credentials {
+    /// Parses a WiFi QR code string, like `WIFI:S:orb;T:WPA;P:secret;H:true;;`.
+    ///
+    /// Values use MECARD-style escaping: `\`, `;`, `,` and `:` are escaped with a
+    /// backslash. Fields can come in any order and unknown fields are ignored. A
+    /// network without `P` is open, whatever its `T` is. `T:WPA2-EAP` reads the
+    /// EAP identity from `I` and its password from `P`.
+    pub fn from_qr_str(s: &str) -> Result<Self, QrParseError> {
+        let rest = s
+            .get(..QR_PREFIX.len())
+            .filter(|prefix| prefix.eq_ignore_ascii_case(QR_PREFIX))
+            .map(|_| &s[QR_PREFIX.len()..])
+            .ok_or(QrParseError::MissingPrefix)?;
+        let mut ssid = None;
+        let mut auth = None;
+        let mut password = None;
+        let mut hidden = None;
+        let mut identity = None;
+        for (name, value) in split_qr_fields(rest)? {
+            let slot = match name.as_str() {
+                "S" => &mut ssid,
+                "T" => &mut auth,
+                "P" => &mut password,
+                "H" => &mut hidden,
+                "I" => &mut identity,
+                _ => continue,
+            };
+            if slot.replace(value).is_some() {
+                return Err(QrParseError::Duplicate { field: name });
+            }
+        }
+
+        let ssid = ssid.ok_or(QrParseError::MissingSsid)?;
+        let hidden = match hidden.as_deref() {
+            None => false,
+            Some(h) if h.eq_ignore_ascii_case("true") => true,
+            Some(h) if h.eq_ignore_ascii_case("false") || h.is_empty() => false,
+            Some(h) => return Err(QrParseError::InvalidHidden(h.to_owned())),
+        };
+        let mut password = password.filter(|p| !p.is_empty()).map(Password);
+        let auth = auth.unwrap_or_default();
+        let auth_type = match auth.to_ascii_uppercase().as_str() {
+            "WPA2-EAP" => AuthType::Eap {
+                identity: identity
+                    .ok_or(QrParseError::MissingEapField { field: "I" })?,
+                password: password
+                    .take()
+                    .ok_or(QrParseError::MissingEapField { field: "P" })?,
+                ca_cert: None,
+            },
+            "WEP" | "WPA" | "WPA2" | "" | "WPA3" | "SAE" if password.is_none() => {
+                AuthType::Nopass
+            }
+            "WEP" => AuthType::Wep,
+            "WPA" | "WPA2" | "" => AuthType::Wpa,
+            "WPA3" | "SAE" => AuthType::Sae,
+            "NOPASS" => {
+                password = None;
+                AuthType::Nopass
+            }
+            _ => return Err(QrParseError::UnknownAuthType(auth)),
+        };
+        Ok(Self {
+            ssid,
+            password,
+            hidden,
+            auth_type,
+        })
+    }
+
+    /// Formats the credentials as a WiFi QR code string, which
+    /// [`from_qr_str`](Self::from_qr_str) parses back.
+    ///
+    /// The CA certificate of [`AuthType::Eap`] can't be represented and is left
+    /// out.
+    pub fn to_qr_string(&self) -> String {
+        let mut qr = String::from(QR_PREFIX);
+        let auth = match &self.auth_type {
+            AuthType::Wep => "WEP",
+            AuthType::Wpa => "WPA",
+            AuthType::Sae => "SAE",
+            AuthType::Eap { .. } => "WPA2-EAP",
+            AuthType::Nopass => "nopass",
+        };
+        push_qr_field(&mut qr, 'T', auth);
+        push_qr_field(&mut qr, 'S', &self.ssid);
+        match &self.auth_type {
+            AuthType::Eap {
+                identity, password, ..
+            } => {
+                push_qr_field(&mut qr, 'I', identity);
+                push_qr_field(&mut qr, 'P', password);
+            }
+            AuthType::Nopass => {}
+            AuthType::Wep | AuthType::Wpa | AuthType::Sae => {
+                if let Some(password) = &self.password {
+                    push_qr_field(&mut qr, 'P', password);
+                }
+            }
+        }
+        if self.hidden {
+            push_qr_field(&mut qr, 'H', "true");
+        }
+        qr.push(';');
+        qr
+    }
+}
+
+/// Splits `NAME:value;` fields up to the empty field of the `;;` terminator,
+/// unescaping the values.
+fn split_qr_fields(s: &str) -> Result<Vec<(String, String)>, QrParseError> {
+    let mut fields = Vec::new();
+    let mut chars = s.chars();
+    loop {
+        let mut name = String::new();
+        loop {
+            match chars.next() {
+                Some(':') => break,
+                // A `;` right at the start of a field is the terminator.
+                Some(';') if name.is_empty() => {
+                    return if chars.as_str().trim_end().is_empty() {
+                        Ok(fields)
+                    } else {
+                        Err(QrParseError::TrailingData)
+                    };
+                }
+                // Some encoders omit the final `;`.
+                None if name.trim().is_empty() => return Ok(fields),
+                Some(';') | None => {
+                    return Err(QrParseError::MissingSeparator {
+                        index: fields.len(),
+                    });
+                }
+                Some(c) => name.push(c),
+            }
+        }
+        let mut value = String::new();
+        loop {
+            match chars.next() {
+                Some('\\') => match chars.next() {
+                    Some(c) => value.push(c),
+                    None => return Err(QrParseError::DanglingEscape { field: name }),
+                },
+                Some(';') => break,
+                Some(c) => value.push(c),
+                None => return Err(QrParseError::Unterminated { field: name }),
+            }
+        }
+        fields.push((name, value));
+    }
+}
+
+fn push_qr_field(qr: &mut String, name: char, value: &str) {
+    write!(qr, "{name}:").unwrap();
+    for c in value.chars() {
+        if matches!(c, '\\' | ';' | ',' | ':') {
+            qr.push('\\');
+        }
+        qr.push(c);
+    }
+    qr.push(';');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn creds(ssid: &str, password: Option<&str>, auth_type: AuthType) -> Credentials {
+        Credentials {
+            ssid: ssid.to_owned(),
+            password: password.map(|p| Password(p.to_owned())),
+            hidden: false,
+            auth_type,
+        }
+    }
+
+    fn hidden(credentials: Credentials) -> Credentials {
+        Credentials {
+            hidden: true,
+            ..credentials
+        }
+    }
+
+    #[test]
+    fn test_from_qr_str() {
+        let cases = [
+            (
+                "WIFI:S:orb;T:WPA;P:secret;;",
+                creds("orb", Some("secret"), AuthType::Wpa),
+            ),
+            (
+                "WIFI:T:WPA2;P:secret;S:orb;H:true;;",
+                hidden(creds("orb", Some("secret"), AuthType::Wpa)),
+            ),
+            (
+                "WIFI:S:orb;T:WEP;P:12345;H:false;;",
+                creds("orb", Some("12345"), AuthType::Wep),
+            ),
+            (
+                "WIFI:S:orb;T:WPA3;P:secret;H:TRUE;;",
+                hidden(creds("orb", Some("secret"), AuthType::Sae)),
+            ),
+            (
+                "WIFI:S:orb;T:sae;P:secret;H:;;",
+                creds("orb", Some("secret"), AuthType::Sae),
+            ),
+            (
+                "WIFI:S:orb;T:nopass;;",
+                creds("orb", None, AuthType::Nopass),
+            ),
+            ("WIFI:S:orb;;", creds("orb", None, AuthType::Nopass)),
+            // missing password means open network
+            ("WIFI:S:orb;T:WPA;;", creds("orb", None, AuthType::Nopass)),
+            (
+                "WIFI:S:orb;T:WPA;P:;;",
+                creds("orb", None, AuthType::Nopass),
+            ),
+            // missing type with a password
+            (
+                "WIFI:S:orb;P:secret;;",
+                creds("orb", Some("secret"), AuthType::Wpa),
+            ),
+            (
+                r"WIFI:S:a\;b\\c\,d\:e;T:WPA;P:p\;w;;",
+                creds(r"a;b\c,d:e", Some("p;w"), AuthType::Wpa),
+            ),
+            // unnecessary escapes are accepted
+            (
+                r#"WIFI:S:\"orb\";;"#,
+                creds(r#""orb""#, None, AuthType::Nopass),
+            ),
+            (
+                "WIFI:S:café 🦀;T:WPA;P:🔑;;",
+                creds("café 🦀", Some("🔑"), AuthType::Wpa),
+            ),
+            (
+                "WIFI:S:trailing  ;T:WPA;P: spaces ;;",
+                creds("trailing  ", Some(" spaces "), AuthType::Wpa),
+            ),
+            ("WIFI:S:;;", creds("", None, AuthType::Nopass)),
+            ("wifi:S:orb;;", creds("orb", None, AuthType::Nopass)),
+            // final `;` omitted, trailing newline
+            ("WIFI:S:orb;", creds("orb", None, AuthType::Nopass)),
+            ("WIFI:S:orb;;\n", creds("orb", None, AuthType::Nopass)),
+            // unknown fields are ignored
+            (
+                "WIFI:S:orb;X:whatever;T:WPA;P:secret;;",
+                creds("orb", Some("secret"), AuthType::Wpa),
+            ),
+            (
+                "WIFI:T:WPA2-EAP;S:corp;I:alice;P:secret;H:true;;",
+                hidden(creds(
+                    "corp",
+                    None,
+                    AuthType::Eap {
+                        identity: "alice".to_owned(),
+                        password: Password("secret".to_owned()),
+                        ca_cert: None,
+                    },
+                )),
+            ),
+        ];
+        for (i, (input, expected)) in cases.into_iter().enumerate() {
+            assert_eq!(
+                Credentials::from_qr_str(input),
+                Ok(expected),
+                "{i}th case failed: {input}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_from_qr_str_errors() {
+        let field = |name: &str| name.to_owned();
+        let cases = [
+            ("S:orb;;", QrParseError::MissingPrefix),
+            ("", QrParseError::MissingPrefix),
+            ("WIFI:T:WPA;P:secret;;", QrParseError::MissingSsid),
+            (
+                "WIFI:S:orb",
+                QrParseError::Unterminated { field: field("S") },
+            ),
+            (
+                "WIFI:S:orb;P:secret",
+                QrParseError::Unterminated { field: field("P") },
+            ),
+            (
+                r"WIFI:S:orb;P:secret\",
+                QrParseError::DanglingEscape { field: field("P") },
+            ),
+            (
+                "WIFI:S:orb;T;;",
+                QrParseError::MissingSeparator { index: 1 },
+            ),
+            ("WIFI:S:orb;T", QrParseError::MissingSeparator { index: 1 }),
+            (
+                "WIFI:S:a;S:b;;",
+                QrParseError::Duplicate { field: field("S") },
+            ),
+            ("WIFI:S:orb;;T:WPA;;", QrParseError::TrailingData),
+            (
+                "WIFI:S:orb;T:WPA4;P:secret;;",
+                QrParseError::UnknownAuthType("WPA4".to_owned()),
+            ),
+            (
+                "WIFI:S:orb;T:WPA4;;",
+                QrParseError::UnknownAuthType("WPA4".to_owned()),
+            ),
+            (
+                "WIFI:S:orb;H:yes;;",
+                QrParseError::InvalidHidden("yes".to_owned()),
+            ),
+            (
+                "WIFI:S:corp;T:WPA2-EAP;P:secret;;",
+                QrParseError::MissingEapField { field: "I" },
+            ),
+            (
+                "WIFI:S:corp;T:WPA2-EAP;I:alice;;",
+                QrParseError::MissingEapField { field: "P" },
+            ),
+        ];
+        for (i, (input, expected)) in cases.into_iter().enumerate() {
+            assert_eq!(
+                Credentials::from_qr_str(input),
+                Err(expected),
+                "{i}th case failed: {input}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_to_qr_string() {
+        let cases = [
+            (
+                creds("orb", Some("secret"), AuthType::Wpa),
+                "WIFI:T:WPA;S:orb;P:secret;;",
+            ),
+            (
+                hidden(creds(r"a;b\c,d:e", Some("p;w"), AuthType::Sae)),
+                r"WIFI:T:SAE;S:a\;b\\c\,d\:e;P:p\;w;H:true;;",
+            ),
+            // an open network has no password
+            (
+                creds("orb", Some("ignored"), AuthType::Nopass),
+                "WIFI:T:nopass;S:orb;;",
+            ),
+        ];
+        for (i, (credentials, expected)) in cases.into_iter().enumerate() {
+            assert_eq!(credentials.to_qr_string(), expected, "{i}th case failed");
+        }
+    }
+
+    /// Round-trips pseudo-random credentials full of characters that need
+    /// escaping.
+    #[test]
+    fn test_qr_round_trip() {
+        const ALPHABET: &[char] = &[
+            'a', 'Z', '0', ' ', ';', ':', ',', '\\', '"', '\'', '=', 'é', '🦀', '\t',
+        ];
+        // xorshift64, to not depend on a random crate
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let mut string = |max_len: u64| -> String {
+            let mut next = || {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state
+            };
+            let len = next() % (max_len + 1);
+            (0..len)
+                .map(|_| ALPHABET[(next() % ALPHABET.len() as u64) as usize])
+                .collect()
+        };
+        for i in 0..2000 {
+            let ssid = string(32);
+            // an empty password would mean an open network
+            let password = Password(format!("{}x", string(16)));
+            let (auth_type, password) = match i % 5 {
+                0 => (AuthType::Wep, Some(password)),
+                1 => (AuthType::Wpa, Some(password)),
+                2 => (AuthType::Sae, Some(password)),
+                3 => (AuthType::Nopass, None),
+                _ => (
+                    AuthType::Eap {
+                        identity: string(8),
+                        password,
+                        ca_cert: None,
+                    },
+                    None,
+                ),
+            };
+            let credentials = Credentials {
+                ssid,
+                password,
+                hidden: i % 3 == 0,
+                auth_type,
+            };
+            let qr = credentials.to_qr_string();
+            assert_eq!(
+                Credentials::from_qr_str(&qr),
+                Ok(credentials),
+                "{i}th case failed: {qr}"
+            );
+        }
+    }
+}
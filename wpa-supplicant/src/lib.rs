@@ -8,13 +8,22 @@ use self::{
     wpa_dbus::{InterfaceProxySignalPoll, NetworkProxyExtractedProps},
 };
 // use crate::logger::{LogOnError, DATADOG, NO_TAGS};
-use data_encoding::HEXLOWER;
-use eyre::{eyre, OptionExt, Result, WrapErr};
+use data_encoding::{HEXLOWER, HEXLOWER_PERMISSIVE};
+use eyre::{OptionExt, Result, WrapErr};
 use futures::StreamExt;
 use ring::{pbkdf2, pbkdf2::PBKDF2_HMAC_SHA1};
-use std::{borrow::Cow, collections::HashMap, num::NonZeroU32, str};
+use std::{borrow::Cow, collections::HashMap, num::NonZeroU32, str, time::Duration};
 use tracing::warn;
 
+/// How long to wait for the `ScanDone` signal after requesting a scan.
+const SCAN_TIMEOUT: Duration = Duration::from_secs(5);
+/// Default time [`join`] waits for the connection to complete.
+pub const DEFAULT_JOIN_TIMEOUT: Duration = Duration::from_secs(30);
+/// IEEE 802.11 reason codes that mean the credentials were rejected: previous
+/// authentication no longer valid, 4-way handshake timeout and 802.1X
+/// authentication failed.
+const AUTH_FAILURE_REASONS: [i32; 3] = [2, 15, 23];
+
 /// Network connection status.
 #[derive(Debug, Clone, Copy)]
 pub enum InterfaceStatus {
@@ -26,6 +35,78 @@ pub enum InterfaceStatus {
     InProgress,
 }
 
+/// Result of [`join`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JoinOutcome {
+    /// The interface reached the `completed` state.
+    Connected,
+    /// The access point rejected the credentials.
+    AuthFailed,
+    /// The connection didn't complete in time, e.g. the access point is not
+    /// responding.
+    Timeout,
+    /// No access point with the SSID was found, even after an active scan.
+    NotFound,
+}
+
+/// A BSS (access point) found while scanning.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanResult {
+    /// Network SSID. Non-utf8 characters are replaced.
+    pub ssid: String,
+    /// Hardware address of the access point.
+    pub bssid: Vec<u8>,
+    /// Signal strength in dBm.
+    pub rssi: i16,
+    /// Frequency in MHz.
+    pub frequency: u16,
+    /// Security supported by the access point.
+    pub security: SecurityFlags,
+}
+
+/// Security flags advertised by a BSS, parsed from its `WPA`/`RSN` key management
+/// suites.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct SecurityFlags {
+    /// WPA (version 1) with a pre-shared key.
+    pub wpa: bool,
+    /// WPA2 with a pre-shared key.
+    pub wpa2: bool,
+    /// WPA3-SAE.
+    pub sae: bool,
+    /// WPA/WPA2 Enterprise (802.1X).
+    pub eap: bool,
+    /// No key management and no privacy, i.e. an open network.
+    pub open: bool,
+}
+
+impl SecurityFlags {
+    /// Builds the flags from the `KeyMgmt` suites of the BSS `WPA` and `RSN`
+    /// properties, and the BSS `Privacy` property.
+    fn from_key_mgmt(wpa: &[String], rsn: &[String], privacy: bool) -> Self {
+        let is_psk = |km: &String| km.starts_with("wpa-") && km.contains("psk");
+        let is_eap = |km: &String| km.starts_with("wpa-") && km.contains("eap");
+        Self {
+            wpa: wpa.iter().any(is_psk),
+            wpa2: rsn.iter().any(is_psk),
+            sae: rsn.iter().any(|km| km.contains("sae")),
+            eap: wpa.iter().chain(rsn).any(is_eap),
+            open: wpa.is_empty() && rsn.is_empty() && !privacy,
+        }
+    }
+}
+
+/// A network configured in wpa_supplicant.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KnownNetwork {
+    /// Network SSID. Non-utf8 characters are replaced.
+    pub ssid: String,
+    /// Whether this is the currently selected network.
+    pub selected: bool,
+    /// Whether the network is disabled.
+    pub disabled: bool,
+}
+
 /// Gets the status of the `iface_name` network interface.
 ///
 /// # Example
@@ -126,67 +207,132 @@ pub async fn current_network_ssid(iface_name: &str) -> Result<String> {
     Ok(cow.to_string())
 }
 
-/// Joins WiFi network using the given `credentials`.
-pub async fn join(iface_name: &str, credentials: Credentials) -> Result<()> {
+/// Lists the networks that wpa_supplicant has configured on `iface_name`.
+///
+/// # Example
+/// ```no_run
+/// # tokio_test::block_on(async {
+/// let networks = orb_wpa_supplicant::list_known_networks("wlan0").await.unwrap();
+/// for network in networks {
+///     println!("{network:?}");
+/// }
+/// # })
+/// ```
+pub async fn list_known_networks(iface_name: &str) -> Result<Vec<KnownNetwork>> {
     let conn = sys_conn().await?;
     let proxy = wpa_dbus::GeneralProxy::new(conn)
         .await
         .wrap_err("failed to create `fi.w1.wpa_supplicant1 (General)` dbus proxy")?;
 
     let iface = get_wifi_interface(conn, proxy, iface_name).await?;
+    let current_network = iface
+        .current_network()
+        .await
+        .wrap_err("failed to get `current_network` property on interface proxy")?;
 
-    let check_for_matching_ssid = || async {
-        get_best_matching_bss(conn, &iface, &credentials.ssid)
+    let mut known_networks = Vec::new();
+    for (net_path, net_proxy) in get_network_list(conn, &iface).await? {
+        let ssid = network_ssid(&net_proxy).await?;
+        let enabled = net_proxy
+            .enabled()
             .await
-            .wrap_err("Failed to search scan results")
-            .map(|bss| bss.is_some())
-    };
+            .wrap_err("failed to get `enabled` property on network proxy")?;
+        known_networks.push(KnownNetwork {
+            ssid,
+            selected: net_path == current_network,
+            disabled: !enabled,
+        });
+    }
 
-    // Scan/check that the network exists
-    if !check_for_matching_ssid().await? {
-        let mut signal_scan_done = iface.receive_scan_done().await.wrap_err(
-            "failed to register `fi.w1.wpa_supplicant1.Interface.ScanDone` signal listener",
-        )?;
-
-        iface
-            .scan(HashMap::from([
-                ("Type", "active".into()),
-                ("SSIDs", vec![&credentials.ssid].into()),
-            ]))
-            .await
-            .wrap_err("failed initiating BSS scan")?;
+    Ok(known_networks)
+}
 
-        tokio::time::timeout(
-            tokio::time::Duration::from_secs(5),
-            signal_scan_done.next(),
-        )
+/// Removes every configured network on `iface_name` whose SSID is `ssid`, leaving
+/// all other networks untouched. Returns whether any network was removed.
+///
+/// # Example
+/// ```no_run
+/// # tokio_test::block_on(async {
+/// let removed = orb_wpa_supplicant::forget_network("wlan0", "worldcoin")
+///     .await
+///     .unwrap();
+/// println!("{removed}");
+/// # })
+/// ```
+pub async fn forget_network(iface_name: &str, ssid: &str) -> Result<bool> {
+    let conn = sys_conn().await?;
+    let proxy = wpa_dbus::GeneralProxy::new(conn)
         .await
-        .wrap_err("scan timed out")
-        // Even if we timeout or there is a dbus error, we should still check if an
-        // unfinished scan found a network that matches our criteria, so we don't
-        // bubble the error up.
-        .map_err(|err| tracing::warn!("error occurred waiting for AP scan: {err:?}"))
-        .ok();
+        .wrap_err("failed to create `fi.w1.wpa_supplicant1 (General)` dbus proxy")?;
 
-        if check_for_matching_ssid().await? {
-            return Err(eyre!("Failed to find matching SSID even after active scan"))?;
-        }
+    let iface = get_wifi_interface(conn, proxy, iface_name).await?;
+    remove_networks_with_ssid(conn, &iface, ssid).await
+}
+
+/// Joins WiFi network using the given `credentials`, waiting up to `timeout` (see
+/// [`DEFAULT_JOIN_TIMEOUT`]) for the connection to complete.
+///
+/// Previously configured networks with the same SSID but different credentials are
+/// removed. Networks with other SSIDs are kept.
+///
+/// # Example
+/// ```no_run
+/// # tokio_test::block_on(async {
+/// use orb_wpa_supplicant::{
+///     credentials::{AuthType, Credentials, Password},
+///     DEFAULT_JOIN_TIMEOUT,
+/// };
+///
+/// let credentials = Credentials {
+///     ssid: "worldcoin".to_owned(),
+///     password: Some(Password("12345678".to_owned())),
+///     hidden: false,
+///     auth_type: AuthType::Wpa,
+/// };
+/// let outcome = orb_wpa_supplicant::join("wlan0", credentials, DEFAULT_JOIN_TIMEOUT)
+///     .await
+///     .unwrap();
+/// println!("{outcome:?}");
+/// # })
+/// ```
+pub async fn join(
+    iface_name: &str,
+    credentials: Credentials,
+    timeout: Duration,
+) -> Result<JoinOutcome> {
+    let conn = sys_conn().await?;
+    let proxy = wpa_dbus::GeneralProxy::new(conn)
+        .await
+        .wrap_err("failed to create `fi.w1.wpa_supplicant1 (General)` dbus proxy")?;
+
+    let iface = get_wifi_interface(conn, proxy, iface_name).await?;
+
+    let scanner = IfaceScanner {
+        conn,
+        iface: &iface,
+    };
+    if !find_ssid(&scanner, &credentials.ssid).await? {
+        tracing::debug!("no access point found for `{}`", credentials.ssid);
+        return Ok(JoinOutcome::NotFound);
     }
 
     let (net_path, _net) = find_or_add_network(conn, &iface, &credentials).await?;
 
-    let future_timeout =
-        std::pin::pin!(tokio::time::sleep(tokio::time::Duration::from_secs(5)));
+    let future_timeout = std::pin::pin!(tokio::time::sleep(timeout));
     let mut signal_state_changed = iface
         .receive_state_changed()
         .await
         .take_until(future_timeout);
 
+    // Left over from an earlier connection, it says nothing about this one.
+    let stale_auth_status_code = iface.auth_status_code().await.ok();
+
     iface
         .select_network(net_path)
         .await
         .wrap_err("failed to select network")?;
 
+    let mut progress = JoinProgress::new(stale_auth_status_code);
     while let Some(value) = signal_state_changed.next().await {
         let state = value
             .get()
@@ -197,24 +343,257 @@ pub async fn join(iface_name: &str, credentials: Credentials) -> Result<()> {
                 )
             })
             .ok();
-        match state.as_deref() {
-            Some("completed" | "disconnected" | "inactive" | "unknown") => {
-                tracing::debug!("connection finished (`{state:?}`)");
-                break;
+        let Some(state) = state else {
+            continue;
+        };
+        tracing::debug!("interface state changed to `{state}`");
+        let mut outcome = progress.on_state(&state);
+        if state == "disconnected" {
+            let disconnect_reason = iface
+                .disconnect_reason()
+                .await
+                .map_err(|err| {
+                    tracing::warn!("failed to get disconnect reason: `{err:?}`")
+                })
+                .ok();
+            let auth_status_code = iface
+                .auth_status_code()
+                .await
+                .map_err(|err| tracing::warn!("failed to get auth status: `{err:?}`"))
+                .ok();
+            tracing::debug!(
+                "disconnected, reason: {disconnect_reason:?}, auth status: \
+                 {auth_status_code:?}"
+            );
+            outcome = progress.on_disconnected(disconnect_reason, auth_status_code);
+        }
+        if let Some(outcome) = outcome {
+            tracing::debug!("connection finished: {outcome:?}");
+            return Ok(outcome);
+        }
+    }
+
+    Ok(JoinOutcome::Timeout)
+}
+
+/// Tracks the interface state transitions while joining a network.
+#[derive(Debug, Default)]
+struct JoinProgress {
+    /// Whether the current association attempt got to the key handshake.
+    handshake_started: bool,
+    /// `AuthStatusCode` from before the current association attempt. The property
+    /// keeps its value across attempts, so it only counts once it changes.
+    stale_auth_status_code: Option<i32>,
+}
+
+impl JoinProgress {
+    fn new(stale_auth_status_code: Option<i32>) -> Self {
+        Self {
+            handshake_started: false,
+            stale_auth_status_code,
+        }
+    }
+
+    /// Returns the outcome if `state` ends the connection attempt.
+    fn on_state(&mut self, state: &str) -> Option<JoinOutcome> {
+        match state {
+            "completed" => Some(JoinOutcome::Connected),
+            "4way_handshake" | "group_handshake" => {
+                self.handshake_started = true;
+                None
             }
-            Some(
-                "scanning" | "authenticating" | "associating" | "associated"
-                | "4way_handshake" | "group_handshake" | _,
-            ) => {
-                tracing::debug!("still waiting for connection (`{state:?}`)...");
+            // A new association attempt.
+            "authenticating" | "associating" => {
+                self.handshake_started = false;
+                None
             }
-            None => {}
-        };
+            // wpa_supplicant keeps retrying, so anything else might still end up
+            // connected.
+            _ => None,
+        }
+    }
+
+    /// Returns [`JoinOutcome::AuthFailed`] if the interface disconnected because
+    /// the credentials were rejected. Other disconnects are retried by
+    /// wpa_supplicant.
+    fn on_disconnected(
+        &mut self,
+        disconnect_reason: Option<i32>,
+        auth_status_code: Option<i32>,
+    ) -> Option<JoinOutcome> {
+        let fresh_auth_status_code = auth_status_code
+            .filter(|_| auth_status_code != self.stale_auth_status_code);
+        // The next attempt starts from this disconnect.
+        self.stale_auth_status_code = auth_status_code;
+        let handshake_started = std::mem::take(&mut self.handshake_started);
+        // Negative reasons are locally generated disconnects.
+        let auth_failed = handshake_started
+            || disconnect_reason
+                .is_some_and(|reason| AUTH_FAILURE_REASONS.contains(&reason.abs()))
+            || fresh_auth_status_code.is_some_and(|code| code != 0);
+        auth_failed.then_some(JoinOutcome::AuthFailed)
+    }
+}
+
+/// The scan operations [`join`] relies on.
+trait BssScanner {
+    /// Whether the scan results contain a BSS with `ssid`.
+    async fn has_bss(&self, ssid: &str) -> Result<bool>;
+
+    /// Runs an active scan for `ssid`.
+    async fn scan(&self, ssid: &str) -> Result<()>;
+}
+
+struct IfaceScanner<'a, 'b> {
+    conn: &'a zbus::Connection,
+    iface: &'a wpa_dbus::InterfaceProxy<'b>,
+}
+
+impl BssScanner for IfaceScanner<'_, '_> {
+    async fn has_bss(&self, ssid: &str) -> Result<bool> {
+        get_best_matching_bss(self.conn, self.iface, ssid)
+            .await
+            .wrap_err("Failed to search scan results")
+            .map(|bss| bss.is_some())
+    }
+
+    async fn scan(&self, ssid: &str) -> Result<()> {
+        scan_and_wait(self.iface, &[ssid]).await
+    }
+}
+
+/// Checks that an access point with `ssid` exists, running an active scan if it
+/// is not in the scan results yet.
+async fn find_ssid(scanner: &impl BssScanner, ssid: &str) -> Result<bool> {
+    if scanner.has_bss(ssid).await? {
+        return Ok(true);
+    }
+    scanner.scan(ssid).await?;
+    scanner.has_bss(ssid).await
+}
+
+/// Triggers an active scan on `iface_name` and returns every network found,
+/// strongest signal first.
+///
+/// Access points sharing an SSID are deduplicated, keeping the one with the
+/// strongest RSSI. Hidden networks (empty SSID) are omitted.
+///
+/// # Example
+/// ```no_run
+/// # tokio_test::block_on(async {
+/// let networks = orb_wpa_supplicant::scan_networks("wlan0").await.unwrap();
+/// for network in networks {
+///     println!("{network:?}");
+/// }
+/// # })
+/// ```
+pub async fn scan_networks(iface_name: &str) -> Result<Vec<ScanResult>> {
+    let conn = sys_conn().await?;
+    let proxy = wpa_dbus::GeneralProxy::new(conn)
+        .await
+        .wrap_err("failed to create `fi.w1.wpa_supplicant1 (General)` dbus proxy")?;
+
+    let iface = get_wifi_interface(conn, proxy, iface_name).await?;
+    scan_and_wait(&iface, &[]).await?;
+
+    let mut results = Vec::new();
+    for bss in get_bss_list(conn, &iface).await? {
+        match scan_result_from_bss(&bss).await {
+            Ok(result) => results.push(result),
+            Err(err) => warn!("skipping BSS with unreadable properties: `{err:?}`"),
+        }
     }
 
+    Ok(dedup_by_strongest_ssid(results))
+}
+
+/// Requests an active scan for `ssids` (or all networks, if empty) and waits for
+/// the `ScanDone` signal.
+async fn scan_and_wait(
+    iface: &wpa_dbus::InterfaceProxy<'_>,
+    ssids: &[&str],
+) -> Result<()> {
+    let mut signal_scan_done = iface.receive_scan_done().await.wrap_err(
+        "failed to register `fi.w1.wpa_supplicant1.Interface.ScanDone` signal listener",
+    )?;
+
+    let mut args = HashMap::from([("Type", "active".into())]);
+    if !ssids.is_empty() {
+        args.insert("SSIDs", ssids.to_vec().into());
+    }
+    iface
+        .scan(args)
+        .await
+        .wrap_err("failed initiating BSS scan")?;
+
+    tokio::time::timeout(SCAN_TIMEOUT, signal_scan_done.next())
+        .await
+        .wrap_err("scan timed out")
+        // Even if we timeout or there is a dbus error, the caller should still check
+        // if an unfinished scan found a network that matches its criteria, so we
+        // don't bubble the error up.
+        .map_err(|err| tracing::warn!("error occurred waiting for AP scan: {err:?}"))
+        .ok();
+
     Ok(())
 }
 
+async fn scan_result_from_bss(bss: &wpa_dbus::BSSProxy<'_>) -> Result<ScanResult> {
+    let ssid = bss
+        .ssid()
+        .await
+        .wrap_err("failed to get `ssid` property on bss proxy")?;
+    let wpa = bss
+        .wpa()
+        .await
+        .wrap_err("failed to get `wpa` property on bss proxy")?;
+    let rsn = bss
+        .rsn()
+        .await
+        .wrap_err("failed to get `rsn` property on bss proxy")?;
+    let privacy = bss
+        .privacy()
+        .await
+        .wrap_err("failed to get `privacy` property on bss proxy")?;
+
+    Ok(ScanResult {
+        ssid: String::from_utf8_lossy(&ssid).into_owned(),
+        bssid: bss
+            .bssid()
+            .await
+            .wrap_err("failed to get `bssid` property on bss proxy")?,
+        rssi: bss
+            .signal()
+            .await
+            .wrap_err("failed to get `signal` property on bss proxy")?,
+        frequency: bss
+            .frequency()
+            .await
+            .wrap_err("failed to get `frequency` property on bss proxy")?,
+        security: SecurityFlags::from_key_mgmt(
+            &wpa_dbus::extract_key_mgmt(&wpa),
+            &wpa_dbus::extract_key_mgmt(&rsn),
+            privacy,
+        ),
+    })
+}
+
+/// Keeps only the strongest BSS per SSID, sorted by descending RSSI.
+fn dedup_by_strongest_ssid(results: Vec<ScanResult>) -> Vec<ScanResult> {
+    let mut strongest: HashMap<String, ScanResult> = HashMap::new();
+    for result in results.into_iter().filter(|r| !r.ssid.is_empty()) {
+        match strongest.get(&result.ssid) {
+            Some(existing) if existing.rssi >= result.rssi => {}
+            _ => {
+                strongest.insert(result.ssid.clone(), result);
+            }
+        }
+    }
+    let mut results: Vec<ScanResult> = strongest.into_values().collect();
+    results.sort_by(|a, b| b.rssi.cmp(&a.rssi).then_with(|| a.ssid.cmp(&b.ssid)));
+    results
+}
+
 static SYS_CONN: tokio::sync::OnceCell<zbus::Connection> =
     tokio::sync::OnceCell::const_new();
 async fn sys_conn() -> Result<&'static zbus::Connection> {
@@ -313,6 +692,84 @@ async fn get_bss_list<'a>(
         .await)
 }
 
+async fn get_network_list<'a>(
+    conn: &zbus::Connection,
+    iface_proxy: &wpa_dbus::InterfaceProxy<'a>,
+) -> Result<Vec<(zbus::zvariant::OwnedObjectPath, wpa_dbus::NetworkProxy<'a>)>> {
+    let network_list: Vec<zbus::zvariant::OwnedObjectPath> = iface_proxy
+        .networks()
+        .await
+        .wrap_err("failed to get `networks` property on interface proxy")?;
+
+    let mut networks = Vec::with_capacity(network_list.len());
+    for net_path in network_list {
+        let net_proxy = wpa_dbus::NetworkProxy::builder(conn)
+            .path(net_path.clone())
+            .wrap_err_with(|| {
+                format!("failed setting network proxy path `{net_path:?}`")
+            })?
+            .build()
+            .await
+            .wrap_err("failed to create `fi.w1.wpa_supplicant1.Network` dbus proxy")?;
+        networks.push((net_path, net_proxy));
+    }
+    Ok(networks)
+}
+
+async fn network_ssid(net_proxy: &wpa_dbus::NetworkProxy<'_>) -> Result<String> {
+    let props = net_proxy
+        .properties()
+        .await
+        .wrap_err("failed to get `properties` property on network proxy")?;
+    let ssid: String = props
+        .get("ssid")
+        .ok_or_eyre("Expected SSID to be present")?
+        .try_clone()
+        .wrap_err("failed to clone property")?
+        .try_into()
+        .wrap_err("failed to convert `ssid` property to string")?;
+    Ok(parse_conf_ssid(&ssid))
+}
+
+/// Removes the configured networks whose SSID is `ssid`. Returns whether any
+/// network was removed.
+async fn remove_networks_with_ssid(
+    conn: &zbus::Connection,
+    iface_proxy: &wpa_dbus::InterfaceProxy<'_>,
+    ssid: &str,
+) -> Result<bool> {
+    let mut removed = false;
+    for (net_path, net_proxy) in get_network_list(conn, iface_proxy).await? {
+        if network_ssid(&net_proxy).await? != ssid {
+            continue;
+        }
+        iface_proxy
+            .remove_network(net_path.clone())
+            .await
+            .wrap_err_with(|| format!("failed to remove network `{net_path:?}`"))?;
+        removed = true;
+    }
+    Ok(removed)
+}
+
+/// Parses an SSID as represented in wpa_supplicant.conf(5): either a quoted string
+/// or an unquoted hex string.
+fn parse_conf_ssid(ssid: &str) -> String {
+    if let Some(unquoted) = ssid
+        .strip_prefix('"')
+        .and_then(|ssid| ssid.strip_suffix('"'))
+    {
+        return unquoted.to_owned();
+    }
+    match HEXLOWER_PERMISSIVE.decode(ssid.as_bytes()) {
+        Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+        Err(_) => {
+            warn!("ssid `{ssid}` is neither quoted nor hex, using it verbatim");
+            ssid.to_owned()
+        }
+    }
+}
+
 /// Find or add a network to the wpa_supplicant daemon
 ///
 /// # Known Issues
@@ -342,72 +799,21 @@ async fn find_or_add_network<'a>(
     iface_proxy: &wpa_dbus::InterfaceProxy<'a>,
     credentials: &Credentials,
 ) -> Result<(zbus::zvariant::OwnedObjectPath, wpa_dbus::NetworkProxy<'a>)> {
-    let network_list: Vec<zbus::zvariant::OwnedObjectPath> = iface_proxy
-        .networks()
-        .await
-        .wrap_err("failed to get `networks` property on interface proxy")?;
-
-    async fn find_network<'a>(
-        conn: &zbus::Connection,
-        network_list: Vec<zbus::zvariant::OwnedObjectPath>,
-        compare_to_creds: &Credentials,
-    ) -> Result<Option<(zbus::zvariant::OwnedObjectPath, wpa_dbus::NetworkProxy<'a>)>>
-    {
-        for net_path in network_list {
-            let net_proxy = wpa_dbus::NetworkProxy::builder(conn)
-                .path(net_path.clone())
-                .wrap_err_with(|| {
-                    format!("failed setting network proxy path `{net_path:?}`")
-                })?
-                .build()
-                .await
-                .wrap_err(
-                    "failed to create `fi.w1.wpa_supplicant1.Network` dbus proxy",
-                )?;
-            let props = net_proxy
-                .properties()
-                .await
-                .wrap_err("failed to get `properties` property on network proxy")?;
-            let extracted_props = NetworkProxyExtractedProps::from_dbus(props)
-                .wrap_err("Failed to extract properties from dbus")?;
-            if extracted_props.matches(compare_to_creds) {
-                return Ok(Some((net_path, net_proxy)));
-            }
+    for (net_path, net_proxy) in get_network_list(conn, iface_proxy).await? {
+        let props = net_proxy
+            .properties()
+            .await
+            .wrap_err("failed to get `properties` property on network proxy")?;
+        let extracted_props = NetworkProxyExtractedProps::from_dbus(props)
+            .wrap_err("Failed to extract properties from dbus")?;
+        if extracted_props.matches(credentials) {
+            return Ok((net_path, net_proxy));
         }
-
-        Ok(None)
     }
 
-    if let Some(network) = find_network(conn, network_list, credentials).await? {
-        return Ok(network);
-    }
+    remove_networks_with_ssid(conn, iface_proxy, &credentials.ssid).await?;
 
-    iface_proxy
-        .remove_all_networks()
-        .await
-        .wrap_err("failed to remove all networks from interface proxy")?;
-
-    let network_properties = {
-        let mut map = HashMap::<&str, zbus::zvariant::Value<'_>>::new();
-        map.insert("ssid", credentials.ssid.as_str().into());
-        if credentials.password.is_some() {
-            let psk: &str = &credentials.password.as_ref().unwrap().0;
-            map.insert("psk", psk.into());
-        }
-        match credentials.auth_type {
-            AuthType::Wep => {
-                map.insert("key_mgmt", "NONE".into());
-            }
-            AuthType::Wpa | AuthType::Sae => {
-                map.insert("key_mgmt", "WPA-PSK".into());
-            }
-            AuthType::Nopass => {}
-        }
-        if credentials.hidden {
-            map.insert("scan_ssid", 1.into());
-        }
-        map
-    };
+    let network_properties = network_properties(credentials);
     iface_proxy
         .add_network(network_properties)
         .await
@@ -427,6 +833,48 @@ async fn find_or_add_network<'a>(
         .await
 }
 
+/// Builds the wpa_supplicant.conf(5) network block for `credentials`, as expected by
+/// the `AddNetwork` DBus method.
+fn network_properties(
+    credentials: &Credentials,
+) -> HashMap<&str, zbus::zvariant::Value<'_>> {
+    let mut map = HashMap::<&str, zbus::zvariant::Value<'_>>::new();
+    map.insert("ssid", credentials.ssid.as_str().into());
+    if let Some(password) = &credentials.password {
+        map.insert("psk", (&**password).into());
+    }
+    match &credentials.auth_type {
+        AuthType::Wep => {
+            map.insert("key_mgmt", "NONE".into());
+        }
+        AuthType::Wpa => {
+            map.insert("key_mgmt", "WPA-PSK".into());
+        }
+        AuthType::Sae => {
+            map.insert("key_mgmt", "SAE".into());
+            // Management frame protection is mandatory for WPA3.
+            map.insert("ieee80211w", 2.into());
+        }
+        AuthType::Eap {
+            identity,
+            password,
+            ca_cert,
+        } => {
+            map.insert("key_mgmt", "WPA-EAP".into());
+            map.insert("identity", identity.as_str().into());
+            map.insert("password", (&**password).into());
+            if let Some(ca_cert) = ca_cert {
+                map.insert("ca_cert", ca_cert.to_string_lossy().into_owned().into());
+            }
+        }
+        AuthType::Nopass => {}
+    }
+    if credentials.hidden {
+        map.insert("scan_ssid", 1.into());
+    }
+    map
+}
+
 // Using hex string encoding, because `wpa_supplicant.conf` string escaping
 // schema is not well-defined.
 fn hex_string<T: AsRef<[u8]>>(input: T) -> String {
@@ -448,6 +896,8 @@ fn wpa_passphrase(ssid: &str, passphrase: &str) -> String {
 #[cfg(test)]
 pub mod tests {
     use super::*;
+    use crate::credentials::Password;
+    use zbus::zvariant::Value;
 
     #[test]
     fn test_hex_string() {
@@ -462,4 +912,252 @@ pub mod tests {
             "5c1f986129b5a10564a66899f10a2989d4deb8f9a9ba504c68e535d7a3c8e5ba"
         );
     }
+
+    #[test]
+    fn test_parse_conf_ssid() {
+        assert_eq!(parse_conf_ssid("\"worldcoin\""), "worldcoin");
+        assert_eq!(parse_conf_ssid("776f726c64636f696e"), "worldcoin");
+        assert_eq!(parse_conf_ssid("776F726C64636F696E"), "worldcoin");
+        assert_eq!(parse_conf_ssid("\"\""), "");
+        assert_eq!(parse_conf_ssid("not-hex"), "not-hex");
+    }
+
+    fn creds(password: Option<&str>, auth_type: AuthType) -> Credentials {
+        Credentials {
+            ssid: "worldcoin".to_owned(),
+            password: password.map(|p| Password(p.to_owned())),
+            hidden: false,
+            auth_type,
+        }
+    }
+
+    #[test]
+    fn test_network_properties_nopass() {
+        let creds = creds(None, AuthType::Nopass);
+        let props = network_properties(&creds);
+        assert_eq!(props.len(), 1);
+        assert_eq!(props["ssid"], Value::from("worldcoin"));
+    }
+
+    #[test]
+    fn test_network_properties_wep() {
+        let creds = creds(Some("12345"), AuthType::Wep);
+        let props = network_properties(&creds);
+        assert_eq!(props["key_mgmt"], Value::from("NONE"));
+        assert_eq!(props["psk"], Value::from("12345"));
+    }
+
+    #[test]
+    fn test_network_properties_wpa() {
+        let creds = Credentials {
+            hidden: true,
+            ..creds(Some("12345678"), AuthType::Wpa)
+        };
+        let props = network_properties(&creds);
+        assert_eq!(props["key_mgmt"], Value::from("WPA-PSK"));
+        assert_eq!(props["psk"], Value::from("12345678"));
+        assert_eq!(props["scan_ssid"], Value::from(1));
+        assert!(!props.contains_key("ieee80211w"));
+    }
+
+    #[test]
+    fn test_network_properties_sae() {
+        let creds = creds(Some("12345678"), AuthType::Sae);
+        let props = network_properties(&creds);
+        assert_eq!(props["key_mgmt"], Value::from("SAE"));
+        assert_eq!(props["ieee80211w"], Value::from(2));
+        assert_eq!(props["psk"], Value::from("12345678"));
+    }
+
+    #[test]
+    fn test_network_properties_eap() {
+        let creds = creds(
+            None,
+            AuthType::Eap {
+                identity: "operator".to_owned(),
+                password: Password("hunter2".to_owned()),
+                ca_cert: Some("/etc/ssl/ca.pem".into()),
+            },
+        );
+        let props = network_properties(&creds);
+        assert_eq!(props["key_mgmt"], Value::from("WPA-EAP"));
+        assert_eq!(props["identity"], Value::from("operator"));
+        assert_eq!(props["password"], Value::from("hunter2"));
+        assert_eq!(props["ca_cert"], Value::from("/etc/ssl/ca.pem"));
+        assert!(!props.contains_key("psk"));
+    }
+
+    fn scan_result(ssid: &str, rssi: i16) -> ScanResult {
+        ScanResult {
+            ssid: ssid.to_owned(),
+            bssid: vec![0; 6],
+            rssi,
+            frequency: 2412,
+            security: SecurityFlags::default(),
+        }
+    }
+
+    #[test]
+    fn test_dedup_keeps_strongest() {
+        let results = dedup_by_strongest_ssid(vec![
+            scan_result("a", -70),
+            scan_result("b", -40),
+            scan_result("a", -50),
+            scan_result("", -10),
+            scan_result("a", -60),
+        ]);
+        assert_eq!(results, vec![scan_result("b", -40), scan_result("a", -50)]);
+    }
+
+    /// Scan results whose content depends on whether a scan ran.
+    struct MockScanner {
+        visible_before_scan: bool,
+        visible_after_scan: bool,
+        scans: std::cell::Cell<u32>,
+    }
+
+    impl BssScanner for MockScanner {
+        async fn has_bss(&self, ssid: &str) -> Result<bool> {
+            assert_eq!(ssid, "worldcoin");
+            Ok(if self.scans.get() == 0 {
+                self.visible_before_scan
+            } else {
+                self.visible_after_scan
+            })
+        }
+
+        async fn scan(&self, _ssid: &str) -> Result<()> {
+            self.scans.set(self.scans.get() + 1);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_find_ssid() {
+        let test_cases = [
+            // (before scan, after scan, expected found, expected scans)
+            (true, true, true, 0),
+            // Used to be reported as missing, because the check was inverted.
+            (false, true, true, 1),
+            (false, false, false, 1),
+        ];
+        for (i, (before, after, found, scans)) in test_cases.into_iter().enumerate() {
+            let scanner = MockScanner {
+                visible_before_scan: before,
+                visible_after_scan: after,
+                scans: Default::default(),
+            };
+            assert_eq!(
+                find_ssid(&scanner, "worldcoin").await.unwrap(),
+                found,
+                "{i}th case failed"
+            );
+            assert_eq!(scanner.scans.get(), scans, "{i}th case failed");
+        }
+    }
+
+    #[test]
+    fn test_join_progress_connected() {
+        let mut progress = JoinProgress::default();
+        for state in ["disconnected", "scanning", "associating", "4way_handshake"] {
+            assert_eq!(progress.on_state(state), None);
+        }
+        assert_eq!(progress.on_state("completed"), Some(JoinOutcome::Connected));
+    }
+
+    #[test]
+    fn test_join_progress_auth_failed() {
+        // 4way_handshake -> disconnected
+        let mut progress = JoinProgress::default();
+        assert_eq!(progress.on_state("associated"), None);
+        assert_eq!(progress.on_state("4way_handshake"), None);
+        assert_eq!(progress.on_state("disconnected"), None);
+        assert_eq!(
+            progress.on_disconnected(Some(3), Some(0)),
+            Some(JoinOutcome::AuthFailed)
+        );
+
+        // disconnect reason
+        let mut progress = JoinProgress::default();
+        assert_eq!(
+            progress.on_disconnected(Some(-15), Some(0)),
+            Some(JoinOutcome::AuthFailed)
+        );
+        assert_eq!(
+            progress.on_disconnected(Some(23), None),
+            Some(JoinOutcome::AuthFailed)
+        );
+
+        // auth status, e.g. SAE rejected
+        assert_eq!(
+            progress.on_disconnected(Some(0), Some(1)),
+            Some(JoinOutcome::AuthFailed)
+        );
+    }
+
+    #[test]
+    fn test_join_progress_other_disconnects_are_retried() {
+        let mut progress = JoinProgress::default();
+        assert_eq!(progress.on_state("associating"), None);
+        assert_eq!(progress.on_disconnected(Some(3), Some(0)), None);
+        assert_eq!(progress.on_disconnected(None, None), None);
+        assert_eq!(progress.on_state("scanning"), None);
+    }
+
+    #[test]
+    fn test_join_progress_ignores_stale_attempts() {
+        // Auth status left over from before the join.
+        let mut progress = JoinProgress::new(Some(15));
+        assert_eq!(progress.on_state("associating"), None);
+        assert_eq!(progress.on_disconnected(Some(4), Some(15)), None);
+
+        // A handshake that was interrupted by a reassociation.
+        assert_eq!(progress.on_state("associating"), None);
+        assert_eq!(progress.on_state("4way_handshake"), None);
+        assert_eq!(progress.on_state("associating"), None);
+        assert_eq!(progress.on_disconnected(Some(4), Some(15)), None);
+
+        // disconnect -> reassociate -> disconnect, with a new auth status.
+        assert_eq!(progress.on_state("associating"), None);
+        assert_eq!(
+            progress.on_disconnected(Some(4), Some(1)),
+            Some(JoinOutcome::AuthFailed)
+        );
+    }
+
+    #[test]
+    fn test_security_flags() {
+        let s = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        let open = SecurityFlags::from_key_mgmt(&[], &[], false);
+        assert_eq!(
+            open,
+            SecurityFlags {
+                open: true,
+                ..Default::default()
+            }
+        );
+
+        let wep = SecurityFlags::from_key_mgmt(&[], &[], true);
+        assert_eq!(wep, SecurityFlags::default());
+
+        let mixed = SecurityFlags::from_key_mgmt(
+            &s(&["wpa-psk"]),
+            &s(&["wpa-psk", "sae", "wpa-eap"]),
+            true,
+        );
+        assert_eq!(
+            mixed,
+            SecurityFlags {
+                wpa: true,
+                wpa2: true,
+                sae: true,
+                eap: true,
+                open: false,
+            }
+        );
+
+        let wpa2_only = SecurityFlags::from_key_mgmt(&[], &s(&["wpa-ft-psk"]), true);
+        assert!(wpa2_only.wpa2 && !wpa2_only.wpa && !wpa2_only.sae);
+    }
 }
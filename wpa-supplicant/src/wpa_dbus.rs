@@ -108,6 +108,9 @@ pub trait Interface {
     #[zbus(property)]
     fn disconnect_reason(&self) -> zbus::Result<i32>;
 
+    #[zbus(property)]
+    fn auth_status_code(&self) -> zbus::Result<i32>;
+
     #[zbus(property)]
     fn networks(&self) -> zbus::Result<Vec<zbus::zvariant::OwnedObjectPath>>;
 
@@ -150,8 +153,33 @@ pub trait BSS {
     #[zbus(property, name = "BSSID")]
     fn bssid(&self) -> zbus::Result<Vec<u8>>;
 
+    #[zbus(property, name = "Frequency")]
+    fn frequency(&self) -> zbus::Result<u16>;
+
+    #[zbus(property, name = "Privacy")]
+    fn privacy(&self) -> zbus::Result<bool>;
+
     #[zbus(property, name = "WPA")]
     fn wpa(&self) -> zbus::Result<HashMap<String, ZbusOwnedValue>>;
+
+    #[zbus(property, name = "RSN")]
+    fn rsn(&self) -> zbus::Result<HashMap<String, ZbusOwnedValue>>;
+}
+
+/// Extracts the `KeyMgmt` string array from a BSS `WPA` or `RSN` property map.
+/// Returns an empty vec if the key is absent or isn't an array of strings.
+pub fn extract_key_mgmt(dbus: &HashMap<String, ZbusOwnedValue>) -> Vec<String> {
+    let Some(zbus::zvariant::Value::Array(array)) = dbus.get("KeyMgmt").map(|v| &**v)
+    else {
+        return Vec::new();
+    };
+    array
+        .iter()
+        .filter_map(|v| match v {
+            zbus::zvariant::Value::Str(s) => Some(s.to_string()),
+            _ => None,
+        })
+        .collect()
 }
 
 /// Network interface wrapping a map which represents a wpa_supplicant.conf(5)
@@ -219,6 +247,9 @@ pub struct NetworkProxyExtractedProps {
     psk: Option<String>,
     // won't exist if there is no password
     key_mgmt: Option<String>,
+    // only present for WPA-EAP networks
+    identity: Option<String>,
+    ca_cert: Option<String>,
 }
 
 impl NetworkProxyExtractedProps {
@@ -227,18 +258,25 @@ impl NetworkProxyExtractedProps {
             extract_prop(&dbus, "ssid")?.ok_or_eyre("Expected SSID to be present")?;
         let password = extract_prop(&dbus, "psk")?;
         let key_mgmt = extract_prop(&dbus, "key_mgmt")?;
+        let identity = extract_prop(&dbus, "identity")?;
+        let ca_cert = extract_prop(&dbus, "ca_cert")?;
 
         Ok(Self {
             ssid,
             psk: password,
             key_mgmt,
+            identity,
+            ca_cert,
         })
     }
 
     /// Note: We return false if the ssid was given to us by dbus as unquoted
+    ///
+    /// For `WPA-EAP` networks only the identity and CA certificate are compared,
+    /// as wpa_supplicant doesn't expose the EAP password.
     pub fn matches(&self, creds: &Credentials) -> bool {
-        let quoted_creds_ssid = format!("\"{}\"", &creds.ssid);
-        if self.ssid != quoted_creds_ssid {
+        let quoted = |s: &str| format!("\"{s}\"");
+        if self.ssid != quoted(&creds.ssid) {
             return false;
         }
         if self.psk
@@ -250,13 +288,27 @@ impl NetworkProxyExtractedProps {
             return false;
         }
         if creds.password.is_none() {
-            assert_eq!(creds.auth_type, AuthType::Nopass);
+            assert!(matches!(
+                creds.auth_type,
+                AuthType::Nopass | AuthType::Eap { .. }
+            ));
         }
-        match (self.key_mgmt.as_deref(), creds.auth_type) {
+        match (self.key_mgmt.as_deref(), &creds.auth_type) {
             (None, AuthType::Nopass) => true,
             (Some("NONE"), AuthType::Wep) => true,
-            (Some("WPA-PSK"), AuthType::Wpa | AuthType::Sae) => true,
-            (Some("WPA-PSK" | "NONE"), _) => false,
+            (Some("WPA-PSK"), AuthType::Wpa) => true,
+            (Some("SAE"), AuthType::Sae) => true,
+            (
+                Some("WPA-EAP"),
+                AuthType::Eap {
+                    identity, ca_cert, ..
+                },
+            ) => {
+                self.identity.as_deref() == Some(quoted(identity).as_str())
+                    && self.ca_cert
+                        == ca_cert.as_ref().map(|p| quoted(&p.to_string_lossy()))
+            }
+            (Some("WPA-PSK" | "NONE" | "SAE" | "WPA-EAP"), _) => false,
             (Some(km), at) => {
                 tracing::warn!(
                     "Unknown auth type encountered! Assuming networks dont match.
@@ -296,6 +348,7 @@ where
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::credentials::Password;
 
     #[test]
     fn test_extract_prop() {
@@ -318,4 +371,62 @@ mod test {
         // check improper conversion
         assert!(extract_prop::<i32>(&map, prop_name).is_err());
     }
+
+    fn extracted_props(key_mgmt: &str) -> NetworkProxyExtractedProps {
+        NetworkProxyExtractedProps {
+            ssid: "\"worldcoin\"".to_owned(),
+            psk: None,
+            key_mgmt: Some(key_mgmt.to_owned()),
+            identity: None,
+            ca_cert: None,
+        }
+    }
+
+    #[test]
+    fn test_matches_sae() {
+        let creds = Credentials {
+            ssid: "worldcoin".to_owned(),
+            password: Some(Password("12345678".to_owned())),
+            hidden: false,
+            auth_type: AuthType::Sae,
+        };
+        let psk = Some(wpa_passphrase("worldcoin", "12345678"));
+        let sae = NetworkProxyExtractedProps {
+            psk: psk.clone(),
+            ..extracted_props("SAE")
+        };
+        let wpa = NetworkProxyExtractedProps {
+            psk,
+            ..extracted_props("WPA-PSK")
+        };
+        assert!(sae.matches(&creds));
+        assert!(!wpa.matches(&creds));
+    }
+
+    #[test]
+    fn test_matches_eap() {
+        let creds = Credentials {
+            ssid: "worldcoin".to_owned(),
+            password: None,
+            hidden: false,
+            auth_type: AuthType::Eap {
+                identity: "operator".to_owned(),
+                password: Password("hunter2".to_owned()),
+                ca_cert: Some("/etc/ssl/ca.pem".into()),
+            },
+        };
+        let eap = NetworkProxyExtractedProps {
+            identity: Some("\"operator\"".to_owned()),
+            ca_cert: Some("\"/etc/ssl/ca.pem\"".to_owned()),
+            ..extracted_props("WPA-EAP")
+        };
+        assert!(eap.matches(&creds));
+
+        let other_identity = NetworkProxyExtractedProps {
+            identity: Some("\"someone-else\"".to_owned()),
+            ..eap
+        };
+        assert!(!other_identity.matches(&creds));
+        assert!(!extracted_props("WPA-PSK").matches(&creds));
+    }
 }